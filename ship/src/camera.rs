@@ -2,20 +2,22 @@
 #![allow(dead_code)]
 
 use raylib::prelude::*;
-use crate::matrix::create_view_matrix;
-use std::f32::consts::PI;
+use crate::matrix::{create_view_matrix_handed, Handedness};
+use crate::quat::Quaternion;
 
 pub struct Camera {
     // Camera position/orientation
     pub eye: Vector3,        // Camera position
-    pub target: Vector3,     // Point the camera is looking at
-    pub up: Vector3,         // Up vector
+    pub target: Vector3,     // Point the camera orbits and looks at
+    pub world_up: Vector3,   // Up of the world: yaw turns around it
+    pub orientation: Quaternion, // Takes -Z to the viewing direction and +Y to the camera's up (roll included)
 
     // Orbit camera parameters
-    pub yaw: f32,            // Rotation around Y axis (left/right)
-    pub pitch: f32,          // Rotation around X axis (up/down)
     pub distance: f32,       // Distance from target
 
+    // Coordinate convention used for the view (and the matching projection)
+    pub handedness: Handedness,
+
     // Movement speed
     pub rotation_speed: f32,
     pub zoom_speed: f32,
@@ -24,69 +26,90 @@ pub struct Camera {
 
 impl Camera {
     pub fn new(eye: Vector3, target: Vector3, up: Vector3) -> Self {
-        // Calculate initial yaw and pitch from eye and target
-        let direction = Vector3::new(
-            eye.x - target.x,
-            eye.y - target.y,   
-            eye.z - target.z,
-        );
-
-        let distance = (direction.x * direction.x + direction.y * direction.y + direction.z * direction.z).sqrt();
-        let pitch = (direction.y / distance).asin();
-        let yaw = direction.z.atan2(direction.x);
+        // Orientación inicial: mirando del ojo al objetivo, con `up` arriba
+        let direction = target - eye;
+        let distance = direction.length();
 
         Camera {
             eye,
             target,
-            up,
-            yaw,
-            pitch,
+            world_up: up,
+            orientation: Quaternion::look_rotation(direction, up),
             distance,
+            handedness: Handedness::default(),
             rotation_speed: 0.05,
             zoom_speed: 0.5,
             pan_speed: 0.1,
         }
     }
 
-    /// Update camera eye position based on yaw, pitch, and distance
-    fn update_eye_position(&mut self) {
-        // Clamp pitch to avoid gimbal lock
-        self.pitch = self.pitch.clamp(-PI / 2.0 + 0.1, PI / 2.0 - 0.1);
+    /// Puts the eye `distance` behind `target` along the viewing direction
+    pub fn update_eye_position(&mut self) {
+        self.eye = self.target - self.forward() * self.distance;
+    }
+
+    /// Orbits the target: `yaw` radians around `world_up` (positive takes the
+    /// eye from +X towards +Z) and `pitch` radians around the camera's right
+    /// axis (positive raises the eye). The eye follows at `distance`. There
+    /// is no limit at the poles: past straight up the camera turns over
+    pub fn rotate(&mut self, yaw: f32, pitch: f32) {
+        let yaw_rotation = Quaternion::from_axis_angle(self.world_up, -yaw);
+        let pitch_rotation = Quaternion::from_axis_angle(self.right(), -pitch);
+        // Renormalizada: los errores de redondeo se acumulan frame a frame
+        self.orientation = (yaw_rotation * pitch_rotation * self.orientation).normalized();
+        self.update_eye_position();
+    }
+
+    /// Banks the view (Dutch angle) by `angle` radians. Positive banks the
+    /// camera to the right, so the image turns counterclockwise
+    pub fn roll(&mut self, angle: f32) {
+        let roll_rotation = Quaternion::from_axis_angle(self.forward(), angle);
+        self.orientation = (roll_rotation * self.orientation).normalized();
+    }
 
-        // Calculate camera position using spherical coordinates
-        // x = distance * cos(pitch) * cos(yaw)
-        // y = distance * sin(pitch)
-        // z = distance * cos(pitch) * sin(yaw)
-        self.eye.x = self.target.x + self.distance * self.pitch.cos() * self.yaw.cos();
-        self.eye.y = self.target.y + self.distance * self.pitch.sin();
-        self.eye.z = self.target.z + self.distance * self.pitch.cos() * self.yaw.sin();
+    /// Unit viewing direction
+    pub fn forward(&self) -> Vector3 {
+        self.orientation.rotate(Vector3::new(0.0, 0.0, -1.0))
+    }
+
+    /// Unit right of the image, `forward` x `up`
+    pub fn right(&self) -> Vector3 {
+        self.orientation.rotate(Vector3::new(1.0, 0.0, 0.0))
+    }
+
+    /// Unit up of the image, roll included
+    pub fn up(&self) -> Vector3 {
+        self.orientation.rotate(Vector3::new(0.0, 1.0, 0.0))
+    }
+
+    /// Heading of the eye around the target: the angle of the target-to-eye
+    /// direction in the XZ plane, from +X towards +Z
+    pub fn yaw(&self) -> f32 {
+        let back = -self.forward();
+        back.z.atan2(back.x)
     }
 
     /// Get the view matrix for this camera
     pub fn get_view_matrix(&self) -> Matrix {
-        create_view_matrix(self.eye, self.target, self.up)
+        create_view_matrix_handed(self.eye, self.eye + self.forward(), self.up(), self.handedness)
     }
 
     /// Process keyboard input to control the camera
     pub fn process_input(&mut self, window: &RaylibHandle) {
         // Rotation controls (yaw)
         if window.is_key_down(KeyboardKey::KEY_A) {
-            self.yaw += self.rotation_speed;
-            self.update_eye_position();
+            self.rotate(self.rotation_speed, 0.0);
         }
         if window.is_key_down(KeyboardKey::KEY_D) {
-            self.yaw -= self.rotation_speed;
-            self.update_eye_position();
+            self.rotate(-self.rotation_speed, 0.0);
         }
 
         // Rotation controls (pitch)
         if window.is_key_down(KeyboardKey::KEY_W) {
-            self.pitch += self.rotation_speed;
-            self.update_eye_position();
+            self.rotate(0.0, self.rotation_speed);
         }
         if window.is_key_down(KeyboardKey::KEY_S) {
-            self.pitch -= self.rotation_speed;
-            self.update_eye_position();
+            self.rotate(0.0, -self.rotation_speed);
         }
 
         // Zoom controls (distance from target) - arrow keys
@@ -102,58 +125,113 @@ impl Camera {
             self.update_eye_position();
         }
 
-        // Pan controls (move target/center point)
-        // Calculate right and forward vectors for panning
-        let forward = Vector3::new(
-            self.target.x - self.eye.x,
-            0.0, // Keep on horizontal plane
-            self.target.z - self.eye.z,
-        );
-        let forward_len = (forward.x * forward.x + forward.z * forward.z).sqrt();
-        let forward_normalized = if forward_len > 0.0 {
-            Vector3::new(forward.x / forward_len, 0.0, forward.z / forward_len)
-        } else {
-            Vector3::new(0.0, 0.0, 1.0)
-        };
-
-        let right = Vector3::new(
-            forward_normalized.z,
-            0.0,
-            -forward_normalized.x,
-        );
+        // Pan controls (move target/center point) along the image's right
+        let right = self.right();
 
         // Q/E keys for horizontal panning
         if window.is_key_down(KeyboardKey::KEY_Q) {
-            self.target.x += right.x * self.pan_speed;
-            self.target.z += right.z * self.pan_speed;
+            self.target -= right * self.pan_speed;
             self.update_eye_position();
         }
         if window.is_key_down(KeyboardKey::KEY_E) {
-            self.target.x -= right.x * self.pan_speed;
-            self.target.z -= right.z * self.pan_speed;
+            self.target += right * self.pan_speed;
             self.update_eye_position();
         }
 
         // Left/Right arrow keys for horizontal panning
         if window.is_key_down(KeyboardKey::KEY_LEFT) {
-            self.target.x += right.x * self.pan_speed;
-            self.target.z += right.z * self.pan_speed;
+            self.target -= right * self.pan_speed;
             self.update_eye_position();
         }
         if window.is_key_down(KeyboardKey::KEY_RIGHT) {
-            self.target.x -= right.x * self.pan_speed;
-            self.target.z -= right.z * self.pan_speed;
+            self.target += right * self.pan_speed;
             self.update_eye_position();
         }
 
-        // Vertical panning
+        // Roll (Z/C)
+        if window.is_key_down(KeyboardKey::KEY_Z) {
+            self.roll(-self.rotation_speed);
+        }
+        if window.is_key_down(KeyboardKey::KEY_C) {
+            self.roll(self.rotation_speed);
+        }
+
+        // Vertical panning, along the image's up
         if window.is_key_down(KeyboardKey::KEY_R) {
-            self.target.y += self.pan_speed;
+            self.target += self.up() * self.pan_speed;
             self.update_eye_position();
         }
         if window.is_key_down(KeyboardKey::KEY_F) {
-            self.target.y -= self.pan_speed;
+            self.target -= self.up() * self.pan_speed;
             self.update_eye_position();
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: Vector3, b: Vector3) {
+        assert!((a - b).length() < 1e-4, "{:?} != {:?}", a, b);
+    }
+
+    fn start_camera() -> Camera {
+        Camera::new(Vector3::new(0.0, 15.0, 25.0), Vector3::zero(), Vector3::new(0.0, 1.0, 0.0))
+    }
+
+    #[test]
+    fn full_turn_of_yaw_returns_to_the_start() {
+        let mut camera = start_camera();
+        let (forward, up, eye) = (camera.forward(), camera.up(), camera.eye);
+        // En pasos de frame, como con las teclas
+        let steps = 360;
+        for _ in 0..steps {
+            camera.rotate(2.0 * std::f32::consts::PI / steps as f32, 0.0);
+        }
+        assert_close(camera.forward(), forward);
+        assert_close(camera.up(), up);
+        assert!((camera.eye - eye).length() < 1e-3);
+    }
+
+    #[test]
+    fn pitching_over_the_pole_stays_finite() {
+        let mut camera = start_camera();
+        for _ in 0..100 {
+            camera.rotate(0.0, 0.05);
+            let view = camera.get_view_matrix();
+            assert!([view.m0, view.m5, view.m10, view.m12, view.m13, view.m14].iter().all(|v| v.is_finite()));
+            assert!((camera.forward().length() - 1.0).abs() < 1e-4);
+            assert!(camera.forward().dot(camera.up()).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn slerp_moves_at_constant_angle() {
+        let y = Vector3::new(0.0, 1.0, 0.0);
+        let start = Quaternion::from_axis_angle(y, 0.0);
+        let end = Quaternion::from_axis_angle(y, std::f32::consts::FRAC_PI_2);
+        let x = Vector3::new(1.0, 0.0, 0.0);
+        assert_close(start.slerp(end, 0.0).rotate(x), x);
+        assert_close(start.slerp(end, 1.0).rotate(x), end.rotate(x));
+        let halfway = Quaternion::from_axis_angle(y, std::f32::consts::FRAC_PI_4);
+        assert_close(start.slerp(end, 0.5).rotate(x), halfway.rotate(x));
+    }
+
+    #[test]
+    fn quarter_roll_swaps_right_and_up() {
+        let mut camera = start_camera();
+        let (forward, right, up, eye) = (camera.forward(), camera.right(), camera.up(), camera.eye);
+        camera.roll(std::f32::consts::FRAC_PI_2);
+        // Inclinada a la derecha: arriba pasa a ser la derecha de antes y la derecha, el abajo
+        assert_close(camera.up(), right);
+        assert_close(camera.right(), -up);
+        assert_close(camera.forward(), forward);
+        assert_eq!(camera.eye, eye);
+
+        camera.roll(-std::f32::consts::FRAC_PI_2);
+        assert_close(camera.right(), right);
+        assert_close(camera.up(), up);
+    }
+}
+
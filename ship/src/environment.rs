@@ -0,0 +1,364 @@
+// environment.rs
+use crate::framebuffer::Framebuffer;
+use crate::matrix::multiply_matrix_vector4;
+use crate::sh_ambient::{self, ShAmbient};
+use raylib::prelude::*;
+use std::f32::consts::PI;
+use std::fs;
+use std::io;
+
+// Resolución del mapa de `Environment::gradient`: el filtrado bilineal suaviza el resto
+const GRADIENT_WIDTH: usize = 64;
+const GRADIENT_HEIGHT: usize = 32;
+
+// Texels como máximo de un mapa cargado (8192x4096, unos 400 MB en Vector3):
+// una cabecera corrupta no puede pedir una reserva mayor
+const MAX_HDR_TEXELS: usize = 8192 * 4096;
+
+/// Light arriving from every direction: an equirectangular HDR map when one
+/// is loaded, the procedural starfield otherwise. Used both as the skybox
+/// and as the reflection source, so the two always agree.
+pub struct Environment {
+    map: Option<EnvironmentMap>,
+    // Proyección SH del mapa, calculada una sola vez al cargarlo
+    ambient: Option<ShAmbient>,
+}
+
+struct EnvironmentMap {
+    width: usize,
+    height: usize,
+    texels: Vec<Vector3>,
+}
+
+impl Environment {
+    pub fn starfield() -> Self {
+        Environment { map: None, ambient: None }
+    }
+
+    /// A small sky-over-ground map for checking reflections: blue zenith,
+    /// pale horizon and dark ground, the horizon warm towards +X and cool
+    /// towards -X, so both a tilt and a turn of the camera show up in it
+    pub fn gradient() -> Self {
+        let (width, height) = (GRADIENT_WIDTH, GRADIENT_HEIGHT);
+        let zenith = Vector3::new(0.15, 0.35, 0.9);
+        let ground = Vector3::new(0.08, 0.06, 0.05);
+        let mut texels = Vec::with_capacity(width * height);
+        for y in 0..height {
+            // Centro de la fila: elevación de +90° (arriba) a -90° (abajo)
+            let elevation = (0.5 - (y as f32 + 0.5) / height as f32) * PI;
+            for x in 0..width {
+                // u = 0.5 mira hacia +X (ver `direction_to_uv`)
+                let azimuth = ((x as f32 + 0.5) / width as f32 - 0.5) * 2.0 * PI;
+                let warmth = azimuth.cos() * 0.5 + 0.5;
+                let horizon = Vector3::new(0.5, 0.7, 0.8) * (1.0 - warmth) + Vector3::new(1.0, 0.6, 0.3) * warmth;
+                let (far, t) = if elevation >= 0.0 { (zenith, elevation.sin()) } else { (ground, -elevation.sin()) };
+                // Raíz: el horizonte es una franja fina y el resto del cielo ya es azul
+                let t = t.sqrt();
+                texels.push(horizon * (1.0 - t) + far * t);
+            }
+        }
+        Environment::from_map(EnvironmentMap { width, height, texels })
+    }
+
+    /// Linear RGB texels, row by row, top row first (+Y). Fails with
+    /// `InvalidData` if there aren't exactly `width * height` of them
+    pub fn from_texels(width: usize, height: usize, texels: Vec<Vector3>) -> io::Result<Self> {
+        if width.checked_mul(height) != Some(texels.len()) {
+            return Err(invalid_data("texel count doesn't match the size"));
+        }
+        Ok(Environment::from_map(EnvironmentMap { width, height, texels }))
+    }
+
+    fn from_map(map: EnvironmentMap) -> Self {
+        let ambient = sh_ambient::project(|direction| map.sample_bilinear(direction_to_uv(direction)));
+        Environment { map: Some(map), ambient: Some(ambient) }
+    }
+
+    /// Loads a Radiance `.hdr` (RGBE) equirectangular image
+    pub fn load(path: &str) -> io::Result<Self> {
+        let bytes = fs::read(path)?;
+        let (width, height, texels) = parse_hdr(&bytes)?;
+        Environment::from_texels(width, height, texels)
+    }
+
+    /// Directional ambient tint for a surface facing `normal` (world space),
+    /// scaled so its average luminance is 1: multiplying a shader's scalar
+    /// ambient by it keeps the overall level and adds the map's direction
+    /// and color. Plain white for the starfield, which has nothing to add
+    pub fn ambient_fill(&self, normal: Vector3) -> Vector3 {
+        let Some(ambient) = &self.ambient else {
+            return Vector3::one();
+        };
+        let average = ambient.average();
+        let luminance = 0.2126 * average.x + 0.7152 * average.y + 0.0722 * average.z;
+        if luminance <= 0.0 {
+            return Vector3::one();
+        }
+        ambient.evaluate(normal) / luminance
+    }
+
+    /// Radiance arriving along `direction` (doesn't need to be normalized)
+    pub fn sample(&self, direction: Vector3) -> Vector3 {
+        match &self.map {
+            Some(map) => map.sample_bilinear(direction_to_uv(direction)),
+            None => starfield(direction),
+        }
+    }
+}
+
+/// Standard equirectangular mapping: u goes around the equator starting at
+/// -X (u = 0.5 looks down +X, 0.75 down +Z), v = 0 is straight up (+Y)
+pub fn direction_to_uv(direction: Vector3) -> Vector2 {
+    let d = direction.normalized();
+    Vector2::new(
+        0.5 + d.z.atan2(d.x) / (2.0 * PI),
+        0.5 - d.y.clamp(-1.0, 1.0).asin() / PI,
+    )
+}
+
+impl EnvironmentMap {
+    fn texel(&self, x: i64, y: i64) -> Vector3 {
+        // Envuelve horizontalmente (longitud), satura en los polos
+        let x = x.rem_euclid(self.width as i64) as usize;
+        let y = y.clamp(0, self.height as i64 - 1) as usize;
+        self.texels[y * self.width + x]
+    }
+
+    fn sample_bilinear(&self, uv: Vector2) -> Vector3 {
+        // Centros de texel en (i + 0.5) / tamaño
+        let x = uv.x * self.width as f32 - 0.5;
+        let y = uv.y * self.height as f32 - 0.5;
+        let x0 = x.floor();
+        let y0 = y.floor();
+        let fx = x - x0;
+        let fy = y - y0;
+        let (x0, y0) = (x0 as i64, y0 as i64);
+
+        let top = self.texel(x0, y0) * (1.0 - fx) + self.texel(x0 + 1, y0) * fx;
+        let bottom = self.texel(x0, y0 + 1) * (1.0 - fx) + self.texel(x0 + 1, y0 + 1) * fx;
+        top * (1.0 - fy) + bottom * fy
+    }
+}
+
+// Campo de estrellas procedural: una estrella posible por celda de la esfera
+fn starfield(direction: Vector3) -> Vector3 {
+    let background = Vector3::new(5.0 / 255.0, 5.0 / 255.0, 15.0 / 255.0);
+    let uv = direction_to_uv(direction);
+
+    let cell_x = (uv.x * 720.0).floor();
+    let cell_y = (uv.y * 360.0).floor();
+    let h = ((cell_x * 127.1 + cell_y * 311.7).sin() * 43758.547).fract().abs();
+
+    if h > 0.995 {
+        let brightness = (h - 0.995) / 0.005;
+        background + Vector3::new(0.8, 0.85, 1.0) * brightness
+    } else {
+        background
+    }
+}
+
+/// Fills every pixel with the environment seen along that pixel's view ray.
+/// Call right after clearing: depth is left untouched so geometry drawn
+/// afterwards covers the sky and the background still counts as empty.
+pub fn render_skybox(framebuffer: &mut Framebuffer, environment: &Environment, view_matrix: &Matrix,
+                     projection_matrix: &Matrix, viewport_matrix: &Matrix, exposure: f32) {
+    // Pantalla -> mundo: inversa de vista-proyección-viewport
+    let screen_to_world = (*view_matrix * *projection_matrix * *viewport_matrix).inverted();
+
+    let unproject = |x: f32, y: f32, z: f32| {
+        let p = multiply_matrix_vector4(&screen_to_world, &Vector4::new(x, y, z, 1.0));
+        Vector3::new(p.x / p.w, p.y / p.w, p.z / p.w)
+    };
+
+    for y in 0..framebuffer.height {
+        for x in 0..framebuffer.width {
+            let (sx, sy) = (x as f32 + 0.5, y as f32 + 0.5);
+            let direction = unproject(sx, sy, 1.0) - unproject(sx, sy, -1.0);
+
+            let color = environment.sample(direction) * exposure;
+            framebuffer.set_current_color(Color::new(
+                (color.x.clamp(0.0, 1.0) * 255.0) as u8,
+                (color.y.clamp(0.0, 1.0) * 255.0) as u8,
+                (color.z.clamp(0.0, 1.0) * 255.0) as u8,
+                255,
+            ));
+            framebuffer.set_pixel(x, y);
+        }
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+// Lector de Radiance RGBE: cabecera de texto, línea "-Y alto +X ancho" y
+// scanlines planas o con el RLE "nuevo" (un canal tras otro)
+fn parse_hdr(bytes: &[u8]) -> io::Result<(usize, usize, Vec<Vector3>)> {
+    let mut cursor = 0;
+    let read_line = |cursor: &mut usize| -> io::Result<String> {
+        let start = *cursor;
+        while *cursor < bytes.len() && bytes[*cursor] != b'\n' {
+            *cursor += 1;
+        }
+        if *cursor >= bytes.len() {
+            return Err(invalid_data("unexpected end of HDR header"));
+        }
+        let line = String::from_utf8_lossy(&bytes[start..*cursor]).trim().to_string();
+        *cursor += 1;
+        Ok(line)
+    };
+
+    let magic = read_line(&mut cursor)?;
+    if !magic.starts_with("#?") {
+        return Err(invalid_data("not a Radiance HDR file"));
+    }
+    loop {
+        let line = read_line(&mut cursor)?;
+        if line.is_empty() {
+            break;
+        }
+        if let Some(format) = line.strip_prefix("FORMAT=")
+            && format != "32-bit_rle_rgbe"
+        {
+            return Err(invalid_data("unsupported HDR pixel format"));
+        }
+    }
+
+    let resolution = read_line(&mut cursor)?;
+    let parts: Vec<&str> = resolution.split_whitespace().collect();
+    let (height, width) = match parts.as_slice() {
+        ["-Y", h, "+X", w] => (
+            h.parse::<usize>().map_err(|_| invalid_data("bad HDR height"))?,
+            w.parse::<usize>().map_err(|_| invalid_data("bad HDR width"))?,
+        ),
+        _ => return Err(invalid_data("unsupported HDR orientation")),
+    };
+
+    // El tamaño sale de la cabecera: se comprueba antes de reservar nada
+    let texel_count = width
+        .checked_mul(height)
+        .filter(|&count| count <= MAX_HDR_TEXELS)
+        .ok_or_else(|| invalid_data("HDR image too large"))?;
+    let mut texels = Vec::with_capacity(texel_count);
+    let mut scanline = vec![[0u8; 4]; width];
+    let next = |cursor: &mut usize| -> io::Result<u8> {
+        let byte = *bytes.get(*cursor).ok_or_else(|| invalid_data("truncated HDR pixel data"))?;
+        *cursor += 1;
+        Ok(byte)
+    };
+
+    for _ in 0..height {
+        let rle = (8..=0x7fff).contains(&width)
+            && bytes.get(cursor..cursor + 4).is_some_and(|head| {
+                head[0] == 2 && head[1] == 2 && ((head[2] as usize) << 8 | head[3] as usize) == width
+            });
+
+        if rle {
+            cursor += 4;
+            for channel in 0..4 {
+                let mut x = 0;
+                while x < width {
+                    let count = next(&mut cursor)? as usize;
+                    if count > 128 {
+                        let run = count - 128;
+                        let value = next(&mut cursor)?;
+                        if x + run > width {
+                            return Err(invalid_data("HDR run overflows the scanline"));
+                        }
+                        for pixel in &mut scanline[x..x + run] {
+                            pixel[channel] = value;
+                        }
+                        x += run;
+                    } else {
+                        if count == 0 || x + count > width {
+                            return Err(invalid_data("bad HDR literal run"));
+                        }
+                        for pixel in &mut scanline[x..x + count] {
+                            pixel[channel] = next(&mut cursor)?;
+                        }
+                        x += count;
+                    }
+                }
+            }
+        } else {
+            for pixel in scanline.iter_mut() {
+                for channel in pixel.iter_mut() {
+                    *channel = next(&mut cursor)?;
+                }
+            }
+        }
+
+        texels.extend(scanline.iter().map(|&[r, g, b, e]| rgbe_to_rgb(r, g, b, e)));
+    }
+
+    Ok((width, height, texels))
+}
+
+fn rgbe_to_rgb(r: u8, g: u8, b: u8, e: u8) -> Vector3 {
+    if e == 0 {
+        return Vector3::zero();
+    }
+    // Mantisa de 8 bits con exponente compartido (sesgo 128)
+    let scale = 2.0f32.powi(e as i32 - 136);
+    Vector3::new(r as f32 * scale, g as f32 * scale, b as f32 * scale)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hdr(resolution: &str, pixels: &[u8]) -> Vec<u8> {
+        let mut bytes = format!("#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n{}\n", resolution).into_bytes();
+        bytes.extend_from_slice(pixels);
+        bytes
+    }
+
+    #[test]
+    fn axes_map_to_their_equirectangular_uv() {
+        // Distancia en u dando la vuelta: -X cae en la costura, 0 o 1
+        let around = |a: f32, b: f32| {
+            let d = (a - b).rem_euclid(1.0);
+            d.min(1.0 - d)
+        };
+        let cases = [
+            (Vector3::new(-1.0, 0.0, 0.0), 0.0),
+            (Vector3::new(0.0, 0.0, -1.0), 0.25),
+            (Vector3::new(1.0, 0.0, 0.0), 0.5),
+            (Vector3::new(0.0, 0.0, 1.0), 0.75),
+        ];
+        for (direction, u) in cases {
+            let uv = direction_to_uv(direction * 3.0);
+            assert!(around(uv.x, u) < 1e-5 && (uv.y - 0.5).abs() < 1e-5, "{:?} -> {:?}", direction, uv);
+        }
+
+        // En los polos u no importa
+        assert!(direction_to_uv(Vector3::new(0.0, 2.0, 0.0)).y.abs() < 1e-5);
+        assert!((direction_to_uv(Vector3::new(0.0, -2.0, 0.0)).y - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn parses_flat_scanlines() {
+        // (128, 64, 0) con exponente 129: 0.5 * 2, 0.25 * 2 y 0
+        let (width, height, texels) = parse_hdr(&hdr("-Y 1 +X 2", &[128, 64, 0, 129, 0, 0, 0, 0])).unwrap();
+        assert_eq!((width, height), (2, 1));
+        assert!((texels[0] - Vector3::new(1.0, 0.5, 0.0)).length() < 0.01, "{:?}", texels[0]);
+        assert_eq!(texels[1], Vector3::zero());
+    }
+
+    #[test]
+    fn rejects_oversized_or_truncated_images() {
+        for resolution in ["-Y 100000 +X 100000", "-Y 18446744073709551615 +X 2"] {
+            let err = parse_hdr(&hdr(resolution, &[])).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{}", resolution);
+        }
+        let err = parse_hdr(&hdr("-Y 2 +X 2", &[0; 9])).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn from_texels_checks_the_count() {
+        let err = Environment::from_texels(4, 2, vec![Vector3::zero(); 7]).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(Environment::from_texels(2, 1, vec![Vector3::one(); 2]).is_ok());
+    }
+}
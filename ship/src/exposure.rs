@@ -0,0 +1,94 @@
+// exposure.rs
+use crate::framebuffer::Framebuffer;
+
+/// Eye adaptation: nudges the exposure every frame so the log-average
+/// luminance of the rendered image drifts toward `target_luminance`.
+pub struct AutoExposure {
+    pub enabled: bool,
+    pub target_luminance: f32,   // Luminancia media deseada (0..1)
+    pub adaptation_speed: f32,   // Qué tan rápido se adapta (1/segundos)
+    pub min_exposure: f32,
+    pub max_exposure: f32,
+    exposure: f32,
+}
+
+impl AutoExposure {
+    pub fn new(target_luminance: f32, adaptation_speed: f32) -> Self {
+        AutoExposure {
+            enabled: true,
+            target_luminance,
+            adaptation_speed,
+            min_exposure: 0.25,
+            max_exposure: 4.0,
+            exposure: 1.0,
+        }
+    }
+
+    /// Exposure to feed into the next frame's uniforms
+    pub fn exposure(&self) -> f32 {
+        if self.enabled { self.exposure } else { 1.0 }
+    }
+
+    /// Adapt toward the target given the luminance measured on the frame that
+    /// was rendered with the current exposure. Works in log space and blends
+    /// exponentially with `dt` so the result doesn't flicker frame to frame.
+    pub fn update(&mut self, measured_luminance: f32, dt: f32) -> f32 {
+        if !self.enabled {
+            return self.exposure();
+        }
+
+        let measured = measured_luminance.max(1e-4);
+        let desired = (self.exposure * self.target_luminance / measured)
+            .clamp(self.min_exposure, self.max_exposure);
+
+        let blend = 1.0 - (-self.adaptation_speed * dt).exp();
+        let log_exposure = self.exposure.ln() + (desired.ln() - self.exposure.ln()) * blend;
+        self.exposure = log_exposure.exp().clamp(self.min_exposure, self.max_exposure);
+        self.exposure
+    }
+
+    /// Measure the framebuffer and adapt in one step. Frames with no geometry
+    /// leave the exposure untouched.
+    pub fn adapt(&mut self, framebuffer: &Framebuffer, dt: f32) -> f32 {
+        match framebuffer.average_log_luminance() {
+            Some(luminance) => self.update(luminance, dt),
+            None => self.exposure(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Exposiciones de 60 frames de una escena cuya luminancia sin exponer es `base`
+    fn adapt_frames(base: f32) -> Vec<f32> {
+        let mut auto_exposure = AutoExposure::new(0.18, 2.0);
+        (0..60).map(|_| auto_exposure.update(base * auto_exposure.exposure(), 1.0 / 30.0)).collect()
+    }
+
+    #[test]
+    fn brightens_a_dark_frame_toward_the_target() {
+        let exposures = adapt_frames(0.05);
+        assert!(exposures.windows(2).all(|pair| pair[1] > pair[0]));
+        // Suave: el primer frame no salta hasta el objetivo
+        assert!(exposures[0] < 1.5);
+        assert!((0.05 * exposures[59] - 0.18).abs() < 0.01);
+    }
+
+    #[test]
+    fn darkens_a_bright_frame_down_to_the_minimum() {
+        let exposures = adapt_frames(1.0);
+        assert!(exposures.windows(2).all(|pair| pair[1] <= pair[0]));
+        assert!(exposures[0] > 0.5);
+        // El objetivo pediría 0.18: se queda en min_exposure
+        assert!((exposures[59] - 0.25).abs() < 0.01);
+    }
+
+    #[test]
+    fn disabled_keeps_unit_exposure() {
+        let mut auto_exposure = AutoExposure::new(0.18, 2.0);
+        auto_exposure.enabled = false;
+        assert_eq!(auto_exposure.update(0.01, 1.0), 1.0);
+    }
+}
@@ -0,0 +1,32 @@
+// file_watch.rs
+use std::fs;
+use std::time::SystemTime;
+
+/// Notices when a file is saved by polling its modification time, cheap
+/// enough to call once per frame
+pub struct FileWatch {
+    path: String,
+    modified: Option<SystemTime>, // None: el archivo no existe (o no se pudo leer)
+}
+
+impl FileWatch {
+    /// Starts from the file as it is now: only later changes count
+    pub fn new(path: &str) -> Self {
+        FileWatch { path: path.to_string(), modified: modified_time(path) }
+    }
+
+    /// True once per change of the modification time, including the file
+    /// being created. A deleted file isn't a change
+    pub fn changed(&mut self) -> bool {
+        let modified = modified_time(&self.path);
+        if modified == self.modified {
+            return false;
+        }
+        self.modified = modified;
+        modified.is_some()
+    }
+}
+
+fn modified_time(path: &str) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
@@ -0,0 +1,129 @@
+// flight.rs
+use raylib::prelude::*;
+use crate::camera::Camera;
+use crate::quat::Quaternion;
+
+// Aceleración de W/S y velocidad máxima, en unidades de la escena por segundo
+const THRUST: f32 = 4.0;
+const MAX_SPEED: f32 = 8.0;
+// Giro de A/D, flechas y Z/C, en radianes por segundo
+const TURN_RATE: f32 = 1.5;
+// Cámara en tercera persona: detrás de la nave y un poco por encima
+const FOLLOW_DISTANCE: f32 = 3.0;
+const FOLLOW_HEIGHT: f32 = 0.5;
+
+/// The ship flown in flight mode. Like the camera's, its orientation takes
+/// -Z to the nose and +Y to the ship's up
+pub struct Ship {
+    pub position: Vector3,
+    pub orientation: Quaternion,
+    pub speed: f32, // Along the nose, never backwards
+}
+
+impl Ship {
+    pub fn new(position: Vector3, orientation: Quaternion) -> Self {
+        Ship { position, orientation, speed: 0.0 }
+    }
+
+    /// Unit direction of the nose
+    pub fn forward(&self) -> Vector3 {
+        self.orientation.rotate(Vector3::new(0.0, 0.0, -1.0))
+    }
+
+    /// Unit up of the ship, roll included
+    pub fn up(&self) -> Vector3 {
+        self.orientation.rotate(Vector3::new(0.0, 1.0, 0.0))
+    }
+
+    /// Unit right wing, `forward` x `up`
+    pub fn right(&self) -> Vector3 {
+        self.orientation.rotate(Vector3::new(1.0, 0.0, 0.0))
+    }
+
+    /// Turns the ship around its own axes and moves it `dt` seconds along
+    /// the nose. `yaw` positive turns left, `pitch` positive raises the nose
+    /// and `roll` positive banks right, each in units of the turn rate.
+    /// `thrust` is -1 (brake) to 1 (accelerate)
+    pub fn steer(&mut self, yaw: f32, pitch: f32, roll: f32, thrust: f32, dt: f32) {
+        let angle = TURN_RATE * dt;
+        let yaw_rotation = Quaternion::from_axis_angle(self.up(), yaw * angle);
+        let pitch_rotation = Quaternion::from_axis_angle(self.right(), pitch * angle);
+        let roll_rotation = Quaternion::from_axis_angle(self.forward(), roll * angle);
+        // Renormalizada: los errores de redondeo se acumulan frame a frame
+        self.orientation = (roll_rotation * pitch_rotation * yaw_rotation * self.orientation).normalized();
+
+        self.speed = (self.speed + thrust * THRUST * dt).clamp(0.0, MAX_SPEED);
+        self.position += self.forward() * self.speed * dt;
+    }
+
+    /// Flies the ship with the keyboard: W/S accelerate and brake, A/D turn,
+    /// Up/Down raise and lower the nose and Z/C roll
+    pub fn process_input(&mut self, window: &RaylibHandle, dt: f32) {
+        let axis = |positive: KeyboardKey, negative: KeyboardKey| {
+            window.is_key_down(positive) as i32 as f32 - window.is_key_down(negative) as i32 as f32
+        };
+        self.steer(
+            axis(KeyboardKey::KEY_A, KeyboardKey::KEY_D),
+            axis(KeyboardKey::KEY_UP, KeyboardKey::KEY_DOWN),
+            axis(KeyboardKey::KEY_C, KeyboardKey::KEY_Z),
+            axis(KeyboardKey::KEY_W, KeyboardKey::KEY_S),
+            dt,
+        );
+    }
+
+    /// Puts `camera` behind and above the ship, looking along its nose with
+    /// its roll. The camera orbits the point above the ship, so leaving
+    /// flight mode keeps the ship on screen
+    pub fn follow(&self, camera: &mut Camera) {
+        camera.orientation = self.orientation;
+        camera.target = self.position + self.up() * FOLLOW_HEIGHT;
+        camera.distance = FOLLOW_DISTANCE;
+        camera.update_eye_position();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: Vector3, b: Vector3) {
+        assert!((a - b).length() < 1e-4, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn thrust_moves_along_the_nose() {
+        let mut ship = Ship::new(Vector3::zero(), Quaternion::look_rotation(Vector3::new(1.0, 0.0, 0.0), Vector3::new(0.0, 1.0, 0.0)));
+        ship.steer(0.0, 0.0, 0.0, 1.0, 0.5);
+        assert!((ship.speed - THRUST * 0.5).abs() < 1e-6);
+        assert_close(ship.position, Vector3::new(ship.speed * 0.5, 0.0, 0.0));
+
+        // Frenar nunca da marcha atrás
+        ship.steer(0.0, 0.0, 0.0, -1.0, 10.0);
+        assert_eq!(ship.speed, 0.0);
+    }
+
+    #[test]
+    fn yaw_turns_left_and_pitch_raises_the_nose() {
+        let dt = std::f32::consts::FRAC_PI_2 / TURN_RATE; // Un cuarto de vuelta
+        let mut ship = Ship::new(Vector3::zero(), Quaternion::look_rotation(Vector3::new(0.0, 0.0, -1.0), Vector3::new(0.0, 1.0, 0.0)));
+        ship.steer(1.0, 0.0, 0.0, 0.0, dt);
+        assert_close(ship.forward(), Vector3::new(-1.0, 0.0, 0.0));
+        ship.steer(0.0, 1.0, 0.0, 0.0, dt);
+        assert_close(ship.forward(), Vector3::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn follow_puts_the_ship_ahead_of_the_camera() {
+        let up = Vector3::new(0.0, 1.0, 0.0);
+        let ship = Ship::new(Vector3::new(2.0, 1.0, -4.0), Quaternion::look_rotation(Vector3::new(0.0, -1.0, 0.0), Vector3::new(1.0, 0.0, 0.0)));
+        let mut camera = Camera::new(Vector3::new(0.0, 0.0, 10.0), Vector3::zero(), up);
+        ship.follow(&mut camera);
+
+        // Mirando hacia abajo también: la misma base que la nave
+        assert_close(camera.forward(), ship.forward());
+        assert_close(camera.up(), ship.up());
+        let to_ship = ship.position - camera.eye;
+        assert!((to_ship.dot(camera.forward()) - FOLLOW_DISTANCE).abs() < 1e-4);
+        assert!((to_ship.dot(camera.up()) + FOLLOW_HEIGHT).abs() < 1e-4);
+    }
+}
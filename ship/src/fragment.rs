@@ -1,11 +1,19 @@
 // fragment.rs
-use raylib::prelude::Vector3;
+use raylib::prelude::{Vector2, Vector3, Vector4};
 
 pub struct Fragment {
     pub position: Vector3, // screen position
     pub color: Vector3,
     pub depth: f32,
     pub world_position: Vector3,
+    pub barycentric: Vector3, // pesos en pantalla (w1, w2, w3) del triángulo que lo generó
+    pub signed_area: f32,     // Doble del área con signo del triángulo en pantalla (su sentido de giro)
+    pub depth_slope: f32,     // Pendiente máxima de la profundidad del triángulo por píxel (max |dz/dx|, |dz/dy|)
+    pub normal: Vector3,      // Normal de la malla interpolada, en espacio de objeto
+    pub material_id: usize,   // Material de la cara (el de su primer vértice)
+    pub tex_coords: Vector2,  // UV interpolada
+    pub vertex_color: Vector3, // Color por vértice de la malla interpolado
+    pub tangent: Vector4,     // Tangente de la malla interpolada (w: signo de la bitangente), en espacio de objeto
 }
 
 impl Fragment {
@@ -15,6 +23,14 @@ impl Fragment {
             color,
             depth,
             world_position,
+            barycentric: Vector3::new(0.0, 0.0, 0.0),
+            signed_area: 0.0,
+            depth_slope: 0.0,
+            normal: Vector3::new(0.0, 0.0, 0.0),
+            material_id: 0,
+            tex_coords: Vector2::new(0.0, 0.0),
+            vertex_color: Vector3::new(0.0, 0.0, 0.0),
+            tangent: Vector4::new(0.0, 0.0, 0.0, 0.0),
         }
     }
 }
\ No newline at end of file
@@ -1,5 +1,11 @@
 // framebuffer.rs
+use crate::jitter::Jitter;
+use crate::parallel::{parallel_points, threads_for};
+use crate::png;
+use crate::stats::RenderStats;
 use raylib::prelude::*;
+use std::fs;
+use std::thread;
 
 pub struct Framebuffer {
     pub width: i32,
@@ -8,6 +14,118 @@ pub struct Framebuffer {
     background_color: Color,
     current_color: Color,
     depth_buffer: Vec<f32>,
+    overdraw_buffer: Vec<u32>,
+    dither_frame: u32, // Desplaza los umbrales de BlendMode::Dithered entre muestras
+    stats: RenderStats,
+    samples_per_axis: i32,  // Supersampling: submuestras por lado de cada píxel (1 = sin AA)
+    resolved: Option<Image>, // Imagen a resolución de pantalla, solo con supersampling
+    display_texture: Option<Texture2D>, // Textura en la GPU que muestra `swap_buffers`, creada en el primer frame
+    upload: Vec<u8>, // Píxeles RGBA8 que se suben a esa textura, reutilizado entre frames
+    viewport: Viewport, // Región donde caen las escrituras (todo el buffer por defecto)
+}
+
+/// A rectangle of the framebuffer in whole pixels, `w` x `h` from its
+/// top-left corner (`x`, `y`). While one is set with
+/// `Framebuffer::set_viewport`, writes outside it are dropped, so a view
+/// can be drawn into part of the buffer (split screen, a rear view)
+/// without touching the rest
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Viewport {
+    pub x: i32,
+    pub y: i32,
+    pub w: i32,
+    pub h: i32,
+}
+
+impl Viewport {
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.x && x < self.x + self.w && y >= self.y && y < self.y + self.h
+    }
+
+    pub fn rectangle(&self) -> Rectangle {
+        Rectangle::new(self.x as f32, self.y as f32, self.w as f32, self.h as f32)
+    }
+}
+
+// Color lineal en [0, 1] a 8 bits por canal, opaco
+fn pixel_color(color: Vector3) -> Color {
+    Color::new(
+        (color.x.clamp(0.0, 1.0) * 255.0) as u8,
+        (color.y.clamp(0.0, 1.0) * 255.0) as u8,
+        (color.z.clamp(0.0, 1.0) * 255.0) as u8,
+        255,
+    )
+}
+
+/// Flat color that stands for object `id` in a pick pass, for
+/// `Framebuffer::read_id_at` to decode: `id + 1` in 24 bits, so black is
+/// left for the background. Each channel sits in the middle of its 8-bit
+/// step, so the conversion to the buffer gives back that exact byte
+pub fn id_color(id: u32) -> Vector3 {
+    let encoded = id + 1;
+    let channel = |shift: u32| (((encoded >> shift) & 0xff) as f32 + 0.5) / 255.0;
+    Vector3::new(channel(0), channel(8), channel(16))
+}
+
+// Conteo a partir del cual el heatmap de overdraw satura en rojo
+const OVERDRAW_SATURATION: u32 = 8;
+
+// Matriz de Bayer 4x4: orden en que se encienden los píxeles de cada bloque
+const BAYER_4X4: [[u32; 4]; 4] = [
+    [0, 8, 2, 10],
+    [12, 4, 14, 6],
+    [3, 11, 1, 9],
+    [15, 7, 13, 5],
+];
+
+/// How `Framebuffer::write` combines a source color with the pixel already
+/// in the buffer. Only `Replace` writes depth: the blended modes are for
+/// translucent layers (glows, particles, flares) that must not occlude
+/// what's drawn after them.
+///
+/// `Dithered` is the exception: stochastic transparency without sorting.
+/// The fragment is written opaque, depth included, where `a` beats the
+/// pixel's dither threshold and discarded elsewhere, so a fraction `a` of
+/// the pixels is covered. Noisy on its own; averaged over accumulation
+/// samples (each with its own threshold pattern) it converges to coverage `a`.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum BlendMode {
+    #[default]
+    Replace,   // src
+    AlphaOver, // src * a + dst * (1 - a)
+    Additive,  // dst + src * a
+    #[allow(dead_code)] // Todavía no la usa ninguna capa
+    Multiply,  // dst * mix(1, src, a)
+    Dithered,  // src si a > umbral del píxel; si no, se descarta
+}
+
+impl BlendMode {
+    fn blend(self, dst: Vector3, src: Vector3, alpha: f32) -> Vector3 {
+        match self {
+            BlendMode::Replace | BlendMode::Dithered => src,
+            BlendMode::AlphaOver => src * alpha + dst * (1.0 - alpha),
+            BlendMode::Additive => dst + src.max(Vector3::zero()) * alpha,
+            BlendMode::Multiply => dst * (Vector3::one() * (1.0 - alpha) + src * alpha),
+        }
+    }
+}
+
+/// Depth offset against z-fighting between coplanar or nearly coplanar
+/// geometry, like the rings at the planet's equator: `constant` plus
+/// `slope_scale` times the triangle's depth slope (see
+/// `triangle::depth_slope`), so a triangle seen nearly edge-on, whose depth
+/// changes a lot from one pixel to the next, is pushed back more than one
+/// seen flat-on. Positive values push away from the camera.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct DepthBias {
+    pub constant: f32,
+    pub slope_scale: f32,
+}
+
+impl DepthBias {
+    pub fn apply(&self, depth: f32, slope: f32) -> f32 {
+        depth + self.constant + self.slope_scale * slope
+    }
 }
 
 impl Framebuffer {
@@ -15,6 +133,7 @@ impl Framebuffer {
         let background_color = Color::BLACK; // Un color por defecto
         let color_buffer = Image::gen_image_color(width, height, background_color);
         let depth_buffer = vec![f32::INFINITY; (width * height) as usize];
+        let overdraw_buffer = vec![0; (width * height) as usize];
         Framebuffer {
             width,
             height,
@@ -22,37 +141,431 @@ impl Framebuffer {
             background_color,
             current_color: Color::WHITE,
             depth_buffer,
+            overdraw_buffer,
+            dither_frame: 0,
+            stats: RenderStats::default(),
+            samples_per_axis: 1,
+            resolved: None,
+            display_texture: None,
+            upload: Vec::new(),
+            viewport: Viewport { x: 0, y: 0, w: width, h: height },
         }
     }
 
+    /// Supersampled framebuffer for a `width` x `height` display: `samples`
+    /// subsamples per pixel on a square grid (4 = 2x2, 9 = 3x3, 16 = 4x4;
+    /// other counts round down to a square). Everything is drawn at the
+    /// subsample resolution, so `width` and `height` are that size, and
+    /// `resolve` averages each pixel's subsamples into the image that
+    /// `swap_buffers` shows.
+    ///
+    /// Memory grows with the count: color, depth and overdraw take 12 bytes
+    /// per subsample, so 2x2 at 1600x900 is about 69 MB instead of 17 MB,
+    /// plus the 5.8 MB display image.
+    pub fn new_with_samples(width: i32, height: i32, samples: u32) -> Self {
+        let per_axis = ((samples.max(1) as f32).sqrt().floor() as i32).max(1);
+        let mut framebuffer = Framebuffer::new(width * per_axis, height * per_axis);
+        framebuffer.samples_per_axis = per_axis;
+        if per_axis > 1 {
+            framebuffer.resolved = Some(Image::gen_image_color(width, height, Color::BLACK));
+        }
+        framebuffer
+    }
+
+    /// Reallocates every buffer for a `width` x `height` display, keeping
+    /// the supersampling: color back to the background, depth to infinity,
+    /// overdraw to zero and the viewport to the whole buffer. Nothing of the
+    /// old size is kept, so the next frame draws and reads at the new one;
+    /// `swap_buffers` sees the new size and makes a new display texture.
+    /// Does nothing if the size is the same
+    pub fn resize(&mut self, width: i32, height: i32) {
+        let (display_width, display_height) = (width.max(1), height.max(1));
+        let per_axis = self.samples_per_axis;
+        let (width, height) = (display_width * per_axis, display_height * per_axis);
+        if (width, height) == (self.width, self.height) {
+            return;
+        }
+
+        self.width = width;
+        self.height = height;
+        self.color_buffer = Image::gen_image_color(width, height, self.background_color);
+        let pixel_count = (width * height) as usize;
+        self.depth_buffer.clear();
+        self.depth_buffer.resize(pixel_count, f32::INFINITY);
+        self.overdraw_buffer.clear();
+        self.overdraw_buffer.resize(pixel_count, 0);
+        if self.resolved.is_some() {
+            self.resolved = Some(Image::gen_image_color(display_width, display_height, Color::BLACK));
+        }
+        self.reset_viewport();
+    }
+
+    /// Subsamples per pixel (1 without supersampling)
+    pub fn samples(&self) -> u32 {
+        (self.samples_per_axis * self.samples_per_axis) as u32
+    }
+
+    /// Subsamples per pixel side: display pixel (x, y) covers the buffer
+    /// pixels from (x, y) times this
+    pub fn samples_per_axis(&self) -> i32 {
+        self.samples_per_axis
+    }
+
+    /// Averages every pixel's subsamples into the display image. Call it
+    /// once the frame is finished, before `swap_buffers` or `save_to_png`;
+    /// without supersampling it does nothing
+    pub fn resolve(&mut self) {
+        let Some(resolved) = &mut self.resolved else {
+            return;
+        };
+        let pixels = self.color_buffer.get_image_data();
+        let per_axis = self.samples_per_axis;
+        let count = (per_axis * per_axis) as u32;
+
+        for y in 0..self.height / per_axis {
+            for x in 0..self.width / per_axis {
+                let mut sum = [0u32; 3];
+                for sy in 0..per_axis {
+                    let row = ((y * per_axis + sy) * self.width + x * per_axis) as usize;
+                    for pixel in &pixels[row..row + per_axis as usize] {
+                        sum[0] += pixel.r as u32;
+                        sum[1] += pixel.g as u32;
+                        sum[2] += pixel.b as u32;
+                    }
+                }
+                // Redondeo al más cercano
+                let average = |total: u32| ((total + count / 2) / count) as u8;
+                resolved.draw_pixel(x, y, Color::new(average(sum[0]), average(sum[1]), average(sum[2]), 255));
+            }
+        }
+    }
+
+    /// What reaches the screen: the resolved image with supersampling, the
+    /// color buffer itself without it
+    pub fn display_image(&self) -> &Image {
+        self.resolved.as_ref().unwrap_or(&self.color_buffer)
+    }
+
+    /// Region that writes are limited to
+    pub fn viewport(&self) -> Viewport {
+        self.viewport
+    }
+
+    /// Limits every write (and `clear`) to `viewport`, cut to the buffer
+    pub fn set_viewport(&mut self, viewport: Viewport) {
+        let (left, top) = (viewport.x.clamp(0, self.width), viewport.y.clamp(0, self.height));
+        let right = (viewport.x + viewport.w).clamp(left, self.width);
+        let bottom = (viewport.y + viewport.h).clamp(top, self.height);
+        self.viewport = Viewport { x: left, y: top, w: right - left, h: bottom - top };
+    }
+
+    /// Writes reach the whole buffer again
+    pub fn reset_viewport(&mut self) {
+        self.viewport = Viewport { x: 0, y: 0, w: self.width, h: self.height };
+    }
+
+    // El viewport cubre todo el buffer: se puede limpiar de una vez
+    fn viewport_is_full(&self) -> bool {
+        self.viewport == Viewport { x: 0, y: 0, w: self.width, h: self.height }
+    }
+
+    // Índices de cada fila del viewport en los buffers de profundidad y overdraw
+    fn viewport_rows(&self) -> impl Iterator<Item = std::ops::Range<usize>> + use<> {
+        let Viewport { x, y, w, h } = self.viewport;
+        let width = self.width;
+        (y..y + h).map(move |row| (row * width + x) as usize..(row * width + x + w) as usize)
+    }
+
+    /// Starts a new frame in the viewport: color, depth and overdraw inside
+    /// it reset, and the stats
     pub fn clear(&mut self) {
-        self.color_buffer.clear_background(self.background_color);
-        self.depth_buffer.fill(f32::INFINITY);
+        self.clear_color();
+        self.clear_depth();
+        if self.viewport_is_full() {
+            self.overdraw_buffer.fill(0);
+        } else {
+            for row in self.viewport_rows() {
+                self.overdraw_buffer[row].fill(0);
+            }
+        }
+        self.stats = RenderStats::default();
+    }
+
+    /// Only the color in the viewport back to the background; depth is kept
+    pub fn clear_color(&mut self) {
+        if self.viewport_is_full() {
+            self.color_buffer.clear_background(self.background_color);
+        } else {
+            let Viewport { x, y, w, h } = self.viewport;
+            self.color_buffer.draw_rectangle(x, y, w, h, self.background_color);
+        }
+    }
+
+    /// Only the depth in the viewport back to empty, so the next pass draws
+    /// over everything already in the color buffer regardless of its depth
+    pub fn clear_depth(&mut self) {
+        if self.viewport_is_full() {
+            self.depth_buffer.fill(f32::INFINITY);
+        } else {
+            for row in self.viewport_rows() {
+                self.depth_buffer[row].fill(f32::INFINITY);
+            }
+        }
+    }
+
+    /// Counters of the frame drawn since the last `clear`
+    pub fn stats(&self) -> RenderStats {
+        self.stats
+    }
+
+    /// Records triangles sent to the rasterizer, and how many of them produced no fragments
+    pub fn count_triangles(&mut self, submitted: u32, culled: u32) {
+        self.stats.triangles_submitted += submitted;
+        self.stats.triangles_culled += culled;
     }
 
     pub fn set_pixel(&mut self, x: i32, y: i32) {
-        if x >= 0 && x < self.width && y >= 0 && y < self.height {
+        if self.viewport.contains(x, y) {
             self.color_buffer.draw_pixel(x, y, self.current_color);
         }
     }
     
     pub fn point(&mut self, x: i32, y: i32, color: Vector3, depth: f32) {
-        if x >= 0 && x < self.width && y >= 0 && y < self.height {
+        if self.viewport.contains(x, y) {
             let index = (y * self.width + x) as usize;
+            self.overdraw_buffer[index] += 1;
+            self.stats.fragments += 1;
 
             if depth < self.depth_buffer[index] {
                 self.depth_buffer[index] = depth;
-                let pixel_color = Color::new(
-                    (color.x.clamp(0.0, 1.0) * 255.0) as u8,
-                    (color.y.clamp(0.0, 1.0) * 255.0) as u8,
-                    (color.z.clamp(0.0, 1.0) * 255.0) as u8,
-                    255,
-                );
-                self.color_buffer.draw_pixel(x, y, pixel_color);
+                self.color_buffer.draw_pixel(x, y, pixel_color(color));
             }
         }
     }
     
+    /// `point` for every `(x, y, color, depth)` of `points`. With
+    /// `parallel::set_parallel_points` the depth tests are split across
+    /// render threads: one pass sorts the points into bands of rows, keeping
+    /// their order, and each thread tests only its own band; the writes that
+    /// pass are then applied band by band. Bands share no pixels, so the
+    /// result is exactly that of calling `point` for each in turn, for any
+    /// thread count.
+    pub fn points(&mut self, points: &[(i32, i32, Vector3, f32)]) {
+        let threads = if parallel_points() { threads_for(points.len()) } else { 1 };
+        if threads == 1 {
+            for &(x, y, color, depth) in points {
+                self.point(x, y, color, depth);
+            }
+            return;
+        }
+
+        let (width, height, viewport) = (self.width, self.height, self.viewport);
+        let band_rows = (height as usize).div_ceil(threads) as i32;
+        let band_len = (band_rows * width) as usize;
+
+        // Un solo recorrido del lote: cada hilo recibe solo los puntos de su banda
+        let mut buckets = vec![Vec::new(); (height as usize).div_ceil(band_rows as usize)];
+        for &(x, y, color, depth) in points {
+            if viewport.contains(x, y) {
+                buckets[(y / band_rows) as usize].push((x, y, color, depth));
+            }
+        }
+
+        let bands: Vec<_> = thread::scope(|scope| {
+            let handles: Vec<_> = self
+                .depth_buffer
+                .chunks_mut(band_len)
+                .zip(self.overdraw_buffer.chunks_mut(band_len))
+                .zip(&buckets)
+                .enumerate()
+                .map(|(band, ((depth_buffer, overdraw_buffer), bucket))| {
+                    scope.spawn(move || {
+                        let first_row = band as i32 * band_rows;
+                        let mut writes = Vec::new();
+                        for &(x, y, color, depth) in bucket {
+                            let index = ((y - first_row) * width + x) as usize;
+                            overdraw_buffer[index] += 1;
+                            if depth < depth_buffer[index] {
+                                depth_buffer[index] = depth;
+                                writes.push((x, y, pixel_color(color)));
+                            }
+                        }
+                        writes
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|handle| handle.join().expect("render thread panicked")).collect()
+        });
+
+        self.stats.fragments += buckets.iter().map(|bucket| bucket.len() as u32).sum::<u32>();
+        for writes in bands {
+            for (x, y, color) in writes {
+                self.color_buffer.draw_pixel(x, y, color);
+            }
+        }
+    }
+
+    /// Depth-tested write combining `color` with the buffer through `mode`;
+    /// `alpha` in [0, 1] scales the source. `point()` is the fast path for
+    /// `BlendMode::Replace` with full alpha.
+    pub fn write(&mut self, x: i32, y: i32, color: Vector3, alpha: f32, depth: f32, mode: BlendMode) {
+        if self.viewport.contains(x, y) {
+            let index = (y * self.width + x) as usize;
+            self.overdraw_buffer[index] += 1;
+            self.stats.fragments += 1;
+
+            if mode == BlendMode::Dithered && alpha <= self.dither_threshold(x, y) {
+                return;
+            }
+
+            if depth < self.depth_buffer[index] {
+                if mode == BlendMode::Replace || mode == BlendMode::Dithered {
+                    self.depth_buffer[index] = depth;
+                }
+                let dst = self.color_buffer.get_color(x, y);
+                let dst = Vector3::new(dst.r as f32, dst.g as f32, dst.b as f32) / 255.0;
+                let alpha = if mode == BlendMode::Dithered { 1.0 } else { alpha.clamp(0.0, 1.0) };
+                let color = mode.blend(dst, color, alpha);
+                self.color_buffer.draw_pixel(x, y, pixel_color(color));
+            }
+        }
+    }
+
+    /// Threshold in (0, 1) that `BlendMode::Dithered` compares alpha against:
+    /// the 4x4 Bayer pattern, shifted each dither frame by the golden ratio
+    /// so successive samples cover different pixels
+    pub fn dither_threshold(&self, x: i32, y: i32) -> f32 {
+        let bayer = (BAYER_4X4[(y & 3) as usize][(x & 3) as usize] as f32 + 0.5) / 16.0;
+        (bayer + self.dither_frame as f32 * 0.618_034).fract()
+    }
+
+    /// Selects the dither pattern for the next writes (one per accumulation sample)
+    pub fn set_dither_frame(&mut self, frame: u32) {
+        self.dither_frame = frame;
+    }
+
+    /// Additive write for glows: depth-tested against the scene but doesn't
+    /// write depth, so translucent shells never occlude what's behind them
+    pub fn add_point(&mut self, x: i32, y: i32, color: Vector3, depth: f32) {
+        self.write(x, y, color, 1.0, depth, BlendMode::Additive);
+    }
+
+    /// Translucent write, `color * alpha + existing * (1 - alpha)`: depth-tested
+    /// but doesn't write depth, so whatever is drawn behind it later isn't hidden
+    pub fn blend_point(&mut self, x: i32, y: i32, color: Vector3, alpha: f32, depth: f32) {
+        self.write(x, y, color, alpha, depth, BlendMode::AlphaOver);
+    }
+
+    /// Antialiased line `width` pixels wide from `p0` to `p1` (x, y in
+    /// pixels, z the depth, interpolated along it). Each pixel is covered
+    /// by how far its center is from the segment, falling off over the
+    /// last pixel at each edge, and alpha-blended with `color.a` times that
+    /// coverage; it is depth-tested but, like every blended write, writes no
+    /// depth. The ends are cut square and half open: a pixel belongs to the
+    /// segment while its center projects in [p0, p1), so the segments of a
+    /// polyline meet without blending twice or leaving gaps where they
+    /// continue straight.
+    pub fn draw_line_thick(&mut self, p0: Vector3, p1: Vector3, color: Color, width: f32) {
+        let direction = Vector2::new(p1.x - p0.x, p1.y - p0.y);
+        let length_sq = direction.dot(direction);
+        if length_sq < 1e-12 {
+            return;
+        }
+        let rgb = Vector3::new(color.r as f32, color.g as f32, color.b as f32) / 255.0;
+        let alpha = color.a as f32 / 255.0;
+        let half_width = width.max(0.0) / 2.0;
+
+        // Caja del segmento más el ancho, recortada al viewport
+        let reach = half_width + 1.0;
+        let Viewport { x: view_x, y: view_y, w: view_w, h: view_h } = self.viewport;
+        let min_x = ((p0.x.min(p1.x) - reach).floor() as i32).max(view_x);
+        let max_x = ((p0.x.max(p1.x) + reach).ceil() as i32).min(view_x + view_w - 1);
+        let min_y = ((p0.y.min(p1.y) - reach).floor() as i32).max(view_y);
+        let max_y = ((p0.y.max(p1.y) + reach).ceil() as i32).min(view_y + view_h - 1);
+
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                // Centro del píxel proyectado sobre el segmento
+                let center = Vector2::new(x as f32 + 0.5 - p0.x, y as f32 + 0.5 - p0.y);
+                let t = center.dot(direction) / length_sq;
+                if !(0.0..1.0).contains(&t) {
+                    continue;
+                }
+                let distance = (center - direction * t).length();
+                let coverage = (half_width + 0.5 - distance).clamp(0.0, 1.0);
+                if coverage > 0.0 {
+                    let depth = p0.z + (p1.z - p0.z) * t;
+                    self.write(x, y, rgb, alpha * coverage, depth, BlendMode::AlphaOver);
+                }
+            }
+        }
+    }
+
+    /// Depth-only write: keeps the nearest depth without touching the color
+    pub fn depth_point(&mut self, x: i32, y: i32, depth: f32) {
+        if self.viewport.contains(x, y) {
+            let index = (y * self.width + x) as usize;
+            if depth < self.depth_buffer[index] {
+                self.depth_buffer[index] = depth;
+            }
+        }
+    }
+
+    /// Nearest depth written at (x, y); infinity for background and out of bounds
+    pub fn depth_at(&self, x: i32, y: i32) -> f32 {
+        self.get_depth(x, y).unwrap_or(f32::INFINITY)
+    }
+
+    /// Stored depth at (x, y) (NDC z, infinity for background; see
+    /// `post::linear_depth` for the distance), None out of bounds
+    pub fn get_depth(&self, x: i32, y: i32) -> Option<f32> {
+        if x >= 0 && x < self.width && y >= 0 && y < self.height {
+            Some(self.depth_buffer[(y * self.width + x) as usize])
+        } else {
+            None
+        }
+    }
+
+    /// The whole depth buffer, row by row, for bulk reads
+    pub fn depth_buffer_slice(&self) -> &[f32] {
+        &self.depth_buffer
+    }
+
+    /// Largest rectangle of aspect `target_aspect` (width / height) centered
+    /// in the viewport, in whole pixels: bars above and below when the
+    /// target is wider than the viewport, at the sides when it's narrower
+    pub fn letterbox_rect(&self, target_aspect: f32) -> Rectangle {
+        let Viewport { x, y, w, h } = self.viewport;
+        let aspect = w as f32 / h.max(1) as f32;
+        let (width, height) = if target_aspect > aspect {
+            (w, ((w as f32 / target_aspect).round() as i32).clamp(1, h.max(1)))
+        } else {
+            (((h as f32 * target_aspect).round() as i32).clamp(1, w.max(1)), h)
+        };
+        Rectangle::new(
+            (x + (w - width) / 2) as f32,
+            (y + (h - height) / 2) as f32,
+            width as f32,
+            height as f32,
+        )
+    }
+
+    /// Fills everything in the viewport outside `rect` with black and empty
+    /// depth (the letterbox bars)
+    pub fn clear_outside(&mut self, rect: Rectangle) {
+        let (left, top) = (rect.x as i32, rect.y as i32);
+        let (right, bottom) = (left + rect.width as i32, top + rect.height as i32);
+        let Viewport { x: view_x, y: view_y, w: view_w, h: view_h } = self.viewport;
+        for y in view_y..view_y + view_h {
+            for x in view_x..view_x + view_w {
+                if x < left || x >= right || y < top || y >= bottom {
+                    self.depth_buffer[(y * self.width + x) as usize] = f32::INFINITY;
+                    self.color_buffer.draw_pixel(x, y, Color::BLACK);
+                }
+            }
+        }
+    }
+
     pub fn get_pixel_color(&mut self, x: i32, y: i32) -> Option<Color> {
         if x >= 0 && x < self.width && y >= 0 && y < self.height {
             Some(self.color_buffer.get_color(x, y))
@@ -61,6 +574,134 @@ impl Framebuffer {
         }
     }
 
+    /// Object id drawn at (x, y) by a pick pass that wrote `id_color` as
+    /// each object's color over a black background. None for background,
+    /// and out of bounds
+    pub fn read_id_at(&mut self, x: i32, y: i32) -> Option<u32> {
+        let color = self.get_pixel_color(x, y)?;
+        let encoded = color.r as u32 | (color.g as u32) << 8 | (color.b as u32) << 16;
+        encoded.checked_sub(1)
+    }
+
+    /// Snapshot of the whole color buffer as tightly packed RGBA8, row by row
+    #[allow(dead_code)] // Para los tests: comparan frames enteros
+    pub fn to_rgba_bytes(&self) -> Vec<u8> {
+        let pixels = self.color_buffer.get_image_data();
+        let mut bytes = Vec::with_capacity(pixels.len() * 4);
+        for pixel in pixels.iter() {
+            bytes.extend_from_slice(&[pixel.r, pixel.g, pixel.b, pixel.a]);
+        }
+        bytes
+    }
+
+    /// Writes the displayed image to `path` as a PNG (the resolved one with
+    /// supersampling, so `resolve` first). A missing directory or any other
+    /// write failure comes back as the error, with the path in it
+    pub fn save_to_png(&self, path: &str) -> Result<(), String> {
+        let image = self.display_image();
+        let mut rgba = Vec::with_capacity((image.width * image.height * 4) as usize);
+        for pixel in image.get_image_data().iter() {
+            rgba.extend_from_slice(&[pixel.r, pixel.g, pixel.b, pixel.a]);
+        }
+        let png = png::encode_rgba(image.width as u32, image.height as u32, &rgba);
+        fs::write(path, png).map_err(|err| format!("{}: {}", path, err))
+    }
+
+    /// Snapshot of the whole color buffer as normalized RGB in [0, 1]
+    pub fn to_vector3_buffer(&self) -> Vec<Vector3> {
+        self.color_buffer
+            .get_image_data()
+            .iter()
+            .map(|pixel| Vector3::new(
+                pixel.r as f32 / 255.0,
+                pixel.g as f32 / 255.0,
+                pixel.b as f32 / 255.0,
+            ))
+            .collect()
+    }
+
+    /// Offline supersampling: calls `render` once per sample with a different
+    /// subpixel jitter (Halton 2,3 in [-0.5, 0.5]), sums every result into a
+    /// float buffer and leaves the average in the color buffer. For plain
+    /// antialiasing everything but the jitter (camera, time) stays fixed
+    /// across the samples; `--record` advances the time between them
+    /// instead, which averages into motion blur.
+    pub fn accumulate<F>(&mut self, samples: u32, mut render: F)
+    where
+        F: FnMut(&mut Framebuffer, Vector2),
+    {
+        let samples = samples.max(1);
+        let mut sum = vec![Vector3::zero(); (self.width * self.height) as usize];
+
+        let mut jitter = Jitter::new();
+        for _ in 0..samples {
+            self.set_dither_frame(jitter.index());
+            render(self, jitter.next_offset());
+
+            for (total, color) in sum.iter_mut().zip(self.to_vector3_buffer()) {
+                *total += color;
+            }
+        }
+        self.set_dither_frame(0);
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let average = sum[(y * self.width + x) as usize] / samples as f32;
+                self.color_buffer.draw_pixel(x, y, Color::new(
+                    (average.x * 255.0).round() as u8,
+                    (average.y * 255.0).round() as u8,
+                    (average.z * 255.0).round() as u8,
+                    255,
+                ));
+            }
+        }
+    }
+
+    /// Log-average luminance of the pixels covered by geometry this frame
+    /// (background pixels are skipped). Returns None if nothing was drawn.
+    pub fn average_log_luminance(&self) -> Option<f32> {
+        let pixels = self.color_buffer.get_image_data();
+        let mut log_sum = 0.0;
+        let mut count = 0;
+
+        for (pixel, depth) in pixels.iter().zip(self.depth_buffer.iter()) {
+            if depth.is_finite() {
+                let luminance = (0.2126 * pixel.r as f32
+                    + 0.7152 * pixel.g as f32
+                    + 0.0722 * pixel.b as f32) / 255.0;
+                log_sum += (luminance + 1e-4).ln();
+                count += 1;
+            }
+        }
+
+        if count == 0 {
+            None
+        } else {
+            Some((log_sum / count as f32).exp())
+        }
+    }
+
+    /// Replaces the color buffer with a heatmap of how many fragments were
+    /// depth-tested per pixel this frame: blue = 1, green = a few, red = many
+    pub fn debug_overdraw_to_color(&mut self) {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let count = self.overdraw_buffer[(y * self.width + x) as usize];
+                if count == 0 {
+                    continue;
+                }
+
+                let t = ((count - 1) as f32 / (OVERDRAW_SATURATION - 1) as f32).min(1.0);
+                let color = if t < 0.5 {
+                    Color::new(0, (t * 2.0 * 255.0) as u8, ((1.0 - t * 2.0) * 255.0) as u8, 255)
+                } else {
+                    Color::new(((t - 0.5) * 2.0 * 255.0) as u8, ((1.0 - (t - 0.5) * 2.0) * 255.0) as u8, 0, 255)
+                };
+                self.color_buffer.draw_pixel(x, y, color);
+            }
+        }
+    }
+
     pub fn set_background_color(&mut self, color: Color) {
         self.background_color = color;
     }
@@ -69,11 +710,257 @@ impl Framebuffer {
         self.current_color = color;
     }
 
-    pub fn swap_buffers(&self, d: &mut RaylibHandle, thread: &RaylibThread) {
-        if let Ok(texture) = d.load_texture_from_image(thread, &self.color_buffer) {
+    /// Draws the displayed image to the window, then `overlay` on top in the
+    /// same drawing pass (the HUD). The GPU texture is created on the first
+    /// call, and again whenever the image changes size; every other frame
+    /// only uploads the new pixels into it
+    pub fn swap_buffers<F>(&mut self, d: &mut RaylibHandle, thread: &RaylibThread, overlay: F)
+    where
+        F: FnOnce(&mut RaylibDrawHandle),
+    {
+        let image = self.resolved.as_ref().unwrap_or(&self.color_buffer);
+        let fits = self
+            .display_texture
+            .as_ref()
+            .is_some_and(|texture| texture.width == image.width && texture.height == image.height);
+
+        let uploaded = fits && {
+            self.upload.clear();
+            for pixel in image.get_image_data().iter() {
+                self.upload.extend_from_slice(&[pixel.r, pixel.g, pixel.b, pixel.a]);
+            }
+            let upload = &self.upload;
+            self.display_texture.as_mut().is_some_and(|texture| texture.update_texture(upload).is_ok())
+        };
+        // Primer frame, otro tamaño o una subida fallida: textura nueva
+        if !uploaded {
+            self.display_texture = d.load_texture_from_image(thread, image).ok();
+        }
+
+        if let Some(texture) = &self.display_texture {
             let mut d = d.begin_drawing(thread);
             d.clear_background(self.background_color);
-            d.draw_texture(&texture, 0, 0, Color::WHITE);
+            d.draw_texture(texture, 0, 0, Color::WHITE);
+            overlay(&mut d);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BACKGROUND: Color = Color::new(51, 102, 204, 255); // (0.2, 0.4, 0.8)
+    const SOURCE: Vector3 = Vector3::new(1.0, 0.5, 0.0);
+
+    // Un píxel sobre el fondo conocido, escrito con `mode` y devuelto como 0..1
+    fn blended(mode: BlendMode, alpha: f32) -> (Vector3, f32) {
+        let mut framebuffer = Framebuffer::new(1, 1);
+        framebuffer.set_background_color(BACKGROUND);
+        framebuffer.clear();
+        framebuffer.write(0, 0, SOURCE, alpha, 0.5, mode);
+        let color = framebuffer.get_pixel_color(0, 0).unwrap();
+        (Vector3::new(color.r as f32, color.g as f32, color.b as f32) / 255.0, framebuffer.depth_at(0, 0))
+    }
+
+    fn assert_color(actual: Vector3, expected: Vector3) {
+        // Cada canal se trunca a 8 bits
+        assert!((actual - expected).length() < 2.0 / 255.0, "{:?} != {:?}", actual, expected);
+    }
+
+    #[test]
+    fn blend_modes_combine_source_and_background() {
+        let dst = Vector3::new(0.2, 0.4, 0.8);
+
+        let (color, depth) = blended(BlendMode::Replace, 0.25);
+        assert_color(color, SOURCE);
+        assert_eq!(depth, 0.5);
+
+        let (color, depth) = blended(BlendMode::AlphaOver, 0.25);
+        assert_color(color, Vector3::new(0.4, 0.425, 0.6));
+        assert_eq!(depth, f32::INFINITY);
+
+        let (color, depth) = blended(BlendMode::Additive, 0.5);
+        assert_color(color, Vector3::new(0.7, 0.65, 0.8));
+        assert_eq!(depth, f32::INFINITY);
+
+        let (color, _) = blended(BlendMode::Multiply, 1.0);
+        assert_color(color, Vector3::new(0.2, 0.2, 0.0));
+        let (color, _) = blended(BlendMode::Multiply, 0.0);
+        assert_color(color, dst);
+    }
+
+    #[test]
+    fn blended_writes_are_depth_tested() {
+        let mut framebuffer = Framebuffer::new(1, 1);
+        framebuffer.set_background_color(BACKGROUND);
+        framebuffer.clear();
+        framebuffer.write(0, 0, Vector3::zero(), 1.0, 0.2, BlendMode::Replace);
+        framebuffer.write(0, 0, SOURCE, 1.0, 0.5, BlendMode::Additive);
+        assert_eq!(framebuffer.get_pixel_color(0, 0), Some(Color::new(0, 0, 0, 255)));
+    }
+
+    #[test]
+    fn dithered_coverage_averages_to_alpha() {
+        const FRAMES: u32 = 64;
+        for alpha in [0.25, 0.5, 0.8] {
+            let mut covered = vec![0u32; 16 * 16];
+            let mut framebuffer = Framebuffer::new(16, 16);
+            for frame in 0..FRAMES {
+                framebuffer.set_dither_frame(frame);
+                framebuffer.clear();
+                for y in 0..16 {
+                    for x in 0..16 {
+                        framebuffer.write(x, y, Vector3::one(), alpha, 0.5, BlendMode::Dithered);
+                    }
+                }
+                for (index, count) in covered.iter_mut().enumerate() {
+                    if framebuffer.depth_buffer[index] < f32::INFINITY {
+                        *count += 1;
+                    }
+                }
+            }
+
+            let average = covered.iter().sum::<u32>() as f32 / (covered.len() as u32 * FRAMES) as f32;
+            assert!((average - alpha).abs() < 0.02, "alpha {}: {}", alpha, average);
+            // Y cada píxel por su cuenta, no solo la media de la superficie
+            assert!(covered.iter().all(|&count| (count as f32 / FRAMES as f32 - alpha).abs() < 0.1));
         }
-    } 
-}
\ No newline at end of file
+    }
+
+    #[test]
+    fn letterbox_rect_is_centered_with_the_target_aspect() {
+        let mut framebuffer = Framebuffer::new(800, 600);
+        // Más ancho que la ventana: barras arriba y abajo
+        let rect = framebuffer.letterbox_rect(2.39);
+        assert_eq!((rect.x, rect.width), (0.0, 800.0));
+        assert!((rect.width / rect.height - 2.39).abs() < 0.01);
+        // Centrado a píxeles enteros: sobra a lo más uno abajo
+        assert_eq!(rect.y, ((600.0 - rect.height) / 2.0).floor());
+
+        // Más angosto: barras a los lados
+        let rect = framebuffer.letterbox_rect(1.0);
+        assert_eq!(rect, Rectangle::new(100.0, 0.0, 600.0, 600.0));
+
+        // Dentro de un viewport, centrado en él
+        framebuffer.set_viewport(Viewport { x: 400, y: 300, w: 400, h: 300 });
+        let rect = framebuffer.letterbox_rect(2.0);
+        assert_eq!(rect, Rectangle::new(400.0, 350.0, 400.0, 200.0));
+    }
+
+    #[test]
+    fn batched_points_match_the_sequential_loop_for_any_thread_count() {
+        // Puntos pseudoaleatorios, con repetidos a la misma profundidad y
+        // algunos fuera del viewport
+        let mut state = 12345u32;
+        let mut next = || {
+            state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            state >> 8
+        };
+        let points: Vec<(i32, i32, Vector3, f32)> = (0..20_000)
+            .map(|_| {
+                let (x, y) = ((next() % 70) as i32 - 3, (next() % 54) as i32 - 3);
+                let color = Vector3::new((next() % 256) as f32, (next() % 256) as f32, 0.0) / 255.0;
+                (x, y, color, (next() % 16) as f32 / 16.0)
+            })
+            .collect();
+        let viewport = Viewport { x: 4, y: 2, w: 50, h: 40 };
+
+        let mut expected = Framebuffer::new(64, 48);
+        expected.set_viewport(viewport);
+        for &(x, y, color, depth) in &points {
+            expected.point(x, y, color, depth);
+        }
+
+        let _guard = crate::parallel::TEST_THREADS.lock().unwrap();
+        crate::parallel::set_min_items_per_thread(1);
+        crate::parallel::set_parallel_points(true);
+        for threads in [1, 2, 3, 8] {
+            crate::parallel::set_render_threads(threads);
+            let mut framebuffer = Framebuffer::new(64, 48);
+            framebuffer.set_viewport(viewport);
+            framebuffer.points(&points);
+            assert!(framebuffer.to_rgba_bytes() == expected.to_rgba_bytes(), "{} hilos", threads);
+            assert_eq!(framebuffer.depth_buffer, expected.depth_buffer);
+            assert_eq!(framebuffer.overdraw_buffer, expected.overdraw_buffer);
+            assert_eq!(framebuffer.stats(), expected.stats());
+        }
+    }
+
+    #[test]
+    fn depth_bias_grows_with_the_slope() {
+        let bias = DepthBias { constant: 1e-4, slope_scale: 0.5 };
+        let offset = |a: Vector3, b: Vector3, c: Vector3| bias.apply(0.5, crate::triangle::depth_slope(a, b, c)) - 0.5;
+
+        // De frente: la profundidad no cambia y solo queda el término constante
+        let flat = offset(Vector3::new(0.0, 0.0, 0.5), Vector3::new(40.0, 0.0, 0.5), Vector3::new(0.0, 40.0, 0.5));
+        assert!((flat - bias.constant).abs() < 1e-7, "{}", flat);
+
+        // Cada vez más de canto: la profundidad cambia más por píxel y el sesgo crece
+        let mut previous = flat;
+        for depth_change in [0.01, 0.05, 0.2, 0.8] {
+            let steep = offset(Vector3::new(0.0, 0.0, 0.1), Vector3::new(40.0, 0.0, 0.1 + depth_change),
+                               Vector3::new(0.0, 40.0, 0.1));
+            assert!(steep > previous, "{}: {} <= {}", depth_change, steep, previous);
+            assert!((steep - (bias.constant + bias.slope_scale * depth_change / 40.0)).abs() < 1e-6);
+            previous = steep;
+        }
+        assert_eq!(DepthBias::default().apply(0.3, 5.0), 0.3);
+    }
+
+    #[test]
+    fn readback_returns_a_known_pixel() {
+        let mut framebuffer = Framebuffer::new(4, 3);
+        framebuffer.set_background_color(Color::new(10, 20, 30, 255));
+        framebuffer.clear();
+        framebuffer.point(2, 1, Vector3::new(1.0, 0.5, 0.0), 0.5);
+
+        let bytes = framebuffer.to_rgba_bytes();
+        assert_eq!(bytes.len(), 4 * 3 * 4);
+        let index = (4 + 2) * 4; // Fila 1, columna 2
+        assert_eq!(&bytes[index..index + 4], &[255, 127, 0, 255]);
+        assert_eq!(&bytes[..4], &[10, 20, 30, 255]);
+
+        let colors = framebuffer.to_vector3_buffer();
+        assert_eq!(colors.len(), 4 * 3);
+        assert_eq!(colors[4 + 2], Vector3::new(1.0, 127.0 / 255.0, 0.0));
+        assert_eq!(framebuffer.get_pixel_color(2, 1), Some(Color::new(255, 127, 0, 255)));
+    }
+
+    #[test]
+    fn accumulation_smooths_a_hard_edge() {
+        // Semiplano blanco bajo una recta inclinada, muestreado en el centro de cada píxel desplazado
+        let inside = |x: f32, y: f32| y > 0.37 * x + 5.3;
+        let draw = |framebuffer: &mut Framebuffer, jitter: Vector2| {
+            framebuffer.clear();
+            for y in 0..16 {
+                for x in 0..32 {
+                    if inside(x as f32 + 0.5 + jitter.x, y as f32 + 0.5 + jitter.y) {
+                        framebuffer.point(x, y, Vector3::one(), 0.5);
+                    }
+                }
+            }
+        };
+        // Error medio frente a la cobertura exacta de cada píxel (64x64 muestras)
+        let error = |framebuffer: &Framebuffer| {
+            let colors = framebuffer.to_vector3_buffer();
+            let mut total = 0.0;
+            for y in 0..16 {
+                for x in 0..32 {
+                    let covered = (0..64 * 64)
+                        .filter(|i| inside(x as f32 + (i % 64) as f32 / 64.0, y as f32 + (i / 64) as f32 / 64.0))
+                        .count() as f32 / 4096.0;
+                    total += (colors[y * 32 + x].x - covered).abs();
+                }
+            }
+            total / (32.0 * 16.0)
+        };
+
+        let mut single = Framebuffer::new(32, 16);
+        draw(&mut single, Vector2::zero());
+        let mut accumulated = Framebuffer::new(32, 16);
+        accumulated.accumulate(32, draw);
+        assert!(error(&accumulated) < error(&single) * 0.3, "{} vs {}", error(&accumulated), error(&single));
+    }
+}
+
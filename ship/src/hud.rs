@@ -0,0 +1,85 @@
+// hud.rs
+use raylib::prelude::*;
+
+// Texto de la interfaz: tamaño, separación entre líneas y margen con el borde
+const HUD_FONT_SIZE: i32 = 20;
+const HUD_LINE_HEIGHT: i32 = 24;
+const HUD_MARGIN: i32 = 10;
+
+// Mira: largo de cada brazo y hueco en el centro, en píxeles
+const CROSSHAIR_ARM: i32 = 8;
+const CROSSHAIR_GAP: i32 = 3;
+const CROSSHAIR_COLOR: Color = Color::new(255, 255, 255, 180);
+
+/// What the overlay shows over the finished frame, in window pixels. It is
+/// drawn by `Framebuffer::swap_buffers` in the same drawing pass as the
+/// scene, so raylib presents one frame with both.
+pub struct Hud<'a> {
+    pub info: &'a str,            // Línea de estado: FPS, modo de cámara y ajustes
+    pub camera_position: Vector3, // Ojo de la cámara con la que se dibujó la escena
+    pub planet_type: Option<i32>, // Tipo del cuerpo que sigue la cámara (None: cámara libre)
+}
+
+impl Hud<'_> {
+    pub fn draw(&self, d: &mut RaylibDrawHandle) {
+        d.draw_text(self.info, HUD_MARGIN, HUD_MARGIN, HUD_FONT_SIZE, Color::WHITE);
+        draw_camera_position(d, self.camera_position, HUD_MARGIN + HUD_LINE_HEIGHT);
+        if let Some(planet_type) = self.planet_type {
+            draw_body_type(d, planet_type, HUD_MARGIN + 2 * HUD_LINE_HEIGHT);
+        }
+        draw_crosshair(d);
+    }
+}
+
+/// Name of the shader a `planet_type` selects
+pub fn planet_type_name(planet_type: i32) -> &'static str {
+    match planet_type {
+        0 => "Rocoso",
+        1 => "Gaseoso",
+        2 => "Océano",
+        3 => "Volcánico",
+        4 => "Cristal",
+        5 => "Sol",
+        10 => "Nave",
+        _ => "Desconocido",
+    }
+}
+
+/// Camera world position at row `y`
+pub fn draw_camera_position(d: &mut RaylibDrawHandle, position: Vector3, y: i32) {
+    let text = format!("Cámara: ({:.1}, {:.1}, {:.1})", position.x, position.y, position.z);
+    d.draw_text(&text, HUD_MARGIN, y, HUD_FONT_SIZE, Color::WHITE);
+}
+
+/// Shader type of the followed body at row `y`
+pub fn draw_body_type(d: &mut RaylibDrawHandle, planet_type: i32, y: i32) {
+    let text = format!("Tipo: {}", planet_type_name(planet_type));
+    d.draw_text(&text, HUD_MARGIN, y, HUD_FONT_SIZE, Color::WHITE);
+}
+
+/// A plus sign with a hollow center in the middle of the window
+pub fn draw_crosshair(d: &mut RaylibDrawHandle) {
+    let (x, y) = (d.get_screen_width() / 2, d.get_screen_height() / 2);
+    d.draw_line(x - CROSSHAIR_GAP - CROSSHAIR_ARM, y, x - CROSSHAIR_GAP, y, CROSSHAIR_COLOR);
+    d.draw_line(x + CROSSHAIR_GAP, y, x + CROSSHAIR_GAP + CROSSHAIR_ARM, y, CROSSHAIR_COLOR);
+    d.draw_line(x, y - CROSSHAIR_GAP - CROSSHAIR_ARM, x, y - CROSSHAIR_GAP, CROSSHAIR_COLOR);
+    d.draw_line(x, y + CROSSHAIR_GAP, x, y + CROSSHAIR_GAP + CROSSHAIR_ARM, CROSSHAIR_COLOR);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_body_type_has_a_name() {
+        // Los tipos que acepta la escena y la nave
+        let names: Vec<&str> = (0..=5).chain([10]).map(planet_type_name).collect();
+        assert!(!names.contains(&"Desconocido"), "{:?}", names);
+        let mut unique = names.clone();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(unique.len(), names.len());
+        assert_eq!(planet_type_name(7), "Desconocido");
+    }
+}
+
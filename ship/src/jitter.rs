@@ -0,0 +1,87 @@
+// jitter.rs
+#![allow(dead_code)]
+
+use raylib::prelude::*;
+
+/// Subpixel camera offsets for accumulation and temporal AA: the Halton
+/// (2, 3) sequence shifted to [-0.5, 0.5], one point per frame. The
+/// sequence is deterministic, so a run (or a test) that resets it gets the
+/// same offsets in the same order every time.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct Jitter {
+    index: u32, // Próximo frame de la secuencia
+}
+
+impl Jitter {
+    pub fn new() -> Self {
+        Jitter { index: 0 }
+    }
+
+    /// Frame index the next call to `next_offset` will return
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    /// Restarts the sequence from frame 0
+    pub fn reset(&mut self) {
+        self.index = 0;
+    }
+
+    /// Offset used for `frame`, without touching any sequence state
+    pub fn offset(frame: u32) -> Vector2 {
+        // Halton empieza en 1: el índice 0 daría (-0.5, -0.5) en todos los ejes
+        Vector2::new(halton(frame + 1, 2) - 0.5, halton(frame + 1, 3) - 0.5)
+    }
+
+    /// Offset for the current frame; advances to the next one
+    pub fn next_offset(&mut self) -> Vector2 {
+        let offset = Jitter::offset(self.index);
+        self.index += 1;
+        offset
+    }
+}
+
+// Secuencia de Halton: puntos bien repartidos en [0, 1) para el jitter
+fn halton(mut index: u32, base: u32) -> f32 {
+    let mut result = 0.0;
+    let mut fraction = 1.0 / base as f32;
+    while index > 0 {
+        result += (index % base) as f32 * fraction;
+        index /= base;
+        fraction /= base as f32;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn halton_matches_known_values() {
+        let base_2 = [0.5, 0.25, 0.75, 0.125, 0.625, 0.375, 0.875];
+        let base_3 = [1.0 / 3.0, 2.0 / 3.0, 1.0 / 9.0, 4.0 / 9.0, 7.0 / 9.0, 2.0 / 9.0];
+        for (index, expected) in base_2.iter().enumerate() {
+            assert!((halton(index as u32 + 1, 2) - expected).abs() < 1e-6);
+        }
+        for (index, expected) in base_3.iter().enumerate() {
+            assert!((halton(index as u32 + 1, 3) - expected).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn offsets_are_centered_and_reset_restarts_them() {
+        let mut jitter = Jitter::new();
+        let first: Vec<Vector2> = (0..8).map(|_| jitter.next_offset()).collect();
+        assert_eq!(jitter.index(), 8);
+        assert!((first[0] - Vector2::new(0.0, 1.0 / 3.0 - 0.5)).length() < 1e-6);
+        assert!((first[1] - Vector2::new(-0.25, 2.0 / 3.0 - 0.5)).length() < 1e-6);
+        assert!(first.iter().all(|o| o.x.abs() <= 0.5 && o.y.abs() <= 0.5));
+
+        jitter.reset();
+        assert_eq!(jitter.index(), 0);
+        let again: Vec<Vector2> = (0..8).map(|_| jitter.next_offset()).collect();
+        assert_eq!(first, again);
+        assert_eq!(Jitter::offset(3), first[3]);
+    }
+}
@@ -1,11 +1,137 @@
 use raylib::prelude::*;
 
+// Luces como máximo: el conjunto es Copy y viaja dentro de Uniforms
+pub const MAX_LIGHTS: usize = 8;
+
+// Luz del Sol por defecto: cálida y algo más fuerte que el blanco
+pub const SUN_COLOR: Vector3 = Vector3::new(1.0, 0.92, 0.8);
+pub const SUN_INTENSITY: f32 = 1.15;
+
+/// Where a light comes from
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LightKind {
+    /// Radiates from `position` in every direction
+    Point { position: Vector3 },
+    /// Parallel rays travelling along `direction`, from infinitely far away
+    Directional { direction: Vector3 },
+}
+
+/// How a point light dims with distance `d`: its radiance is scaled by
+/// 1 / (constant + linear·d + quadratic·d²). `NONE` (1, 0, 0) keeps it
+/// constant; with only `quadratic` it's the physical inverse square
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Attenuation {
+    pub constant: f32,
+    pub linear: f32,
+    pub quadratic: f32,
+}
+
+impl Attenuation {
+    pub const NONE: Attenuation = Attenuation { constant: 1.0, linear: 0.0, quadratic: 0.0 };
+
+    pub fn new(constant: f32, linear: f32, quadratic: f32) -> Self {
+        Attenuation { constant, linear, quadratic }
+    }
+
+    /// Factor at `distance`; a denominator at 0 (no constant term, right at
+    /// the light) is kept from dividing by zero
+    pub fn at(&self, distance: f32) -> f32 {
+        let denominator = self.constant + self.linear * distance + self.quadratic * distance * distance;
+        1.0 / denominator.max(1e-6)
+    }
+}
+
+impl Default for Attenuation {
+    fn default() -> Self {
+        Attenuation::NONE
+    }
+}
+
+/// A light source. `color` is multiplied by `intensity` (and, for point
+/// lights, the distance falloff) before it reaches a surface
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Light {
-    pub position: Vector3,
+    pub kind: LightKind,
+    pub color: Vector3,
+    pub intensity: f32,
+    pub attenuation: Attenuation, // Solo para luces puntuales
 }
 
 impl Light {
-    pub fn new(position: Vector3) -> Self {
-        Light { position }
+    /// White point light of intensity 1
+    pub fn point(position: Vector3) -> Self {
+        Light { kind: LightKind::Point { position }, color: Vector3::one(), intensity: 1.0, attenuation: Attenuation::NONE }
+    }
+
+    /// White directional light of intensity 1; `direction` needn't be normalized
+    pub fn directional(direction: Vector3) -> Self {
+        Light {
+            kind: LightKind::Directional { direction: direction.normalized() },
+            color: Vector3::one(),
+            intensity: 1.0,
+            attenuation: Attenuation::NONE,
+        }
+    }
+
+    pub fn with_color(mut self, color: Vector3, intensity: f32) -> Self {
+        self.color = color;
+        self.intensity = intensity;
+        self
     }
-}
\ No newline at end of file
+
+    pub fn with_attenuation(mut self, attenuation: Attenuation) -> Self {
+        self.attenuation = attenuation;
+        self
+    }
+
+    /// Unit vector from `world` toward the light
+    pub fn direction_from(&self, world: Vector3) -> Vector3 {
+        match self.kind {
+            LightKind::Point { position } => (position - world).normalized(),
+            LightKind::Directional { direction } => -direction,
+        }
+    }
+
+    /// How far the light is from `world`: infinite for a directional light
+    pub fn distance_from(&self, world: Vector3) -> f32 {
+        match self.kind {
+            LightKind::Point { position } => (position - world).length(),
+            LightKind::Directional { .. } => f32::INFINITY,
+        }
+    }
+
+    /// `attenuation` at the distance from `world`; 1 for a directional
+    /// light, which has no distance to dim over
+    pub fn attenuation_at(&self, world: Vector3) -> f32 {
+        match self.kind {
+            LightKind::Point { .. } => self.attenuation.at(self.distance_from(world)),
+            LightKind::Directional { .. } => 1.0,
+        }
+    }
+}
+
+/// The lights that shade the planets, up to `MAX_LIGHTS`. Their
+/// diffuse and specular terms add up, and an empty rig leaves only the
+/// ambient fill. The first light is the sun: it's the one the rings shadow.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LightRig {
+    lights: [Light; MAX_LIGHTS],
+    len: usize,
+}
+
+impl LightRig {
+    /// Extra lights past `MAX_LIGHTS` are dropped
+    pub fn new(lights: &[Light]) -> Self {
+        let mut rig = LightRig { lights: [Light::point(Vector3::zero()); MAX_LIGHTS], len: lights.len().min(MAX_LIGHTS) };
+        rig.lights[..rig.len].copy_from_slice(&lights[..rig.len]);
+        rig
+    }
+
+    pub fn lights(&self) -> &[Light] {
+        &self.lights[..self.len]
+    }
+
+    pub fn primary(&self) -> Option<&Light> {
+        self.lights().first()
+    }
+}
@@ -1,7 +1,8 @@
 // line.rs
 use crate::fragment::Fragment;
 use crate::vertex::Vertex;
-use raylib::math::Vector3;
+use crate::matrix::multiply_matrix_vector4;
+use raylib::math::{Matrix, Vector2, Vector3, Vector4};
 
 pub fn line(a: &Vertex, b: &Vertex) -> Vec<Fragment> {
     let mut fragments = Vec::new();
@@ -21,20 +22,27 @@ pub fn line(a: &Vertex, b: &Vertex) -> Vec<Fragment> {
     let sy = if y0 < y1 { 1 } else { -1 };
     
     let mut err = if dx > dy { dx / 2 } else { -dy / 2 };
+
+    // Progreso a lo largo del eje dominante para interpolar profundidad
+    let steps = dx.max(dy);
+    let mut step = 0;
     
     loop {
-        let z = if (end.x - start.x).abs() > 0.001 { 
-            start.z + (end.z - start.z) * (x0 - start.x as i32) as f32 / (end.x - start.x) as f32 
-        } else { 
-            start.z 
-        };
+        let t = if steps > 0 { step as f32 / steps as f32 } else { 0.0 };
+        let z = start.z + (end.z - start.z) * t;
+        let world_position = Vector3::new(
+            a.position.x + (b.position.x - a.position.x) * t,
+            a.position.y + (b.position.y - a.position.y) * t,
+            a.position.z + (b.position.z - a.position.z) * t,
+        );
         
         // Usar el color interpolado o blanco por defecto
         fragments.push(Fragment::new(
             x0 as f32, 
             y0 as f32, 
             Vector3::new(1.0, 1.0, 1.0), 
-            z
+            z,
+            world_position,
         ));
         
         if x0 == x1 && y0 == y1 { break; }
@@ -48,7 +56,48 @@ pub fn line(a: &Vertex, b: &Vertex) -> Vec<Fragment> {
             err += dx;
             y0 += sy;
         }
+        step += 1;
     }
     
     fragments
-}
\ No newline at end of file
+}
+
+// Recorta el segmento a..b (espacio de clip) contra el frustum: -w <= x, y, z <= w,
+// con los planos laterales abiertos `guard_band` veces (1: el frustum justo).
+// Devuelve el tramo visible como fracciones (t0, t1) del segmento
+pub fn clip_segment(a: Vector4, b: Vector4, guard_band: f32) -> Option<(f32, f32)> {
+    let (mut t0, mut t1) = (0.0_f32, 1.0_f32);
+    let (wa, wb) = (a.w * guard_band, b.w * guard_band);
+    // Distancia con signo a cada plano (>= 0 dentro), en a y en b
+    let planes = [
+        (wa + a.x, wb + b.x), (wa - a.x, wb - b.x),
+        (wa + a.y, wb + b.y), (wa - a.y, wb - b.y),
+        (a.w + a.z, b.w + b.z), (a.w - a.z, b.w - b.z),
+    ];
+    for (da, db) in planes {
+        if da < 0.0 && db < 0.0 {
+            return None;
+        }
+        if da < 0.0 {
+            t0 = t0.max(da / (da - db));
+        } else if db < 0.0 {
+            t1 = t1.min(da / (da - db));
+        }
+    }
+    (t0 <= t1).then_some((t0, t1))
+}
+
+/// Clips the edge a..b against the frustum and projects what's left with
+/// `viewport_matrix`. `None` when the edge is entirely outside
+pub fn clip_line(a: &Vertex, b: &Vertex, viewport_matrix: &Matrix) -> Option<(Vertex, Vertex)> {
+    let (t0, t1) = clip_segment(a.clip_position, b.clip_position, 1.0)?;
+    let to_screen = |t: f32| {
+        let clip = a.clip_position.lerp(b.clip_position, t);
+        let ndc = Vector4::new(clip.x / clip.w, clip.y / clip.w, clip.z / clip.w, 1.0);
+        let screen = multiply_matrix_vector4(viewport_matrix, &ndc);
+        let mut vertex = Vertex::new(a.position.lerp(b.position, t), Vector3::zero(), Vector2::zero());
+        vertex.transformed_position = Vector3::new(screen.x, screen.y, screen.z);
+        vertex
+    };
+    Some((to_screen(t0), to_screen(t1)))
+}
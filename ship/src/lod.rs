@@ -0,0 +1,129 @@
+// lod.rs
+use std::cell::RefCell;
+use crate::vertex::Vertex;
+
+/// How far past a level's threshold the projected size has to go before
+/// that level is switched to, as a fraction of the threshold: an object
+/// hovering right at a threshold keeps the mesh it already had
+const LOD_HYSTERESIS: f32 = 0.15;
+
+/// The same mesh at several levels of detail, finest first. Each level is
+/// drawn while the object's projected radius (in pixels) is at least its
+/// threshold; the last level's threshold is ignored, it takes everything
+/// smaller
+pub struct LodChain<'a> {
+    levels: Vec<(&'a [Vertex], f32)>,
+    radius: f32, // Radio de la esfera que contiene la malla, en espacio del modelo
+}
+
+impl<'a> LodChain<'a> {
+    /// `levels` as (vertex array, minimum projected radius) pairs, finest
+    /// first with decreasing thresholds, for a mesh whose bounding sphere
+    /// has `radius` in model space. Panics without any level
+    pub fn new(radius: f32, levels: Vec<(&'a [Vertex], f32)>) -> Self {
+        assert!(!levels.is_empty(), "LodChain necesita al menos un nivel");
+        LodChain { levels, radius }
+    }
+
+    pub fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    /// The vertex array of level `level` (clamped to the coarsest)
+    pub fn mesh(&self, level: usize) -> &'a [Vertex] {
+        self.levels[level.min(self.levels.len() - 1)].0
+    }
+
+    pub fn finest(&self) -> &'a [Vertex] {
+        self.mesh(0)
+    }
+
+    /// Level to draw at `screen_radius` pixels for an object that used
+    /// `previous` last frame. The thresholds are widened by `LOD_HYSTERESIS`
+    /// around the current level: going finer needs the size above the
+    /// raised threshold and going coarser below the lowered one
+    pub fn select(&self, screen_radius: f32, previous: usize) -> usize {
+        let first_fitting = |factor: f32| {
+            self.levels[..self.levels.len() - 1]
+                .iter()
+                .position(|&(_, threshold)| screen_radius >= threshold * factor)
+                .unwrap_or(self.levels.len() - 1)
+        };
+        // Con el umbral rebajado se alcanza un nivel más fino que con el elevado
+        let finest_allowed = first_fitting(1.0 - LOD_HYSTERESIS);
+        let coarsest_allowed = first_fitting(1.0 + LOD_HYSTERESIS);
+        previous.clamp(finest_allowed, coarsest_allowed)
+    }
+}
+
+/// The level each object was drawn at last frame in one view, for the
+/// hysteresis of `LodChain::select`. Each view keeps its own: the same
+/// object has a different projected size in each viewport
+#[derive(Default)]
+pub struct LodHistory {
+    levels: RefCell<Vec<usize>>, // Por id de objeto; los que no han salido aún, al nivel más fino
+}
+
+impl LodHistory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `chain.select` for object `id` from the level it had last time in
+    /// this view, remembering the new one
+    pub fn select(&self, chain: &LodChain, id: usize, screen_radius: f32) -> usize {
+        let mut levels = self.levels.borrow_mut();
+        if levels.len() <= id {
+            levels.resize(id + 1, 0);
+        }
+        levels[id] = chain.select(screen_radius, levels[id]);
+        levels[id]
+    }
+}
+
+/// Radius in pixels of a sphere of `radius` at `distance` from the camera,
+/// for a projection with `focal_length` (`cot(fov_y / 2)`, the matrix's
+/// `m5`) on a viewport `viewport_height` pixels tall. With the camera inside
+/// the sphere it's infinite
+pub fn projected_radius(radius: f32, distance: f32, focal_length: f32, viewport_height: f32) -> f32 {
+    if distance <= radius {
+        return f32::INFINITY;
+    }
+    radius / distance * focal_length * viewport_height * 0.5
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Tres niveles con umbrales en 100 y 40 píxeles
+    fn chain() -> LodChain<'static> {
+        LodChain::new(1.0, vec![(&[], 100.0), (&[], 40.0), (&[], 0.0)])
+    }
+
+    #[test]
+    fn select_keeps_the_previous_level_inside_the_band() {
+        let chain = chain();
+        // Dentro de ±15% del umbral de 100 se queda donde estaba
+        for size in [86.0, 95.0, 100.0, 114.0] {
+            assert_eq!(chain.select(size, 0), 0, "{}", size);
+            assert_eq!(chain.select(size, 1), 1, "{}", size);
+        }
+        // Fuera de la banda cambia
+        assert_eq!(chain.select(84.0, 0), 1);
+        assert_eq!(chain.select(116.0, 1), 0);
+        assert_eq!(chain.select(30.0, 0), 2);
+        assert_eq!(chain.select(200.0, 2), 0);
+    }
+
+    #[test]
+    fn each_history_keeps_its_own_levels() {
+        let chain = chain();
+        let (main, rear) = (LodHistory::new(), LodHistory::new());
+        assert_eq!(main.select(&chain, 3, 90.0), 0);
+        assert_eq!(rear.select(&chain, 3, 30.0), 2);
+        // La vista trasera no mueve la principal: sigue con la histéresis del nivel 0
+        assert_eq!(main.select(&chain, 3, 90.0), 0);
+        assert_eq!(rear.select(&chain, 3, 45.0), 2);
+    }
+}
@@ -0,0 +1,105 @@
+// look.rs
+use crate::scene_config::set_shader_param;
+use crate::shaders::ShaderParams;
+use std::fs;
+use std::io;
+use std::str::FromStr;
+
+/// Shading parameters kept apart from the scene so they can be tuned while
+/// the app runs: the file is watched and reapplied every time it's saved.
+///
+/// Same `key = value` format as the scene. Top-level `ambient_floor` and
+/// `light_falloff` override the render settings; a `[name]` line starts the
+/// shader keys (`noise_scale`, `palette_N`, `palette_file`...) of the body
+/// with that name. A body listed here takes all of its shader parameters
+/// from this file, starting from the defaults; the others keep the ones
+/// from the scene.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Look {
+    pub ambient_floor: Option<f32>,
+    pub light_falloff: Option<f32>,
+    pub bodies: Vec<(String, ShaderParams)>,
+}
+
+impl Look {
+    pub fn load(path: &str) -> io::Result<Self> {
+        fs::read_to_string(path)?
+            .parse()
+            .map_err(|err: String| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// Parameters for the body called `name`; if it's listed twice, the last
+    /// section wins
+    pub fn shader_params(&self, name: &str) -> Option<ShaderParams> {
+        self.bodies
+            .iter()
+            .rev()
+            .find(|(body, _)| body == name)
+            .map(|(_, params)| *params)
+    }
+}
+
+impl FromStr for Look {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut look = Look::default();
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Some(name) = line.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')) {
+                let name = name.trim();
+                if name.is_empty() {
+                    return Err(format!("line {}: section without a body name", number + 1));
+                }
+                look.bodies.push((name.to_string(), ShaderParams::default()));
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("line {}: expected `key = value`", number + 1))?;
+            let (key, value) = (key.trim(), value.trim());
+            let invalid = || format!("line {}: invalid value for `{}`: {}", number + 1, key, value);
+
+            match look.bodies.last_mut() {
+                None => match key {
+                    "ambient_floor" => look.ambient_floor = Some(value.parse().map_err(|_| invalid())?),
+                    "light_falloff" => look.light_falloff = Some(value.parse().map_err(|_| invalid())?),
+                    _ => {} // Claves desconocidas: se ignoran
+                },
+                Some((_, params)) => {
+                    set_shader_param(params, key, value).map_err(|err| format!("line {}: {}", number + 1, err))?;
+                }
+            }
+        }
+
+        Ok(look)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_overrides_per_body() {
+        let look: Look = "ambient_floor = 0.25\n[Tierra]\nnoise_scale = 3\n[Marte]\nseed = 4\n[Tierra]\nseed = 9\n"
+            .parse()
+            .unwrap();
+        assert_eq!(look.ambient_floor, Some(0.25));
+        assert_eq!(look.light_falloff, None);
+        // La última sección gana, y empieza de los valores por defecto
+        assert_eq!(look.shader_params("Tierra"), Some(ShaderParams { seed: 9, ..ShaderParams::default() }));
+        assert_eq!(look.shader_params("Marte").map(|params| params.seed), Some(4));
+        assert_eq!(look.shader_params("Venus"), None);
+
+        assert!("[ ]\n".parse::<Look>().unwrap_err().starts_with("line 1"));
+        assert!("ambient_floor = mucho\n".parse::<Look>().is_err());
+    }
+}
+
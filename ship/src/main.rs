@@ -8,88 +8,344 @@ mod vertex;
 mod camera;
 mod shaders;
 mod light;
+mod exposure;
+mod jitter;
+mod line;
+mod wireframe;
+mod environment;
+mod sh_ambient;
+mod orbit;
+mod parallel;
+mod post;
+mod settings;
+mod stats;
+mod scene_config;
+mod surface_cache;
+mod palette;
+mod look;
+mod file_watch;
+mod png;
+mod render_queue;
+mod texture;
+mod hud;
+mod quat;
+mod lod;
+mod flight;
 
-use triangle::triangle;
-use obj::Obj;
-use framebuffer::Framebuffer;
+use triangle::{clip_to_frustum, signed_area, triangle, CullMode, RasterPrecision};
+use fragment::Fragment;
+use obj::{Material, Obj, Winding};
+use framebuffer::{id_color, DepthBias, Framebuffer, Viewport};
 use raylib::prelude::*;
-use std::thread;
-use std::time::Duration;
+use std::time::{SystemTime, UNIX_EPOCH};
 use std::f32::consts::PI;
-use matrix::{create_model_matrix, create_projection_matrix, create_viewport_matrix};
+use matrix::{multiply_matrix_vector4, create_model_matrix, create_model_matrix_oriented, create_projection_matrix_handed, create_tile_projection_matrix, create_viewport_matrix, camera_position_from_view, Handedness, ModelTransform};
 use vertex::Vertex;
 use camera::Camera;
-use shaders::{vertex_shader, fragment_shader};
-use light::Light;
+use flight::Ship;
+use shaders::{vertex_shader, fragment_shader, shade_fragment, resolve_color, light_attenuation, render_atmosphere, render_cloud_layer, render_moon, render_rings, ColorClampMode, Fog, ShaderParams, SurfaceMaterial, LAYER_ALL, RING_INNER_RADIUS, RING_OUTER_RADIUS};
+use light::{Light, LightRig};
+use exposure::AutoExposure;
+use wireframe::{FillMode, WireframeStyle, render_wireframe};
+use environment::{Environment, render_skybox};
+use settings::RenderSettings;
+use scene_config::{BodyConfig, SceneConfig};
+use orbit::Orbit;
+use line::{clip_line, clip_segment, line};
+use parallel::parallel_map;
+use surface_cache::{ShadedFragment, SurfaceCache};
+use stats::StatsLog;
+use look::Look;
+use render_queue::{view_depth, RenderQueue};
+use texture::Texture;
+use file_watch::FileWatch;
+use hud::{planet_type_name, Hud};
+use lod::{projected_radius, LodChain, LodHistory};
+use std::cell::RefCell;
+use std::str::FromStr;
+use quat::Quaternion;
+
+// Distancia de la cámara al cuerpo que sigue, en radios del cuerpo
+const FOLLOW_DISTANCE_RADII: f32 = 10.0;
+
+// Niveles de subdivisión de la esfera (cada nivel multiplica los triángulos por 4)
+const SPHERE_SUBDIVISIONS: u32 = 1;
+
+// Radio en pantalla (píxeles) desde el que se dibuja la esfera subdividida y
+// desde el que se dibuja la del archivo; más chicos, un icosaedro subdividido
+// SPHERE_FAR_SUBDIVISIONS veces
+const SPHERE_LOD_RADII: [f32; 2] = [48.0, 12.0];
+const SPHERE_FAR_SUBDIVISIONS: u32 = 1;
+
+// Cuadriláteros alrededor de la malla de los anillos
+const RING_SEGMENTS: u32 = 128;
+
+// Luna: radio de su órbita y tamaño en unidades del planeta (se multiplican
+// por su escala), velocidad angular y cuánto sube y baja al orbitar
+const MOON_ORBIT_RADIUS: f32 = 4.5;
+const MOON_ORBIT_SPEED: f32 = 0.3;
+const MOON_ORBIT_BOB: f32 = 0.2;
+const MOON_SCALE: f32 = 0.25;
+
+// Mapa de entorno HDR equirectangular opcional (si falta, campo de estrellas)
+const ENVIRONMENT_PATH: &str = "models/environment.hdr";
+
+// Archivo de ajustes de render (F5 guarda, F9 carga)
+const SETTINGS_PATH: &str = "render_settings.cfg";
+
+// Descripción de la escena: cuerpos, cámara inicial y luz (F6 recarga)
+const SCENE_PATH: &str = "scene.cfg";
+
+// Parámetros de los shaders para ajustarlos en vivo: se reaplican al guardar el archivo
+const SHADER_PARAMS_PATH: &str = "shader_params.cfg";
+
+// Límite de la vista en vivo (--fps N, 0 sin límite); raylib espera lo que falte de cada frame
+const DEFAULT_TARGET_FPS: u32 = 60;
+
+// Render offline (--render-samples N): imagen de salida y frames para estabilizar la exposición
+const RENDER_OUTPUT_PATH: &str = "render.png";
+const EXPOSURE_WARMUP_FRAMES: u32 = 8;
+
+// Render por teselas (--render-size ANCHOxALTO): lado máximo de cada tesela
+const RENDER_TILE_SIZE: i32 = 512;
+
+// Grabación (--record N): carpeta de los frames y paso fijo de simulación entre ellos
+const RECORD_DIR: &str = "record";
+const RECORD_FRAME_DT: f32 = 1.0 / 30.0;
+
+// Capa de nubes de los planetas océano: radio del cascarón, giro (la superficie gira a 0.4) y cobertura
+const CLOUD_SHELL_SCALE: f32 = 1.03;
+const CLOUD_SHELL_SPEED: f32 = 0.55;
+const CLOUD_SHELL_COVERAGE: f32 = 0.45;
+
+// Aspecto del letterbox (TAB): formato panorámico de cine
+const CINEMATIC_ASPECT: f32 = 2.39;
+
+// Planos de recorte de la proyección por defecto (ajustables en los ajustes de render)
+const NEAR_PLANE: f32 = 0.1;
+const FAR_PLANE: f32 = 200.0;
+
+// Cuánto cambia el campo de visión cada paso de la rueda del ratón (radianes)
+const FOV_WHEEL_STEP: f32 = 2.5 * PI / 180.0;
+
+// Fondo del framebuffer, donde no hay nada dibujado
+const BACKGROUND_COLOR: Color = Color::new(5, 5, 15, 255);
+
+// Minimapa (M): tamaño en píxeles, margen interior y largo de la flecha de la cámara
+const MINIMAP_SIZE: f32 = 160.0;
+const MINIMAP_MARGIN: f32 = 6.0;
+const MINIMAP_CAMERA_ARROW: f32 = 10.0;
+
+// Ancho de las órbitas en píxeles de pantalla
+const ORBIT_LINE_WIDTH: f32 = 1.5;
+// Cuánto más allá de los bordes del frustum se recortan sus tramos: cortados en
+// el borde, perderían la parte de su ancho que cae dentro (la costura entre teselas)
+const ORBIT_GUARD_BAND: f32 = 4.0;
+
+// Retrovisor (F10): fracción del ancho y alto de la imagen que ocupa y margen superior en píxeles
+const REAR_VIEW_FRACTION: i32 = 4;
+const REAR_VIEW_MARGIN: i32 = 10;
 
 #[derive(Clone)]
-pub struct Uniforms {
-    pub model_matrix: Matrix,
+pub struct Uniforms<'a> {
+    pub model: ModelTransform,     // Matriz de modelo y las que se derivan de ella
     pub view_matrix: Matrix,
     pub projection_matrix: Matrix,
     pub viewport_matrix: Matrix,
+    pub viewport: Viewport,        // Píxeles del framebuffer donde se dibuja (lo de fuera se descarta)
+    pub camera_position: Vector3,  // Ojo de la cámara en el mundo: de él sale la dirección de vista
     pub time: f32,
     pub dt: f32,
     pub planet_type: i32,
-    pub render_type: i32,
+    pub render_type: i32,          // 0: normal, 1: anillos, 2: luna, 3: debug baricéntrico, 4: debug de caras, 5: colores por vértice
+    pub handedness: Handedness,    // Decide qué signo de área en pantalla es cara frontal
+    pub exposure: f32,
+    pub layer_mask: u32,           // Capas procedurales activas (bit N-1 = CAPA N)
+    pub environment: &'a Environment, // Skybox y fuente de reflejos
+    pub raster_precision: RasterPrecision,
+    pub specular_aa: bool,         // Toksvig sobre las normales de las caras del cristal
+    pub double_sided: bool,        // Sombrear las caras traseras con la normal invertida
+    pub ring_shadow: bool,         // Los anillos del tipo 3 oscurecen su superficie
+    pub depth_bias: DepthBias,     // Desplazamiento de profundidad contra z-fighting (cero en los cuerpos)
+    pub dithered_transparency: bool, // Capas translúcidas con dithering en vez de mezcla alfa
+    pub deterministic_draw: bool,  // Escribir los fragmentos ordenados por profundidad
+    pub color_clamp: ColorClampMode, // Cómo se lleva el color final a [0, 1]
+    pub gamma_correct: bool,       // Codificar el color final con gamma 2.2
+    pub lights: LightRig,          // Luces del cuerpo; la primera es la que proyecta la sombra de los anillos
+    pub light_falloff: f32,        // Exponente de atenuación de la luz directa (0: sin atenuación)
+    pub ambient_floor: f32,        // Mínimo del término ambiental, independiente de la atenuación
+    pub shader_params: ShaderParams, // Ajustes del shader propios de cada cuerpo
+    pub materials: &'a [Material], // Materiales de la malla por `material_id` (vacío: gris por defecto)
+    pub texture: Option<&'a Texture>, // Albedo de la malla por UV, multiplica su color difuso
+    pub normal_map: Option<&'a Texture>, // Normales en espacio tangente por UV (ver `normal_mapped`)
+    pub material: SurfaceMaterial, // Cómo toma la luz la superficie de un planeta
+    pub fill_mode: FillMode,       // Triángulos rellenos, solo sus aristas o ambos
+    pub fog: Fog,                  // Niebla por profundidad de vista (densidad 0: sin niebla)
+    pub cull_mode: CullMode,       // Qué caras se descartan antes de rasterizar
+    pub front_face: Winding,       // Sentido de giro de las caras frontales en el modelo
+}
+
+impl Uniforms<'_> {
+    /// Sign of the screen-space signed area of this body's front faces
+    pub fn front_face_sign(&self) -> f32 {
+        let winding = match self.front_face {
+            Winding::CounterClockwise => 1.0,
+            Winding::Clockwise => -1.0,
+        };
+        self.handedness.front_face_sign() * winding
+    }
+
+    // Si el triángulo (ya recortado) se descarta por su cara; la vista de
+    // debug de caras las muestra todas. Cuenta el área de todo el polígono
+    // recortado: el primer triángulo del abanico puede quedar degenerado
+    // (p. ej. contra el borde de una tesela) y pasaría por cara trasera
+    fn culls(&self, clipped: &[[Vertex; 3]]) -> bool {
+        if clipped.is_empty() {
+            return false;
+        }
+        let area: f32 = clipped.iter().map(|[a, b, c]| signed_area(a, b, c)).sum();
+        self.render_type != 4 && self.cull_mode.culls(area, self.front_face_sign())
+    }
 }
 
 // Estructura para representar un cuerpo celeste
 struct CelestialBody {
     planet_type: i32,
-    orbital_radius: f32,
-    orbital_speed: f32,
+    orbit: Orbit,
     rotation_speed: f32,
     scale: f32,
-    orbital_angle: f32,
+    elapsed: f32,
     rotation_angle: f32,
-    name: &'static str,
+    name: String,
+    shader_params: ShaderParams,
+    surface_cache: Option<RefCell<SurfaceCache>>, // Solo si la configuración lo pide
+    has_moon: bool,
+    normal_map: Option<Texture>,
 }
 
 impl CelestialBody {
     fn new(planet_type: i32, orbital_radius: f32, orbital_speed: f32,
-           rotation_speed: f32, scale: f32, name: &'static str) -> Self {
+           rotation_speed: f32, scale: f32, name: String) -> Self {
         CelestialBody {
             planet_type,
-            orbital_radius,
-            orbital_speed,
+            orbit: Orbit::circular(orbital_radius, orbital_speed),
             rotation_speed,
             scale,
-            orbital_angle: 0.0,
+            elapsed: 0.0,
             rotation_angle: 0.0,
             name,
+            shader_params: ShaderParams::default(),
+            surface_cache: None,
+            has_moon: false,
+            normal_map: None,
         }
     }
 
+    // Forma de la órbita (excentricidad, inclinación y orientación, en radianes)
+    fn with_orbit_shape(mut self, eccentricity: f32, inclination: f32,
+                        ascending_node: f32, periapsis: f32) -> Self {
+        self.orbit.eccentricity = eccentricity;
+        self.orbit.inclination = inclination;
+        self.orbit.longitude_of_ascending_node = ascending_node;
+        self.orbit.argument_of_periapsis = periapsis;
+        self
+    }
+
+    fn from_config(config: &BodyConfig) -> Self {
+        let mut body = CelestialBody::new(config.planet_type, config.orbit_radius, config.orbit_speed,
+                                          config.rotation_speed, config.scale, config.name.clone())
+            .with_orbit_shape(config.eccentricity, config.inclination,
+                              config.ascending_node, config.periapsis);
+        body.shader_params = config.shader_params;
+        body.has_moon = config.moon;
+        if config.cache_surface {
+            body.surface_cache = Some(RefCell::new(SurfaceCache::new()));
+        }
+        body.normal_map = config.normal_map.as_ref().and_then(|path| match Texture::load(path) {
+            Ok(texture) => Some(texture),
+            Err(err) => {
+                println!("No se pudo cargar el mapa de normales {} ({}): {} queda sin él", path, err, config.name);
+                None
+            }
+        });
+        body
+    }
+
     fn update(&mut self, dt: f32) {
-        self.orbital_angle += self.orbital_speed * dt;
+        self.elapsed += dt;
         self.rotation_angle += self.rotation_speed * dt;
     }
 
     fn get_position(&self) -> Vector3 {
-        Vector3::new(
-            self.orbital_radius * self.orbital_angle.cos(),
-            0.0,
-            self.orbital_radius * self.orbital_angle.sin(),
-        )
+        self.orbit.position_at(self.elapsed)
     }
 
     fn get_orbit_points(&self, segments: usize) -> Vec<Vector3> {
-        let mut points = Vec::new();
-        for i in 0..=segments {
-            let angle = (i as f32 / segments as f32) * 2.0 * PI;
-            points.push(Vector3::new(
-                self.orbital_radius * angle.cos(),
-                0.0,
-                self.orbital_radius * angle.sin(),
-            ));
+        self.orbit.path(segments)
+    }
+}
+
+// Sol y planetas de la configuración, ya avanzados hasta `time` para que
+// recargar la escena no reinicie las órbitas
+fn bodies_from_config(config: &SceneConfig, time: f32) -> (CelestialBody, Vec<CelestialBody>) {
+    let mut sun = CelestialBody::from_config(&config.sun);
+    sun.update(time);
+    let planets = config.planets
+        .iter()
+        .map(|planet_config| {
+            let mut planet = CelestialBody::from_config(planet_config);
+            planet.update(time);
+            planet
+        })
+        .collect();
+    (sun, planets)
+}
+
+// Cuerpos y luz de una configuración recargada en marcha, con el archivo de shaders encima
+fn apply_scene_config(config: &SceneConfig, look: &Look, time: f32,
+                      settings: &mut RenderSettings) -> (CelestialBody, Vec<CelestialBody>) {
+    let (mut sun, mut planets) = bodies_from_config(config, time);
+    settings.light_position = config.light_position;
+    settings.light_color = config.light_color;
+    settings.light_intensity = config.light_intensity;
+    settings.light_attenuation = config.light_attenuation;
+    apply_look(look, config, &mut sun, &mut planets, settings);
+    (sun, planets)
+}
+
+// Parámetros del archivo de shaders sobre los cuerpos (los que no aparecen
+// vuelven a los de la escena) y los ajustes de luz que fije
+fn apply_look(look: &Look, config: &SceneConfig, sun: &mut CelestialBody, planets: &mut [CelestialBody],
+              settings: &mut RenderSettings) {
+    let bodies = std::iter::once((sun, &config.sun)).chain(planets.iter_mut().zip(&config.planets));
+    for (body, body_config) in bodies {
+        body.shader_params = look.shader_params(&body_config.name).unwrap_or(body_config.shader_params);
+    }
+    if let Some(ambient_floor) = look.ambient_floor {
+        settings.ambient_floor = ambient_floor;
+    }
+    if let Some(light_falloff) = look.light_falloff {
+        settings.light_falloff = light_falloff;
+    }
+}
+
+// El archivo de shaders es opcional: sin él no cambia nada
+fn load_look() -> Look {
+    match Look::load(SHADER_PARAMS_PATH) {
+        Ok(look) => look,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Look::default(),
+        Err(err) => {
+            println!("Parámetros de shader no válidos ({}): se ignoran", err);
+            Look::default()
         }
-        points
     }
 }
 
-fn render_body(framebuffer: &mut Framebuffer, uniforms: &Uniforms,
-               vertex_array: &[Vertex], light: &Light) {
+// Vertex shader y rasterizado de un cuerpo (en paralelo, resultado en orden).
+// Los triángulos quedan contados en las estadísticas del framebuffer. Con
+// `deterministic_draw` los fragmentos salen de adelante hacia atrás
+fn rasterize_body(framebuffer: &mut Framebuffer, uniforms: &Uniforms, vertex_array: &[Vertex],
+                  light: &Light) -> Vec<Fragment> {
     let mut transformed_vertices = Vec::with_capacity(vertex_array.len());
 
     for vertex in vertex_array {
@@ -108,107 +364,1213 @@ fn render_body(framebuffer: &mut Framebuffer, uniforms: &Uniforms,
         }
     }
 
-    let mut fragments = Vec::new();
-    for tri in &triangles {
-        fragments.extend(triangle(&tri[0], &tri[1], &tri[2], light));
+    // Recortados contra el frustum (menos el plano lejano) y descartados por
+    // su cara antes de rasterizar. La orientación sale de la parte visible: la
+    // proyección de los vértices detrás de la cámara no sirve para decidirla
+    let per_triangle = parallel_map(&triangles, |tri| {
+        let clipped = clip_to_frustum(&tri[0], &tri[1], &tri[2], &uniforms.viewport_matrix);
+        if uniforms.culls(&clipped) {
+            return Vec::new();
+        }
+        clipped
+            .iter()
+            .flat_map(|tri| triangle(&tri[0], &tri[1], &tri[2], light, uniforms.raster_precision))
+            .collect::<Vec<_>>()
+    });
+    // Descartados: sin ningún fragmento dentro del viewport
+    let culled = per_triangle
+        .iter()
+        .filter(|fragments| !fragments.iter().any(|fragment| {
+            uniforms.viewport.contains(fragment.position.x.floor() as i32, fragment.position.y.floor() as i32)
+        }))
+        .count();
+    framebuffer.count_triangles(triangles.len() as u32, culled as u32);
+
+    let mut fragments: Vec<Fragment> = per_triangle.into_iter().flatten().collect();
+    if uniforms.deterministic_draw {
+        // Orden estable: a igual profundidad se conserva el orden de los triángulos
+        fragments.sort_by(|a, b| a.depth.total_cmp(&b.depth));
     }
+    fragments
+}
 
-    for fragment in fragments {
-        let final_color = fragment_shader(&fragment, uniforms);
-        framebuffer.point(
-            fragment.position.x as i32,
-            fragment.position.y as i32,
-            final_color,
-            fragment.depth,
+fn render_body(framebuffer: &mut Framebuffer, uniforms: &Uniforms,
+               vertex_array: &[Vertex], light: &Light) {
+    if uniforms.fill_mode.fills() {
+        render_body_fill(framebuffer, uniforms, vertex_array, light);
+    }
+    if uniforms.fill_mode.draws_edges() {
+        render_body_edges(framebuffer, uniforms, vertex_array);
+    }
+}
+
+fn render_body_fill(framebuffer: &mut Framebuffer, uniforms: &Uniforms,
+                    vertex_array: &[Vertex], light: &Light) {
+    // Rasterizado, sombreado y escritura en paralelo; la escritura respeta el
+    // orden de los fragmentos en cada píxel, así que el resultado no depende de los hilos
+    let fragments = rasterize_body(framebuffer, uniforms, vertex_array, light);
+    let width = framebuffer.width;
+    let depth_buffer = framebuffer.depth_buffer_slice();
+    let points = parallel_map(&fragments, |fragment| {
+        let (x, y) = (fragment.position.x as i32, fragment.position.y as i32);
+        let depth = uniforms.depth_bias.apply(fragment.depth, fragment.depth_slope);
+        // Early-z: lo ya tapado por cuerpos anteriores no pasará el test al
+        // escribirse, así que ni se sombrea (sigue contando como fragmento);
+        // fuera del viewport tampoco se escribirá
+        let hidden = !uniforms.viewport.contains(x, y)
+            || depth >= depth_buffer[(y * width + x) as usize];
+        let color = if hidden { Vector3::zero() } else { fragment_shader(fragment, uniforms) };
+        (x, y, color, depth)
+    });
+    framebuffer.points(&points);
+}
+
+// Color de las aristas en los modos de wireframe de los cuerpos
+const BODY_EDGE_COLOR: Vector3 = Vector3::new(0.3, 1.0, 0.5);
+// Cuánto se adelantan las aristas para ganarle a su propia superficie,
+// proporcional a (1 - z) como el de la pasada de líneas ocultas
+const BODY_EDGE_DEPTH_OFFSET: f32 = 0.002;
+
+/// Draws the three edges of each triangle of the body as depth-tested
+/// lines. Triangles fully outside the frustum and the faces `cull_mode`
+/// drops are skipped like the rasterizer would; each
+/// edge is clipped in clip space, so no vertex behind the camera is
+/// projected. Edges shared by two faces are drawn twice.
+fn render_body_edges(framebuffer: &mut Framebuffer, uniforms: &Uniforms, vertex_array: &[Vertex]) {
+    let transformed_vertices: Vec<Vertex> = parallel_map(vertex_array, |vertex| vertex_shader(vertex, uniforms));
+
+    let per_triangle = parallel_map(&transformed_vertices.chunks_exact(3).collect::<Vec<_>>(), |tri| {
+        let clipped = clip_to_frustum(&tri[0], &tri[1], &tri[2], &uniforms.viewport_matrix);
+        if clipped.is_empty() || uniforms.culls(&clipped) {
+            return Vec::new();
+        }
+
+        let mut points = Vec::new();
+        for (a, b) in [(&tri[0], &tri[1]), (&tri[1], &tri[2]), (&tri[2], &tri[0])] {
+            let Some((start, end)) = clip_line(a, b, &uniforms.viewport_matrix) else {
+                continue;
+            };
+            for fragment in line(&start, &end) {
+                let depth = fragment.depth - BODY_EDGE_DEPTH_OFFSET * (1.0 - fragment.depth).abs().max(1e-6);
+                points.push((fragment.position.x as i32, fragment.position.y as i32, BODY_EDGE_COLOR, depth));
+            }
+        }
+        points
+    });
+
+    // Sin el relleno nadie más cuenta los triángulos del cuerpo
+    if !uniforms.fill_mode.fills() {
+        let culled = per_triangle.iter().filter(|points| points.is_empty()).count();
+        framebuffer.count_triangles(per_triangle.len() as u32, culled as u32);
+    }
+    framebuffer.points(&per_triangle.concat());
+}
+
+// Como render_body, pero con la caché de superficie del cuerpo si la tiene:
+// mientras nada cambie se reescriben los fragmentos del frame anterior y solo
+// se vuelven a aplicar la exposición y el clamp
+fn render_celestial_body(framebuffer: &mut Framebuffer, uniforms: &Uniforms, vertex_array: &[Vertex],
+                         light: &Light, body: &CelestialBody) {
+    let Some(cache) = &body.surface_cache else {
+        render_body(framebuffer, uniforms, vertex_array, light);
+        return;
+    };
+    // La caché solo guarda el relleno; las aristas se dibujan siempre de nuevo
+    if !uniforms.fill_mode.fills() {
+        render_body_edges(framebuffer, uniforms, vertex_array);
+        return;
+    }
+
+    let mut cache = cache.borrow_mut();
+    let fragments = cache.fragments(uniforms, vertex_array, || {
+        let fragments = rasterize_body(framebuffer, uniforms, vertex_array, light);
+        let colors = parallel_map(&fragments, |fragment| shade_fragment(fragment, uniforms));
+        fragments.iter().zip(colors).map(|(fragment, color)| ShadedFragment {
+            x: fragment.position.x as i32,
+            y: fragment.position.y as i32,
+            depth: uniforms.depth_bias.apply(fragment.depth, fragment.depth_slope),
+            color,
+        }).collect()
+    });
+
+    let points = parallel_map(fragments, |fragment| {
+        (fragment.x, fragment.y, resolve_color(fragment.color, uniforms), fragment.depth)
+    });
+    framebuffer.points(&points);
+    if uniforms.fill_mode.draws_edges() {
+        render_body_edges(framebuffer, uniforms, vertex_array);
+    }
+}
+
+// Lo que dibuja una entrada de la cola de render, con sus uniforms ya armados
+enum DrawCommand<'a> {
+    Body(Uniforms<'a>, &'a CelestialBody),
+    Moon(Uniforms<'a>),
+    Rings(Uniforms<'a>),
+    Clouds(Uniforms<'a>),
+    Atmosphere(Uniforms<'a>),
+}
+
+fn draw_command(framebuffer: &mut Framebuffer, command: &DrawCommand, vertex_array: &[Vertex], light: &Light) {
+    match command {
+        DrawCommand::Body(uniforms, body) => render_celestial_body(framebuffer, uniforms, vertex_array, light, body),
+        DrawCommand::Moon(uniforms) => render_moon(framebuffer, uniforms, vertex_array, light),
+        DrawCommand::Rings(uniforms) => render_rings(framebuffer, uniforms, vertex_array, light),
+        DrawCommand::Clouds(uniforms) => render_cloud_layer(
+            framebuffer,
+            uniforms,
+            vertex_array,
+            CLOUD_SHELL_SCALE,
+            CLOUD_SHELL_SPEED,
+            CLOUD_SHELL_COVERAGE,
+            light,
+        ),
+        DrawCommand::Atmosphere(uniforms) => {
+            render_atmosphere(framebuffer, uniforms, vertex_array, 1.12, Vector3::new(0.3, 0.6, 1.0), light)
+        }
+    }
+}
+
+/// Draws `orbit` as a closed polyline of `segments` pieces through the same
+/// model-view-projection-viewport pipeline as the bodies. Each segment is
+/// clipped in clip space against the frustum (its sides widened by
+/// `ORBIT_GUARD_BAND`), so pieces behind the camera
+/// or past the near plane never get projected. Depth-tested against the
+/// scene, blended with the color's alpha and without writing depth.
+fn render_orbit_path(framebuffer: &mut Framebuffer, uniforms: &Uniforms, orbit: &Orbit,
+                     color: Color, segments: usize) {
+    let clip_matrix = uniforms.model.matrix * uniforms.view_matrix * uniforms.projection_matrix;
+    // El ancho es en píxeles de pantalla: con supersampling se escala a submuestras
+    let width = ORBIT_LINE_WIDTH * framebuffer.samples_per_axis() as f32;
+
+    let to_screen = |clip: Vector4| {
+        let ndc = Vector4::new(clip.x / clip.w, clip.y / clip.w, clip.z / clip.w, 1.0);
+        let screen = multiply_matrix_vector4(&uniforms.viewport_matrix, &ndc);
+        Vector3::new(screen.x, screen.y, screen.z)
+    };
+
+    let points = orbit.path(segments);
+    for pair in points.windows(2) {
+        let (a, b) = (pair[0], pair[1]);
+        let clip_a = multiply_matrix_vector4(&clip_matrix, &Vector4::new(a.x, a.y, a.z, 1.0));
+        let clip_b = multiply_matrix_vector4(&clip_matrix, &Vector4::new(b.x, b.y, b.z, 1.0));
+        let Some((t0, t1)) = clip_segment(clip_a, clip_b, ORBIT_GUARD_BAND) else {
+            continue;
+        };
+
+        let start = to_screen(clip_a.lerp(clip_b, t0));
+        let end = to_screen(clip_a.lerp(clip_b, t1));
+        framebuffer.draw_line_thick(start, end, color, width);
+    }
+}
+
+// Lo que cambia de frame a frame y no pertenece a los ajustes de render
+struct Scene<'a> {
+    sun: &'a CelestialBody,
+    planets: &'a [CelestialBody],
+    sphere: &'a LodChain<'a>, // Niveles de detalle de la esfera de los cuerpos
+    // Niveles del frame anterior en esta vista (id 0 el Sol, luego los planetas).
+    // Sin historial no hay histéresis, y la pasada no deja rastro en las demás vistas
+    lod_history: Option<&'a LodHistory>,
+    ring_vertex_array: &'a [Vertex],
+    nave_vertex_array: &'a [Vertex],
+    nave_materials: &'a [Material],
+    nave_texture: Option<&'a Texture>,
+    nave_scale: f32,
+    nave_offset: Vector3,
+    // Posición y orientación de la nave en modo vuelo; sin ella va pegada a la cámara
+    nave_pose: Option<(Vector3, Quaternion)>,
+    environment: &'a Environment,
+    time: f32,
+    dt: f32,
+}
+
+// Lo que no cambia entre frames: las mallas, la nave y el entorno. Los
+// cuerpos y el tiempo se actualizan cada frame, así que los recibe `scene`
+#[derive(Clone, Copy)]
+struct SceneAssets<'a> {
+    sphere: &'a LodChain<'a>,
+    ring_vertex_array: &'a [Vertex],
+    nave_vertex_array: &'a [Vertex],
+    nave_materials: &'a [Material],
+    nave_texture: Option<&'a Texture>,
+    nave_scale: f32,
+    nave_offset: Vector3,
+    environment: &'a Environment,
+}
+
+impl<'a> SceneAssets<'a> {
+    fn scene<'b>(&self, sun: &'b CelestialBody, planets: &'b [CelestialBody], time: f32, dt: f32) -> Scene<'b>
+    where
+        'a: 'b,
+    {
+        Scene {
+            sun,
+            planets,
+            sphere: self.sphere,
+            lod_history: None,
+            ring_vertex_array: self.ring_vertex_array,
+            nave_vertex_array: self.nave_vertex_array,
+            nave_materials: self.nave_materials,
+            nave_texture: self.nave_texture,
+            nave_scale: self.nave_scale,
+            nave_offset: self.nave_offset,
+            nave_pose: None,
+            environment: self.environment,
+            time,
+            dt,
+        }
+    }
+}
+
+// Dibuja un frame completo en el viewport del framebuffer (todo, salvo que se
+// haya fijado otro); `jitter` desplaza la imagen en fracciones de píxel.
+// Con `target_aspect`, la imagen ocupa el rectángulo centrado de ese aspecto
+// y el resto queda en negro (letterbox)
+fn render_scene(framebuffer: &mut Framebuffer, scene: &Scene, camera: &Camera,
+                settings: &RenderSettings, exposure: f32, jitter: Vector2) {
+    let (image_rect, projection_matrix) = scene_projection(framebuffer, camera, settings);
+    let viewport = Rectangle::new(image_rect.x + jitter.x, image_rect.y + jitter.y,
+                                  image_rect.width, image_rect.height);
+    render_scene_with_projection(framebuffer, scene, camera, settings, projection_matrix, exposure, viewport);
+
+    if settings.target_aspect.is_some() {
+        framebuffer.clear_outside(image_rect);
+    }
+}
+
+// Píxeles que ocupa la imagen en el viewport (el rectángulo del letterbox si
+// hay `target_aspect`) y la proyección de la cámara con ese aspecto
+fn scene_projection(framebuffer: &Framebuffer, camera: &Camera, settings: &RenderSettings) -> (Rectangle, Matrix) {
+    let image_rect = match settings.target_aspect {
+        Some(aspect) => framebuffer.letterbox_rect(aspect),
+        None => framebuffer.viewport().rectangle(),
+    };
+    let projection_matrix = create_projection_matrix_handed(
+        settings.fov_y,
+        image_rect.width / image_rect.height,
+        settings.near_plane,
+        settings.far_plane,
+        camera.handedness
+    );
+    (image_rect, projection_matrix)
+}
+
+// Luces que sombrean un cuerpo centrado en `target`: por ahora solo el Sol.
+// Comparado con el tamaño del cuerpo está tan lejos que sus rayos llegan
+// paralelos, así que es direccional, atenuado por la distancia al centro del
+// cuerpo (una direccional no se atenúa sola: su intensidad ya lleva la
+// atenuación del Sol a esa distancia). En la posición misma de la luz no hay
+// dirección y queda puntual
+fn sun_light(settings: &RenderSettings, target: Vector3) -> LightRig {
+    let to_target = target - settings.light_position;
+    let distance = to_target.length();
+    let light = if distance > 1e-6 {
+        let attenuation = light_attenuation(distance, settings.light_falloff) * settings.light_attenuation.at(distance);
+        Light::directional(to_target).with_color(settings.light_color, settings.light_intensity * attenuation)
+    } else {
+        Light::point(settings.light_position)
+            .with_color(settings.light_color, settings.light_intensity)
+            .with_attenuation(settings.light_attenuation)
+    };
+    LightRig::new(&[light])
+}
+
+// Centro de la luna de un planeta en `planet_position` con escala `planet_scale`
+fn moon_position(planet_position: Vector3, planet_scale: f32, time: f32) -> Vector3 {
+    let angle = time * MOON_ORBIT_SPEED;
+    let offset = Vector3::new(
+        MOON_ORBIT_RADIUS * angle.cos(),
+        (angle * 2.0).sin() * MOON_ORBIT_BOB,
+        MOON_ORBIT_RADIUS * angle.sin(),
+    );
+    planet_position + offset * planet_scale
+}
+
+// Malla de la esfera con la que se dibuja el cuerpo `id` (0 el Sol, luego los
+// planetas), según el radio que ocupa en pantalla; con el LOD apagado,
+// siempre la más fina. Sin historial, el nivel más fino que admite la banda
+fn body_mesh<'a>(scene: &Scene<'a>, settings: &RenderSettings, id: usize, body: &CelestialBody,
+                 camera_position: Vector3, projection_matrix: &Matrix, viewport_height: f32) -> &'a [Vertex] {
+    if !settings.level_of_detail {
+        return scene.sphere.finest();
+    }
+    let distance = (body.get_position() - camera_position).length();
+    let radius = projected_radius(body.scale * scene.sphere.radius(), distance, projection_matrix.m5.abs(), viewport_height);
+    let level = match scene.lod_history {
+        Some(history) => history.select(scene.sphere, id, radius),
+        None => scene.sphere.select(radius, 0),
+    };
+    scene.sphere.mesh(level)
+}
+
+// Como render_scene, pero con la proyección dada (p. ej. el sub-frustum de una tesela).
+// `viewport` son los píxeles que cubre la imagen, con el jitter ya sumado
+fn render_scene_with_projection(framebuffer: &mut Framebuffer, scene: &Scene, camera: &Camera,
+                                settings: &RenderSettings, projection_matrix: Matrix,
+                                exposure: f32, viewport: Rectangle) {
+    framebuffer.clear();
+    let light = Light::point(settings.light_position);
+    let render_type = if settings.debug_barycentric {
+        3
+    } else if settings.debug_facing {
+        4
+    } else if settings.debug_vertex_colors {
+        5
+    } else {
+        0
+    };
+
+    // Matrices de transformación
+    let view_matrix = camera.get_view_matrix();
+    let camera_position = camera_position_from_view(&view_matrix);
+    let pixel_viewport = framebuffer.viewport();
+    let viewport_matrix = create_viewport_matrix(viewport.x, viewport.y, viewport.width, viewport.height);
+
+    // Fondo: el entorno visto a lo largo del rayo de cada píxel (sin gamma: el
+    // campo de estrellas ya tiene los colores pensados para la pantalla)
+    render_skybox(
+        framebuffer,
+        scene.environment,
+        &view_matrix,
+        &projection_matrix,
+        &viewport_matrix,
+        exposure,
+    );
+
+    // Renderizar el Sol
+    let sun = scene.sun;
+    let sun_pos = sun.get_position();
+    let sun_rotation = Vector3::new(0.0, sun.rotation_angle, 0.0);
+    let sun_model_matrix = create_model_matrix(sun_pos, sun.scale, sun_rotation);
+
+    let sun_uniforms = Uniforms {
+        model: ModelTransform::new(sun_model_matrix),
+        view_matrix,
+        projection_matrix,
+        viewport_matrix,
+        viewport: pixel_viewport,
+        camera_position,
+        time: scene.time,
+        dt: scene.dt,
+        planet_type: sun.planet_type,
+        shader_params: sun.shader_params,
+        material: sun.shader_params.material(sun.planet_type),
+        render_type,
+        handedness: camera.handedness,
+        exposure,
+        layer_mask: settings.layer_mask,
+        environment: scene.environment,
+        raster_precision: settings.raster_precision,
+        specular_aa: settings.specular_aa,
+        double_sided: false,
+        ring_shadow: settings.ring_shadow,
+        depth_bias: DepthBias::default(),
+        dithered_transparency: settings.dithered_transparency,
+        deterministic_draw: settings.deterministic_draw,
+        color_clamp: settings.color_clamp,
+        gamma_correct: settings.gamma_correct,
+        lights: sun_light(settings, sun_pos),
+        light_falloff: settings.light_falloff,
+        ambient_floor: settings.ambient_floor,
+        materials: &[],
+        texture: None,
+        normal_map: None,
+        fill_mode: settings.body_fill,
+        fog: Fog { color: settings.fog_color, density: settings.fog_density },
+        cull_mode: settings.cull_mode,
+        front_face: settings.front_face,
+    };
+    // Con la cámara dentro del Sol (vista desde la luz) no se vería nada de él,
+    // y sus triángulos cruzarían el plano de la cámara
+    let camera_inside_sun = (camera.eye - sun_pos).length() <= sun.scale;
+
+    // Sol en pantalla para los god rays: centro, profundidad de su cara visible y radio
+    let toward_camera = (camera.eye - sun_pos).normalized();
+    let camera_right = Vector3::new(view_matrix.m0, view_matrix.m4, view_matrix.m8);
+    let project = |point: Vector3| project_to_screen(point, &view_matrix, &projection_matrix, &viewport_matrix);
+    let sun_screen = match (project(sun_pos), project(sun_pos + toward_camera * sun.scale),
+                            project(sun_pos + camera_right * sun.scale)) {
+        (Some(center), Some(face), Some(edge)) if !camera_inside_sun => Some((
+            Vector3::new(center.x, center.y, face.z),
+            Vector2::new(edge.x - center.x, edge.y - center.y).length(),
+        )),
+        _ => None,
+    };
+
+    // Cola de render: cada cuerpo y sus capas, por la profundidad de su origen en la vista
+    let depth_of = |position: Vector3| view_depth(position, &view_matrix, camera.handedness);
+    let mut queue = RenderQueue::new();
+    if !camera_inside_sun {
+        let mesh = body_mesh(scene, settings, 0, sun, camera_position, &projection_matrix, viewport.height);
+        queue.push_opaque(depth_of(sun_pos), (DrawCommand::Body(sun_uniforms.clone(), sun), mesh));
+    }
+
+    for (index, planet) in scene.planets.iter().enumerate() {
+        let planet_pos = planet.get_position();
+        let planet_rotation = Vector3::new(0.0, planet.rotation_angle, 0.0);
+        let planet_model_matrix = create_model_matrix(
+            planet_pos,
+            planet.scale,
+            planet_rotation
         );
+
+        let planet_uniforms = Uniforms {
+            model: ModelTransform::new(planet_model_matrix),
+            view_matrix,
+            projection_matrix,
+            viewport_matrix,
+            viewport: pixel_viewport,
+            camera_position,
+            time: scene.time,
+            dt: scene.dt,
+            planet_type: planet.planet_type,
+            shader_params: planet.shader_params,
+            material: planet.shader_params.material(planet.planet_type),
+            render_type,
+            handedness: camera.handedness,
+            exposure,
+            layer_mask: settings.layer_mask,
+            environment: scene.environment,
+            raster_precision: settings.raster_precision,
+            specular_aa: settings.specular_aa,
+            double_sided: false,
+            ring_shadow: settings.ring_shadow,
+            depth_bias: DepthBias::default(),
+            dithered_transparency: settings.dithered_transparency,
+            deterministic_draw: settings.deterministic_draw,
+            color_clamp: settings.color_clamp,
+            gamma_correct: settings.gamma_correct,
+            lights: sun_light(settings, planet_pos),
+            light_falloff: settings.light_falloff,
+            ambient_floor: settings.ambient_floor,
+            materials: &[],
+            texture: None,
+            normal_map: planet.normal_map.as_ref(),
+            fill_mode: settings.body_fill,
+            fog: Fog { color: settings.fog_color, density: settings.fog_density },
+            cull_mode: settings.cull_mode,
+            front_face: settings.front_face,
+        };
+        let depth = depth_of(planet_pos);
+        let mesh = body_mesh(scene, settings, index + 1, planet, camera_position, &projection_matrix, viewport.height);
+        queue.push_opaque(depth, (DrawCommand::Body(planet_uniforms.clone(), planet), mesh));
+
+        // La luna orbita el centro del planeta sin heredar su giro
+        if planet.has_moon {
+            let moon_pos = moon_position(planet_pos, planet.scale, scene.time);
+            let moon_uniforms = Uniforms {
+                model: ModelTransform::new(create_model_matrix(moon_pos, planet.scale * MOON_SCALE, Vector3::zero())),
+                ..planet_uniforms.clone()
+            };
+            queue.push_opaque(depth_of(moon_pos), (DrawCommand::Moon(moon_uniforms), mesh));
+        }
+
+        // Anillos si es Saturno (tipo 3): las bandas son opacas, pero los huecos se mezclan
+        if planet.planet_type == 3 {
+            queue.push_transparent(depth, (DrawCommand::Rings(planet_uniforms.clone()), scene.ring_vertex_array));
+        }
+
+        // Nubes (mezcla alfa) y atmósfera (aditiva) de la Tierra
+        if planet.planet_type == 2 {
+            if settings.cloud_shell {
+                queue.push_transparent(depth, (DrawCommand::Clouds(planet_uniforms.clone()), mesh));
+            }
+            queue.push_transparent(depth, (DrawCommand::Atmosphere(planet_uniforms), mesh));
+        }
+    }
+
+    let (opaque, transparent) = queue.into_passes();
+    for (command, mesh) in &opaque {
+        draw_command(framebuffer, command, mesh, &light);
+    }
+
+    // Órbitas: translúcidas y sin escribir profundidad, así que van después
+    // de los cuerpos opacos y no los tapan cuando pasan por detrás
+    if settings.show_orbits {
+        let orbit_uniforms = Uniforms { model: ModelTransform::new(Matrix::identity()), ..sun_uniforms.clone() };
+        for planet in scene.planets {
+            render_orbit_path(framebuffer, &orbit_uniforms, &planet.orbit, Color::new(100, 100, 150, 100), 64);
+        }
+    }
+
+    // Anillos, nubes y atmósferas, de atrás hacia adelante sobre todo lo anterior
+    for (command, mesh) in &transparent {
+        draw_command(framebuffer, command, mesh, &light);
     }
+
+    // Renderizar la nave espacial: en modo vuelo donde esté; si no, pegada a la
+    // cámara, enfrente. Sigue la base de la cámara, así que acompaña el pitch,
+    // el roll y el paso por los polos
+    let (nave_position, nave_heading) = scene.nave_pose.unwrap_or_else(|| (
+        camera.eye
+            + camera.forward() * -scene.nave_offset.z
+            + camera.right() * scene.nave_offset.x
+            + camera.up() * scene.nave_offset.y,
+        camera.orientation,
+    ));
+
+    // Orientación con un cuarto de vuelta en Y: el modelo tiene el largo
+    // sobre su eje Z, que queda atravesado frente a la cámara
+    let nave_orientation = nave_heading * Quaternion::from_axis_angle(Vector3::new(0.0, 1.0, 0.0), -PI / 2.0);
+    let nave_model_matrix = create_model_matrix_oriented(nave_position, scene.nave_scale, nave_orientation);
+
+    let nave_uniforms = Uniforms {
+        model: ModelTransform::new(nave_model_matrix),
+        view_matrix,
+        projection_matrix,
+        viewport_matrix,
+        viewport: pixel_viewport,
+        camera_position,
+        time: scene.time,
+        dt: scene.dt,
+        planet_type: 10, // Tipo especial para la nave
+        shader_params: ShaderParams::default(),
+        material: SurfaceMaterial::for_planet_type(10),
+        materials: scene.nave_materials,
+        texture: scene.nave_texture,
+        normal_map: None,
+        fill_mode: FillMode::Solid, // La nave tiene su propio wireframe (nave_wireframe)
+        fog: Fog { color: settings.fog_color, density: settings.fog_density },
+        cull_mode: CullMode::None, // Doble cara: se dibujan las dos
+        front_face: settings.front_face,
+        render_type,
+        handedness: camera.handedness,
+        exposure,
+        layer_mask: settings.layer_mask,
+        environment: scene.environment,
+        raster_precision: settings.raster_precision,
+        specular_aa: settings.specular_aa,
+        double_sided: true, // Alas finas: se ven por ambos lados
+        ring_shadow: settings.ring_shadow,
+        depth_bias: DepthBias::default(),
+        dithered_transparency: settings.dithered_transparency,
+        deterministic_draw: settings.deterministic_draw,
+        color_clamp: settings.color_clamp,
+        gamma_correct: settings.gamma_correct,
+        lights: sun_light(settings, nave_position),
+        light_falloff: settings.light_falloff,
+        ambient_floor: settings.ambient_floor,
+    };
+    match settings.nave_wireframe {
+        Some(style) => render_wireframe(
+            framebuffer,
+            &nave_uniforms,
+            scene.nave_vertex_array,
+            style,
+            Vector3::new(0.4, 0.9, 1.0),
+        ),
+        None => render_body(framebuffer, &nave_uniforms, scene.nave_vertex_array, &light),
+    }
+
+    settings.post.apply(framebuffer, sun_screen, settings.near_plane, settings.far_plane);
 }
 
-fn render_orbit(_framebuffer: &mut Framebuffer, _points: &[Vector3],
-                _view_matrix: &Matrix, _projection_matrix: &Matrix,
-                _viewport_matrix: &Matrix, _color: Color) {
-    // Esta función renderiza las órbitas como líneas
-    // Necesitarías implementar esto según tu sistema de proyección
-    // Por ahora es un placeholder
+// Pasada de selección: el Sol (id 0) y los planetas (id 1 en adelante) con
+// su id como color plano, para leerlo con `read_id_at`. El test de
+// profundidad deja el cuerpo más cercano en cada píxel. Solo cuentan los
+// cuerpos: anillos, lunas, órbitas y la nave no se pueden elegir
+fn render_pick(framebuffer: &mut Framebuffer, scene: &Scene, camera: &Camera, settings: &RenderSettings) {
+    framebuffer.clear();
+    let light = Light::point(settings.light_position);
+    let (image_rect, projection_matrix) = scene_projection(framebuffer, camera, settings);
+    let view_matrix = camera.get_view_matrix();
+    let base_uniforms = Uniforms {
+        model: ModelTransform::new(Matrix::identity()),
+        view_matrix,
+        projection_matrix,
+        viewport_matrix: create_viewport_matrix(image_rect.x, image_rect.y, image_rect.width, image_rect.height),
+        viewport: framebuffer.viewport(),
+        camera_position: camera.eye,
+        time: scene.time,
+        dt: scene.dt,
+        planet_type: 0,
+        shader_params: ShaderParams::default(),
+        material: SurfaceMaterial::for_planet_type(0),
+        render_type: 0,
+        handedness: camera.handedness,
+        exposure: 1.0,
+        layer_mask: settings.layer_mask,
+        environment: scene.environment,
+        raster_precision: settings.raster_precision,
+        specular_aa: false,
+        double_sided: false,
+        ring_shadow: false,
+        depth_bias: DepthBias::default(),
+        dithered_transparency: false,
+        deterministic_draw: settings.deterministic_draw,
+        color_clamp: settings.color_clamp,
+        gamma_correct: false,
+        lights: sun_light(settings, Vector3::zero()),
+        light_falloff: settings.light_falloff,
+        ambient_floor: settings.ambient_floor,
+        materials: &[],
+        texture: None,
+        normal_map: None,
+        fill_mode: FillMode::Solid,
+        fog: Fog { color: settings.fog_color, density: 0.0 },
+        cull_mode: settings.cull_mode,
+        front_face: settings.front_face,
+    };
+
+    // Con la cámara dentro del Sol no se dibuja, como en render_scene
+    let sun_visible = (camera.eye - scene.sun.get_position()).length() > scene.sun.scale;
+    let bodies = std::iter::once(scene.sun).filter(|_| sun_visible).map(|sun| (0, sun))
+        .chain(scene.planets.iter().enumerate().map(|(index, planet)| (index as u32 + 1, planet)));
+    for (id, body) in bodies {
+        let rotation = Vector3::new(0.0, body.rotation_angle, 0.0);
+        let uniforms = Uniforms {
+            model: ModelTransform::new(create_model_matrix(body.get_position(), body.scale, rotation)),
+            planet_type: body.planet_type,
+            ..base_uniforms.clone()
+        };
+        // Mismo rasterizado que el relleno, pero sin sombrear
+        let color = id_color(id);
+        let points: Vec<_> = rasterize_body(framebuffer, &uniforms, scene.sphere.finest(), &light)
+            .iter()
+            .map(|fragment| (fragment.position.x as i32, fragment.position.y as i32, color, fragment.depth))
+            .collect();
+        framebuffer.points(&points);
+    }
 }
 
-fn main() {
-    let window_width = 1600;
-    let window_height = 900;
+// Mundo -> pantalla (x, y en píxeles, z = profundidad NDC); None detrás de la cámara
+fn project_to_screen(point: Vector3, view_matrix: &Matrix, projection_matrix: &Matrix,
+                     viewport_matrix: &Matrix) -> Option<Vector3> {
+    let world_to_screen = *view_matrix * *projection_matrix * *viewport_matrix;
+    let p = multiply_matrix_vector4(&world_to_screen, &Vector4::new(point.x, point.y, point.z, 1.0));
+    if p.w <= 0.0 {
+        return None;
+    }
+    Some(Vector3::new(p.x / p.w, p.y / p.w, p.z / p.w))
+}
 
-    let (mut window, raylib_thread) = raylib::init()
-        .size(window_width, window_height)
-        .title("Sistema Solar con Nave - Software Renderer")
-        .log_level(TraceLogLevel::LOG_WARNING)
-        .build();
+// Cámara de diagnóstico en la luz, mirando a donde mira la cámara principal
+// (hacia la cámara si lo que mira es la propia luz, p. ej. el sol)
+fn light_camera(camera: &Camera, light_position: Vector3) -> Camera {
+    let target = if (camera.target - light_position).length() > 1e-3 { camera.target } else { camera.eye };
+    let mut light_camera = Camera::new(light_position, target, Vector3::new(0.0, 1.0, 0.0));
+    light_camera.handedness = camera.handedness;
+    light_camera
+}
+
+// Cámara del retrovisor: en el mismo ojo, mirando en sentido contrario
+fn rear_camera(camera: &Camera) -> Camera {
+    let behind = camera.eye * 2.0 - camera.target;
+    let mut rear_camera = Camera::new(camera.eye, behind, camera.world_up);
+    rear_camera.handedness = camera.handedness;
+    rear_camera
+}
+
+// Un frame de `--record`: cada subframe avanza una fracción del paso y se
+// acumula con los demás, así lo que se mueve queda promediado a lo largo del frame
+fn record_frame(framebuffer: &mut Framebuffer, assets: &SceneAssets, sun: &mut CelestialBody,
+                planets: &mut [CelestialBody], time: &mut f32, subframes: u32,
+                draw: impl Fn(&mut Framebuffer, &Scene, Vector2)) {
+    let subframe_dt = RECORD_FRAME_DT / subframes as f32;
+    framebuffer.accumulate(subframes, |framebuffer, jitter| {
+        *time += subframe_dt;
+        sun.update(subframe_dt);
+        for planet in planets.iter_mut() {
+            planet.update(subframe_dt);
+        }
+        draw(framebuffer, &assets.scene(sun, planets, *time, subframe_dt), jitter);
+    });
+}
+
+/// Renders a `total_width` x `total_height` image as independent tiles of at
+/// most `tile_size` pixels, each through its own off-center frustum, and
+/// stitches them together. Only one tile-sized framebuffer is alive at a
+/// time, so the output can be far larger than the window. Screen-space
+/// effects (bloom) run per tile and don't bleed across tile edges
+fn render_tiled(scene: &Scene, camera: &Camera, settings: &RenderSettings, exposure: f32,
+                total_width: i32, total_height: i32, tile_size: i32) -> Image {
+    let mut image = Image::gen_image_color(total_width, total_height, Color::BLACK);
+    // Sin historial de LOD: cada tesela es otra vista y no debe mover el de la principal
+    let tile_scene = Scene { lod_history: None, ..*scene };
+
+    for tile_y in (0..total_height).step_by(tile_size as usize) {
+        for tile_x in (0..total_width).step_by(tile_size as usize) {
+            let width = tile_size.min(total_width - tile_x);
+            let height = tile_size.min(total_height - tile_y);
+            let tile = Rectangle::new(tile_x as f32, tile_y as f32, width as f32, height as f32);
+            let projection_matrix = create_tile_projection_matrix(
+                settings.fov_y,
+                total_width as f32,
+                total_height as f32,
+                tile,
+                settings.near_plane,
+                settings.far_plane,
+                camera.handedness,
+            );
+
+            let mut framebuffer = Framebuffer::new(width, height);
+            let viewport = Rectangle::new(0.0, 0.0, width as f32, height as f32);
+            render_scene_with_projection(&mut framebuffer, &tile_scene, camera, settings, projection_matrix,
+                                         exposure, viewport);
+
+            // Copiar la tesela a su lugar en la imagen completa
+            for (index, pixel) in framebuffer.color_buffer.get_image_data().iter().enumerate() {
+                let (x, y) = (index as i32 % width, index as i32 / width);
+                image.draw_pixel(tile_x + x, tile_y + y, *pixel);
+            }
+        }
+    }
+
+    image
+}
+
+// Minimapa cenital: cuerpos proyectados ortográficamente sobre XZ dentro de `rect`,
+// más la posición y orientación de la cámara. Se dibuja encima de todo, sin profundidad
+fn render_minimap(framebuffer: &mut Framebuffer, scene: &Scene, camera: &Camera, rect: Rectangle) {
+    // Fondo oscuro y borde
+    framebuffer.set_current_color(Color::new(10, 10, 25, 255));
+    for y in rect.y as i32..(rect.y + rect.height) as i32 {
+        for x in rect.x as i32..(rect.x + rect.width) as i32 {
+            framebuffer.set_pixel(x, y);
+        }
+    }
+    let (left, top) = (rect.x, rect.y);
+    let (right, bottom) = (rect.x + rect.width - 1.0, rect.y + rect.height - 1.0);
+    let border = Color::new(120, 120, 160, 255);
+    draw_minimap_line(framebuffer, Vector2::new(left, top), Vector2::new(right, top), border);
+    draw_minimap_line(framebuffer, Vector2::new(right, top), Vector2::new(right, bottom), border);
+    draw_minimap_line(framebuffer, Vector2::new(right, bottom), Vector2::new(left, bottom), border);
+    draw_minimap_line(framebuffer, Vector2::new(left, bottom), Vector2::new(left, top), border);
+
+    // Escala para que quepa el afelio más lejano
+    let extent = scene.planets
+        .iter()
+        .map(|planet| planet.orbit.semi_major_axis * (1.0 + planet.orbit.eccentricity))
+        .fold(1.0, f32::max);
+    let scale = (rect.width.min(rect.height) / 2.0 - MINIMAP_MARGIN) / extent;
+    let center = Vector2::new(rect.x + rect.width / 2.0, rect.y + rect.height / 2.0);
+
+    // Vista desde +Y: x a la derecha, z hacia abajo. Lo que cae fuera se pega al borde
+    let to_map = |position: Vector3| Vector2::new(
+        (center.x + position.x * scale).clamp(left + 1.0, right - 1.0),
+        (center.y + position.z * scale).clamp(top + 1.0, bottom - 1.0),
+    );
+
+    for planet in scene.planets {
+        let points = planet.get_orbit_points(48);
+        for pair in points.windows(2) {
+            draw_minimap_line(framebuffer, to_map(pair[0]), to_map(pair[1]), Color::new(50, 50, 90, 255));
+        }
+    }
+
+    for body in std::iter::once(scene.sun).chain(scene.planets) {
+        let radius = if body.planet_type == 5 { 3 } else { 1 };
+        draw_minimap_dot(framebuffer, to_map(body.get_position()), radius, minimap_color(body.planet_type));
+    }
+
+    // Cámara: punto y línea hacia donde mira
+    let eye = to_map(camera.eye);
+    let forward = Vector2::new(camera.target.x - camera.eye.x, camera.target.z - camera.eye.z);
+    if forward.length() > 1e-4 {
+        let tip = eye + forward.normalized() * MINIMAP_CAMERA_ARROW;
+        draw_minimap_line(framebuffer, eye, tip, Color::WHITE);
+    }
+    draw_minimap_dot(framebuffer, eye, 1, Color::WHITE);
+}
+
+fn minimap_color(planet_type: i32) -> Color {
+    match planet_type {
+        0 => Color::new(170, 140, 110, 255), // Rocoso
+        1 => Color::new(230, 190, 120, 255), // Gaseoso
+        2 => Color::new(90, 160, 230, 255),  // Tierra
+        3 => Color::new(220, 200, 150, 255), // Anillos
+        4 => Color::new(150, 220, 255, 255), // Cristal
+        _ => Color::new(255, 220, 80, 255),  // Sol
+    }
+}
+
+fn draw_minimap_line(framebuffer: &mut Framebuffer, from: Vector2, to: Vector2, color: Color) {
+    let mut start = Vertex::new(Vector3::zero(), Vector3::zero(), Vector2::zero());
+    let mut end = start.clone();
+    start.transformed_position = Vector3::new(from.x, from.y, 0.0);
+    end.transformed_position = Vector3::new(to.x, to.y, 0.0);
+
+    framebuffer.set_current_color(color);
+    for fragment in line(&start, &end) {
+        framebuffer.set_pixel(fragment.position.x as i32, fragment.position.y as i32);
+    }
+}
+
+fn draw_minimap_dot(framebuffer: &mut Framebuffer, center: Vector2, radius: i32, color: Color) {
+    framebuffer.set_current_color(color);
+    for dy in -radius..=radius {
+        for dx in -radius..=radius {
+            if dx * dx + dy * dy <= radius * radius {
+                framebuffer.set_pixel(center.x as i32 + dx, center.y as i32 + dy);
+            }
+        }
+    }
+}
+
+// Deja las caras del modelo en sentido antihorario (frontales en pantalla, ver
+// Handedness::front_face_sign), venga como venga el archivo
+fn orient_counter_clockwise(obj: &mut Obj) {
+    if obj.detect_winding() == Winding::Clockwise {
+        obj.flip_winding();
+    }
+}
+
+// Nombre con la hora (milisegundos Unix) para no pisar capturas anteriores
+fn screenshot_path() -> String {
+    let millis = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_millis());
+    format!("screenshot_{}.png", millis)
+}
+
+// Tamaño de imagen en la línea de comandos: ANCHOxALTO
+struct ImageSize {
+    width: i32,
+    height: i32,
+}
+
+impl FromStr for ImageSize {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (width, height) = value.split_once('x').ok_or(())?;
+        Ok(ImageSize { width: width.parse().map_err(|_| ())?, height: height.parse().map_err(|_| ())? })
+    }
+}
+
+// Valor de la opción `name` en `args`: None si no aparece, Some(None) si su
+// valor falta, no se puede leer o `valid` lo rechaza
+fn flag_value<T: FromStr>(args: &[String], name: &str, valid: impl Fn(&T) -> bool) -> Option<Option<T>> {
+    let index = args.iter().position(|arg| arg == name)?;
+    Some(args.get(index + 1).and_then(|value| value.parse().ok()).filter(valid))
+}
+
+// La opción `name` de la línea de comandos; con un valor no válido se sale
+// explicando lo que necesita
+fn parse_flag<T: FromStr>(name: &str, error_message: &str, valid: impl Fn(&T) -> bool) -> Option<T> {
+    let args: Vec<String> = std::env::args().collect();
+    flag_value(&args, name, valid).map(|value| value.unwrap_or_else(|| {
+        eprintln!("{} {}", name, error_message);
+        std::process::exit(1);
+    }))
+}
+
+// Los modelos son obligatorios: sin ellos no hay nada que dibujar, así que se sale explicando por qué
+fn load_model(path: &str) -> Obj {
+    Obj::load(path).unwrap_or_else(|err| {
+        eprintln!("No se pudo cargar el modelo {}: {}", path, err);
+        std::process::exit(1);
+    })
+}
+
+fn main() {
+    let mut window_width = 1600;
+    let mut window_height = 900;
+    // `--render-samples N` en la línea de comandos activa el render offline
+    let render_samples = parse_flag("--render-samples", "necesita un número de muestras mayor que 0", |&samples: &u32| samples > 0);
+    // Tamaño de un render offline por teselas, `--render-size 6400x3600`
+    let render_size = parse_flag("--render-size", "necesita un tamaño ANCHOxALTO, p. ej. 6400x3600",
+                                 |size: &ImageSize| size.width > 0 && size.height > 0)
+        .map(|size| (size.width, size.height));
+    // `--record N` graba N frames con paso fijo en RECORD_DIR
+    let record_frames = parse_flag("--record", "necesita un número de frames mayor que 0", |&frames: &u32| frames > 0);
+    // `--subframes N` promedia N instantes por frame grabado (desenfoque de movimiento)
+    let record_subframes = parse_flag("--subframes", "necesita un número de subframes mayor que 0", |&subframes: &u32| subframes > 0)
+        .unwrap_or(1);
+    // `--fps N` fija el límite de frames por segundo de la ventana; `--fps 0` lo quita
+    let target_fps = parse_flag("--fps", "necesita un número de frames por segundo (0: sin límite)", |_: &u32| true)
+        .unwrap_or(DEFAULT_TARGET_FPS);
+    // `--threads N` limita los hilos del render (si no, RENDER_THREADS o todos los núcleos)
+    if let Some(threads) = parse_flag("--threads", "necesita un número de hilos mayor que 0", |&threads: &usize| threads > 0) {
+        parallel::set_render_threads(threads);
+    }
+    // `--parallel-min N`: elementos por hilo a partir de los que se reparte el trabajo
+    if let Some(items) = parse_flag("--parallel-min", "necesita un número de elementos por hilo mayor que 0",
+                                    |&items: &usize| items > 0) {
+        parallel::set_min_items_per_thread(items);
+    }
+    // `--parallel-points` reparte también los tests de profundidad entre los hilos
+    if std::env::args().any(|arg| arg == "--parallel-points") {
+        parallel::set_parallel_points(true);
+    }
+    // `--log stats.csv` guarda las estadísticas de cada frame
+    let stats_log_path = parse_flag("--log", "necesita la ruta del archivo CSV, p. ej. stats.csv",
+                                    |path: &String| !path.starts_with("--"));
+    let mut stats_log = stats_log_path.and_then(|path| match StatsLog::create(&path) {
+        Ok(log) => {
+            println!("Estadísticas por frame en {}", path);
+            Some(log)
+        }
+        Err(err) => {
+            println!("No se pudo crear el registro de estadísticas {}: {}", path, err);
+            None
+        }
+    });
 
     let mut framebuffer = Framebuffer::new(window_width, window_height);
 
+    let mut scene_config = match SceneConfig::load(SCENE_PATH) {
+        Ok(config) => config,
+        Err(err) => {
+            println!("Sin configuración de escena ({}): usando el sistema por defecto", err);
+            SceneConfig::default()
+        }
+    };
+
     // Cámara inicial
     let mut camera = Camera::new(
-        Vector3::new(0.0, 15.0, 25.0),
-        Vector3::new(0.0, 0.0, 0.0),
-        Vector3::new(0.0, 1.0, 0.0),
+        scene_config.camera_eye,
+        scene_config.camera_target,
+        scene_config.camera_up,
     );
 
-    // Luz desde el sol
-    let light = Light::new(Vector3::new(0.0, 0.0, 0.0));
-
     // Cargar modelos
-    let sphere_obj = Obj::load("models/sphere.obj").expect("Failed to load sphere.obj");
-    let sphere_vertex_array = sphere_obj.get_vertex_array();
+    let mut sphere_obj = load_model("models/sphere.obj");
+    orient_counter_clockwise(&mut sphere_obj);
+    // Las UV del archivo son una por cara: los mapas de normales necesitan unas que envuelvan la esfera
+    sphere_obj.with_spherical_uvs();
+    sphere_obj.subdivide(SPHERE_SUBDIVISIONS, true);
+    // Para los cuerpos lejanos: la esfera sin subdividir y un icosaedro de su radio medio
+    let mut sphere_coarse = load_model("models/sphere.obj");
+    orient_counter_clockwise(&mut sphere_coarse);
+    sphere_coarse.with_spherical_uvs();
+    let sphere_center = sphere_obj.center();
+    let sphere_mean_radius = sphere_obj.vertices.iter().map(|vertex| (vertex.position - sphere_center).length()).sum::<f32>()
+        / sphere_obj.vertices.len() as f32;
+    let sphere_far = Obj::icosphere(sphere_center, sphere_mean_radius, SPHERE_FAR_SUBDIVISIONS);
+    let sphere_lod = LodChain::new(sphere_obj.radius(), vec![
+        (sphere_obj.get_vertex_array(), SPHERE_LOD_RADII[0]),
+        (sphere_coarse.get_vertex_array(), SPHERE_LOD_RADII[1]),
+        (sphere_far.get_vertex_array(), 0.0),
+    ]);
+    let ring_obj = Obj::annulus(RING_INNER_RADIUS, RING_OUTER_RADIUS, RING_SEGMENTS);
+    let ring_vertex_array = ring_obj.get_vertex_array();
     
-    let nave_obj = Obj::load("models/nave.obj").expect("Failed to load nave.obj");
+    let mut nave_obj = load_model("models/nave.obj");
+    orient_counter_clockwise(&mut nave_obj);
     let nave_vertex_array = nave_obj.get_vertex_array();
+    let (nave_min, nave_max) = nave_obj.bounding_box();
+    let nave_size = nave_max - nave_min;
+    println!("Nave: {} triángulos, caja de {:.1} x {:.1} x {:.1}",
+             nave_vertex_array.len() / 3, nave_size.x, nave_size.y, nave_size.z);
+    // Textura difusa de la nave: la del primer material que nombre una (`map_Kd`)
+    let nave_texture = nave_obj
+        .materials
+        .iter()
+        .find_map(|material| material.diffuse_texture.as_deref())
+        .and_then(|path| match Texture::load(path) {
+            Ok(texture) => Some(texture),
+            Err(err) => {
+                println!("No se pudo cargar la textura {} ({}): la nave queda sin textura", path, err);
+                None
+            }
+        });
 
-    framebuffer.set_background_color(Color::new(5, 5, 15, 255));
+    framebuffer.set_background_color(BACKGROUND_COLOR);
 
-    // Crear el sistema solar
-    let mut sun = CelestialBody::new(5, 0.0, 0.0, 0.1, 2.5, "Sol");
+    // `--environment mapa.hdr` cambia el mapa de entorno; `--environment gradient` usa el degradado de prueba
+    let environment_path = parse_flag("--environment", "necesita la ruta de un .hdr o \"gradient\"",
+                                      |path: &String| !path.starts_with("--"));
+    let environment = match environment_path.as_deref() {
+        Some("gradient") => Environment::gradient(),
+        path => match Environment::load(path.unwrap_or(ENVIRONMENT_PATH)) {
+            Ok(environment) => environment,
+            Err(err) => {
+                println!("Sin mapa de entorno ({}): usando campo de estrellas", err);
+                Environment::starfield()
+            }
+        },
+    };
 
-    let mut planets = vec![
-        CelestialBody::new(0, 5.0, 0.8, 2.0, 0.6, "Mercurio"),    // Rocky
-        CelestialBody::new(1, 8.0, 0.6, 1.5, 0.9, "Venus"),       // Gaseous
-        CelestialBody::new(2, 12.0, 0.5, 1.8, 1.0, "Tierra"),     // Custom
-        CelestialBody::new(3, 18.0, 0.3, 1.2, 1.3, "Saturno"),    // Con anillos
-        CelestialBody::new(4, 24.0, 0.2, 0.9, 1.1, "Neptuno"),    // Extra planet
-    ];
+    // Crear el sistema solar
+    let (mut sun, mut planets) = bodies_from_config(&scene_config, 0.0);
 
     // Nave espacial - posicionada en la cámara
     let nave_scale = 0.3;  // Tamaño visible
-    let nave_offset = Vector3::new(0.8, -0.5, -3.0);  // Offset desde la cámara (más lejos hacia adelante)
+    let nave_offset = Vector3::new(-0.8, -0.5, -3.0);  // Offset desde la cámara: derecha, arriba y atrás (z < 0 es adelante)
+    let assets = SceneAssets {
+        sphere: &sphere_lod,
+        ring_vertex_array,
+        nave_vertex_array,
+        nave_materials: &nave_obj.materials,
+        nave_texture: nave_texture.as_ref(),
+        nave_scale,
+        nave_offset,
+        environment: &environment,
+    };
 
     let mut time = 0.0;
+    let mut paused = false;
+    let mut view_from_light = false; // Diagnóstico: ver la escena desde la luz
     let mut warp_target: Option<usize> = None;
     let mut warp_progress = 0.0;
-    let mut show_orbits = true;
     let mut camera_mode = 0; // 0: free, 1-5: following planets
+    let mut flight: Option<Ship> = None; // Modo vuelo: WASD pilota la nave y la cámara la sigue
+    // Histéresis del LOD por vista: el retrovisor tiene otro tamaño de viewport
+    let (main_lod, rear_lod) = (LodHistory::new(), LodHistory::new());
+    let mut settings = RenderSettings {
+        light_position: scene_config.light_position,
+        light_color: scene_config.light_color,
+        light_intensity: scene_config.light_intensity,
+        light_attenuation: scene_config.light_attenuation,
+        ..RenderSettings::default()
+    };
+    let mut look = load_look();
+    let mut look_watch = FileWatch::new(SHADER_PARAMS_PATH);
+    let mut auto_exposure = AutoExposure::new(settings.target_luminance, settings.adaptation_speed);
+
+    // Secuencia de capas: todas, acumulando una a una, y luego aisladas
+    let layer_presets = [LAYER_ALL, 0b0001, 0b0011, 0b0111, 0b0010, 0b0100, 0b1000];
+    let mut layer_preset = 0;
+
+    // Render offline: sin ventana, cámara y tiempo fijos. N muestras con jitter
+    // o, con --render-size, a cualquier resolución por teselas. Con --record,
+    // una secuencia de frames con el tiempo avanzando a paso fijo
+    if render_samples.is_some() || render_size.is_some() || record_frames.is_some() {
+        if let Ok(saved) = RenderSettings::load(SETTINGS_PATH) {
+            settings = saved;
+        }
+        apply_look(&look, &scene_config, &mut sun, &mut planets, &mut settings);
+        auto_exposure.enabled = settings.auto_exposure;
+        auto_exposure.target_luminance = settings.target_luminance;
+        camera.handedness = settings.handedness;
+
+        let lod_history = LodHistory::new();
+        let scene = Scene { lod_history: Some(&lod_history), ..assets.scene(&sun, &planets, time, 0.0) };
+
+        // La exposición se estabiliza antes de acumular
+        for _ in 0..EXPOSURE_WARMUP_FRAMES {
+            render_scene(&mut framebuffer, &scene, &camera, &settings, auto_exposure.exposure(), Vector2::zero());
+            auto_exposure.adapt(&framebuffer, 1.0);
+        }
+
+        if let Some(frames) = record_frames {
+            if let Err(err) = std::fs::create_dir_all(RECORD_DIR) {
+                eprintln!("No se pudo crear la carpeta {}: {}", RECORD_DIR, err);
+                std::process::exit(1);
+            }
+
+            for frame in 0..frames {
+                let exposure = auto_exposure.exposure();
+                record_frame(&mut framebuffer, &assets, &mut sun, &mut planets, &mut time, record_subframes,
+                             |framebuffer, scene, jitter| {
+                    let scene = Scene { lod_history: Some(&lod_history), ..*scene };
+                    render_scene(framebuffer, &scene, &camera, &settings, exposure, jitter);
+                });
+                auto_exposure.adapt(&framebuffer, RECORD_FRAME_DT);
+                framebuffer.color_buffer.export_image(&format!("{}/frame_{:04}.png", RECORD_DIR, frame));
+            }
+            println!("{} frames ({} subframes cada uno) guardados en {}/", frames, record_subframes, RECORD_DIR);
+            return;
+        }
+
+        let exposure = auto_exposure.exposure();
+        match render_size {
+            Some((width, height)) => {
+                let image = render_tiled(&scene, &camera, &settings, exposure,
+                                         width, height, RENDER_TILE_SIZE);
+                image.export_image(RENDER_OUTPUT_PATH);
+                println!("Render de {}x{} guardado en {}", width, height, RENDER_OUTPUT_PATH);
+            }
+            None => {
+                let samples = render_samples.unwrap_or(1);
+                framebuffer.accumulate(samples, |framebuffer, jitter| {
+                    render_scene(framebuffer, &scene, &camera, &settings, exposure, jitter);
+                });
+                framebuffer.color_buffer.export_image(RENDER_OUTPUT_PATH);
+                println!("Render de {} muestras guardado en {}", samples, RENDER_OUTPUT_PATH);
+            }
+        }
+        return;
+    }
+
+    let (mut window, raylib_thread) = raylib::init()
+        .size(window_width, window_height)
+        .resizable()
+        .title("Sistema Solar con Nave - Software Renderer")
+        .log_level(TraceLogLevel::LOG_WARNING)
+        .build();
+    // 0 deja el bucle sin límite; el tiempo de la simulación avanza con el dt real igual
+    window.set_target_fps(target_fps);
 
     println!("=== Controles ===");
     println!("WASD/Flechas: Mover cámara");
     println!("Q/E: Subir/Bajar cámara");
+    println!("Z/C: Alabear (roll) la cámara");
     println!("1-5: Seguir planetas");
     println!("0: Cámara libre");
+    println!("6: Pilotar la nave (W/S: acelerar/frenar, A/D: girar, flechas: cabeceo, Z/C: alabeo)");
+    println!("Rueda del ratón: Zoom (campo de visión)");
     println!("SPACE: Warp al siguiente planeta");
+    println!("ENTER: Pausar / reanudar la simulación");
+    println!("F1: Ver la escena desde la luz");
+    println!("F2: Antialiasing por supersampling 2x2");
     println!("O: Toggle órbitas");
+    println!("M: Toggle minimapa");
+    println!("F10: Toggle retrovisor");
     println!("R: Reset cámara");
+    println!("X: Toggle auto-exposición");
+    println!("G: Estilo wireframe de la nave");
+    println!("F3: Cuerpos sólidos / aristas / aristas sobre sólido");
+    println!("L: Ciclar capas de los shaders");
+    println!("H: Heatmap de overdraw");
+    println!("P: Rasterizado float / punto fijo");
+    println!("K: Toggle antialiasing especular");
+    println!("T: Modo de color (clamp / preservar tono / tone map)");
+    println!("F4: Toggle corrección gamma 2.2");
+    println!("F7: Caras descartadas (ninguna / traseras / frontales)");
+    println!("B: Debug de coordenadas baricéntricas");
+    println!("V: Debug de caras frontales (verde) / traseras (rojo)");
+    println!("F11: Colores por vértice de las mallas (blanco si no tienen)");
+    println!("\\: Toggle nivel de detalle de las esferas lejanas");
+    println!("N: Toggle bloom");
+    println!("J: Toggle god rays");
+    println!("U: Toggle capa de nubes");
+    println!("I: Toggle sombra de los anillos");
+    println!(";: Nubes con mezcla alfa / transparencia con dithering");
+    println!("TAB: Pantalla completa / letterbox 2.39:1");
+    println!("[/]: Umbral del bloom  -/=: Intensidad del bloom");
+    println!("Y: Toggle profundidad de campo  ,/.: Distancia de foco  Clic: Enfocar bajo el cursor");
+    println!("Clic derecho: Qué cuerpo hay bajo el cursor");
+    println!("F5/F9: Guardar/Cargar ajustes de render");
+    println!("F6: Recargar la escena ({})", SCENE_PATH);
+    println!("F8: Guardar una captura del render (screenshot_<hora>.png)");
+    println!("Los parámetros de {} se reaplican al guardar el archivo", SHADER_PARAMS_PATH);
 
+    apply_look(&look, &scene_config, &mut sun, &mut planets, &mut settings);
     while !window.window_should_close() {
         let dt = window.get_frame_time();
-        time += dt;
+        // En pausa la simulación se detiene; la cámara y la interfaz siguen con `dt`
+        let simulation_dt = if paused { 0.0 } else { dt };
+        time += simulation_dt;
 
         // Actualizar cuerpos celestes
-        sun.update(dt);
+        sun.update(simulation_dt);
         for planet in &mut planets {
-            planet.update(dt);
+            planet.update(simulation_dt);
         }
 
         // Input handling
         if window.is_key_pressed(KeyboardKey::KEY_ZERO) {
             camera_mode = 0;
             warp_target = None;
+            flight = None;
         }
-        for i in 0..5 {
+        // La nave despega desde donde se ve, pegada a la cámara
+        if window.is_key_pressed(KeyboardKey::KEY_SIX) {
+            flight = match flight {
+                Some(_) => None,
+                None => Some(Ship::new(
+                    camera.eye
+                        + camera.forward() * -nave_offset.z
+                        + camera.right() * nave_offset.x
+                        + camera.up() * nave_offset.y,
+                    camera.orientation,
+                )),
+            };
+            camera_mode = 0;
+            warp_target = None;
+        }
+        for i in 0..5.min(planets.len()) {
             if window.is_key_pressed(match i {
                 0 => KeyboardKey::KEY_ONE,
                 1 => KeyboardKey::KEY_TWO,
@@ -218,6 +1580,7 @@ fn main() {
             }) {
                 warp_target = Some(i);
                 warp_progress = 0.0;
+                flight = None;
             }
         }
 
@@ -228,20 +1591,224 @@ fn main() {
                 warp_target = Some(0);
             }
             warp_progress = 0.0;
+            flight = None;
+        }
+
+        if window.is_key_pressed(KeyboardKey::KEY_ENTER) {
+            paused = !paused;
+        }
+
+        if window.is_key_pressed(KeyboardKey::KEY_F2) {
+            settings.supersampling = if settings.supersampling == 1 { 4 } else { 1 };
+        }
+
+        if window.is_key_pressed(KeyboardKey::KEY_F1) {
+            view_from_light = !view_from_light;
         }
 
         if window.is_key_pressed(KeyboardKey::KEY_O) {
-            show_orbits = !show_orbits;
+            settings.show_orbits = !settings.show_orbits;
+        }
+
+        if window.is_key_pressed(KeyboardKey::KEY_M) {
+            settings.show_minimap = !settings.show_minimap;
+        }
+
+        if window.is_key_pressed(KeyboardKey::KEY_F10) {
+            settings.rear_view = !settings.rear_view;
+        }
+
+        if window.is_key_pressed(KeyboardKey::KEY_X) {
+            settings.auto_exposure = !settings.auto_exposure;
+        }
+
+        if window.is_key_pressed(KeyboardKey::KEY_G) {
+            settings.nave_wireframe = match settings.nave_wireframe {
+                None => Some(WireframeStyle::DepthTested),
+                Some(style) => style.next(),
+            };
+        }
+
+        if window.is_key_pressed(KeyboardKey::KEY_F7) {
+            settings.cull_mode = settings.cull_mode.next();
+        }
+
+        if window.is_key_pressed(KeyboardKey::KEY_F4) {
+            settings.gamma_correct = !settings.gamma_correct;
+        }
+
+        if window.is_key_pressed(KeyboardKey::KEY_F3) {
+            settings.body_fill = settings.body_fill.next();
+        }
+
+        if window.is_key_pressed(KeyboardKey::KEY_H) {
+            settings.show_overdraw = !settings.show_overdraw;
+        }
+
+        if window.is_key_pressed(KeyboardKey::KEY_P) {
+            settings.raster_precision = match settings.raster_precision {
+                RasterPrecision::Float => RasterPrecision::Fixed,
+                RasterPrecision::Fixed => RasterPrecision::Float,
+            };
+        }
+
+        if window.is_key_pressed(KeyboardKey::KEY_K) {
+            settings.specular_aa = !settings.specular_aa;
+        }
+
+        if window.is_key_pressed(KeyboardKey::KEY_T) {
+            settings.color_clamp = settings.color_clamp.next();
+        }
+
+        if window.is_key_pressed(KeyboardKey::KEY_B) {
+            settings.debug_barycentric = !settings.debug_barycentric;
+        }
+
+        if window.is_key_pressed(KeyboardKey::KEY_V) {
+            settings.debug_facing = !settings.debug_facing;
+        }
+
+        if window.is_key_pressed(KeyboardKey::KEY_F11) {
+            settings.debug_vertex_colors = !settings.debug_vertex_colors;
+        }
+
+        if window.is_key_pressed(KeyboardKey::KEY_BACKSLASH) {
+            settings.level_of_detail = !settings.level_of_detail;
+        }
+
+        if window.is_key_pressed(KeyboardKey::KEY_U) {
+            settings.cloud_shell = !settings.cloud_shell;
+        }
+
+        if window.is_key_pressed(KeyboardKey::KEY_I) {
+            settings.ring_shadow = !settings.ring_shadow;
+        }
+
+        if window.is_key_pressed(KeyboardKey::KEY_SEMICOLON) {
+            settings.dithered_transparency = !settings.dithered_transparency;
+        }
+
+        if window.is_key_pressed(KeyboardKey::KEY_TAB) {
+            settings.target_aspect = match settings.target_aspect {
+                Some(_) => None,
+                None => Some(CINEMATIC_ASPECT),
+            };
+        }
+
+        if window.is_key_pressed(KeyboardKey::KEY_N) {
+            settings.post.bloom_enabled = !settings.post.bloom_enabled;
+        }
+        if window.is_key_pressed(KeyboardKey::KEY_J) {
+            settings.post.god_rays_enabled = !settings.post.god_rays_enabled;
+        }
+        if window.is_key_pressed(KeyboardKey::KEY_LEFT_BRACKET) {
+            settings.post.adjust_bloom_threshold(-0.05);
+        }
+        if window.is_key_pressed(KeyboardKey::KEY_RIGHT_BRACKET) {
+            settings.post.adjust_bloom_threshold(0.05);
+        }
+        if window.is_key_pressed(KeyboardKey::KEY_MINUS) {
+            settings.post.adjust_bloom_intensity(-0.1);
+        }
+        if window.is_key_pressed(KeyboardKey::KEY_EQUAL) {
+            settings.post.adjust_bloom_intensity(0.1);
+        }
+
+        if window.is_key_pressed(KeyboardKey::KEY_Y) {
+            settings.post.dof_enabled = !settings.post.dof_enabled;
+        }
+        // El foco avanza en proporción a su distancia: fino de cerca, rápido de lejos
+        let focus_step = settings.post.dof_focus_distance * dt;
+        if window.is_key_down(KeyboardKey::KEY_COMMA) {
+            settings.post.adjust_dof_focus(-focus_step, settings.near_plane, settings.far_plane);
+        }
+        if window.is_key_down(KeyboardKey::KEY_PERIOD) {
+            settings.post.adjust_dof_focus(focus_step, settings.near_plane, settings.far_plane);
+        }
+        // Clic: enfocar lo que hay bajo el cursor (profundidad del último frame)
+        if settings.post.dof_enabled && window.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_LEFT) {
+            let mouse = window.get_mouse_position() * framebuffer.samples_per_axis() as f32;
+            let depth = framebuffer.depth_at(mouse.x as i32, mouse.y as i32);
+            if depth.is_finite() {
+                settings.post.dof_focus_distance = post::linear_depth(depth, settings.near_plane, settings.far_plane);
+            }
+        }
+
+        // Rueda: lente con zoom; hacia adelante cierra el campo de visión
+        let wheel = window.get_mouse_wheel_move();
+        if wheel != 0.0 {
+            settings.adjust_fov(-wheel * FOV_WHEEL_STEP);
+        }
+
+        if window.is_key_pressed(KeyboardKey::KEY_L) {
+            layer_preset = (layer_preset + 1) % layer_presets.len();
+            settings.layer_mask = layer_presets[layer_preset];
+        }
+
+        if window.is_key_pressed(KeyboardKey::KEY_F5) {
+            match settings.save(SETTINGS_PATH) {
+                Ok(()) => println!("Ajustes guardados en {}", SETTINGS_PATH),
+                Err(err) => println!("No se pudieron guardar los ajustes: {}", err),
+            }
+        }
+
+        if window.is_key_pressed(KeyboardKey::KEY_F6) {
+            // Si el archivo no es válido se conserva la escena actual
+            match SceneConfig::load(SCENE_PATH) {
+                Ok(config) => {
+                    (sun, planets) = apply_scene_config(&config, &look, time, &mut settings);
+                    scene_config = config;
+
+                    // El planeta seguido puede haber desaparecido
+                    if camera_mode > planets.len() {
+                        camera_mode = 0;
+                    }
+                    if warp_target.is_some_and(|target| target >= planets.len()) {
+                        warp_target = None;
+                    }
+                    println!("Escena recargada de {} ({} planetas)", SCENE_PATH, planets.len());
+                }
+                Err(err) => println!("No se pudo recargar la escena: {}", err),
+            }
+        }
+
+        if window.is_key_pressed(KeyboardKey::KEY_F9) {
+            match RenderSettings::load(SETTINGS_PATH) {
+                Ok(loaded) => {
+                    settings = loaded;
+                    println!("Ajustes cargados de {}", SETTINGS_PATH);
+                }
+                Err(err) => println!("No se pudieron cargar los ajustes: {}", err),
+            }
+        }
+
+        // Archivo de shaders guardado: si tiene errores se conservan los valores anteriores
+        if look_watch.changed() {
+            match Look::load(SHADER_PARAMS_PATH) {
+                Ok(loaded) => {
+                    look = loaded;
+                    apply_look(&look, &scene_config, &mut sun, &mut planets, &mut settings);
+                    println!("Parámetros de shader recargados de {}", SHADER_PARAMS_PATH);
+                }
+                Err(err) => println!("No se pudieron recargar los parámetros de shader: {}", err),
+            }
         }
 
+        // Los ajustes mandan sobre el estado derivado
+        auto_exposure.enabled = settings.auto_exposure;
+        auto_exposure.target_luminance = settings.target_luminance;
+        auto_exposure.adaptation_speed = settings.adaptation_speed;
+        camera.handedness = settings.handedness;
+
         if window.is_key_pressed(KeyboardKey::KEY_R) {
             camera = Camera::new(
-                Vector3::new(0.0, 15.0, 25.0),
-                Vector3::new(0.0, 0.0, 0.0),
-                Vector3::new(0.0, 1.0, 0.0),
+                scene_config.camera_eye,
+                scene_config.camera_target,
+                scene_config.camera_up,
             );
             camera_mode = 0;
             warp_target = None;
+            flight = None;
         }
 
         // Warp animation - actualizar primero
@@ -255,186 +1822,484 @@ fn main() {
         }
 
         // Camera control
-        if camera_mode > 0 && camera_mode <= 5 {
-            let planet_idx = camera_mode - 1;
-            let planet_pos = planets[planet_idx].get_position();
+        if let Some(ship) = &mut flight {
+            ship.process_input(&window, dt);
+            ship.follow(&mut camera);
+        } else if camera_mode > 0 && camera_mode <= planets.len() {
+            let planet = &planets[camera_mode - 1];
+            let planet_model_matrix = create_model_matrix(
+                planet.get_position(),
+                planet.scale,
+                Vector3::new(0.0, planet.rotation_angle, 0.0),
+            );
+            let center = sphere_obj.center();
+            let center = multiply_matrix_vector4(&planet_model_matrix, &Vector4::new(center.x, center.y, center.z, 1.0));
 
-            // La cámara sigue al planeta
-            camera.target = planet_pos;
-            camera.distance = 5.0;
+            // La cámara sigue al planeta, a una distancia proporcional a su tamaño
+            camera.target = Vector3::new(center.x, center.y, center.z);
+            camera.distance = planet.scale * sphere_obj.radius() * FOLLOW_DISTANCE_RADII;
 
             // Permitir rotación alrededor del planeta
             if window.is_key_down(KeyboardKey::KEY_A) {
-                camera.yaw += camera.rotation_speed;
+                camera.rotate(camera.rotation_speed, 0.0);
             }
             if window.is_key_down(KeyboardKey::KEY_D) {
-                camera.yaw -= camera.rotation_speed;
+                camera.rotate(-camera.rotation_speed, 0.0);
             }
             if window.is_key_down(KeyboardKey::KEY_W) {
-                camera.pitch += camera.rotation_speed;
+                camera.rotate(0.0, camera.rotation_speed);
             }
             if window.is_key_down(KeyboardKey::KEY_S) {
-                camera.pitch -= camera.rotation_speed;
+                camera.rotate(0.0, -camera.rotation_speed);
+            }
+
+            if window.is_key_down(KeyboardKey::KEY_Z) {
+                camera.roll(-camera.rotation_speed);
+            }
+            if window.is_key_down(KeyboardKey::KEY_C) {
+                camera.roll(camera.rotation_speed);
             }
 
-            camera.pitch = camera.pitch.clamp(-PI / 2.0 + 0.1, PI / 2.0 - 0.1);
-            camera.eye.x = camera.target.x + camera.distance * camera.pitch.cos() * camera.yaw.cos();
-            camera.eye.y = camera.target.y + camera.distance * camera.pitch.sin();
-            camera.eye.z = camera.target.z + camera.distance * camera.pitch.cos() * camera.yaw.sin();
+            camera.update_eye_position();
         } else {
             camera.process_input(&window);
         }
 
-        framebuffer.clear();
+        // Ventana redimensionada (minimizada mide 0): buffers al tamaño nuevo antes
+        // de dibujar. El viewport y la proyección salen del framebuffer en cada frame
+        if window.is_window_resized() {
+            window_width = window.get_screen_width().max(1);
+            window_height = window.get_screen_height().max(1);
+            framebuffer.resize(window_width, window_height);
+        }
+        // Supersampling cambiado (F2 o F9): framebuffer nuevo a la resolución de las submuestras
+        if framebuffer.samples() != settings.supersampling {
+            framebuffer = Framebuffer::new_with_samples(window_width, window_height, settings.supersampling);
+            framebuffer.set_background_color(BACKGROUND_COLOR);
+        }
 
-        // Matrices de transformación
-        let view_matrix = camera.get_view_matrix();
-        let projection_matrix = create_projection_matrix(
-            PI / 3.0,
-            window_width as f32 / window_height as f32,
-            0.1,
-            200.0
-        );
-        let viewport_matrix = create_viewport_matrix(
-            0.0, 0.0,
-            window_width as f32,
-            window_height as f32
-        );
+        let scene = Scene {
+            lod_history: Some(&main_lod),
+            nave_pose: flight.as_ref().map(|ship| (ship.position, ship.orientation)),
+            ..assets.scene(&sun, &planets, time, simulation_dt)
+        };
+        // La cámara principal sigue recibiendo la entrada; solo cambia desde dónde se dibuja
+        let light_view = view_from_light.then(|| light_camera(&camera, settings.light_position));
+        let view_camera = light_view.as_ref().unwrap_or(&camera);
+        render_scene(&mut framebuffer, &scene, view_camera, &settings, auto_exposure.exposure(), Vector2::zero());
 
-        // Renderizar órbitas
-        if show_orbits {
-            for planet in &planets {
-                let orbit_points = planet.get_orbit_points(64);
-                render_orbit(
-                    &mut framebuffer,
-                    &orbit_points,
-                    &view_matrix,
-                    &projection_matrix,
-                    &viewport_matrix,
-                    Color::new(100, 100, 150, 100)
-                );
+        // Clic derecho: qué cuerpo hay bajo el cursor, con una pasada de ids a resolución
+        // de pantalla (el izquierdo es del enfoque de la profundidad de campo)
+        if window.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_RIGHT) {
+            let mouse = window.get_mouse_position();
+            let mut pick_buffer = Framebuffer::new(window_width, window_height);
+            render_pick(&mut pick_buffer, &scene, view_camera, &settings);
+            let picked = match pick_buffer.read_id_at(mouse.x as i32, mouse.y as i32) {
+                Some(0) => Some(&sun),
+                Some(id) => planets.get(id as usize - 1),
+                None => None,
+            };
+            match picked {
+                Some(body) => println!("Seleccionado: {} ({})", body.name, planet_type_name(body.planet_type)),
+                None => println!("Nada bajo el cursor"),
             }
         }
 
-        // Renderizar el Sol
-        let sun_pos = sun.get_position();
-        let sun_rotation = Vector3::new(0.0, sun.rotation_angle, 0.0);
-        let sun_model_matrix = create_model_matrix(sun_pos, sun.scale, sun_rotation);
+        // Adaptar la exposición para el siguiente frame
+        auto_exposure.adapt(&framebuffer, dt);
 
-        let sun_uniforms = Uniforms {
-            model_matrix: sun_model_matrix,
-            view_matrix,
-            projection_matrix,
-            viewport_matrix,
-            time,
-            dt,
-            planet_type: sun.planet_type,
-            render_type: 0,
-        };
-        render_body(&mut framebuffer, &sun_uniforms, &sphere_vertex_array, &light);
+        if let Some(log) = &mut stats_log
+            && let Err(err) = log.log(dt, &framebuffer.stats(), &settings)
+        {
+            println!("No se pudo escribir el registro de estadísticas: {}", err);
+            stats_log = None;
+        }
 
-        // Renderizar planetas
-        for planet in &planets {
-            let planet_pos = planet.get_position();
-            let planet_rotation = Vector3::new(0.0, planet.rotation_angle, 0.0);
-            let planet_model_matrix = create_model_matrix(
-                planet_pos,
-                planet.scale,
-                planet_rotation
+        // Diagnóstico: sustituir la imagen por el conteo de fragmentos por píxel
+        if settings.show_overdraw {
+            framebuffer.debug_overdraw_to_color();
+        }
+
+        if settings.rear_view {
+            // La escena otra vez, hacia atrás, en un recuadro arriba al centro;
+            // sin la nave, que va delante de la cámara principal
+            let margin = REAR_VIEW_MARGIN * framebuffer.samples_per_axis();
+            let (width, height) = (framebuffer.width / REAR_VIEW_FRACTION, framebuffer.height / REAR_VIEW_FRACTION);
+            framebuffer.set_viewport(Viewport { x: (framebuffer.width - width) / 2, y: margin, w: width, h: height });
+            let rear_scene = Scene { lod_history: Some(&rear_lod), nave_vertex_array: &[], ..scene };
+            render_scene(&mut framebuffer, &rear_scene, &rear_camera(view_camera), &settings,
+                         auto_exposure.exposure(), Vector2::zero());
+            framebuffer.reset_viewport();
+        }
+
+        if settings.show_minimap {
+            // En píxeles de pantalla: con supersampling se escala a submuestras
+            let scale = framebuffer.samples_per_axis() as f32;
+            let minimap_rect = Rectangle::new(
+                framebuffer.width as f32 - (MINIMAP_SIZE + 10.0) * scale,
+                10.0 * scale,
+                MINIMAP_SIZE * scale,
+                MINIMAP_SIZE * scale,
             );
+            render_minimap(&mut framebuffer, &scene, view_camera, minimap_rect);
+        }
 
-            let planet_uniforms = Uniforms {
-                model_matrix: planet_model_matrix,
-                view_matrix,
-                projection_matrix,
-                viewport_matrix,
-                time,
-                dt,
-                planet_type: planet.planet_type,
-                render_type: 0,
-            };
-            render_body(&mut framebuffer, &planet_uniforms, &sphere_vertex_array, &light);
+        framebuffer.resolve();
 
-            // Renderizar anillos si es Saturno (tipo 3)
-            if planet.planet_type == 3 {
-                // Llamar a render_rings con las transformaciones apropiadas
+        // Captura del framebuffer, sin el texto de la interfaz (raylib lo dibuja encima).
+        // F12 no: raylib ya la usa para su propia captura de la ventana
+        if window.is_key_pressed(KeyboardKey::KEY_F8) {
+            let path = screenshot_path();
+            match framebuffer.save_to_png(&path) {
+                Ok(()) => println!("Captura guardada en {}", path),
+                Err(err) => println!("No se pudo guardar la captura: {}", err),
             }
         }
 
-        // Renderizar la nave espacial (pegada a la cámara, enfrente)
-        // Calcular la dirección hacia donde mira la cámara
-        let camera_forward = Vector3::new(
-            camera.target.x - camera.eye.x,
-            camera.target.y - camera.eye.y,
-            camera.target.z - camera.eye.z,
-        );
-        let forward_length = (camera_forward.x * camera_forward.x + 
-                             camera_forward.y * camera_forward.y + 
-                             camera_forward.z * camera_forward.z).sqrt();
-        let camera_forward = Vector3::new(
-            camera_forward.x / forward_length,
-            camera_forward.y / forward_length,
-            camera_forward.z / forward_length,
-        );
-        
-        // Vector derecho de la cámara
-        let camera_right = Vector3::new(
-            camera_forward.z,
-            0.0,
-            -camera_forward.x,
-        );
-        let right_length = (camera_right.x * camera_right.x + 
-                           camera_right.z * camera_right.z).sqrt();
-        let camera_right = Vector3::new(
-            camera_right.x / right_length,
-            camera_right.y,
-            camera_right.z / right_length,
-        );
-        
-        let camera_up = Vector3::new(0.0, 1.0, 0.0);
-        
-        // Posición de la nave ENFRENTE de la cámara
-        let nave_position = Vector3::new(
-            camera.eye.x + camera_forward.x * (-nave_offset.z) + camera_right.x * nave_offset.x + camera_up.x * nave_offset.y,
-            camera.eye.y + camera_forward.y * (-nave_offset.z) + camera_right.y * nave_offset.x + camera_up.y * nave_offset.y,
-            camera.eye.z + camera_forward.z * (-nave_offset.z) + camera_right.z * nave_offset.x + camera_up.z * nave_offset.y,
-        );
-        
-        // Rotación de la nave para que apunte hacia adelante
-        let nave_rotation = Vector3::new(0.0, camera.yaw + PI, 0.0);
-        
-        let nave_model_matrix = create_model_matrix(
-            nave_position,
-            nave_scale,
-            nave_rotation
+        // UI Info
+        let info_text = format!(
+            "FPS: {}{} | Modo: {} | FOV: {:.0}° | Órbitas: {} | Exposición: {:.2}{} | Nave: {} | Cuerpos: {} | Capas: {:04b} | Raster: {} | Color: {}{}{}{}{}{}{}",
+            window.get_fps(),
+            if paused { " (pausa)" } else { "" },
+            if flight.is_some() { "Nave".to_string() }
+            else if camera_mode == 0 { "Libre".to_string() }
+            else { planets[camera_mode - 1].name.to_string() },
+            settings.fov_y.to_degrees(),
+            if settings.show_orbits { "ON" } else { "OFF" },
+            auto_exposure.exposure(),
+            if auto_exposure.enabled { " (auto)" } else { "" },
+            settings.nave_wireframe.map_or("Sólida", |style| style.name()),
+            settings.body_fill.name(),
+            settings.layer_mask,
+            settings.raster_precision.name(),
+            settings.color_clamp.name(),
+            if settings.gamma_correct { " + gamma" } else { "" },
+            if settings.post.bloom_enabled {
+                format!(" | Bloom: umbral {:.2} intensidad {:.2}", settings.post.bloom_threshold, settings.post.bloom_intensity)
+            } else {
+                String::new()
+            },
+            if settings.post.dof_enabled {
+                format!(" | Foco: {:.1}", settings.post.dof_focus_distance)
+            } else {
+                String::new()
+            },
+            if settings.cull_mode != CullMode::Back {
+                format!(" | Culling: {}", settings.cull_mode.name())
+            } else {
+                String::new()
+            },
+            if settings.rear_view { " | Retrovisor" } else { "" },
+            if view_from_light { " | LIGHT VIEW" } else { "" }
         );
 
-        let nave_uniforms = Uniforms {
-            model_matrix: nave_model_matrix,
-            view_matrix,
-            projection_matrix,
-            viewport_matrix,
-            time,
-            dt,
-            planet_type: 10, // Tipo especial para la nave
+        let hud = Hud {
+            info: &info_text,
+            camera_position: light_view.as_ref().unwrap_or(&camera).eye,
+            planet_type: camera_mode.checked_sub(1).and_then(|index| planets.get(index)).map(|planet| planet.planet_type),
+        };
+        framebuffer.swap_buffers(&mut window, &raylib_thread, |d| hud.draw(d));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Un frame del sistema de la escena por defecto, sin archivos: la esfera es
+    // una icoesfera y el entorno el de estrellas
+    fn render_frame(settings: &RenderSettings, camera: &Camera, width: i32, height: i32) -> Framebuffer {
+        let (sun, planets) = bodies_from_config(&SceneConfig::default(), 3.0);
+        render_bodies(&sun, &planets, settings, camera, width, height)
+    }
+
+    fn render_bodies(sun: &CelestialBody, planets: &[CelestialBody], settings: &RenderSettings, camera: &Camera,
+                     width: i32, height: i32) -> Framebuffer {
+        with_assets(|assets| {
+            let mut framebuffer = Framebuffer::new(width, height);
+            framebuffer.set_background_color(BACKGROUND_COLOR);
+            render_scene(&mut framebuffer, &assets.scene(sun, planets, 3.0, 0.0), camera, settings, 1.0, Vector2::zero());
+            framebuffer
+        })
+    }
+
+    // Las mallas y el entorno de la escena de prueba
+    fn with_assets<R>(draw: impl FnOnce(&SceneAssets) -> R) -> R {
+        let sphere_obj = Obj::icosphere(Vector3::zero(), 1.0, 2);
+        let sphere = LodChain::new(sphere_obj.radius(), vec![(sphere_obj.get_vertex_array(), 0.0)]);
+        let ring_obj = Obj::annulus(RING_INNER_RADIUS, RING_OUTER_RADIUS, 32);
+        let environment = Environment::starfield();
+        let assets = SceneAssets {
+            sphere: &sphere,
+            ring_vertex_array: ring_obj.get_vertex_array(),
+            nave_vertex_array: &[],
+            nave_materials: &[],
+            nave_texture: None,
+            nave_scale: 1.0,
+            nave_offset: Vector3::zero(),
+            environment: &environment,
+        };
+        draw(&assets)
+    }
+
+    fn default_camera() -> Camera {
+        let config = SceneConfig::default();
+        Camera::new(config.camera_eye, config.camera_target, config.camera_up)
+    }
+
+    // Uniformes para dibujar una malla suelta en un framebuffer de `width` x
+    // `height`: cámara en +Z mirando al origen y la luz llegando desde +X+Z
+    pub(crate) fn test_uniforms(environment: &Environment, width: i32, height: i32) -> Uniforms<'_> {
+        let settings = RenderSettings::default();
+        let camera = Camera::new(Vector3::new(0.0, 0.0, 3.0), Vector3::zero(), Vector3::new(0.0, 1.0, 0.0));
+        Uniforms {
+            model: ModelTransform::new(Matrix::identity()),
+            view_matrix: camera.get_view_matrix(),
+            projection_matrix: create_projection_matrix_handed(settings.fov_y, width as f32 / height as f32,
+                                                               settings.near_plane, settings.far_plane, camera.handedness),
+            viewport_matrix: create_viewport_matrix(0.0, 0.0, width as f32, height as f32),
+            viewport: Viewport { x: 0, y: 0, w: width, h: height },
+            camera_position: camera.eye,
+            time: 0.0,
+            dt: 0.0,
+            planet_type: 0,
+            shader_params: ShaderParams::default(),
+            material: SurfaceMaterial::for_planet_type(0),
             render_type: 0,
+            handedness: camera.handedness,
+            exposure: 1.0,
+            layer_mask: LAYER_ALL,
+            environment,
+            raster_precision: settings.raster_precision,
+            specular_aa: false,
+            double_sided: false,
+            ring_shadow: false,
+            depth_bias: DepthBias::default(),
+            dithered_transparency: false,
+            deterministic_draw: false,
+            color_clamp: settings.color_clamp,
+            gamma_correct: false,
+            lights: LightRig::new(&[Light::directional(Vector3::new(-1.0, 0.0, -1.0))]),
+            light_falloff: 0.0,
+            ambient_floor: settings.ambient_floor,
+            materials: &[],
+            texture: None,
+            normal_map: None,
+            fill_mode: FillMode::Solid,
+            fog: Fog { color: settings.fog_color, density: 0.0 },
+            cull_mode: CullMode::None,
+            front_face: Winding::CounterClockwise,
+        }
+    }
+
+    // Triángulo de un color en el plano z, de cara a la cámara de `test_uniforms`
+    fn colored_triangle(corners: [(f32, f32); 3], z: f32, color: Vector3) -> Vec<Vertex> {
+        corners.iter().map(|&(x, y)| Vertex::new_with_color(Vector3::new(x, y, z), color)).collect()
+    }
+
+    #[test]
+    fn renders_the_same_with_any_thread_count() {
+        let _guard = parallel::TEST_THREADS.lock().unwrap();
+        // Que hasta las mallas chicas se repartan entre hilos. No se restaura:
+        // el resultado no depende de los hilos
+        parallel::set_min_items_per_thread(1);
+        let settings = RenderSettings::default();
+        let frames: Vec<(Vec<u8>, stats::RenderStats)> = [1, 3, 8]
+            .into_iter()
+            .map(|threads| {
+                parallel::set_render_threads(threads);
+                let framebuffer = render_frame(&settings, &default_camera(), 160, 120);
+                (framebuffer.to_rgba_bytes(), framebuffer.stats())
+            })
+            .collect();
+        assert!(frames[0].1.fragments > 1000, "{:?}", frames[0].1);
+        assert!(frames.iter().all(|frame| *frame == frames[0]));
+    }
+
+    #[test]
+    fn reloading_the_shader_file_changes_the_uniforms() {
+        let path = std::env::temp_dir().join(format!("ship_look_{}.cfg", std::process::id()));
+        let path = path.to_str().unwrap();
+        let _ = std::fs::remove_file(path);
+        let mut watch = FileWatch::new(path);
+        assert!(!watch.changed());
+
+        let config = SceneConfig::default();
+        let (mut sun, mut planets) = bodies_from_config(&config, 3.0);
+        let mut settings = RenderSettings::default();
+        let before = render_bodies(&sun, &planets, &settings, &default_camera(), 160, 120).to_rgba_bytes();
+
+        // Como en el bucle principal: al guardarse se vuelve a leer y aplicar
+        std::fs::write(path, "ambient_floor = 0.4\n[Sol]\nnoise_scale = 4\n[Tierra]\nnoise_scale = 1.5\n").unwrap();
+        assert!(watch.changed());
+        let look = Look::load(path).unwrap();
+        apply_look(&look, &config, &mut sun, &mut planets, &mut settings);
+        assert_eq!(settings.ambient_floor, 0.4);
+        assert_eq!(sun.shader_params.noise_scale, 4.0);
+        let earth = planets.iter().find(|planet| planet.name == "Tierra").unwrap();
+        assert_eq!(earth.shader_params.noise_scale, 1.5);
+        let after = render_bodies(&sun, &planets, &settings, &default_camera(), 160, 120).to_rgba_bytes();
+        assert_ne!(before, after);
+
+        // Un archivo con errores no se puede cargar: se quedan los valores anteriores
+        std::fs::write(path, "[Sol]\nnoise_scale = mucho\n").unwrap();
+        let modified = std::time::SystemTime::now() + std::time::Duration::from_secs(5);
+        std::fs::File::options().write(true).open(path).unwrap().set_modified(modified).unwrap();
+        assert!(watch.changed());
+        assert!(Look::load(path).is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn deterministic_draw_resolves_equal_depths_the_same_way() {
+        let _guard = parallel::TEST_THREADS.lock().unwrap();
+        parallel::set_min_items_per_thread(1);
+        let environment = Environment::starfield();
+        let uniforms = Uniforms { render_type: 5, deterministic_draw: true, ..test_uniforms(&environment, 96, 96) };
+        // Dos triángulos en el mismo plano que se pisan casi enteros
+        let red = Vector3::new(1.0, 0.0, 0.0);
+        let blue = Vector3::new(0.0, 0.0, 1.0);
+        let mut mesh = colored_triangle([(-1.0, -1.0), (1.0, -1.0), (0.0, 1.0)], 0.0, red);
+        mesh.extend(colored_triangle([(-1.0, -0.9), (1.0, -0.9), (0.0, 1.1)], 0.0, blue));
+
+        let frames: Vec<Vec<u8>> = [1, 1, 3, 8, 8]
+            .into_iter()
+            .map(|threads| {
+                parallel::set_render_threads(threads);
+                let mut framebuffer = Framebuffer::new(96, 96);
+                render_body(&mut framebuffer, &uniforms, &mesh, &Light::point(Vector3::zero()));
+                framebuffer.to_rgba_bytes()
+            })
+            .collect();
+        assert!(frames.iter().all(|frame| *frame == frames[0]));
+        // A igual profundidad gana el primer triángulo de la malla
+        let center = (48 * 96 + 48) * 4;
+        assert_eq!(&frames[0][center..center + 4], &[255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn tiled_render_matches_a_single_render() {
+        let camera = default_camera();
+        let (sun, planets) = bodies_from_config(&SceneConfig::default(), 3.0);
+        let render_2x2 = |settings: &RenderSettings| {
+            let image = with_assets(|assets| {
+                render_tiled(&assets.scene(&sun, &planets, 3.0, 0.0), &camera, settings, 1.0, 160, 120, 80)
+            });
+            let tiled: Vec<u8> = image.get_image_data().iter().flat_map(|c| [c.r, c.g, c.b, c.a]).collect();
+            (tiled, render_frame(settings, &camera, 160, 120).to_rgba_bytes())
         };
-        render_body(&mut framebuffer, &nave_uniforms, &nave_vertex_array, &light);
 
-        // UI Info
-        let info_text = format!(
-            "FPS: {:.0} | Modo: {} | Órbitas: {}",
-            1.0 / dt,
-            if camera_mode == 0 { "Libre".to_string() }
-            else { planets[camera_mode - 1].name.to_string() },
-            if show_orbits { "ON" } else { "OFF" }
-        );
+        let (tiled, whole) = render_2x2(&RenderSettings { show_orbits: false, ..RenderSettings::default() });
+        assert!(tiled == whole);
 
-        framebuffer.swap_buffers(&mut window, &raylib_thread);
+        // Las órbitas se cubren en las coordenadas de cada tesela: sin costuras, pero
+        // el redondeo de la cobertura puede mover un nivel en algún píxel
+        let (tiled, whole) = render_2x2(&RenderSettings::default());
+        assert!(tiled.iter().zip(&whole).all(|(a, b)| a.abs_diff(*b) <= 1));
+    }
 
-        let mut d = window.begin_drawing(&raylib_thread);
-        d.draw_text(&info_text, 10, 10, 20, Color::WHITE);
+    #[test]
+    fn facing_debug_shows_back_faces_in_red() {
+        let environment = Environment::starfield();
+        let uniforms = Uniforms { render_type: 4, cull_mode: CullMode::Back, ..test_uniforms(&environment, 64, 64) };
+        let white = Vector3::one();
+        // Antihorario visto desde la cámara (de frente) y el mismo al revés
+        let front = colored_triangle([(-1.0, -1.0), (1.0, -1.0), (0.0, 1.0)], 0.0, white);
+        let back = colored_triangle([(-1.0, -1.0), (0.0, 1.0), (1.0, -1.0)], 0.0, white);
+        for (mesh, expected) in [(front, Color::new(0, 255, 0, 255)), (back, Color::new(255, 0, 0, 255))] {
+            let mut framebuffer = Framebuffer::new(64, 64);
+            // La vista de debug muestra las dos caras aunque el modo descarte las traseras
+            render_body(&mut framebuffer, &uniforms, &mesh, &Light::point(Vector3::zero()));
+            assert_eq!(framebuffer.get_pixel_color(32, 32), Some(expected));
+        }
+    }
 
-        thread::sleep(Duration::from_millis(16));
+    #[test]
+    fn double_sided_back_faces_shade_like_their_front() {
+        let environment = Environment::starfield();
+        // La nave: la única que se sombrea con la normal de su malla
+        let uniforms = Uniforms { planet_type: 10, double_sided: true, ..test_uniforms(&environment, 64, 64) };
+        let facing = |normal: Vector3| -> Vec<Vertex> {
+            [(-1.0, -1.0), (1.0, -1.0), (0.0, 1.0)]
+                .iter()
+                .map(|&(x, y)| Vertex::new(Vector3::new(x, y, 0.0), normal, Vector2::zero()))
+                .collect()
+        };
+        let center = |uniforms: &Uniforms, mesh: &[Vertex]| {
+            let mut framebuffer = Framebuffer::new(64, 64);
+            render_body(&mut framebuffer, uniforms, mesh, &Light::point(Vector3::zero()));
+            framebuffer.get_pixel_color(32, 32).unwrap()
+        };
+
+        // Con la normal hacia la cámara y con la normal hacia atrás: la misma cara vista por cada lado
+        let toward = center(&uniforms, &facing(Vector3::new(0.0, 0.0, 1.0)));
+        let away = facing(Vector3::new(0.0, 0.0, -1.0));
+        assert_eq!(center(&uniforms, &away), toward);
+        // De una sola cara, la trasera queda a oscuras
+        let single = Uniforms { double_sided: false, ..uniforms.clone() };
+        let unlit = center(&single, &away);
+        assert!(unlit.r < toward.r && unlit.g < toward.g, "{:?} {:?}", unlit, toward);
+    }
+
+    #[test]
+    fn reapplying_a_modified_scene_updates_the_bodies() {
+        let mut settings = RenderSettings::default();
+        let look = Look::default();
+        let (_, planets) = apply_scene_config(&SceneConfig::default(), &look, 3.0, &mut settings);
+        assert_eq!(planets.len(), SceneConfig::default().planets.len());
+
+        // El archivo editado: dos planetas nuevos y otra luz
+        let edited: SceneConfig = "light_intensity = 2.5\n\
+                                   [planet]\nname = Uno\norbit_radius = 4\n\
+                                   [planet]\nname = Dos\nplanet_type = 2\norbit_radius = 9\n".parse().unwrap();
+        let (_, planets) = apply_scene_config(&edited, &look, 3.0, &mut settings);
+        let names: Vec<&str> = planets.iter().map(|planet| planet.name.as_str()).collect();
+        assert_eq!(names, ["Uno", "Dos"]);
+        assert_eq!(planets[1].planet_type, 2);
+        assert_eq!(settings.light_intensity, 2.5);
+        // Los cuerpos nuevos siguen el reloj de la escena, no empiezan de cero
+        assert_eq!(planets[0].elapsed, 3.0);
+    }
+
+    #[test]
+    fn subframes_blur_a_moving_planet() {
+        // Sin la órbita dibujada: solo cuenta el propio planeta
+        let settings = RenderSettings { show_orbits: false, ..RenderSettings::default() };
+        let camera = default_camera();
+        // Cuántos píxeles cambia el planeta (rapidísimo) sobre el frame sin él
+        let smear = |subframes: u32| with_assets(|assets| {
+            let record = |planets: &mut [CelestialBody]| {
+                let mut sun = CelestialBody::new(5, 0.0, 0.0, 0.0, 1.0, "Sol".to_string());
+                let mut framebuffer = Framebuffer::new(96, 96);
+                framebuffer.set_background_color(BACKGROUND_COLOR);
+                record_frame(&mut framebuffer, assets, &mut sun, planets, &mut 3.0, subframes,
+                             |framebuffer, scene, jitter| render_scene(framebuffer, scene, &camera, &settings, 1.0, jitter));
+                framebuffer.to_rgba_bytes()
+            };
+            let planet = CelestialBody::new(0, 6.0, 20.0, 0.0, 1.0, "Veloz".to_string());
+            let with = record(&mut [planet]);
+            let without = record(&mut []);
+            with.chunks(4).zip(without.chunks(4)).filter(|(a, b)| a != b).count()
+        });
+
+        let sharp = smear(1);
+        let blurred = smear(8);
+        assert!(sharp > 0);
+        assert!(blurred > sharp * 2, "{} vs {}", blurred, sharp);
+    }
+
+    #[test]
+    fn flag_values_are_parsed_and_validated() {
+        let args: Vec<String> = ["ship", "--record", "30", "--render-size", "640x0", "--log"]
+            .iter().map(|arg| arg.to_string()).collect();
+        assert_eq!(flag_value(&args, "--record", |&frames: &u32| frames > 0), Some(Some(30)));
+        assert_eq!(flag_value(&args, "--threads", |&threads: &usize| threads > 0), None);
+        // Un alto de 0 se lee pero no vale
+        let size = flag_value(&args, "--render-size", |size: &ImageSize| size.width > 0 && size.height > 0);
+        assert!(matches!(size, Some(None)));
+        assert!(matches!(flag_value(&args, "--render-size", |_: &ImageSize| true), Some(Some(ImageSize { width: 640, height: 0 }))));
+        // Sin valor detrás
+        assert_eq!(flag_value(&args, "--log", |path: &String| !path.starts_with("--")), Some(None));
     }
 }
+
@@ -2,6 +2,7 @@
 #![allow(dead_code)]
 
 use raylib::prelude::*;
+use crate::quat::Quaternion;
 
 pub fn multiply_matrix_vector4(matrix: &Matrix, vector: &Vector4) -> Vector4 {
     Vector4::new(
@@ -12,6 +13,39 @@ pub fn multiply_matrix_vector4(matrix: &Matrix, vector: &Vector4) -> Vector4 {
     )
 }
 
+/// Handedness of the world/view coordinate system.
+///
+/// Default is `RightHanded`, the OpenGL convention used everywhere in this
+/// renderer: X right, Y up, and the camera looks down -Z in view space.
+/// `LeftHanded` is the DirectX convention: the camera looks down +Z.
+/// NDC depth is [-1, 1] in both cases.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum Handedness {
+    #[default]
+    RightHanded,
+    LeftHanded,
+}
+
+impl Handedness {
+    /// Sign of the screen-space signed area (y down, after the viewport flip)
+    /// that counter-clockwise-wound front faces end up with. Backface culling
+    /// keeps triangles whose area has this sign.
+    pub fn front_face_sign(self) -> f32 {
+        match self {
+            Handedness::RightHanded => -1.0,
+            Handedness::LeftHanded => 1.0,
+        }
+    }
+
+    /// Sign of the view-space z of points in front of the camera
+    pub fn view_forward_sign(self) -> f32 {
+        match self {
+            Handedness::RightHanded => -1.0,
+            Handedness::LeftHanded => 1.0,
+        }
+    }
+}
+
 /// Creates a 4x4 matrix from 16 float values, specified in traditional row-major order.
 pub fn new_matrix4(
     // Row 0
@@ -80,7 +114,26 @@ pub fn create_model_matrix(translation: Vector3, scale: f32, rotation: Vector3)
     );
 
     let rotation_matrix = rotation_matrix_z * rotation_matrix_y * rotation_matrix_x;
+    compose_model_matrix(translation, scale, rotation_matrix)
+}
 
+/// Like `create_model_matrix`, with the rotation given as a quaternion:
+/// any orientation, with no Euler angles to lock at the poles
+pub fn create_model_matrix_oriented(translation: Vector3, scale: f32, orientation: Quaternion) -> Matrix {
+    // Columnas: dónde quedan los ejes X, Y y Z del modelo
+    let x = orientation.rotate(Vector3::new(1.0, 0.0, 0.0));
+    let y = orientation.rotate(Vector3::new(0.0, 1.0, 0.0));
+    let z = orientation.rotate(Vector3::new(0.0, 0.0, 1.0));
+    let rotation_matrix = new_matrix3(
+        x.x, y.x, z.x,
+        x.y, y.y, z.y,
+        x.z, y.z, z.z,
+    );
+    compose_model_matrix(translation, scale, rotation_matrix)
+}
+
+// Escala y traslación alrededor de una rotación ya armada
+fn compose_model_matrix(translation: Vector3, scale: f32, rotation_matrix: Matrix) -> Matrix {
     // Scaling matrix
     let scale_matrix = new_matrix4(
         scale, 0.0,   0.0,   0.0,
@@ -100,9 +153,22 @@ pub fn create_model_matrix(translation: Vector3, scale: f32, rotation: Vector3)
     scale_matrix * translation_matrix * rotation_matrix 
 }
 
+// Seno del ángulo entre `up` y la vista por debajo del cual se los toma por paralelos
+const VIEW_UP_EPSILON: f32 = 1e-4;
+
 /// Creates a view matrix using camera position, target, and up vector
 /// This implements a lookAt matrix for camera transformations
 pub fn create_view_matrix(eye: Vector3, target: Vector3, up: Vector3) -> Matrix {
+    create_view_matrix_handed(eye, target, up, Handedness::default())
+}
+
+/// lookAt matrix for the given handedness. Right-handed looks down -Z,
+/// left-handed down +Z (and mirrors X so the basis stays left-handed).
+/// Always a rigid transform that takes `eye` to the origin: when `up` is
+/// parallel to the view direction (a camera looking straight down, say)
+/// the world axis least aligned with it stands in for `up`, and with
+/// `eye == target` the camera looks down -Z
+pub fn create_view_matrix_handed(eye: Vector3, target: Vector3, up: Vector3, handedness: Handedness) -> Matrix {
     // Calculate forward vector (from eye to target, normalized)
     let mut forward = Vector3::new(
         target.x - eye.x,
@@ -111,16 +177,36 @@ pub fn create_view_matrix(eye: Vector3, target: Vector3, up: Vector3) -> Matrix
     );
     // Normalize forward
     let forward_length = (forward.x * forward.x + forward.y * forward.y + forward.z * forward.z).sqrt();
-    forward.x /= forward_length;
-    forward.y /= forward_length;
-    forward.z /= forward_length;
+    if forward_length > 0.0 {
+        forward.x /= forward_length;
+        forward.y /= forward_length;
+        forward.z /= forward_length;
+    } else {
+        forward = Vector3::new(0.0, 0.0, -1.0);
+    }
+
+    // `up` paralelo a la vista (o nulo) no define la derecha: se usa el eje
+    // del mundo más perpendicular a la vista
+    let up = if forward.cross(up).length() > VIEW_UP_EPSILON * up.length() {
+        up
+    } else if forward.x.abs() <= forward.y.abs() && forward.x.abs() <= forward.z.abs() {
+        Vector3::new(1.0, 0.0, 0.0)
+    } else if forward.y.abs() <= forward.z.abs() {
+        Vector3::new(0.0, 1.0, 0.0)
+    } else {
+        Vector3::new(0.0, 0.0, 1.0)
+    };
 
     // Calculate right vector (cross product of forward and up, normalized)
+    // Left-handed uses up x forward instead
     let mut right = Vector3::new(
         forward.y * up.z - forward.z * up.y,
         forward.z * up.x - forward.x * up.z,
         forward.x * up.y - forward.y * up.x,
     );
+    if handedness == Handedness::LeftHanded {
+        right = Vector3::new(-right.x, -right.y, -right.z);
+    }
     // Normalize right
     let right_length = (right.x * right.x + right.y * right.y + right.z * right.z).sqrt();
     right.x /= right_length;
@@ -128,38 +214,153 @@ pub fn create_view_matrix(eye: Vector3, target: Vector3, up: Vector3) -> Matrix
     right.z /= right_length;
 
     // Calculate actual up vector (cross product of right and forward)
-    let actual_up = Vector3::new(
+    let mut actual_up = Vector3::new(
         right.y * forward.z - right.z * forward.y,
         right.z * forward.x - right.x * forward.z,
         right.x * forward.y - right.y * forward.x,
     );
+    if handedness == Handedness::LeftHanded {
+        actual_up = Vector3::new(-actual_up.x, -actual_up.y, -actual_up.z);
+    }
+
+    // View-space Z axis: -forward for right-handed, +forward for left-handed
+    let z_axis = match handedness {
+        Handedness::RightHanded => Vector3::new(-forward.x, -forward.y, -forward.z),
+        Handedness::LeftHanded => forward,
+    };
 
     // Create the view matrix (inverse of camera transformation)
     // This is the lookAt matrix formula
     new_matrix4(
         right.x, right.y, right.z, -(right.x * eye.x + right.y * eye.y + right.z * eye.z),
         actual_up.x, actual_up.y, actual_up.z, -(actual_up.x * eye.x + actual_up.y * eye.y + actual_up.z * eye.z),
-        -forward.x, -forward.y, -forward.z, forward.x * eye.x + forward.y * eye.y + forward.z * eye.z,
+        z_axis.x, z_axis.y, z_axis.z, -(z_axis.x * eye.x + z_axis.y * eye.y + z_axis.z * eye.z),
         0.0, 0.0, 0.0, 1.0,
     )
 }
 
+/// Recovers the camera position (eye) from a rigid view matrix.
+/// The view matrix is [R | -R*eye], so eye = -R^T * t
+pub fn camera_position_from_view(view: &Matrix) -> Vector3 {
+    Vector3::new(
+        -(view.m0 * view.m12 + view.m1 * view.m13 + view.m2 * view.m14),
+        -(view.m4 * view.m12 + view.m5 * view.m13 + view.m6 * view.m14),
+        -(view.m8 * view.m12 + view.m9 * view.m13 + view.m10 * view.m14),
+    )
+}
+
+/// Matrix that takes normals to world space under `model`: the inverse
+/// transpose of its upper 3x3, so they stay perpendicular to the surface
+/// when the scale isn't uniform. The translation is dropped (normals have
+/// w = 0 anyway) and the result still has to be normalized.
+pub fn inverse_transpose(m: &Matrix) -> Matrix {
+    let linear = Matrix { m12: 0.0, m13: 0.0, m14: 0.0, ..*m };
+    linear.inverted().transposed()
+}
+
+/// A model matrix together with what the shaders derive from it, computed
+/// once per draw instead of for every vertex and fragment
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ModelTransform {
+    pub matrix: Matrix,
+    pub normal_matrix: Matrix, // `inverse_transpose` de `matrix`: normales al mundo
+    pub world_to_object: Matrix, // Inversa de `matrix`: luces y cámara al espacio del modelo
+}
+
+impl ModelTransform {
+    pub fn new(matrix: Matrix) -> Self {
+        ModelTransform { matrix, normal_matrix: inverse_transpose(&matrix), world_to_object: matrix.inverted() }
+    }
+}
+
 /// Creates a perspective projection matrix
 /// fov_y: Field of view in radians (vertical)
 /// aspect: Aspect ratio (width / height)
 /// near: Near clipping plane distance
 /// far: Far clipping plane distance
 pub fn create_projection_matrix(fov_y: f32, aspect: f32, near: f32, far: f32) -> Matrix {
+    create_projection_matrix_handed(fov_y, aspect, near, far, Handedness::default())
+}
+
+/// Perspective projection for the given handedness. Both map view depth
+/// [near, far] to NDC z [-1, 1]; they differ in which view-space Z the
+/// camera looks down (-Z right-handed, +Z left-handed)
+pub fn create_projection_matrix_handed(fov_y: f32, aspect: f32, near: f32, far: f32,
+                                       handedness: Handedness) -> Matrix {
     let tan_half_fov = (fov_y / 2.0).tan();
+    let z_sign = match handedness {
+        Handedness::RightHanded => -1.0,
+        Handedness::LeftHanded => 1.0,
+    };
 
     new_matrix4(
         1.0 / (aspect * tan_half_fov), 0.0, 0.0, 0.0,
         0.0, 1.0 / tan_half_fov, 0.0, 0.0,
-        0.0, 0.0, -(far + near) / (far - near), -(2.0 * far * near) / (far - near),
-        0.0, 0.0, -1.0, 0.0,
+        0.0, 0.0, z_sign * (far + near) / (far - near), -(2.0 * far * near) / (far - near),
+        0.0, 0.0, z_sign, 0.0,
     )
 }
 
+/// Off-center perspective projection (glFrustum): `left`, `right`, `bottom`
+/// and `top` are the edges of the view window on the near plane, so the
+/// frustum can be sheared to cover only part of the image. With symmetric
+/// edges it matches `create_projection_matrix_handed`
+pub fn create_off_center_projection_matrix(left: f32, right: f32, bottom: f32, top: f32,
+                                           near: f32, far: f32, handedness: Handedness) -> Matrix {
+    let z_sign = match handedness {
+        Handedness::RightHanded => -1.0,
+        Handedness::LeftHanded => 1.0,
+    };
+
+    new_matrix4(
+        2.0 * near / (right - left), 0.0, -z_sign * (right + left) / (right - left), 0.0,
+        0.0, 2.0 * near / (top - bottom), -z_sign * (top + bottom) / (top - bottom), 0.0,
+        0.0, 0.0, z_sign * (far + near) / (far - near), -(2.0 * far * near) / (far - near),
+        0.0, 0.0, z_sign, 0.0,
+    )
+}
+
+/// Orthographic projection (glOrtho): the box `left`..`right`,
+/// `bottom`..`top`, `near`..`far` in front of the camera maps to NDC
+/// [-1, 1] on every axis, with no perspective divide (w stays 1)
+pub fn create_orthographic_matrix(left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) -> Matrix {
+    create_orthographic_matrix_handed(left, right, bottom, top, near, far, Handedness::default())
+}
+
+/// Orthographic projection for the given handedness: `near` and `far` are
+/// distances along the view direction (-Z right-handed, +Z left-handed)
+pub fn create_orthographic_matrix_handed(left: f32, right: f32, bottom: f32, top: f32,
+                                         near: f32, far: f32, handedness: Handedness) -> Matrix {
+    let z_sign = match handedness {
+        Handedness::RightHanded => -1.0,
+        Handedness::LeftHanded => 1.0,
+    };
+
+    new_matrix4(
+        2.0 / (right - left), 0.0, 0.0, -(right + left) / (right - left),
+        0.0, 2.0 / (top - bottom), 0.0, -(top + bottom) / (top - bottom),
+        0.0, 0.0, z_sign * 2.0 / (far - near), -(far + near) / (far - near),
+        0.0, 0.0, 0.0, 1.0,
+    )
+}
+
+/// Projection for the pixels `tile` of a `total_width` x `total_height`
+/// image seen with vertical field of view `fov_y`: rendering every tile with
+/// its own matrix and placing them side by side reproduces the full image
+pub fn create_tile_projection_matrix(fov_y: f32, total_width: f32, total_height: f32, tile: Rectangle,
+                                     near: f32, far: f32, handedness: Handedness) -> Matrix {
+    let half_height = near * (fov_y / 2.0).tan();
+    let half_width = half_height * total_width / total_height;
+
+    // Píxeles -> plano cercano (y de pantalla hacia abajo, y de vista hacia arriba)
+    let left = -half_width + 2.0 * half_width * tile.x / total_width;
+    let right = -half_width + 2.0 * half_width * (tile.x + tile.width) / total_width;
+    let top = half_height - 2.0 * half_height * tile.y / total_height;
+    let bottom = half_height - 2.0 * half_height * (tile.y + tile.height) / total_height;
+
+    create_off_center_projection_matrix(left, right, bottom, top, near, far, handedness)
+}
+
 /// Creates a viewport matrix to transform NDC coordinates to screen space
 /// x, y: Viewport position (typically 0, 0)
 /// width, height: Viewport dimensions in pixels
@@ -173,4 +374,123 @@ pub fn create_viewport_matrix(x: f32, y: f32, width: f32, height: f32) -> Matrix
         0.0, 0.0, 1.0, 0.0,
         0.0, 0.0, 0.0, 1.0,
     )
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transform(m: &Matrix, v: Vector3, w: f32) -> Vector3 {
+        let r = multiply_matrix_vector4(m, &Vector4::new(v.x, v.y, v.z, w));
+        Vector3::new(r.x, r.y, r.z)
+    }
+
+    #[test]
+    fn normal_matrix_keeps_normals_perpendicular_under_non_uniform_scale() {
+        // X escalado por 2, girado y desplazado
+        let scale = new_matrix4(
+            2.0, 0.0, 0.0, 0.0,
+            0.0, 1.0, 0.0, 0.0,
+            0.0, 0.0, 1.0, 0.0,
+            0.0, 0.0, 0.0, 1.0,
+        );
+        let model = scale * create_model_matrix(Vector3::new(3.0, -1.0, 2.0), 1.0, Vector3::new(0.3, 0.7, -0.2));
+        let model_transform = ModelTransform::new(model);
+
+        // Una superficie inclinada: la tangente está en ella y la normal no es un eje
+        let normal = Vector3::new(1.0, 1.0, 0.0).normalized();
+        let tangent = Vector3::new(1.0, -1.0, 0.0).normalized();
+        let world_normal = transform(&model_transform.normal_matrix, normal, 0.0).normalized();
+        let world_tangent = transform(&model, tangent, 0.0).normalized();
+        assert!(world_normal.dot(world_tangent).abs() < 1e-5);
+
+        // La matriz del modelo sin más la deja torcida
+        let naive = transform(&model, normal, 0.0).normalized();
+        assert!(naive.dot(world_tangent).abs() > 0.1);
+    }
+
+    #[test]
+    fn orthographic_maps_the_unit_cube_onto_ndc() {
+        for (handedness, depth_sign) in [(Handedness::RightHanded, -1.0), (Handedness::LeftHanded, 1.0)] {
+            let ortho = create_orthographic_matrix_handed(0.0, 1.0, 0.0, 1.0, 0.0, 1.0, handedness);
+            for corner in 0..8 {
+                let (x, y, z) = ((corner & 1) as f32, (corner >> 1 & 1) as f32, (corner >> 2 & 1) as f32);
+                // z es la distancia a lo largo de la dirección de vista: 0 cerca, 1 lejos
+                let ndc = transform(&ortho, Vector3::new(x, y, z * depth_sign), 1.0);
+                let expected = Vector3::new(2.0 * x - 1.0, 2.0 * y - 1.0, 2.0 * z - 1.0);
+                assert!((ndc - expected).length() < 1e-6, "{:?}: {:?} != {:?}", handedness, ndc, expected);
+            }
+        }
+
+        // Con el viewport, la esquina de arriba a la izquierda cae en el píxel (0, 0)
+        let viewport = create_viewport_matrix(0.0, 0.0, 640.0, 480.0);
+        let ortho = create_orthographic_matrix(-2.0, 2.0, -1.0, 1.0, 0.1, 10.0);
+        let to_screen = |x: f32, y: f32| {
+            let screen = multiply_matrix_vector4(&viewport, &multiply_matrix_vector4(&ortho, &Vector4::new(x, y, -1.0, 1.0)));
+            Vector2::new(screen.x, screen.y)
+        };
+        assert!((to_screen(-2.0, 1.0) - Vector2::new(0.0, 0.0)).length() < 1e-4);
+        assert!((to_screen(2.0, -1.0) - Vector2::new(640.0, 480.0)).length() < 1e-4);
+    }
+
+    #[test]
+    fn view_matrix_takes_eye_to_origin_even_when_degenerate() {
+        let y = Vector3::new(0.0, 1.0, 0.0);
+        let eye = Vector3::new(3.0, 5.0, -2.0);
+        let cases = [
+            (eye + Vector3::new(-1.0, -0.5, -4.0), y), // Una cámara cualquiera
+            (eye - y * 7.0, y),                        // Hacia abajo, con up sobre la vista
+            (eye + y * 7.0, y),                        // Hacia arriba
+            (eye + Vector3::new(0.0, 0.0, 2.0), Vector3::zero()),
+            (eye, y),                                  // Ojo y objetivo iguales
+        ];
+        for handedness in [Handedness::RightHanded, Handedness::LeftHanded] {
+            let view_sign = if handedness == Handedness::RightHanded { -1.0 } else { 1.0 };
+            for (target, up) in cases {
+                let view = create_view_matrix_handed(eye, target, up, handedness);
+                let entries = [view.m0, view.m1, view.m2, view.m4, view.m5, view.m6, view.m8, view.m9, view.m10,
+                               view.m12, view.m13, view.m14];
+                assert!(entries.iter().all(|value| value.is_finite()), "{:?} {:?}", target, up);
+
+                assert!(transform(&view, eye, 1.0).length() < 1e-4);
+                assert!((camera_position_from_view(&view) - eye).length() < 1e-4);
+                // La rotación es ortonormal: las longitudes se conservan
+                for axis in [Vector3::new(1.0, 0.0, 0.0), y, Vector3::new(0.0, 0.0, 1.0)] {
+                    assert!((transform(&view, axis, 0.0).length() - 1.0).abs() < 1e-5);
+                }
+                // Y el objetivo queda sobre el eje de vista
+                let distance = (target - eye).length();
+                if distance > 0.0 {
+                    let seen = transform(&view, target, 1.0);
+                    assert!((seen - Vector3::new(0.0, 0.0, view_sign * distance)).length() < 1e-3, "{:?}", seen);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn points_land_in_the_expected_screen_quadrant() {
+        let (eye, target, up) = (Vector3::new(1.0, 2.0, 8.0), Vector3::new(1.0, 2.0, 0.0), Vector3::new(0.0, 1.0, 0.0));
+        let forward = (target - eye).normalized();
+        let viewport = create_viewport_matrix(0.0, 0.0, 200.0, 100.0);
+        let screen = |point: Vector3, handedness: Handedness| {
+            let view = create_view_matrix_handed(eye, target, up, handedness);
+            let projection = create_projection_matrix_handed(std::f32::consts::FRAC_PI_3, 2.0, 0.1, 100.0, handedness);
+            let clip = multiply_matrix_vector4(&(view * projection * viewport), &Vector4::new(point.x, point.y, point.z, 1.0));
+            assert!(clip.w > 0.0, "{:?} detrás de la cámara", point);
+            Vector2::new(clip.x / clip.w, clip.y / clip.w)
+        };
+
+        // Derecha de cada convención: forward x up en la de OpenGL, up x forward en la de DirectX
+        for (handedness, right) in [(Handedness::RightHanded, forward.cross(up)), (Handedness::LeftHanded, up.cross(forward))] {
+            let upper_right = screen(eye + forward * 5.0 + right + up, handedness);
+            assert!(upper_right.x > 100.0 && upper_right.x < 200.0, "{:?}: {:?}", handedness, upper_right);
+            assert!(upper_right.y > 0.0 && upper_right.y < 50.0, "{:?}: {:?}", handedness, upper_right);
+        }
+
+        // El mismo punto del mundo sale reflejado en horizontal al cambiar de convención
+        let point = eye + forward * 5.0 + forward.cross(up) + up;
+        let (right_handed, left_handed) = (screen(point, Handedness::RightHanded), screen(point, Handedness::LeftHanded));
+        assert!((right_handed.x + left_handed.x - 200.0).abs() < 1e-3 && (right_handed.y - left_handed.y).abs() < 1e-3);
+    }
+}
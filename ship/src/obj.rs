@@ -1,23 +1,192 @@
 // obj.rs
 use crate::vertex::Vertex;
-use raylib::math::{Vector2, Vector3};
+use std::cell::OnceCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use raylib::math::{Vector2, Vector3, Vector4};
+use std::f32::consts::PI;
 use tobj;
 
 pub struct Obj {
     pub vertices: Vec<Vertex>,
     pub indices: Vec<u32>,
+    pub materials: Vec<Material>,
+    pub face_materials: Vec<usize>, // Material de cada cara (cada 3 índices), índice en `materials`
+    pub has_normals: bool,          // Normales del archivo (vn) o ya calculadas
+    pub has_tex_coords: bool,       // UV del archivo (vt) o generadas: sin ellas no hay tangentes
+    bounds: Bounds,                 // Calculados al cargar y al subdividir
+    vertex_array: OnceCell<Vec<Vertex>>, // `get_vertex_array`, armado la primera vez que se pide
+}
+
+// Caja alineada a los ejes y esfera que la envuelve, en espacio de modelo
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Bounds {
+    min: Vector3,
+    max: Vector3,
+    radius: f32, // Distancia del vértice más lejano al centro de la caja
+}
+
+impl Bounds {
+    // Una malla vacía queda como una caja de tamaño cero en el origen
+    fn of(vertices: &[Vertex]) -> Self {
+        let Some(first) = vertices.first() else {
+            return Bounds { min: Vector3::zero(), max: Vector3::zero(), radius: 0.0 };
+        };
+        let (min, max) = vertices.iter().fold((first.position, first.position), |(min, max), vertex| {
+            let p = vertex.position;
+            (
+                Vector3::new(min.x.min(p.x), min.y.min(p.y), min.z.min(p.z)),
+                Vector3::new(max.x.max(p.x), max.y.max(p.y), max.z.max(p.z)),
+            )
+        });
+        let center = (min + max) * 0.5;
+        let radius = vertices.iter().map(|vertex| (vertex.position - center).length()).fold(0.0, f32::max);
+        Bounds { min, max, radius }
+    }
+}
+
+/// Surface properties from the `.mtl` file an OBJ names with `mtllib`:
+/// diffuse color (`Kd`), specular color (`Ks`), Blinn-Phong exponent
+/// (`Ns`) and the diffuse texture's path (`map_Kd`, relative to the OBJ's
+/// folder as written in the file). Missing entries keep the default gray's
+/// values, without a texture.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Material {
+    pub name: String,
+    pub diffuse: Vector3,
+    pub specular: Vector3,
+    pub shininess: f32,
+    pub diffuse_texture: Option<String>,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Material {
+            name: "default".to_string(),
+            diffuse: Vector3::new(0.5, 0.5, 0.5),
+            specular: Vector3::zero(),
+            shininess: 32.0,
+            diffuse_texture: None,
+        }
+    }
+}
+
+impl Material {
+    fn from_mtl(material: &tobj::Material, folder: &Path) -> Self {
+        let default = Material::default();
+        let color = |rgb: Option<[f32; 3]>, default: Vector3| rgb.map_or(default, |[r, g, b]| Vector3::new(r, g, b));
+        Material {
+            name: material.name.clone(),
+            diffuse: color(material.diffuse, default.diffuse),
+            specular: color(material.specular, default.specular),
+            shininess: material.shininess.unwrap_or(default.shininess),
+            diffuse_texture: material
+                .diffuse_texture
+                .as_ref()
+                .map(|texture| folder.join(texture).to_string_lossy().into_owned()),
+        }
+    }
+}
+
+/// Order of a face's vertices seen from outside a closed mesh
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Winding {
+    CounterClockwise, // Convención de OpenGL, la que espera el renderer
+    Clockwise,
+}
+
+/// Why `Obj::load` couldn't read a model. Line numbers start at 1
+#[derive(Debug)]
+pub enum ObjError {
+    NotFound,                                  // La ruta no existe
+    Io(io::Error),                             // Existe pero no se pudo leer
+    Empty,                                     // Ninguna cara que dibujar
+    MalformedFace { line: usize },             // Menos de 3 vértices, o un índice que no es número
+    IndexOutOfRange { line: usize, index: i64 }, // Índice (tal como está escrito) sin v/vt/vn al que referirse
+    Parse(tobj::LoadError),                    // Cualquier otra línea que tobj rechace
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ObjError::NotFound => write!(f, "el archivo no existe"),
+            ObjError::Io(err) => write!(f, "no se pudo leer ({})", err),
+            ObjError::Empty => write!(f, "el archivo no tiene caras"),
+            ObjError::MalformedFace { line } => write!(f, "cara mal formada en la línea {}", line),
+            ObjError::IndexOutOfRange { line, index } => {
+                write!(f, "índice {} fuera de rango en la línea {}", index, line)
+            }
+            ObjError::Parse(err) => write!(f, "OBJ inválido ({})", err),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ObjError::Io(err) => Some(err),
+            ObjError::Parse(err) => Some(err),
+            _ => None,
+        }
+    }
 }
 
 impl Obj {
-    pub fn load(path: &str) -> Result<Self, tobj::LoadError> {
-        let (models, _materials) = tobj::load_obj(path, &tobj::GPU_LOAD_OPTIONS)?;
+    /// Geometry plus the materials of its `.mtl`. If that file is missing or
+    /// can't be parsed, or a face names no known material, the face gets
+    /// `Material::default()` (appended to `materials` only when needed).
+    /// Without `vn` lines the normals are computed (`with_computed_normals`).
+    /// `v x y z r g b` lines give the vertex colors; vertices of a file
+    /// without them are white
+    /// Quads and larger polygons arrive fan-triangulated (0, 1, 2 then 0, 2, 3...)
+    /// by tobj's `GPU_LOAD_OPTIONS`, whether their indices are `v`, `v/vt`,
+    /// `v//vn` or `v/vt/vn`
+    pub fn load(path: &str) -> Result<Self, ObjError> {
+        let source = fs::read_to_string(path).map_err(|err| match err.kind() {
+            io::ErrorKind::NotFound => ObjError::NotFound,
+            _ => ObjError::Io(err),
+        })?;
+        let folder = Path::new(path).parent().unwrap_or(Path::new(""));
+        Obj::parse(&source, folder)
+    }
+
+    /// The contents of an OBJ file; `mtllib` paths are relative to `folder`
+    pub fn parse(source: &str, folder: &Path) -> Result<Self, ObjError> {
+        check_faces(source)?;
+        let (models, materials) = tobj::load_obj_buf(&mut source.as_bytes(), &tobj::GPU_LOAD_OPTIONS, |mtl_path| {
+            tobj::load_mtl(folder.join(mtl_path))
+        })
+        .map_err(ObjError::Parse)?;
+        let mut materials: Vec<Material> = materials.map_or_else(|_| Vec::new(), |materials| {
+            materials.iter().map(|material| Material::from_mtl(material, folder)).collect()
+        });
+        let loaded_materials = materials.len();
 
         let mut vertices = Vec::new();
         let mut indices = Vec::new();
+        let mut face_materials = Vec::new();
+        let has_normals = models.iter().all(|model| !model.mesh.normals.is_empty());
+        let has_tex_coords = models.iter().all(|model| !model.mesh.texcoords.is_empty());
 
         for model in models {
             let mesh = &model.mesh;
             let num_vertices = mesh.positions.len() / 3;
+            // Los índices de cada modelo empiezan en 0: se desplazan tras los vértices anteriores
+            let first_vertex = vertices.len() as u32;
+
+            let material = match mesh.material_id {
+                Some(id) if id < loaded_materials => id,
+                _ => {
+                    if materials.len() == loaded_materials {
+                        materials.push(Material::default());
+                    }
+                    loaded_materials
+                }
+            };
+            face_materials.extend(std::iter::repeat_n(material, mesh.indices.len() / 3));
 
             for i in 0..num_vertices {
                 let x = mesh.positions[i * 3];
@@ -42,19 +211,559 @@ impl Obj {
                     Vector2::zero()
                 };
 
-                vertices.push(Vertex::new(position, normal, tex_coords));
+                // Colores por vértice (`v x y z r g b`), exportados por algunas herramientas
+                let color = if !mesh.vertex_color.is_empty() {
+                    let r = mesh.vertex_color[i * 3];
+                    let g = mesh.vertex_color[i * 3 + 1];
+                    let b = mesh.vertex_color[i * 3 + 2];
+                    Vector3::new(r, g, b)
+                } else {
+                    Vector3::one()
+                };
+
+                let mut vertex = Vertex::new(position, normal, tex_coords);
+                vertex.color = color;
+                vertices.push(vertex);
+            }
+            indices.extend(mesh.indices.iter().map(|&index| first_vertex + index));
+        }
+
+        let bounds = Bounds::of(&vertices);
+        let mut obj = Obj {
+            vertices, indices, materials, face_materials, has_normals, has_tex_coords, bounds,
+            vertex_array: OnceCell::new(),
+        };
+        obj.with_computed_normals();
+        obj.with_computed_tangents();
+        Ok(obj)
+    }
+
+    /// A sphere of `radius` around `center` made from an icosahedron split
+    /// `levels` times (20 triangles, then 4 times as many per level), with
+    /// spherical UVs and counterclockwise faces. Meant as a low-poly stand-in
+    /// for a loaded sphere seen from far away
+    pub fn icosphere(center: Vector3, radius: f32, levels: u32) -> Self {
+        let phi = (1.0 + 5.0_f32.sqrt()) * 0.5;
+        let corners = [
+            (-1.0, phi, 0.0), (1.0, phi, 0.0), (-1.0, -phi, 0.0), (1.0, -phi, 0.0),
+            (0.0, -1.0, phi), (0.0, 1.0, phi), (0.0, -1.0, -phi), (0.0, 1.0, -phi),
+            (phi, 0.0, -1.0), (phi, 0.0, 1.0), (-phi, 0.0, -1.0), (-phi, 0.0, 1.0),
+        ];
+        let vertices: Vec<Vertex> = corners.iter().map(|&(x, y, z)| {
+            let direction = Vector3::new(x, y, z).normalized();
+            Vertex::new(direction * radius, direction, Vector2::zero())
+        }).collect();
+        let indices = vec![
+            0, 11, 5, 0, 5, 1, 0, 1, 7, 0, 7, 10, 0, 10, 11,
+            1, 5, 9, 5, 11, 4, 11, 10, 2, 10, 7, 6, 7, 1, 8,
+            3, 9, 4, 3, 4, 2, 3, 2, 6, 3, 6, 8, 3, 8, 9,
+            4, 9, 5, 2, 4, 11, 6, 2, 10, 8, 6, 7, 9, 8, 1,
+        ];
+
+        let bounds = Bounds::of(&vertices);
+        let mut obj = Obj {
+            vertices, indices, materials: vec![Material::default()], face_materials: vec![0; 20],
+            has_normals: true, has_tex_coords: false, bounds, vertex_array: OnceCell::new(),
+        };
+        // Centrada en el origen mientras se subdivide: los vértices nuevos se proyectan sobre esa esfera
+        obj.with_spherical_uvs();
+        obj.subdivide(levels, true);
+        for vertex in &mut obj.vertices {
+            vertex.position += center;
+        }
+        obj.bounds = Bounds::of(&obj.vertices);
+        obj
+    }
+
+    /// A flat ring on the XZ plane between `inner_radius` and `outer_radius`,
+    /// facing +Y, split into `segments` quads around the Y axis. `u` goes
+    /// once around (the first column of vertices is repeated at `u` = 1)
+    /// and `v` from the inner edge (0) to the outer one (1)
+    pub fn annulus(inner_radius: f32, outer_radius: f32, segments: u32) -> Self {
+        let segments = segments.max(3);
+        let mut vertices = Vec::with_capacity(2 * (segments as usize + 1));
+        for i in 0..=segments {
+            let u = i as f32 / segments as f32;
+            let (sin, cos) = (u * 2.0 * PI).sin_cos();
+            for (radius, v) in [(inner_radius, 0.0), (outer_radius, 1.0)] {
+                let position = Vector3::new(radius * cos, 0.0, radius * sin);
+                vertices.push(Vertex::new(position, Vector3::new(0.0, 1.0, 0.0), Vector2::new(u, v)));
+            }
+        }
+        // Cada cuadrilátero: interior y exterior de esta columna y de la siguiente
+        let mut indices = Vec::with_capacity(6 * segments as usize);
+        for i in 0..segments {
+            let (inner, outer, next_inner, next_outer) = (2 * i, 2 * i + 1, 2 * i + 2, 2 * i + 3);
+            indices.extend_from_slice(&[inner, next_inner, outer, outer, next_inner, next_outer]);
+        }
+
+        let bounds = Bounds::of(&vertices);
+        let mut obj = Obj {
+            vertices, indices, materials: vec![Material::default()], face_materials: vec![0; 2 * segments as usize],
+            has_normals: true, has_tex_coords: true, bounds, vertex_array: OnceCell::new(),
+        };
+        obj.with_computed_tangents();
+        obj
+    }
+
+    /// Smooth normals for a mesh loaded without them: each vertex gets the
+    /// average of the normals of the faces around it, weighted by their
+    /// area, turned outward like `detect_winding` decides. Does nothing when
+    /// `has_normals` is already set. If only some models of the file had
+    /// normals, all of them are recomputed.
+    pub fn with_computed_normals(&mut self) {
+        if self.has_normals {
+            return;
+        }
+
+        let mut normals = vec![Vector3::zero(); self.vertices.len()];
+        for face in self.indices.chunks_exact(3) {
+            let [a, b, c] = [face[0], face[1], face[2]].map(|index| self.vertices[index as usize].position);
+            let normal = (b - a).cross(c - a); // Largo = 2 * área: el promedio queda ponderado
+            for &index in face {
+                normals[index as usize] += normal;
+            }
+        }
+
+        // Caras en sentido horario: el producto cruz apunta hacia adentro
+        let outward = if self.detect_winding() == Winding::CounterClockwise { 1.0 } else { -1.0 };
+        for (vertex, normal) in self.vertices.iter_mut().zip(normals) {
+            vertex.normal = normal.normalized() * outward;
+        }
+        self.has_normals = true;
+        self.vertex_array.take();
+    }
+
+    /// Per-vertex tangents for normal mapping, from positions and UVs: the
+    /// direction in which `u` grows along the surface, made perpendicular to
+    /// the vertex normal, with `w` = -1 where `v` grows against
+    /// normal x tangent (mirrored UVs) and 1 elsewhere. Without
+    /// `has_tex_coords` the tangents stay zero, which the shaders read as
+    /// "no normal map here"
+    pub fn with_computed_tangents(&mut self) {
+        if !self.has_tex_coords {
+            return;
+        }
+
+        let mut tangents = vec![Vector3::zero(); self.vertices.len()];
+        let mut bitangents = vec![Vector3::zero(); self.vertices.len()];
+        for face in self.indices.chunks_exact(3) {
+            let [a, b, c] = [face[0], face[1], face[2]].map(|index| &self.vertices[index as usize]);
+            let (edge1, edge2) = (b.position - a.position, c.position - a.position);
+            let (duv1, duv2) = (b.tex_coords - a.tex_coords, c.tex_coords - a.tex_coords);
+            let determinant = duv1.x * duv2.y - duv2.x * duv1.y;
+            // Cara con UV degeneradas: no dice hacia dónde crecen u y v
+            if determinant.abs() < 1e-12 {
+                continue;
+            }
+            // dP/du y dP/dv de la cara: el sistema [edge1; edge2] = [duv1; duv2] * [T; B]
+            let tangent = (edge1 * duv2.y - edge2 * duv1.y) / determinant;
+            let bitangent = (edge2 * duv1.x - edge1 * duv2.x) / determinant;
+            for &index in face {
+                tangents[index as usize] += tangent;
+                bitangents[index as usize] += bitangent;
+            }
+        }
+
+        for ((vertex, tangent), bitangent) in self.vertices.iter_mut().zip(tangents).zip(bitangents) {
+            // Gram-Schmidt: la tangente queda sobre el plano de la normal
+            let normal = vertex.normal;
+            let tangent = tangent - normal * normal.dot(tangent);
+            vertex.tangent = if tangent.length() < 1e-12 {
+                Vector4::new(0.0, 0.0, 0.0, 0.0)
+            } else {
+                let tangent = tangent.normalized();
+                let handedness = if normal.cross(tangent).dot(bitangent) < 0.0 { -1.0 } else { 1.0 };
+                Vector4::new(tangent.x, tangent.y, tangent.z, handedness)
+            };
+        }
+        self.vertex_array.take();
+    }
+
+    /// Replaces the UVs with the equirectangular mapping around `center`, as
+    /// a globe map is drawn seen from outside: `u` goes once around the Y
+    /// axis eastwards (from +X towards -Z, 0.5 at +X) and `v` from the bottom
+    /// pole (0) to the top one (1). Faces across the seam get copies of their
+    /// vertices with `u` past 1 and pole vertices take the `u` of their face,
+    /// so every face samples a continuous piece of the image (with
+    /// `TextureWrap::Repeat`)
+    pub fn with_spherical_uvs(&mut self) {
+        let center = self.center();
+        for vertex in &mut self.vertices {
+            let d = (vertex.position - center).normalized();
+            vertex.tex_coords = Vector2::new(0.5 + (-d.z).atan2(d.x) / (2.0 * PI), 0.5 + d.y.clamp(-1.0, 1.0).asin() / PI);
+        }
+
+        let is_pole = |vertex: &Vertex| {
+            let d = (vertex.position - center).normalized();
+            d.y.abs() > 0.9999
+        };
+        for face in 0..self.indices.len() / 3 {
+            let corners = [0, 1, 2].map(|corner| self.indices[face * 3 + corner] as usize);
+            let mut uvs = corners.map(|index| self.vertices[index].tex_coords);
+            let poles = corners.map(|index| is_pole(&self.vertices[index]));
+
+            // Costura: si la cara abarca más de media vuelta, sus u bajas siguen después de 1
+            let us = (0..3).filter(|&corner| !poles[corner]).map(|corner| uvs[corner].x);
+            let (min_u, max_u) = us.fold((f32::MAX, f32::MIN), |(min, max), u| (min.min(u), max.max(u)));
+            if max_u - min_u > 0.5 {
+                for uv in uvs.iter_mut().filter(|uv| uv.x < 0.5) {
+                    uv.x += 1.0;
+                }
+            }
+            // Polo: la u de la cara, promedio de las otras esquinas
+            let others: Vec<f32> = (0..3).filter(|&corner| !poles[corner]).map(|corner| uvs[corner].x).collect();
+            if !others.is_empty() {
+                let face_u = others.iter().sum::<f32>() / others.len() as f32;
+                for corner in (0..3).filter(|&corner| poles[corner]) {
+                    uvs[corner].x = face_u;
+                }
+            }
+
+            // Las esquinas que cambiaron pasan a una copia propia del vértice
+            for corner in 0..3 {
+                let index = corners[corner];
+                if uvs[corner] != self.vertices[index].tex_coords {
+                    let mut copy = self.vertices[index].clone();
+                    copy.tex_coords = uvs[corner];
+                    self.indices[face * 3 + corner] = self.vertices.len() as u32;
+                    self.vertices.push(copy);
+                }
+            }
+        }
+        self.has_tex_coords = true;
+        self.with_computed_tangents();
+    }
+
+    /// Min and max corners of the axis-aligned box around every vertex, in
+    /// model space. A mesh without vertices gives a zero-size box at the origin
+    pub fn bounding_box(&self) -> (Vector3, Vector3) {
+        (self.bounds.min, self.bounds.max)
+    }
+
+    /// Center of `bounding_box`
+    pub fn center(&self) -> Vector3 {
+        (self.bounds.min + self.bounds.max) * 0.5
+    }
+
+    /// Radius of the sphere around `center` that holds every vertex
+    pub fn radius(&self) -> f32 {
+        self.bounds.radius
+    }
+
+    /// Three vertices per face, each with its face's `material_id`. Built
+    /// on the first call and kept: `flip_winding`, `subdivide` and
+    /// `with_computed_normals` drop it, but editing the public fields
+    /// directly after a call leaves it stale
+    pub fn get_vertex_array(&self) -> &[Vertex] {
+        self.vertex_array.get_or_init(|| {
+            let mut vertex_array = Vec::with_capacity(self.indices.len());
+            for (face, indices) in self.indices.chunks_exact(3).enumerate() {
+                let material_id = self.face_materials.get(face).copied().unwrap_or(0);
+                for &index in indices {
+                    let mut vertex = self.vertices[index as usize].clone();
+                    vertex.material_id = material_id;
+                    vertex_array.push(vertex);
+                }
+            }
+            vertex_array
+        })
+    }
+
+    /// Dominant winding of a closed mesh: each face votes, weighted by its
+    /// area, on whether its normal (from the vertex order) points away from
+    /// the mesh centroid or toward it.
+    pub fn detect_winding(&self) -> Winding {
+        let positions: Vec<Vector3> = self.indices.iter().map(|&index| self.vertices[index as usize].position).collect();
+        if positions.is_empty() {
+            return Winding::CounterClockwise;
+        }
+        let centroid = positions.iter().fold(Vector3::zero(), |sum, &p| sum + p) / positions.len() as f32;
+
+        let mut outward = 0.0;
+        for face in positions.chunks_exact(3) {
+            let normal = (face[1] - face[0]).cross(face[2] - face[0]); // Largo = 2 * área
+            let center = (face[0] + face[1] + face[2]) / 3.0;
+            outward += normal.dot(center - centroid);
+        }
+
+        if outward >= 0.0 { Winding::CounterClockwise } else { Winding::Clockwise }
+    }
+
+    /// Reverses the vertex order of every face
+    pub fn flip_winding(&mut self) {
+        for face in self.indices.chunks_exact_mut(3) {
+            face.swap(1, 2);
+        }
+        self.vertex_array.take();
+    }
+
+    /// Midpoint subdivision: each triangle is split into 4, `levels` times.
+    /// With `project_to_sphere` new vertices are pushed out onto the sphere
+    /// through their edge's endpoints (the unit sphere for a unit mesh),
+    /// which rounds the silhouette instead of only adding flat triangles.
+    pub fn subdivide(&mut self, levels: u32, project_to_sphere: bool) {
+        for _ in 0..levels {
+            let mut midpoints: HashMap<(u32, u32), u32> = HashMap::new();
+            let faces = std::mem::take(&mut self.indices);
+            let mut indices = Vec::with_capacity(faces.len() * 4);
+            // Las 4 caras nuevas heredan el material de la original
+            self.face_materials = self.face_materials.iter().flat_map(|&material| [material; 4]).collect();
+
+            for face in faces.chunks_exact(3) {
+                let (a, b, c) = (face[0], face[1], face[2]);
+                let ab = self.midpoint(&mut midpoints, a, b, project_to_sphere);
+                let bc = self.midpoint(&mut midpoints, b, c, project_to_sphere);
+                let ca = self.midpoint(&mut midpoints, c, a, project_to_sphere);
+
+                indices.extend_from_slice(&[a, ab, ca]);
+                indices.extend_from_slice(&[ab, b, bc]);
+                indices.extend_from_slice(&[ca, bc, c]);
+                indices.extend_from_slice(&[ab, bc, ca]);
             }
-            indices.extend_from_slice(&mesh.indices);
+
+            self.indices = indices;
+        }
+        // Proyectados a la esfera, los vértices nuevos pueden salirse de la caja anterior
+        self.bounds = Bounds::of(&self.vertices);
+        // Las tangentes de los vértices nuevos salen de sus caras, como las originales
+        self.with_computed_tangents();
+        // Sin UV with_computed_tangents no hace nada: el arreglo viejo se descarta aquí
+        self.vertex_array.take();
+    }
+
+    // Índice del vértice medio de la arista (a, b), compartido entre las caras vecinas
+    fn midpoint(&mut self, midpoints: &mut HashMap<(u32, u32), u32>, a: u32, b: u32, project_to_sphere: bool) -> u32 {
+        let key = (a.min(b), a.max(b));
+        if let Some(&index) = midpoints.get(&key) {
+            return index;
         }
 
-        Ok(Obj { vertices, indices })
+        let va = &self.vertices[a as usize];
+        let vb = &self.vertices[b as usize];
+        let mut position = (va.position + vb.position) * 0.5;
+        let tex_coords = (va.tex_coords + vb.tex_coords) * 0.5;
+        let color = (va.color + vb.color) * 0.5;
+
+        let normal = if project_to_sphere {
+            let radius = (va.position.length() + vb.position.length()) * 0.5;
+            let direction = position.normalized();
+            position = direction * radius;
+            direction
+        } else {
+            let sum = va.normal + vb.normal;
+            if sum.length() > 0.0 { sum.normalized() } else { sum }
+        };
+
+        let index = self.vertices.len() as u32;
+        let mut vertex = Vertex::new(position, normal, tex_coords);
+        vertex.color = color;
+        self.vertices.push(vertex);
+        midpoints.insert(key, index);
+        index
     }
+}
+
+// Revisa las líneas `f` antes que tobj, que dice qué falló pero no dónde.
+// Los índices negativos cuentan hacia atrás desde los v/vt/vn leídos hasta
+// esa línea; los positivos se comparan con el total del archivo, como tobj
+fn check_faces(source: &str) -> Result<(), ObjError> {
+    let mut counts = [0i64; 3]; // v, vt y vn vistos hasta ahora
+    let mut positive = Vec::new(); // (línea, tipo, índice) para comparar con el total al final
+    let mut faces = 0;
 
-    pub fn get_vertex_array(&self) -> Vec<Vertex> {
-        let mut vertex_array = Vec::new();
-        for &index in &self.indices {
-            vertex_array.push(self.vertices[index as usize].clone());
+    for (number, text) in source.lines().enumerate() {
+        let line = number + 1;
+        let mut words = text.split_whitespace();
+        match words.next() {
+            Some("v") => counts[0] += 1,
+            Some("vt") => counts[1] += 1,
+            Some("vn") => counts[2] += 1,
+            Some("f") => {
+                let mut corners = 0;
+                for word in words {
+                    let parts: Vec<&str> = word.split('/').collect();
+                    // `v`, `v/vt`, `v//vn` o `v/vt/vn`: la posición es obligatoria
+                    if parts.len() > 3 || parts[0].is_empty() {
+                        return Err(ObjError::MalformedFace { line });
+                    }
+                    for (kind, part) in parts.iter().enumerate().filter(|(_, part)| !part.is_empty()) {
+                        let index: i64 = part.parse().map_err(|_| ObjError::MalformedFace { line })?;
+                        if index == 0 || (index < 0 && counts[kind] + index < 0) {
+                            return Err(ObjError::IndexOutOfRange { line, index });
+                        }
+                        if index > 0 {
+                            positive.push((line, kind, index));
+                        }
+                    }
+                    corners += 1;
+                }
+                if corners < 3 {
+                    return Err(ObjError::MalformedFace { line });
+                }
+                faces += 1;
+            }
+            _ => {}
         }
-        vertex_array
+    }
+
+    if let Some(&(line, _, index)) = positive.iter().find(|&&(_, kind, index)| index > counts[kind]) {
+        return Err(ObjError::IndexOutOfRange { line, index });
+    }
+    if faces == 0 {
+        return Err(ObjError::Empty);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRIANGLE_VERTICES: &str = "v 0 0 0\nv 1 0 0\nv 0 1 0\n";
+
+    fn parse(source: &str) -> Result<Obj, ObjError> {
+        Obj::parse(source, Path::new(""))
+    }
+
+    #[test]
+    fn rejects_out_of_range_indices() {
+        for (face, index) in [("f 0 1 2", 0), ("f 1 2 4", 4), ("f -1 -2 -4", -4), ("f 1/3 2/1 3/1", 3)] {
+            let source = format!("{}vt 0 0\nvt 1 0\n{}\n", TRIANGLE_VERTICES, face);
+            match parse(&source) {
+                Err(ObjError::IndexOutOfRange { line: 6, index: found }) => assert_eq!(found, index, "{}", face),
+                other => panic!("{}: {:?}", face, other.map(|_| ())),
+            }
+        }
+        // Los negativos cuentan solo lo leído antes de la cara
+        let source = "v 0 0 0\nv 1 0 0\nf -1 -2 -3\nv 0 1 0\n";
+        assert!(matches!(parse(source), Err(ObjError::IndexOutOfRange { line: 3, index: -3 })));
+    }
+
+    #[test]
+    fn rejects_malformed_faces() {
+        for face in ["f 1 2", "f 1 2 x", "f 1 /2 3", "f 1/1/1/1 2 3"] {
+            let source = format!("{}{}\n", TRIANGLE_VERTICES, face);
+            assert!(matches!(parse(&source), Err(ObjError::MalformedFace { line: 4 })), "{}", face);
+        }
+    }
+
+    #[test]
+    fn rejects_files_without_faces() {
+        assert!(matches!(parse(""), Err(ObjError::Empty)));
+        assert!(matches!(parse(TRIANGLE_VERTICES), Err(ObjError::Empty)));
+    }
+
+    #[test]
+    fn reports_missing_files() {
+        assert!(matches!(Obj::load("models/no_existe.obj"), Err(ObjError::NotFound)));
+        assert!(parse(&format!("{}f 1 2 3\n", TRIANGLE_VERTICES)).is_ok());
+    }
+
+    // Octaedro unitario, caras en sentido antihorario
+    const OCTAHEDRON: &str = "v 1 0 0\nv -1 0 0\nv 0 1 0\nv 0 -1 0\nv 0 0 1\nv 0 0 -1\n\
+        f 1 3 5\nf 3 2 5\nf 2 4 5\nf 4 1 5\nf 3 1 6\nf 2 3 6\nf 4 2 6\nf 1 4 6\n";
+
+    #[test]
+    fn subdivision_quadruples_the_faces_onto_the_sphere() {
+        let mut obj = parse(OCTAHEDRON).unwrap();
+        assert_eq!(obj.get_vertex_array().len(), 8 * 3);
+
+        obj.subdivide(1, true);
+        let vertex_array = obj.get_vertex_array();
+        assert_eq!(vertex_array.len(), 8 * 4 * 3);
+        assert!(vertex_array.iter().all(|vertex| (vertex.position.length() - 1.0).abs() < 1e-5));
+        // Vértices medios compartidos entre caras vecinas: 6 esquinas y 12 aristas
+        assert_eq!(obj.vertices.len(), 6 + 12);
+
+        obj.subdivide(2, true);
+        assert_eq!(obj.get_vertex_array().len(), 8 * 4 * 4 * 4 * 3);
+    }
+
+    #[test]
+    fn flat_subdivision_keeps_midpoints_on_the_edges() {
+        let mut obj = parse(OCTAHEDRON).unwrap();
+        obj.subdivide(1, false);
+        let midpoint = (Vector3::new(1.0, 0.0, 0.0) + Vector3::new(0.0, 1.0, 0.0)) * 0.5;
+        assert!(obj.vertices.iter().any(|vertex| (vertex.position - midpoint).length() < 1e-6));
+        assert!(obj.vertices.iter().skip(6).all(|vertex| vertex.position.length() < 1.0));
+    }
+
+    // Cubo de lado 2 sin `vn`, caras cuadradas en sentido antihorario
+    const CUBE: &str = "v -1 -1 -1\nv 1 -1 -1\nv 1 1 -1\nv -1 1 -1\nv -1 -1 1\nv 1 -1 1\nv 1 1 1\nv -1 1 1\n\
+        f 1 4 3 2\nf 5 6 7 8\nf 1 2 6 5\nf 4 8 7 3\nf 1 5 8 4\nf 2 3 7 6\n";
+
+    #[test]
+    fn computes_outward_normals_without_vn() {
+        let obj = parse(CUBE).unwrap();
+        assert_eq!(obj.get_vertex_array().len(), 6 * 2 * 3);
+
+        // Una normal promediada por esquina, hacia afuera: una en cada octante
+        let mut normals: Vec<Vector3> = Vec::new();
+        for vertex in &obj.vertices {
+            let (normal, position) = (vertex.normal, vertex.position);
+            assert!((normal.length() - 1.0).abs() < 1e-5);
+            assert!(normal.x * position.x > 0.0 && normal.y * position.y > 0.0 && normal.z * position.z > 0.0,
+                    "{:?} en {:?}", normal, position);
+            if !normals.iter().any(|seen| (*seen - normal).length() < 1e-4) {
+                normals.push(normal);
+            }
+        }
+        assert_eq!(normals.len(), 8);
+
+        // Con las caras en sentido horario también quedan hacia afuera
+        let mut flipped = parse(CUBE).unwrap();
+        flipped.flip_winding();
+        flipped.has_normals = false;
+        flipped.with_computed_normals();
+        assert!(flipped.vertices.iter().all(|vertex| vertex.normal.dot(vertex.position) > 0.0));
+    }
+
+    #[test]
+    fn detects_the_winding_of_a_closed_mesh() {
+        let mut cube = parse(CUBE).unwrap();
+        assert_eq!(cube.detect_winding(), Winding::CounterClockwise);
+        cube.flip_winding();
+        assert_eq!(cube.detect_winding(), Winding::Clockwise);
+        // Lejos del origen: se mide desde el centroide, no desde el origen
+        let shifted = CUBE.replace("v -1", "v 9").replace("v 1", "v 11");
+        assert_eq!(parse(&shifted).unwrap().detect_winding(), Winding::CounterClockwise);
+    }
+
+    #[test]
+    fn keeps_the_normals_of_the_file() {
+        let obj = parse("v 0 0 0\nv 1 0 0\nv 0 1 0\nvn 0 0 -1\nf 1//1 2//1 3//1\n").unwrap();
+        assert!(obj.vertices.iter().all(|vertex| vertex.normal == Vector3::new(0.0, 0.0, -1.0)));
+    }
+
+    #[test]
+    fn reads_vertex_colors_or_defaults_to_white() {
+        let colored = parse("v 0 0 0 1 0 0\nv 1 0 0 0 1 0\nv 0 1 0 0.2 0.4 0.6\nf 1 2 3\n").unwrap();
+        let colors: Vec<Vector3> = colored.vertices.iter().map(|vertex| vertex.color).collect();
+        assert_eq!(colors, [Vector3::new(1.0, 0.0, 0.0), Vector3::new(0.0, 1.0, 0.0), Vector3::new(0.2, 0.4, 0.6)]);
+
+        let plain = parse(&format!("{}f 1 2 3\n", TRIANGLE_VERTICES)).unwrap();
+        assert!(plain.vertices.iter().all(|vertex| vertex.color == Vector3::one()));
+        // El color llega al arreglo de vértices que se dibuja
+        assert_eq!(colored.get_vertex_array()[2].color, Vector3::new(0.2, 0.4, 0.6));
+    }
+
+    #[test]
+    fn fan_triangulates_quads_in_every_index_form() {
+        let header = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvt 0 0\nvt 1 0\nvt 1 1\nvt 0 1\nvn 0 0 1\n";
+        let corners = [Vector3::new(0.0, 0.0, 0.0), Vector3::new(1.0, 0.0, 0.0), Vector3::new(1.0, 1.0, 0.0), Vector3::new(0.0, 1.0, 0.0)];
+        for face in ["f 1 2 3 4", "f 1/1 2/2 3/3 4/4", "f 1//1 2//1 3//1 4//1", "f 1/1/1 2/2/1 3/3/1 4/4/1"] {
+            let obj = parse(&format!("{}{}\n", header, face)).unwrap();
+            let positions: Vec<Vector3> = obj.get_vertex_array().iter().map(|vertex| vertex.position).collect();
+            let expected = [0, 1, 2, 0, 2, 3].map(|corner| corners[corner]);
+            assert_eq!(positions, expected, "{}", face);
+            // Los dos triángulos conservan el sentido antihorario del cuadrado
+            for triangle in positions.chunks_exact(3) {
+                assert!((triangle[1] - triangle[0]).cross(triangle[2] - triangle[0]).z > 0.0, "{}", face);
+            }
+        }
+
+        let pentagon = parse("v 0 0 0\nv 2 0 0\nv 3 1 0\nv 1 2 0\nv -1 1 0\nf 1 2 3 4 5\n").unwrap();
+        assert_eq!(pentagon.get_vertex_array().len(), 9);
     }
 }
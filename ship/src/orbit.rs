@@ -0,0 +1,124 @@
+// orbit.rs
+use raylib::prelude::*;
+use std::f32::consts::PI;
+
+/// Keplerian orbital elements around a body fixed at the origin.
+///
+/// Angles are in radians and the reference plane is the renderer's XZ
+/// plane (Y up). With zero inclination and node the orbit advances from +X
+/// toward +Z, the same direction as the old circular orbits.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Orbit {
+    pub semi_major_axis: f32,
+    pub eccentricity: f32,                // 0 = circular, < 1 = elíptica
+    pub inclination: f32,                 // Inclinación respecto al plano XZ
+    pub longitude_of_ascending_node: f32,
+    pub argument_of_periapsis: f32,
+    pub mean_anomaly_at_epoch: f32,
+    pub mean_motion: f32,                 // Radianes de anomalía media por segundo
+    pub epoch: f32,                       // Tiempo en el que vale mean_anomaly_at_epoch
+}
+
+// Iteraciones de Newton para la ecuación de Kepler (converge en 3-4 para e < 0.9)
+const KEPLER_ITERATIONS: usize = 8;
+
+impl Orbit {
+    pub fn circular(radius: f32, mean_motion: f32) -> Self {
+        Orbit {
+            semi_major_axis: radius,
+            eccentricity: 0.0,
+            inclination: 0.0,
+            longitude_of_ascending_node: 0.0,
+            argument_of_periapsis: 0.0,
+            mean_anomaly_at_epoch: 0.0,
+            mean_motion,
+            epoch: 0.0,
+        }
+    }
+
+    /// Solves Kepler's equation M = E - e sin E for the eccentric anomaly
+    pub fn eccentric_anomaly(&self, mean_anomaly: f32) -> f32 {
+        let e = self.eccentricity;
+        let mean_anomaly = mean_anomaly.rem_euclid(2.0 * PI);
+        let mut anomaly = if e < 0.8 { mean_anomaly } else { PI };
+
+        for _ in 0..KEPLER_ITERATIONS {
+            let delta = (anomaly - e * anomaly.sin() - mean_anomaly) / (1.0 - e * anomaly.cos());
+            anomaly -= delta;
+            if delta.abs() < 1e-6 {
+                break;
+            }
+        }
+        anomaly
+    }
+
+    pub fn position_at(&self, time: f32) -> Vector3 {
+        let mean_anomaly = self.mean_anomaly_at_epoch + self.mean_motion * (time - self.epoch);
+        self.position_at_eccentric_anomaly(self.eccentric_anomaly(mean_anomaly))
+    }
+
+    /// `segments + 1` points around the whole ellipse (first == last)
+    pub fn path(&self, segments: usize) -> Vec<Vector3> {
+        (0..=segments)
+            .map(|i| self.position_at_eccentric_anomaly(i as f32 / segments as f32 * 2.0 * PI))
+            .collect()
+    }
+
+    fn position_at_eccentric_anomaly(&self, anomaly: f32) -> Vector3 {
+        let a = self.semi_major_axis;
+        let e = self.eccentricity;
+
+        // Posición en el plano de la órbita, con el periapsis sobre +x
+        let x = a * (anomaly.cos() - e);
+        let y = a * (1.0 - e * e).sqrt() * anomaly.sin();
+
+        // Rotaciones clásicas (ω, i, Ω) en un marco con z como normal del plano de referencia
+        let (sin_w, cos_w) = self.argument_of_periapsis.sin_cos();
+        let (sin_i, cos_i) = self.inclination.sin_cos();
+        let (sin_o, cos_o) = self.longitude_of_ascending_node.sin_cos();
+
+        let px = x * cos_w - y * sin_w;
+        let py = x * sin_w + y * cos_w;
+
+        let rx = px * cos_o - py * cos_i * sin_o;
+        let ry = px * sin_o + py * cos_i * cos_o;
+        let rz = py * sin_i;
+
+        // Marco de referencia -> mundo: el plano de referencia es XZ y la normal es +Y
+        Vector3::new(rx, rz, ry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ellipse() -> Orbit {
+        Orbit { eccentricity: 0.5, ..Orbit::circular(2.0, 1.0) }
+    }
+
+    #[test]
+    fn solves_keplers_equation() {
+        // Referencia: E - 0.5 sin E = π/2, resuelta en doble precisión
+        let orbit = ellipse();
+        assert!((orbit.eccentric_anomaly(PI / 2.0) - 2.020_98).abs() < 1e-5);
+        assert!((orbit.position_at(PI / 2.0) - Vector3::new(-1.870_262, 0.0, 1.559_482)).length() < 1e-4);
+        // Muy excéntrica, también converge
+        let eccentric = Orbit { eccentricity: 0.95, ..orbit };
+        for mean_anomaly in [0.1, 1.0, 3.0, 5.5] {
+            let anomaly = eccentric.eccentric_anomaly(mean_anomaly);
+            assert!((anomaly - 0.95 * anomaly.sin() - mean_anomaly).abs() < 1e-4, "{}", mean_anomaly);
+        }
+    }
+
+    #[test]
+    fn periapsis_and_apoapsis_are_at_a_times_one_minus_and_plus_e() {
+        for orbit in [ellipse(), Orbit { inclination: 0.6, longitude_of_ascending_node: 1.2, argument_of_periapsis: 2.5, ..ellipse() }] {
+            assert!((orbit.position_at(0.0).length() - 2.0 * (1.0 - 0.5)).abs() < 1e-5);
+            assert!((orbit.position_at(PI).length() - 2.0 * (1.0 + 0.5)).abs() < 1e-5);
+        }
+        // Sin inclinación ni orientación el periapsis cae sobre +X y avanza hacia +Z
+        assert!((ellipse().position_at(0.0) - Vector3::new(1.0, 0.0, 0.0)).length() < 1e-5);
+        assert!(ellipse().position_at(0.1).z > 0.0);
+    }
+}
@@ -0,0 +1,165 @@
+// palette.rs
+use raylib::prelude::*;
+use std::fs;
+use std::io;
+use std::str::FromStr;
+
+// Paradas como máximo: la paleta es Copy y viaja dentro de ShaderParams
+pub const MAX_PALETTE_STOPS: usize = 16;
+
+/// A color gradient: up to `MAX_PALETTE_STOPS` stops at positions in
+/// [0, 1], linearly interpolated by `sample`. The planet shaders map their
+/// noise (elevation, lava activity, band mix...) through one, so a body can
+/// be rethemed by loading a different palette file.
+///
+/// The file format is one stop per line, `r, g, b`, optionally preceded by
+/// its position, `t: r, g, b`. Without positions the stops are spread
+/// evenly from 0 to 1; either every stop has a position or none does.
+/// Empty lines and `#` comments are ignored.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Palette {
+    stops: [(f32, Vector3); MAX_PALETTE_STOPS],
+    len: usize,
+}
+
+impl Palette {
+    /// Stops as (position, color), sorted by position. Extra stops past
+    /// `MAX_PALETTE_STOPS` are dropped; an empty list samples black.
+    pub fn new(stops: &[(f32, Vector3)]) -> Self {
+        let mut palette = Palette { stops: [(0.0, Vector3::zero()); MAX_PALETTE_STOPS], len: stops.len().min(MAX_PALETTE_STOPS) };
+        palette.stops[..palette.len].copy_from_slice(&stops[..palette.len]);
+        palette.stops[..palette.len].sort_by(|a, b| a.0.total_cmp(&b.0));
+        palette
+    }
+
+    /// `colors` spread evenly from 0 to 1
+    pub fn evenly_spaced(colors: &[Vector3]) -> Self {
+        let last = (colors.len().max(2) - 1) as f32;
+        let stops: Vec<_> = colors.iter().enumerate().map(|(i, color)| (i as f32 / last, *color)).collect();
+        Palette::new(&stops)
+    }
+
+    pub fn load(path: &str) -> io::Result<Self> {
+        fs::read_to_string(path)?
+            .parse()
+            .map_err(|err: String| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// Color at `t`: linear between the two stops around it, the first or
+    /// last stop's color outside them
+    pub fn sample(&self, t: f32) -> Vector3 {
+        sample_stops(&self.stops[..self.len], t)
+    }
+}
+
+/// `Palette::sample` over stops already sorted by position, for gradients
+/// built on the fly without a `Palette`
+pub fn sample_stops(stops: &[(f32, Vector3)], t: f32) -> Vector3 {
+    let (Some(first), Some(last)) = (stops.first(), stops.last()) else {
+        return Vector3::zero();
+    };
+    if t <= first.0 {
+        return first.1;
+    }
+
+    for pair in stops.windows(2) {
+        let ((start, from), (end, to)) = (pair[0], pair[1]);
+        if t <= end {
+            let span = end - start;
+            if span <= 0.0 {
+                return to;
+            }
+            return from + (to - from) * ((t - start) / span);
+        }
+    }
+    last.1
+}
+
+fn parse_color(value: &str) -> Option<Vector3> {
+    let parts: Vec<f32> = value
+        .split(',')
+        .map(|part| part.trim().parse())
+        .collect::<Result<_, _>>()
+        .ok()?;
+    match parts.as_slice() {
+        [r, g, b] => Some(Vector3::new(*r, *g, *b)),
+        _ => None,
+    }
+}
+
+impl FromStr for Palette {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut positioned = Vec::new();
+        let mut colors = Vec::new();
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = || format!("line {}: expected `r, g, b` or `t: r, g, b`: {}", number + 1, line);
+
+            match line.split_once(':') {
+                Some((position, color)) => {
+                    let position: f32 = position.trim().parse().map_err(|_| invalid())?;
+                    if !(0.0..=1.0).contains(&position) {
+                        return Err(invalid());
+                    }
+                    positioned.push((position, parse_color(color).ok_or_else(invalid)?));
+                }
+                None => colors.push(parse_color(line).ok_or_else(invalid)?),
+            }
+        }
+
+        let count = positioned.len() + colors.len();
+        if count == 0 {
+            return Err("palette has no stops".to_string());
+        }
+        if count > MAX_PALETTE_STOPS {
+            return Err(format!("palette has {} stops, at most {} are supported", count, MAX_PALETTE_STOPS));
+        }
+        match (positioned.is_empty(), colors.is_empty()) {
+            (true, _) => Ok(Palette::evenly_spaced(&colors)),
+            (_, true) => Ok(Palette::new(&positioned)),
+            _ => Err("either every stop has a position or none does".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: Vector3, b: Vector3) {
+        assert!((a - b).length() < 1e-6, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn two_stops_interpolate_linearly() {
+        let palette: Palette = "0, 0, 0\n1, 0.5, 0.2\n".parse().unwrap();
+        for t in [0.0, 0.25, 0.5, 0.9, 1.0] {
+            assert_close(palette.sample(t), Vector3::new(1.0, 0.5, 0.2) * t);
+        }
+        // Fuera de las paradas, el color del extremo
+        assert_close(palette.sample(-1.0), Vector3::zero());
+        assert_close(palette.sample(2.0), Vector3::new(1.0, 0.5, 0.2));
+    }
+
+    #[test]
+    fn positioned_stops_interpolate_within_their_span() {
+        let palette: Palette = "1: 0, 0, 1\n# agua\n0.5: 0, 1, 0\n0: 1, 0, 0\n".parse().unwrap();
+        assert_close(palette.sample(0.25), Vector3::new(0.5, 0.5, 0.0));
+        assert_close(palette.sample(0.75), Vector3::new(0.0, 0.5, 0.5));
+    }
+
+    #[test]
+    fn rejects_invalid_palettes() {
+        assert!("".parse::<Palette>().is_err());
+        assert!("1, 0".parse::<Palette>().is_err());
+        assert!("1.5: 1, 0, 0".parse::<Palette>().is_err());
+        assert!("0: 1, 0, 0\n0, 1, 0".parse::<Palette>().is_err());
+        assert!("1, 1, 1\n".repeat(MAX_PALETTE_STOPS + 1).parse::<Palette>().is_err());
+    }
+}
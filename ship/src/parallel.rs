@@ -0,0 +1,88 @@
+// parallel.rs
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+
+// Hilos para el render; 0 = sin fijar (se toma de RENDER_THREADS o de los núcleos)
+static RENDER_THREADS: AtomicUsize = AtomicUsize::new(0);
+
+// Por debajo de esto por hilo, lanzar hilos cuesta más de lo que ahorra
+const DEFAULT_MIN_ITEMS_PER_THREAD: usize = 256;
+static MIN_ITEMS_PER_THREAD: AtomicUsize = AtomicUsize::new(DEFAULT_MIN_ITEMS_PER_THREAD);
+
+// Reparto de los tests de profundidad de `Framebuffer::points` entre hilos.
+// Apagado por defecto: todavía no hay una medición en varios núcleos que
+// demuestre que gana al bucle secuencial
+static PARALLEL_POINTS: AtomicBool = AtomicBool::new(false);
+
+/// Fixes how many threads `parallel_map` uses from now on (at least 1)
+pub fn set_render_threads(threads: usize) {
+    RENDER_THREADS.store(threads.max(1), Ordering::Relaxed);
+}
+
+/// Fixes how many work items each extra thread needs before `parallel_map`
+/// spawns it (at least 1). Raising it keeps small meshes on the calling
+/// thread; `usize::MAX` makes everything serial
+pub fn set_min_items_per_thread(items: usize) {
+    MIN_ITEMS_PER_THREAD.store(items.max(1), Ordering::Relaxed);
+}
+
+/// Lets `Framebuffer::points` split its depth tests across render threads
+pub fn set_parallel_points(enabled: bool) {
+    PARALLEL_POINTS.store(enabled, Ordering::Relaxed);
+}
+
+pub fn parallel_points() -> bool {
+    PARALLEL_POINTS.load(Ordering::Relaxed)
+}
+
+/// Threads used for rendering: whatever `set_render_threads` set, else the
+/// `RENDER_THREADS` environment variable, else every core
+pub fn render_threads() -> usize {
+    match RENDER_THREADS.load(Ordering::Relaxed) {
+        0 => std::env::var("RENDER_THREADS")
+            .ok()
+            .and_then(|value| value.parse::<usize>().ok())
+            .filter(|&threads| threads > 0)
+            .unwrap_or_else(|| thread::available_parallelism().map_or(1, |cores| cores.get())),
+        threads => threads,
+    }
+}
+
+/// Threads worth using for `items` work items: `render_threads()`, but no
+/// more than leave each at least the `set_min_items_per_thread` minimum
+/// (256 by default), and at least 1
+pub fn threads_for(items: usize) -> usize {
+    render_threads().min(items / MIN_ITEMS_PER_THREAD.load(Ordering::Relaxed)).max(1)
+}
+
+/// `items.iter().map(f).collect()` split across `render_threads()` scoped
+/// threads. Each thread maps one contiguous chunk and the chunks are joined
+/// back in order, so the result is the same for any thread count.
+pub fn parallel_map<T, R, F>(items: &[T], f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    let threads = threads_for(items.len());
+    if threads == 1 {
+        return items.iter().map(&f).collect();
+    }
+
+    let chunk_size = items.len().div_ceil(threads);
+    let f = &f;
+    thread::scope(|scope| {
+        let handles: Vec<_> = items
+            .chunks(chunk_size)
+            .map(|chunk| scope.spawn(move || chunk.iter().map(f).collect::<Vec<R>>()))
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("render thread panicked"))
+            .collect()
+    })
+}
+
+// Las pruebas que cambian los hilos de render no pueden correr a la vez
+#[cfg(test)]
+pub static TEST_THREADS: std::sync::Mutex<()> = std::sync::Mutex::new(());
@@ -0,0 +1,129 @@
+// png.rs
+
+// Bloque "stored" de deflate: como máximo 65535 bytes sin comprimir
+const STORED_BLOCK_SIZE: usize = 65535;
+
+/// Encodes tightly packed RGBA8 pixels, row by row, as a PNG. The image data
+/// goes in uncompressed deflate blocks: the files are larger than a real
+/// encoder's, but it needs no dependencies and the same pixels always give
+/// the same bytes, which keeps regression comparisons simple.
+pub fn encode_rgba(width: u32, height: u32, rgba: &[u8]) -> Vec<u8> {
+    assert_eq!(rgba.len(), width as usize * height as usize * 4, "rgba no coincide con el tamaño");
+
+    // Cada fila empieza con su tipo de filtro (0: ninguno)
+    let row_size = width as usize * 4;
+    let mut raw = Vec::with_capacity((row_size + 1) * height as usize);
+    for y in 0..height as usize {
+        raw.push(0);
+        raw.extend_from_slice(&rgba[y * row_size..(y + 1) * row_size]);
+    }
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    header.extend_from_slice(&[8, 6, 0, 0, 0]); // 8 bits, RGBA, deflate, filtro estándar, sin entrelazado
+
+    let mut png = vec![0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+    write_chunk(&mut png, b"IHDR", &header);
+    write_chunk(&mut png, b"IDAT", &zlib_stored(&raw));
+    write_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+// Flujo zlib con bloques deflate sin compresión
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let blocks = data.len().div_ceil(STORED_BLOCK_SIZE).max(1);
+    let mut out = Vec::with_capacity(data.len() + blocks * 5 + 6);
+    out.extend_from_slice(&[0x78, 0x01]);
+
+    for index in 0..blocks {
+        let block = &data[index * STORED_BLOCK_SIZE..data.len().min((index + 1) * STORED_BLOCK_SIZE)];
+        let last = index + 1 == blocks;
+        let length = block.len() as u16;
+        out.push(last as u8);
+        out.extend_from_slice(&length.to_le_bytes());
+        out.extend_from_slice(&(!length).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in bytes {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksums_match_the_reference_values() {
+        assert_eq!(crc32(b"IEND"), 0xae42_6082);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+    }
+
+    #[test]
+    fn stored_blocks_hold_every_row_with_its_filter_byte() {
+        // Más de 65535 bytes: necesita dos bloques
+        let (width, height) = (200u32, 100u32);
+        let rgba: Vec<u8> = (0..width * height * 4).map(|i| (i * 7 % 251) as u8).collect();
+        let png = encode_rgba(width, height, &rgba);
+
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(&png[16..24], [0, 0, 0, 200, 0, 0, 0, 100]);
+        assert_eq!(&png[png.len() - 12..png.len() - 4], [0, 0, 0, 0, b'I', b'E', b'N', b'D']);
+
+        // Deshacer los bloques del IDAT
+        let idat_length = u32::from_be_bytes(png[33..37].try_into().unwrap()) as usize;
+        assert_eq!(&png[37..41], b"IDAT");
+        let zlib = &png[41..41 + idat_length];
+        let (mut raw, mut at, mut blocks) = (Vec::new(), 2, 0);
+        loop {
+            let last = zlib[at] == 1;
+            let length = u16::from_le_bytes([zlib[at + 1], zlib[at + 2]]) as usize;
+            raw.extend_from_slice(&zlib[at + 5..at + 5 + length]);
+            at += 5 + length;
+            blocks += 1;
+            if last {
+                break;
+            }
+        }
+        assert_eq!(blocks, 2);
+        assert_eq!(u32::from_be_bytes(zlib[at..at + 4].try_into().unwrap()), adler32(&raw));
+
+        let row_size = width as usize * 4;
+        for (y, row) in raw.chunks(row_size + 1).enumerate() {
+            assert_eq!(row[0], 0);
+            assert_eq!(&row[1..], &rgba[y * row_size..(y + 1) * row_size]);
+        }
+    }
+}
+
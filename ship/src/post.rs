@@ -0,0 +1,359 @@
+// post.rs
+use crate::framebuffer::Framebuffer;
+use raylib::prelude::*;
+
+/// Screen-space effects applied to the finished frame
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PostEffects {
+    pub bloom_enabled: bool,
+    pub bloom_threshold: f32, // Luminancia a partir de la cual un píxel brilla
+    pub bloom_intensity: f32, // Cuánto del brillo difuminado se suma a la imagen
+    pub god_rays_enabled: bool,
+    pub god_rays_density: f32, // Fracción del camino hacia el sol que recorren las muestras
+    pub god_rays_decay: f32,   // Atenuación de cada muestra respecto a la anterior
+    pub god_rays_weight: f32,  // Peso de cada muestra (controla el brillo de los rayos)
+    pub dof_enabled: bool,
+    pub dof_focus_distance: f32, // Distancia a la cámara que queda nítida
+    pub dof_aperture: f32,       // Cuánto crece el desenfoque al alejarse del foco
+}
+
+pub const BLOOM_THRESHOLD_RANGE: (f32, f32) = (0.0, 1.0);
+pub const BLOOM_INTENSITY_RANGE: (f32, f32) = (0.0, 4.0);
+
+// El brillo se difumina a 1/4 de resolución: más barato y con un halo más ancho
+const BLOOM_DOWNSAMPLE: i32 = 4;
+const BLOOM_BLUR_RADIUS: i32 = 4;
+
+// God rays: también a 1/4 de resolución, con 64 muestras por píxel hacia el sol
+const GOD_RAYS_DOWNSAMPLE: i32 = 4;
+const GOD_RAYS_SAMPLES: usize = 64;
+
+// Profundidad de campo: radio máximo del círculo de confusión (píxeles) y
+// muestras del disco de desenfoque (espiral de ángulo áureo)
+const DOF_MAX_RADIUS: f32 = 8.0;
+const DOF_SAMPLES: usize = 32;
+
+impl Default for PostEffects {
+    fn default() -> Self {
+        PostEffects {
+            bloom_enabled: false,
+            bloom_threshold: 0.7,
+            bloom_intensity: 0.8,
+            god_rays_enabled: false,
+            god_rays_density: 0.9,
+            god_rays_decay: 0.97,
+            god_rays_weight: 0.05,
+            dof_enabled: false,
+            dof_focus_distance: 10.0,
+            dof_aperture: 1.0,
+        }
+    }
+}
+
+impl PostEffects {
+    pub fn adjust_bloom_threshold(&mut self, delta: f32) {
+        let (min, max) = BLOOM_THRESHOLD_RANGE;
+        self.bloom_threshold = (self.bloom_threshold + delta).clamp(min, max);
+    }
+
+    pub fn adjust_bloom_intensity(&mut self, delta: f32) {
+        let (min, max) = BLOOM_INTENSITY_RANGE;
+        self.bloom_intensity = (self.bloom_intensity + delta).clamp(min, max);
+    }
+
+    /// Kept between the clip planes: nothing nearer or farther is drawn
+    pub fn adjust_dof_focus(&mut self, delta: f32, near: f32, far: f32) {
+        self.dof_focus_distance = (self.dof_focus_distance + delta).clamp(near, far);
+    }
+
+    /// `sun_screen` is the sun's screen position (z = depth of its visible
+    /// face) and its radius in pixels, or None when it's behind the camera.
+    /// `near` and `far` are the clip planes the depth buffer was drawn with
+    pub fn apply(&self, framebuffer: &mut Framebuffer, sun_screen: Option<(Vector3, f32)>, near: f32, far: f32) {
+        if self.dof_enabled && self.dof_aperture > 0.0 {
+            apply_dof(framebuffer, self.dof_focus_distance, self.dof_aperture, near, far);
+        }
+        if let Some((sun_position, sun_radius)) = sun_screen
+            && self.god_rays_enabled
+        {
+            apply_god_rays(framebuffer, sun_position, sun_radius, self.god_rays_density,
+                           self.god_rays_decay, self.god_rays_weight);
+        }
+        if self.bloom_enabled && self.bloom_intensity > 0.0 {
+            self.apply_bloom(framebuffer);
+        }
+    }
+
+    fn apply_bloom(&self, framebuffer: &mut Framebuffer) {
+        let (width, height) = (framebuffer.width, framebuffer.height);
+        let small_width = (width + BLOOM_DOWNSAMPLE - 1) / BLOOM_DOWNSAMPLE;
+        let small_height = (height + BLOOM_DOWNSAMPLE - 1) / BLOOM_DOWNSAMPLE;
+        let pixels = framebuffer.to_vector3_buffer();
+
+        // Extracción: promedio por bloque de lo que supera el umbral
+        let mut bright = vec![Vector3::zero(); (small_width * small_height) as usize];
+        for y in 0..height {
+            for x in 0..width {
+                let color = pixels[(y * width + x) as usize];
+                let luminance = 0.2126 * color.x + 0.7152 * color.y + 0.0722 * color.z;
+                if luminance > self.bloom_threshold {
+                    // Conserva el tono, escalado por cuánto pasa del umbral
+                    let excess = (luminance - self.bloom_threshold) / luminance;
+                    let index = ((y / BLOOM_DOWNSAMPLE) * small_width + x / BLOOM_DOWNSAMPLE) as usize;
+                    bright[index] += color * excess;
+                }
+            }
+        }
+        let block_area = (BLOOM_DOWNSAMPLE * BLOOM_DOWNSAMPLE) as f32;
+        for value in bright.iter_mut() {
+            *value /= block_area;
+        }
+
+        let blurred = blur(&blur(&bright, small_width, small_height, (1, 0)), small_width, small_height, (0, 1));
+        add_upsampled(framebuffer, &pixels, &blurred, BLOOM_DOWNSAMPLE, self.bloom_intensity);
+    }
+}
+
+/// Distance from the camera for a depth-buffer value (NDC z in [-1, 1]) of a
+/// projection with clip planes `near` and `far`; background pixels
+/// (infinite depth) are at the far plane
+pub fn linear_depth(depth: f32, near: f32, far: f32) -> f32 {
+    if !depth.is_finite() {
+        return far;
+    }
+    2.0 * far * near / ((far + near) - depth.clamp(-1.0, 1.0) * (far - near))
+}
+
+/// Depth of field: every pixel gets a circle of confusion from how far its
+/// depth is from `focus_distance` (relative to its own distance, as with a
+/// thin lens) and is averaged over a disk of that radius. A neighbor only
+/// contributes if its own circle reaches the pixel, so sharp objects at the
+/// focus distance don't smear into the blurred background around them.
+pub fn apply_dof(framebuffer: &mut Framebuffer, focus_distance: f32, aperture: f32, near: f32, far: f32) {
+    let (width, height, viewport) = (framebuffer.width, framebuffer.height, framebuffer.viewport());
+    let pixels = framebuffer.to_vector3_buffer();
+
+    let mut coc = vec![0.0; pixels.len()];
+    for y in 0..height {
+        for x in 0..width {
+            let distance = linear_depth(framebuffer.depth_at(x, y), near, far);
+            let blur = aperture * (distance - focus_distance).abs() / distance;
+            coc[(y * width + x) as usize] = (blur * DOF_MAX_RADIUS).min(DOF_MAX_RADIUS);
+        }
+    }
+
+    // Disco de muestras con radio 1, repartidas uniformemente
+    let golden_angle = std::f32::consts::PI * (3.0 - 5.0_f32.sqrt());
+    let disk: Vec<Vector2> = (0..DOF_SAMPLES)
+        .map(|i| {
+            let radius = ((i as f32 + 0.5) / DOF_SAMPLES as f32).sqrt();
+            let (sin_a, cos_a) = (i as f32 * golden_angle).sin_cos();
+            Vector2::new(cos_a * radius, sin_a * radius)
+        })
+        .collect();
+
+    for y in 0..height {
+        for x in 0..width {
+            let index = (y * width + x) as usize;
+            let radius = coc[index];
+            // Menos de medio píxel: ya está enfocado (o fuera del viewport)
+            if radius < 0.5 || !viewport.contains(x, y) {
+                continue;
+            }
+
+            let mut sum = pixels[index];
+            let mut total = 1.0;
+            for offset in &disk {
+                let sx = (x as f32 + offset.x * radius).round() as i32;
+                let sy = (y as f32 + offset.y * radius).round() as i32;
+                if sx < 0 || sx >= width || sy < 0 || sy >= height {
+                    continue;
+                }
+                let sample = (sy * width + sx) as usize;
+                if coc[sample] >= offset.length() * radius {
+                    sum += pixels[sample];
+                    total += 1.0;
+                }
+            }
+
+            let color = sum / total;
+            framebuffer.color_buffer.draw_pixel(x, y, Color::new(
+                (color.x * 255.0).round() as u8,
+                (color.y * 255.0).round() as u8,
+                (color.z * 255.0).round() as u8,
+                255,
+            ));
+        }
+    }
+}
+
+/// Crepuscular rays as a screen-space radial blur (GPU Gems 3, ch. 13):
+/// every pixel marches toward the sun accumulating the light mask with an
+/// exponentially decaying weight. `sun_screen` is (x, y, depth of the sun's
+/// visible face). Only the pixels of the sun's disk that nothing covers
+/// emit, so a planet in front of the sun blocks it and the light streams
+/// around it.
+pub fn apply_god_rays(framebuffer: &mut Framebuffer, sun_screen: Vector3, sun_radius: f32,
+                      density: f32, decay: f32, weight: f32) {
+    let (width, height) = (framebuffer.width, framebuffer.height);
+    let small_width = (width + GOD_RAYS_DOWNSAMPLE - 1) / GOD_RAYS_DOWNSAMPLE;
+    let small_height = (height + GOD_RAYS_DOWNSAMPLE - 1) / GOD_RAYS_DOWNSAMPLE;
+    let pixels = framebuffer.to_vector3_buffer();
+
+    // Máscara de luz: el disco del sol allí donde nada queda por delante
+    let mut mask = vec![Vector3::zero(); (small_width * small_height) as usize];
+    let min_x = ((sun_screen.x - sun_radius).floor() as i32).max(0);
+    let max_x = ((sun_screen.x + sun_radius).ceil() as i32).min(width - 1);
+    let min_y = ((sun_screen.y - sun_radius).floor() as i32).max(0);
+    let max_y = ((sun_screen.y + sun_radius).ceil() as i32).min(height - 1);
+    for y in min_y..=max_y {
+        for x in min_x..=max_x {
+            let (dx, dy) = (x as f32 + 0.5 - sun_screen.x, y as f32 + 0.5 - sun_screen.y);
+            let depth = framebuffer.depth_at(x, y);
+            if dx * dx + dy * dy <= sun_radius * sun_radius && depth.is_finite() && depth >= sun_screen.z {
+                let index = ((y / GOD_RAYS_DOWNSAMPLE) * small_width + x / GOD_RAYS_DOWNSAMPLE) as usize;
+                mask[index] += pixels[(y * width + x) as usize];
+            }
+        }
+    }
+    let block_area = (GOD_RAYS_DOWNSAMPLE * GOD_RAYS_DOWNSAMPLE) as f32;
+    for value in mask.iter_mut() {
+        *value /= block_area;
+    }
+
+    // Marcha radial hacia el sol, en coordenadas de la rejilla reducida
+    let sun = Vector2::new(
+        (sun_screen.x + 0.5) / GOD_RAYS_DOWNSAMPLE as f32 - 0.5,
+        (sun_screen.y + 0.5) / GOD_RAYS_DOWNSAMPLE as f32 - 0.5,
+    );
+    let mut rays = vec![Vector3::zero(); mask.len()];
+    for y in 0..small_height {
+        for x in 0..small_width {
+            let start = Vector2::new(x as f32, y as f32);
+            let step = (start - sun) * (density / GOD_RAYS_SAMPLES as f32);
+            let mut position = start;
+            let mut illumination = weight;
+            let mut sum = Vector3::zero();
+
+            for _ in 0..GOD_RAYS_SAMPLES {
+                position -= step;
+                let (sx, sy) = (position.x.round() as i32, position.y.round() as i32);
+                if sx >= 0 && sx < small_width && sy >= 0 && sy < small_height {
+                    sum += mask[(sy * small_width + sx) as usize] * illumination;
+                }
+                illumination *= decay;
+            }
+            rays[(y * small_width + x) as usize] = sum;
+        }
+    }
+
+    add_upsampled(framebuffer, &pixels, &rays, GOD_RAYS_DOWNSAMPLE, 1.0);
+}
+
+// Suma aditiva de `glow` (a 1/downsample de resolución) sobre `pixels`,
+// reescalando con interpolación bilineal
+fn add_upsampled(framebuffer: &mut Framebuffer, pixels: &[Vector3], glow: &[Vector3], downsample: i32, intensity: f32) {
+    let (width, height, viewport) = (framebuffer.width, framebuffer.height, framebuffer.viewport());
+    let small_width = (width + downsample - 1) / downsample;
+    let small_height = (height + downsample - 1) / downsample;
+
+    for y in 0..height {
+        for x in 0..width {
+            let glow = sample_bilinear(
+                glow,
+                small_width,
+                small_height,
+                (x as f32 + 0.5) / downsample as f32 - 0.5,
+                (y as f32 + 0.5) / downsample as f32 - 0.5,
+            );
+            if glow.x + glow.y + glow.z <= 0.0 || !viewport.contains(x, y) {
+                continue;
+            }
+            let color = pixels[(y * width + x) as usize] + glow * intensity;
+            framebuffer.color_buffer.draw_pixel(x, y, Color::new(
+                (color.x.min(1.0) * 255.0) as u8,
+                (color.y.min(1.0) * 255.0) as u8,
+                (color.z.min(1.0) * 255.0) as u8,
+                255,
+            ));
+        }
+    }
+}
+
+// Una pasada de blur gaussiano separable en la dirección (dx, dy)
+fn blur(source: &[Vector3], width: i32, height: i32, (dx, dy): (i32, i32)) -> Vec<Vector3> {
+    let sigma = BLOOM_BLUR_RADIUS as f32 / 2.0;
+    let weights: Vec<f32> = (-BLOOM_BLUR_RADIUS..=BLOOM_BLUR_RADIUS)
+        .map(|offset| (-(offset * offset) as f32 / (2.0 * sigma * sigma)).exp())
+        .collect();
+    let total: f32 = weights.iter().sum();
+
+    let mut result = vec![Vector3::zero(); source.len()];
+    for y in 0..height {
+        for x in 0..width {
+            let mut sum = Vector3::zero();
+            for (offset, weight) in (-BLOOM_BLUR_RADIUS..=BLOOM_BLUR_RADIUS).zip(weights.iter()) {
+                let sx = (x + offset * dx).clamp(0, width - 1);
+                let sy = (y + offset * dy).clamp(0, height - 1);
+                sum += source[(sy * width + sx) as usize] * *weight;
+            }
+            result[(y * width + x) as usize] = sum / total;
+        }
+    }
+    result
+}
+
+fn sample_bilinear(source: &[Vector3], width: i32, height: i32, x: f32, y: f32) -> Vector3 {
+    let x = x.clamp(0.0, (width - 1) as f32);
+    let y = y.clamp(0.0, (height - 1) as f32);
+    let (x0, y0) = (x.floor() as i32, y.floor() as i32);
+    let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+    let (fx, fy) = (x - x0 as f32, y - y0 as f32);
+
+    let at = |sx: i32, sy: i32| source[(sy * width + sx) as usize];
+    let top = at(x0, y0) * (1.0 - fx) + at(x1, y0) * fx;
+    let bottom = at(x0, y1) * (1.0 - fx) + at(x1, y1) * fx;
+    top * (1.0 - fy) + bottom * fy
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Diferencia media entre píxeles vecinos de las columnas `columns`: alta en un damero nítido
+    fn contrast(framebuffer: &mut Framebuffer, columns: std::ops::Range<i32>) -> f32 {
+        let mut total = 0.0;
+        let mut count = 0;
+        for y in 8..56 {
+            for x in columns.clone() {
+                let a = framebuffer.get_pixel_color(x, y).unwrap();
+                let b = framebuffer.get_pixel_color(x + 1, y).unwrap();
+                total += (a.r as f32 - b.r as f32).abs();
+                count += 1;
+            }
+        }
+        total / count as f32
+    }
+
+    #[test]
+    fn depth_of_field_blurs_only_away_from_the_focus_plane() {
+        let (near, far) = (0.1, 100.0);
+        // Damero: la mitad izquierda en el plano de foco, la derecha al fondo
+        let (focused_depth, far_depth) = (0.9, 0.999);
+        let mut framebuffer = Framebuffer::new(64, 64);
+        for y in 0..64 {
+            for x in 0..64 {
+                let color = if (x + y) % 2 == 0 { Vector3::one() } else { Vector3::zero() };
+                framebuffer.point(x, y, color, if x < 32 { focused_depth } else { far_depth });
+            }
+        }
+        let before = (contrast(&mut framebuffer, 4..24), contrast(&mut framebuffer, 40..60));
+
+        apply_dof(&mut framebuffer, linear_depth(focused_depth, near, far), 0.5, near, far);
+        let after = (contrast(&mut framebuffer, 4..24), contrast(&mut framebuffer, 40..60));
+
+        assert_eq!(after.0, before.0);
+        assert!(after.1 < before.1 * 0.5, "{:?} -> {:?}", before, after);
+    }
+}
+
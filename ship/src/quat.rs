@@ -0,0 +1,197 @@
+// quat.rs
+#![allow(dead_code)]
+
+use raylib::math::Vector3;
+use std::ops::Mul;
+
+/// A rotation as a unit quaternion `w + xi + yj + zk`. Composing with `*`
+/// applies the right-hand side first, like matrices: `(a * b).rotate(v)`
+/// is `a.rotate(b.rotate(v))`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quaternion {
+    pub w: f32,
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl Quaternion {
+    pub const IDENTITY: Quaternion = Quaternion { w: 1.0, x: 0.0, y: 0.0, z: 0.0 };
+
+    /// Rotation of `angle` radians around `axis`, counterclockwise seen from
+    /// its tip (right-hand rule). A zero axis gives the identity
+    pub fn from_axis_angle(axis: Vector3, angle: f32) -> Self {
+        let length = axis.length();
+        if length < 1e-12 {
+            return Quaternion::IDENTITY;
+        }
+        let (sin_half, cos_half) = (angle * 0.5).sin_cos();
+        let axis = axis / length * sin_half;
+        Quaternion { w: cos_half, x: axis.x, y: axis.y, z: axis.z }
+    }
+
+    /// Rotation that takes the X, Y and Z axes to `right`, `up` and `back`,
+    /// which must be orthonormal and right-handed (Shepperd's method: the
+    /// largest diagonal term is the one divided by, for precision)
+    pub fn from_basis(right: Vector3, up: Vector3, back: Vector3) -> Self {
+        let trace = right.x + up.y + back.z;
+        let q = if trace > 0.0 {
+            let s = (trace + 1.0).sqrt() * 2.0;
+            Quaternion { w: 0.25 * s, x: (up.z - back.y) / s, y: (back.x - right.z) / s, z: (right.y - up.x) / s }
+        } else if right.x > up.y && right.x > back.z {
+            let s = (1.0 + right.x - up.y - back.z).sqrt() * 2.0;
+            Quaternion { w: (up.z - back.y) / s, x: 0.25 * s, y: (up.x + right.y) / s, z: (back.x + right.z) / s }
+        } else if up.y > back.z {
+            let s = (1.0 + up.y - right.x - back.z).sqrt() * 2.0;
+            Quaternion { w: (back.x - right.z) / s, x: (up.x + right.y) / s, y: 0.25 * s, z: (back.y + up.z) / s }
+        } else {
+            let s = (1.0 + back.z - right.x - up.y).sqrt() * 2.0;
+            Quaternion { w: (right.y - up.x) / s, x: (back.x + right.z) / s, y: (back.y + up.z) / s, z: 0.25 * s }
+        };
+        q.normalized()
+    }
+
+    /// Rotation that points -Z along `forward` with +Y as close to `up` as
+    /// it can be, the way a camera looks down -Z. An `up` parallel to
+    /// `forward` (or zero) is replaced by the world axis most perpendicular to it
+    pub fn look_rotation(forward: Vector3, up: Vector3) -> Self {
+        let forward = if forward.length() > 1e-12 { forward.normalized() } else { Vector3::new(0.0, 0.0, -1.0) };
+        let up = if forward.cross(up).length() > 1e-6 * up.length() {
+            up
+        } else if forward.x.abs() <= forward.y.abs() && forward.x.abs() <= forward.z.abs() {
+            Vector3::new(1.0, 0.0, 0.0)
+        } else if forward.y.abs() <= forward.z.abs() {
+            Vector3::new(0.0, 1.0, 0.0)
+        } else {
+            Vector3::new(0.0, 0.0, 1.0)
+        };
+        let right = forward.cross(up).normalized();
+        let up = right.cross(forward);
+        Quaternion::from_basis(right, up, -forward)
+    }
+
+    /// `v` turned by this rotation
+    pub fn rotate(&self, v: Vector3) -> Vector3 {
+        // v + 2w (q × v) + 2 q × (q × v), con q la parte vectorial
+        let q = Vector3::new(self.x, self.y, self.z);
+        let t = q.cross(v) * 2.0;
+        v + t * self.w + q.cross(t)
+    }
+
+    pub fn dot(&self, other: Quaternion) -> f32 {
+        self.w * other.w + self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    /// Back to unit length, which composing many rotations slowly loses
+    pub fn normalized(self) -> Self {
+        let length = self.dot(self).sqrt();
+        if length < 1e-12 {
+            return Quaternion::IDENTITY;
+        }
+        Quaternion { w: self.w / length, x: self.x / length, y: self.y / length, z: self.z / length }
+    }
+
+    /// Spherical interpolation from `self` (t = 0) to `other` (t = 1) at
+    /// constant angular speed, along the shorter of the two arcs
+    pub fn slerp(self, other: Quaternion, t: f32) -> Self {
+        // q y -q son la misma rotación: se toma la que queda más cerca
+        let (other, cos_theta) = match self.dot(other) {
+            dot if dot < 0.0 => (Quaternion { w: -other.w, x: -other.x, y: -other.y, z: -other.z }, -dot),
+            dot => (other, dot),
+        };
+        // Casi iguales: el seno del ángulo se anula y basta la interpolación lineal
+        let (a, b) = if cos_theta > 0.9995 {
+            (1.0 - t, t)
+        } else {
+            let theta = cos_theta.min(1.0).acos();
+            let sin_theta = theta.sin();
+            (((1.0 - t) * theta).sin() / sin_theta, (t * theta).sin() / sin_theta)
+        };
+        Quaternion {
+            w: self.w * a + other.w * b,
+            x: self.x * a + other.x * b,
+            y: self.y * a + other.y * b,
+            z: self.z * a + other.z * b,
+        }
+        .normalized()
+    }
+}
+
+impl Mul for Quaternion {
+    type Output = Quaternion;
+
+    fn mul(self, rhs: Quaternion) -> Quaternion {
+        Quaternion {
+            w: self.w * rhs.w - self.x * rhs.x - self.y * rhs.y - self.z * rhs.z,
+            x: self.w * rhs.x + self.x * rhs.w + self.y * rhs.z - self.z * rhs.y,
+            y: self.w * rhs.y - self.x * rhs.z + self.y * rhs.w + self.z * rhs.x,
+            z: self.w * rhs.z + self.x * rhs.y - self.y * rhs.x + self.z * rhs.w,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::{FRAC_PI_2, PI};
+
+    fn close(a: Vector3, b: Vector3) -> bool {
+        (a - b).length() < 1e-5
+    }
+
+    #[test]
+    fn rotations_follow_the_right_hand_rule_and_compose_right_to_left() {
+        let (x, y, z) = (Vector3::new(1.0, 0.0, 0.0), Vector3::new(0.0, 1.0, 0.0), Vector3::new(0.0, 0.0, 1.0));
+        let about_z = Quaternion::from_axis_angle(z, FRAC_PI_2);
+        let about_x = Quaternion::from_axis_angle(x, FRAC_PI_2);
+        assert!(close(about_z.rotate(x), y));
+        assert!(close((about_x * about_z).rotate(x), about_x.rotate(about_z.rotate(x))));
+        assert!(close((about_x * about_z).rotate(x), z));
+        assert_eq!(Quaternion::from_axis_angle(Vector3::zero(), 1.0), Quaternion::IDENTITY);
+    }
+
+    #[test]
+    fn from_basis_recovers_the_rotation_in_every_branch() {
+        // Medias vueltas en cada eje: la traza es negativa y cada una cae en otra rama
+        let axes = [Vector3::new(1.0, 0.0, 0.0), Vector3::new(0.0, 1.0, 0.0), Vector3::new(0.0, 0.0, 1.0),
+                    Vector3::new(1.0, 2.0, 3.0)];
+        for axis in axes {
+            for angle in [0.4, PI] {
+                let q = Quaternion::from_axis_angle(axis, angle);
+                let basis = [Vector3::new(1.0, 0.0, 0.0), Vector3::new(0.0, 1.0, 0.0), Vector3::new(0.0, 0.0, 1.0)]
+                    .map(|v| q.rotate(v));
+                let rebuilt = Quaternion::from_basis(basis[0], basis[1], basis[2]);
+                // q y -q son la misma rotación
+                assert!((rebuilt.dot(q).abs() - 1.0).abs() < 1e-5, "{:?} {}: {:?} vs {:?}", axis, angle, rebuilt, q);
+            }
+        }
+    }
+
+    #[test]
+    fn look_rotation_survives_looking_straight_up() {
+        let up = Vector3::new(0.0, 1.0, 0.0);
+        let forward = Vector3::new(1.0, 0.0, -1.0).normalized();
+        let q = Quaternion::look_rotation(forward, up);
+        assert!(close(q.rotate(Vector3::new(0.0, 0.0, -1.0)), forward));
+        assert!(close(q.rotate(up), up));
+
+        // Mirando justo hacia arriba, con el mismo up: sigue siendo una rotación válida
+        let q = Quaternion::look_rotation(up, up);
+        assert!(close(q.rotate(Vector3::new(0.0, 0.0, -1.0)), up));
+        assert!((q.dot(q) - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn slerp_moves_at_constant_speed_along_the_short_arc() {
+        let axis = Vector3::new(0.0, 1.0, 0.0);
+        let start = Quaternion::from_axis_angle(axis, 0.2);
+        let end = Quaternion::from_axis_angle(axis, 1.4);
+        let middle = start.slerp(end, 0.25);
+        assert!((middle.dot(Quaternion::from_axis_angle(axis, 0.5)) - 1.0).abs() < 1e-5);
+
+        // -end es la misma rotación: el camino no da la vuelta larga
+        let flipped = Quaternion { w: -end.w, x: -end.x, y: -end.y, z: -end.z };
+        assert!(start.slerp(flipped, 0.25).dot(middle).abs() > 1.0 - 1e-5);
+    }
+}
+
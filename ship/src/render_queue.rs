@@ -0,0 +1,83 @@
+// render_queue.rs
+use crate::matrix::{multiply_matrix_vector4, Handedness};
+use raylib::prelude::*;
+
+/// One frame's draw commands, handed back in draw order: opaque ones front
+/// to back, so the nearest surfaces fill the depth buffer first and what
+/// they hide is rejected before shading, and transparent ones back to
+/// front, so each blends over what's already behind it. Commands at the
+/// same depth keep the order they were pushed in.
+pub struct RenderQueue<T> {
+    opaque: Vec<(f32, T)>,
+    transparent: Vec<(f32, T)>,
+}
+
+impl<T> Default for RenderQueue<T> {
+    fn default() -> Self {
+        RenderQueue::new()
+    }
+}
+
+impl<T> RenderQueue<T> {
+    pub fn new() -> Self {
+        RenderQueue { opaque: Vec::new(), transparent: Vec::new() }
+    }
+
+    /// `depth` is the view-space depth of the object's origin (`view_depth`)
+    pub fn push_opaque(&mut self, depth: f32, command: T) {
+        self.opaque.push((depth, command));
+    }
+
+    pub fn push_transparent(&mut self, depth: f32, command: T) {
+        self.transparent.push((depth, command));
+    }
+
+    /// The opaque pass and the transparent pass, each sorted
+    pub fn into_passes(mut self) -> (Vec<T>, Vec<T>) {
+        self.opaque.sort_by(|a, b| a.0.total_cmp(&b.0));
+        self.transparent.sort_by(|a, b| b.0.total_cmp(&a.0));
+        (
+            self.opaque.into_iter().map(|(_, command)| command).collect(),
+            self.transparent.into_iter().map(|(_, command)| command).collect(),
+        )
+    }
+}
+
+/// Distance of `point` in front of the camera along its view axis: the
+/// view-space z, signed so larger is farther for either handedness
+pub fn view_depth(point: Vector3, view_matrix: &Matrix, handedness: Handedness) -> f32 {
+    let view = multiply_matrix_vector4(view_matrix, &Vector4::new(point.x, point.y, point.z, 1.0));
+    view.z * handedness.view_forward_sign()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::Camera;
+
+    #[test]
+    fn opaque_front_to_back_and_transparent_back_to_front() {
+        let mut queue = RenderQueue::new();
+        for (depth, name) in [(5.0, "medio"), (1.0, "cerca"), (9.0, "lejos"), (5.0, "medio 2")] {
+            queue.push_opaque(depth, name);
+            queue.push_transparent(depth, name);
+        }
+        let (opaque, transparent) = queue.into_passes();
+        assert_eq!(opaque, ["cerca", "medio", "medio 2", "lejos"]);
+        // A la misma profundidad se mantiene el orden de llegada
+        assert_eq!(transparent, ["lejos", "medio", "medio 2", "cerca"]);
+    }
+
+    #[test]
+    fn view_depth_grows_away_from_the_camera_in_either_handedness() {
+        for handedness in [Handedness::RightHanded, Handedness::LeftHanded] {
+            let mut camera = Camera::new(Vector3::new(0.0, 0.0, 10.0), Vector3::zero(), Vector3::new(0.0, 1.0, 0.0));
+            camera.handedness = handedness;
+            let view = camera.get_view_matrix();
+            let depth = |z: f32| view_depth(Vector3::new(0.5, -0.5, z), &view, handedness);
+            assert!((depth(0.0) - 10.0).abs() < 1e-4, "{:?}: {}", handedness, depth(0.0));
+            assert!(depth(-5.0) > depth(0.0) && depth(5.0) < depth(0.0));
+        }
+    }
+}
+
@@ -0,0 +1,353 @@
+// scene_config.rs
+use crate::light::{Attenuation, SUN_COLOR, SUN_INTENSITY};
+use crate::palette::Palette;
+use crate::shaders::{ShaderParams, PALETTE_SLOTS};
+use raylib::prelude::*;
+use std::fs;
+use std::io;
+use std::str::FromStr;
+
+/// What the scene contains: the light (position, color, intensity and
+/// distance attenuation), the starting camera (eye, target and up) and the
+/// bodies.
+/// Loaded from a text file so the system can be edited without recompiling,
+/// and reloaded while the app runs.
+///
+/// Same `key = value` format as the render settings. Top-level keys describe
+/// the scene; a `[sun]` line starts the sun's keys and every `[planet]` line
+/// starts a new planet. Unknown keys are ignored and missing keys keep their
+/// defaults.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SceneConfig {
+    pub light_position: Vector3,
+    pub light_color: Vector3,
+    pub light_intensity: f32,
+    pub light_attenuation: Attenuation,
+    pub camera_eye: Vector3,
+    pub camera_target: Vector3,
+    pub camera_up: Vector3,
+    pub sun: BodyConfig,
+    pub planets: Vec<BodyConfig>,
+}
+
+/// One body: its shader, how it orbits and how it spins. Angles in radians.
+/// `palette_N = r, g, b`, `noise_scale`, `surface_rotation`,
+/// `parallax_scale` and `seed` tweak the shader for this body only, and
+/// `palette_file = path` replaces its gradient with a `Palette`. `cache_surface`
+/// keeps its shaded surface between frames while nothing changes (see
+/// `SurfaceCache`), `moon = true` gives a planet a cratered moon and
+/// `normal_map = path` tilts its shading normals with a tangent-space map
+#[derive(Clone, Debug, PartialEq)]
+pub struct BodyConfig {
+    pub name: String,
+    pub planet_type: i32,
+    pub orbit_radius: f32,
+    pub orbit_speed: f32,
+    pub rotation_speed: f32,
+    pub scale: f32,
+    pub eccentricity: f32,
+    pub inclination: f32,
+    pub ascending_node: f32,
+    pub periapsis: f32,
+    pub shader_params: ShaderParams,
+    pub cache_surface: bool,
+    pub moon: bool,
+    pub normal_map: Option<String>, // Ruta de la imagen; se carga al crear el cuerpo
+}
+
+impl BodyConfig {
+    fn new(name: &str, planet_type: i32, orbit_radius: f32, orbit_speed: f32,
+           rotation_speed: f32, scale: f32) -> Self {
+        BodyConfig {
+            name: name.to_string(),
+            planet_type,
+            orbit_radius,
+            orbit_speed,
+            rotation_speed,
+            scale,
+            eccentricity: 0.0,
+            inclination: 0.0,
+            ascending_node: 0.0,
+            periapsis: 0.0,
+            shader_params: ShaderParams::default(),
+            cache_surface: false,
+            moon: false,
+            normal_map: None,
+        }
+    }
+
+    fn with_orbit_shape(mut self, eccentricity: f32, inclination: f32,
+                        ascending_node: f32, periapsis: f32) -> Self {
+        self.eccentricity = eccentricity;
+        self.inclination = inclination;
+        self.ascending_node = ascending_node;
+        self.periapsis = periapsis;
+        self
+    }
+
+    fn with_moon(mut self) -> Self {
+        self.moon = true;
+        self
+    }
+}
+
+impl Default for SceneConfig {
+    fn default() -> Self {
+        SceneConfig {
+            light_position: Vector3::new(0.0, 0.0, 0.0),
+            light_color: SUN_COLOR,
+            light_intensity: SUN_INTENSITY,
+            light_attenuation: Attenuation::NONE,
+            camera_eye: Vector3::new(0.0, 15.0, 25.0),
+            camera_target: Vector3::new(0.0, 0.0, 0.0),
+            camera_up: Vector3::new(0.0, 1.0, 0.0),
+            sun: BodyConfig::new("Sol", 5, 0.0, 0.0, 0.1, 2.5),
+            planets: vec![
+                BodyConfig::new("Mercurio", 0, 5.0, 0.8, 2.0, 0.6)    // Rocky
+                    .with_orbit_shape(0.21, 0.12, 0.84, 0.51),
+                BodyConfig::new("Venus", 1, 8.0, 0.6, 1.5, 0.9)       // Gaseous
+                    .with_orbit_shape(0.01, 0.06, 1.34, 0.96),
+                BodyConfig::new("Tierra", 2, 12.0, 0.5, 1.8, 1.0)     // Custom
+                    .with_orbit_shape(0.02, 0.0, 0.0, 1.99)
+                    .with_moon(),
+                BodyConfig::new("Saturno", 3, 18.0, 0.3, 1.2, 1.3)    // Con anillos
+                    .with_orbit_shape(0.06, 0.04, 1.98, 5.92),
+                BodyConfig::new("Neptuno", 4, 24.0, 0.2, 0.9, 1.1)    // Extra planet
+                    .with_orbit_shape(0.01, 0.03, 2.30, 4.82),
+            ],
+        }
+    }
+}
+
+impl SceneConfig {
+    pub fn load(path: &str) -> io::Result<Self> {
+        fs::read_to_string(path)?
+            .parse()
+            .map_err(|err: String| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+}
+
+// Tipos de shader de los cuerpos: 0 rocoso, 1 gaseoso, 2 océano, 3 volcánico, 4 cristal, 5 sol
+const BODY_TYPES: std::ops::RangeInclusive<i32> = 0..=5;
+
+/// `x, y, z`
+pub fn parse_vector3(value: &str) -> Option<Vector3> {
+    let parts: Vec<f32> = value
+        .split(',')
+        .map(|part| part.trim().parse())
+        .collect::<Result<_, _>>()
+        .ok()?;
+    match parts.as_slice() {
+        [x, y, z] => Some(Vector3::new(*x, *y, *z)),
+        _ => None,
+    }
+}
+
+/// `constant, linear, quadratic` coefficients of a light's `Attenuation`:
+/// none negative and not all zero, so it never divides by zero or grows
+/// with distance
+pub fn parse_attenuation(value: &str) -> Option<Attenuation> {
+    let coefficients = parse_vector3(value)?;
+    let valid = coefficients.x >= 0.0 && coefficients.y >= 0.0 && coefficients.z >= 0.0
+        && coefficients.x + coefficients.y + coefficients.z > 0.0;
+    valid.then(|| Attenuation::new(coefficients.x, coefficients.y, coefficients.z))
+}
+
+/// Applies one per-body shader key (`noise_scale`, `surface_rotation`,
+/// `parallax_scale`, `seed`, `palette_N`, `palette_file`, or the material's
+/// `diffuse`, `specular_strength`, `shininess`, `ambient`, `rim_color` and
+/// `rim_power`) to `params`.
+/// Returns false if `key` isn't one of them
+pub fn set_shader_param(params: &mut ShaderParams, key: &str, value: &str) -> Result<bool, String> {
+    let invalid = || format!("invalid value for `{}`: {}", key, value);
+
+    match key {
+        "noise_scale" => params.noise_scale = value.parse().map_err(|_| invalid())?,
+        "parallax_scale" => params.parallax_scale = value.parse().map_err(|_| invalid())?,
+        "seed" => params.seed = value.parse().map_err(|_| invalid())?,
+        "surface_rotation" => params.surface_rotation = Some(value.parse().map_err(|_| invalid())?),
+        "diffuse" => params.diffuse = Some(parse_vector3(value).ok_or_else(invalid)?),
+        "specular_strength" => params.specular_strength = Some(value.parse().map_err(|_| invalid())?),
+        "shininess" => match value.parse() {
+            Ok(shininess) if shininess > 0.0 => params.shininess = Some(shininess),
+            _ => return Err(invalid()),
+        },
+        "ambient" => params.ambient = Some(value.parse().map_err(|_| invalid())?),
+        "rim_color" => params.rim_color = Some(parse_vector3(value).ok_or_else(invalid)?),
+        "rim_power" => match value.parse() {
+            Ok(power) if power >= 0.0 => params.rim_power = Some(power),
+            _ => return Err(invalid()),
+        },
+        "palette_file" => {
+            let palette = Palette::load(value).map_err(|err| format!("palette `{}`: {}", value, err))?;
+            params.gradient = Some(palette);
+        }
+        _ => {
+            // palette_0 .. palette_7
+            let Some(slot) = key.strip_prefix("palette_") else {
+                return Ok(false);
+            };
+            let slot: usize = slot.parse().map_err(|_| invalid())?;
+            if slot >= PALETTE_SLOTS {
+                return Err(invalid());
+            }
+            params.palette[slot] = Some(parse_vector3(value).ok_or_else(invalid)?);
+        }
+    }
+    Ok(true)
+}
+
+impl FromStr for SceneConfig {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let defaults = SceneConfig::default();
+        let mut config = SceneConfig { planets: Vec::new(), ..defaults.clone() };
+        let mut has_planets = false;
+
+        // Sección actual: None = nivel de escena, Some(None) = sol, Some(Some(i)) = planeta i
+        let mut section: Option<Option<usize>> = None;
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            match line {
+                "[sun]" => {
+                    section = Some(None);
+                    continue;
+                }
+                "[planet]" => {
+                    has_planets = true;
+                    let index = config.planets.len();
+                    config.planets.push(BodyConfig::new(&format!("Planeta {}", index + 1), 0, 0.0, 0.0, 0.0, 1.0));
+                    section = Some(Some(index));
+                    continue;
+                }
+                _ if line.starts_with('[') => {
+                    return Err(format!("line {}: unknown section `{}`", number + 1, line));
+                }
+                _ => {}
+            }
+
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("line {}: expected `key = value`", number + 1))?;
+            let (key, value) = (key.trim(), value.trim());
+            let invalid = || format!("line {}: invalid value for `{}`: {}", number + 1, key, value);
+
+            let body = match section {
+                None => {
+                    match key {
+                        "light_position" => config.light_position = parse_vector3(value).ok_or_else(invalid)?,
+                        "light_color" => config.light_color = parse_vector3(value).ok_or_else(invalid)?,
+                        "light_intensity" => match value.parse::<f32>() {
+                            Ok(intensity) if intensity >= 0.0 => config.light_intensity = intensity,
+                            _ => return Err(invalid()),
+                        },
+                        "light_attenuation" => config.light_attenuation = parse_attenuation(value).ok_or_else(invalid)?,
+                        "camera_eye" => config.camera_eye = parse_vector3(value).ok_or_else(invalid)?,
+                        "camera_target" => config.camera_target = parse_vector3(value).ok_or_else(invalid)?,
+                        "camera_up" => match parse_vector3(value) {
+                            Some(up) if up.length() > 0.0 => config.camera_up = up,
+                            _ => return Err(invalid()),
+                        },
+                        _ => {} // Claves desconocidas: se ignoran
+                    }
+                    continue;
+                }
+                Some(None) => &mut config.sun,
+                Some(Some(index)) => &mut config.planets[index],
+            };
+
+            match key {
+                "name" => body.name = value.to_string(),
+                "planet_type" => {
+                    let planet_type: i32 = value.parse().map_err(|_| invalid())?;
+                    // Un tipo sin shader se vería gris: mejor avisar al cargar
+                    if !BODY_TYPES.contains(&planet_type) {
+                        return Err(format!(
+                            "line {}: unknown planet_type {} (0 rocky, 1 gaseous, 2 ocean, 3 volcanic, 4 crystal, 5 sun)",
+                            number + 1, planet_type,
+                        ));
+                    }
+                    body.planet_type = planet_type;
+                }
+                "orbit_radius" => body.orbit_radius = value.parse().map_err(|_| invalid())?,
+                "orbit_speed" => body.orbit_speed = value.parse().map_err(|_| invalid())?,
+                "rotation_speed" => body.rotation_speed = value.parse().map_err(|_| invalid())?,
+                "scale" => body.scale = value.parse().map_err(|_| invalid())?,
+                "eccentricity" => {
+                    let eccentricity: f32 = value.parse().map_err(|_| invalid())?;
+                    // La ecuación de Kepler solo está resuelta para órbitas cerradas
+                    if !(0.0..1.0).contains(&eccentricity) {
+                        return Err(invalid());
+                    }
+                    body.eccentricity = eccentricity;
+                }
+                "inclination" => body.inclination = value.parse().map_err(|_| invalid())?,
+                "ascending_node" => body.ascending_node = value.parse().map_err(|_| invalid())?,
+                "periapsis" => body.periapsis = value.parse().map_err(|_| invalid())?,
+                "cache_surface" => body.cache_surface = value.parse().map_err(|_| invalid())?,
+                "moon" => body.moon = value.parse().map_err(|_| invalid())?,
+                "normal_map" => body.normal_map = Some(value.to_string()),
+                // Claves del shader; el resto de claves desconocidas se ignoran
+                _ => {
+                    set_shader_param(&mut body.shader_params, key, value)
+                        .map_err(|err| format!("line {}: {}", number + 1, err))?;
+                }
+            }
+        }
+
+        // Sin ningún [planet], se mantienen los planetas por defecto
+        if !has_planets {
+            config.planets = defaults.planets;
+        }
+
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_the_scene_and_its_bodies() {
+        let text = "# Sistema de prueba\n\
+                    camera_eye = 0, 5, 10\n\
+                    light_attenuation = 1, 0, 0.01\n\
+                    [sun]\nscale = 3\n\
+                    [planet]\nname = Roca\norbit_radius = 6\neccentricity = 0.3\nnoise_scale = 2\n";
+        let config: SceneConfig = text.parse().unwrap();
+        assert_eq!(config.camera_eye, Vector3::new(0.0, 5.0, 10.0));
+        assert_eq!(config.light_attenuation, Attenuation::new(1.0, 0.0, 0.01));
+        assert_eq!(config.sun.scale, 3.0);
+        assert_eq!(config.planets.len(), 1);
+        let planet = &config.planets[0];
+        assert_eq!((planet.name.as_str(), planet.orbit_radius, planet.eccentricity), ("Roca", 6.0, 0.3));
+        assert_eq!(planet.shader_params.noise_scale, 2.0);
+        // Lo que no aparece queda como en la escena por defecto
+        assert_eq!(config.camera_target, SceneConfig::default().camera_target);
+    }
+
+    #[test]
+    fn rejects_invalid_lines_with_their_number() {
+        for (text, line) in [
+            ("[planet]\neccentricity = 1.2\n", "line 2"),
+            ("[planet]\nplanet_type = 9\n", "line 2"),
+            ("camera_up = 0, 0, 0\n", "line 1"),
+            ("\n[moon]\n", "line 2"),
+            ("light_attenuation = 0, 0, 0\n", "line 1"),
+        ] {
+            let err = text.parse::<SceneConfig>().unwrap_err();
+            assert!(err.starts_with(line), "{:?}: {}", text, err);
+        }
+        // Sin [planet] se mantienen los planetas por defecto
+        let config: SceneConfig = "light_intensity = 2\n".parse().unwrap();
+        assert_eq!(config.planets, SceneConfig::default().planets);
+    }
+}
+
@@ -0,0 +1,369 @@
+// settings.rs
+use crate::matrix::Handedness;
+use crate::light::{Attenuation, SUN_COLOR, SUN_INTENSITY};
+use crate::post::PostEffects;
+use crate::scene_config::{parse_attenuation, parse_vector3};
+use crate::shaders::{ColorClampMode, LAYER_ALL};
+use crate::obj::Winding;
+use crate::triangle::{CullMode, RasterPrecision};
+use crate::wireframe::{FillMode, WireframeStyle};
+use crate::{FAR_PLANE, NEAR_PLANE};
+use raylib::prelude::*;
+use std::f32::consts::PI;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::str::FromStr;
+
+// Límites del campo de visión vertical (radianes): más cerrado es un
+// telescopio y más abierto deforma demasiado los bordes
+pub const FOV_RANGE: (f32, f32) = (10.0 * PI / 180.0, 120.0 * PI / 180.0);
+
+/// Every tunable render parameter in one place, so a tuned look can be
+/// saved to disk and restored later.
+///
+/// The file format is one `key = value` per line. Unknown keys are ignored
+/// and missing keys keep their defaults, so older files keep loading as
+/// settings are added.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RenderSettings {
+    pub fov_y: f32,
+    pub near_plane: f32, // Planos de recorte de la proyección
+    pub far_plane: f32,
+    pub target_aspect: Option<f32>, // None: la imagen llena la ventana
+    pub handedness: Handedness,
+    pub light_position: Vector3,
+    pub light_color: Vector3,
+    pub light_intensity: f32,
+    pub light_falloff: f32,
+    pub light_attenuation: Attenuation, // De la luz puntual con la distancia: 1 / (c + l·d + q·d²)
+    pub ambient_floor: f32,
+    pub fog_density: f32, // 0: sin niebla
+    pub fog_color: Vector3,
+    pub auto_exposure: bool,
+    pub target_luminance: f32,
+    pub adaptation_speed: f32,
+    pub show_orbits: bool,
+    pub show_minimap: bool,
+    pub rear_view: bool, // Retrovisor en la parte de arriba de la imagen
+    pub cloud_shell: bool,
+    pub ring_shadow: bool,
+    pub dithered_transparency: bool,
+    pub supersampling: u32, // Submuestras por píxel en la vista en vivo: 1, 4, 9 o 16
+    pub deterministic_draw: bool, // Fragmentos ordenados por profundidad antes de escribirlos
+    pub level_of_detail: bool, // Esferas con menos triángulos para los cuerpos chicos en pantalla
+    pub layer_mask: u32,
+    pub nave_wireframe: Option<WireframeStyle>,
+    pub body_fill: FillMode,
+    pub show_overdraw: bool,
+    pub raster_precision: RasterPrecision,
+    pub cull_mode: CullMode,
+    pub front_face: Winding, // Sentido de las caras frontales de los modelos
+    pub specular_aa: bool,
+    pub color_clamp: ColorClampMode,
+    pub gamma_correct: bool,
+    pub debug_barycentric: bool,
+    pub debug_facing: bool,
+    pub debug_vertex_colors: bool,
+    pub post: PostEffects,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        RenderSettings {
+            fov_y: PI / 3.0,
+            near_plane: NEAR_PLANE,
+            far_plane: FAR_PLANE,
+            target_aspect: None,
+            handedness: Handedness::default(),
+            light_position: Vector3::new(0.0, 0.0, 0.0),
+            light_color: SUN_COLOR,
+            light_intensity: SUN_INTENSITY,
+            light_falloff: 0.0,
+            light_attenuation: Attenuation::NONE,
+            ambient_floor: 0.0,
+            fog_density: 0.0,
+            fog_color: Vector3::new(5.0 / 255.0, 5.0 / 255.0, 15.0 / 255.0), // El fondo del campo de estrellas
+            auto_exposure: true,
+            target_luminance: 0.35,
+            adaptation_speed: 1.5,
+            show_orbits: true,
+            show_minimap: false,
+            rear_view: false,
+            cloud_shell: false,
+            ring_shadow: false,
+            dithered_transparency: false,
+            supersampling: 1,
+            deterministic_draw: false,
+            level_of_detail: true,
+            layer_mask: LAYER_ALL,
+            nave_wireframe: None,
+            body_fill: FillMode::default(),
+            show_overdraw: false,
+            raster_precision: RasterPrecision::default(),
+            cull_mode: CullMode::default(),
+            front_face: Winding::CounterClockwise,
+            specular_aa: true,
+            color_clamp: ColorClampMode::default(),
+            gamma_correct: false,
+            debug_barycentric: false,
+            debug_facing: false,
+            debug_vertex_colors: false,
+            post: PostEffects::default(),
+        }
+    }
+}
+
+impl RenderSettings {
+    /// Widens (`delta` > 0) or narrows the field of view, within `FOV_RANGE`
+    pub fn adjust_fov(&mut self, delta: f32) {
+        let (min, max) = FOV_RANGE;
+        self.fov_y = (self.fov_y + delta).clamp(min, max);
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+
+    pub fn load(path: &str) -> io::Result<Self> {
+        fs::read_to_string(path)?
+            .parse()
+            .map_err(|err: String| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+}
+
+impl fmt::Display for RenderSettings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let light = self.light_position;
+        writeln!(f, "fov_y = {}", self.fov_y)?;
+        writeln!(f, "near_plane = {}", self.near_plane)?;
+        writeln!(f, "far_plane = {}", self.far_plane)?;
+        match self.target_aspect {
+            Some(aspect) => writeln!(f, "target_aspect = {}", aspect)?,
+            None => writeln!(f, "target_aspect = None")?,
+        }
+        writeln!(f, "handedness = {:?}", self.handedness)?;
+        writeln!(f, "light_position = {}, {}, {}", light.x, light.y, light.z)?;
+        writeln!(f, "light_color = {}, {}, {}", self.light_color.x, self.light_color.y, self.light_color.z)?;
+        writeln!(f, "light_intensity = {}", self.light_intensity)?;
+        writeln!(f, "light_falloff = {}", self.light_falloff)?;
+        let attenuation = self.light_attenuation;
+        writeln!(f, "light_attenuation = {}, {}, {}", attenuation.constant, attenuation.linear, attenuation.quadratic)?;
+        writeln!(f, "ambient_floor = {}", self.ambient_floor)?;
+        writeln!(f, "fog_density = {}", self.fog_density)?;
+        writeln!(f, "fog_color = {}, {}, {}", self.fog_color.x, self.fog_color.y, self.fog_color.z)?;
+        writeln!(f, "auto_exposure = {}", self.auto_exposure)?;
+        writeln!(f, "target_luminance = {}", self.target_luminance)?;
+        writeln!(f, "adaptation_speed = {}", self.adaptation_speed)?;
+        writeln!(f, "show_orbits = {}", self.show_orbits)?;
+        writeln!(f, "show_minimap = {}", self.show_minimap)?;
+        writeln!(f, "rear_view = {}", self.rear_view)?;
+        writeln!(f, "cloud_shell = {}", self.cloud_shell)?;
+        writeln!(f, "ring_shadow = {}", self.ring_shadow)?;
+        writeln!(f, "dithered_transparency = {}", self.dithered_transparency)?;
+        writeln!(f, "supersampling = {}", self.supersampling)?;
+        writeln!(f, "deterministic_draw = {}", self.deterministic_draw)?;
+        writeln!(f, "level_of_detail = {}", self.level_of_detail)?;
+        writeln!(f, "layer_mask = {:#06b}", self.layer_mask)?;
+        match self.nave_wireframe {
+            Some(style) => writeln!(f, "nave_wireframe = {:?}", style)?,
+            None => writeln!(f, "nave_wireframe = None")?,
+        }
+        writeln!(f, "body_fill = {:?}", self.body_fill)?;
+        writeln!(f, "show_overdraw = {}", self.show_overdraw)?;
+        writeln!(f, "raster_precision = {:?}", self.raster_precision)?;
+        writeln!(f, "cull_mode = {:?}", self.cull_mode)?;
+        writeln!(f, "front_face = {:?}", self.front_face)?;
+        writeln!(f, "specular_aa = {}", self.specular_aa)?;
+        writeln!(f, "color_clamp = {:?}", self.color_clamp)?;
+        writeln!(f, "gamma_correct = {}", self.gamma_correct)?;
+        writeln!(f, "debug_barycentric = {}", self.debug_barycentric)?;
+        writeln!(f, "debug_facing = {}", self.debug_facing)?;
+        writeln!(f, "debug_vertex_colors = {}", self.debug_vertex_colors)?;
+        writeln!(f, "bloom_enabled = {}", self.post.bloom_enabled)?;
+        writeln!(f, "bloom_threshold = {}", self.post.bloom_threshold)?;
+        writeln!(f, "bloom_intensity = {}", self.post.bloom_intensity)?;
+        writeln!(f, "god_rays_enabled = {}", self.post.god_rays_enabled)?;
+        writeln!(f, "god_rays_density = {}", self.post.god_rays_density)?;
+        writeln!(f, "god_rays_decay = {}", self.post.god_rays_decay)?;
+        writeln!(f, "god_rays_weight = {}", self.post.god_rays_weight)?;
+        writeln!(f, "dof_enabled = {}", self.post.dof_enabled)?;
+        writeln!(f, "dof_focus_distance = {}", self.post.dof_focus_distance)?;
+        writeln!(f, "dof_aperture = {}", self.post.dof_aperture)
+    }
+}
+
+impl FromStr for RenderSettings {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut settings = RenderSettings::default();
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("line {}: expected `key = value`", number + 1))?;
+            let (key, value) = (key.trim(), value.trim());
+            let invalid = || format!("line {}: invalid value for `{}`: {}", number + 1, key, value);
+
+            match key {
+                "fov_y" => settings.fov_y = match value.parse::<f32>() {
+                    Ok(fov) if (FOV_RANGE.0..=FOV_RANGE.1).contains(&fov) => fov,
+                    _ => return Err(invalid()),
+                },
+                "near_plane" => settings.near_plane = match value.parse::<f32>() {
+                    Ok(near) if near > 0.0 => near,
+                    _ => return Err(invalid()),
+                },
+                "far_plane" => settings.far_plane = value.parse().map_err(|_| invalid())?,
+                "target_aspect" => settings.target_aspect = match value {
+                    "None" => None,
+                    _ => match value.parse::<f32>() {
+                        Ok(aspect) if aspect > 0.0 => Some(aspect),
+                        _ => return Err(invalid()),
+                    },
+                },
+                "handedness" => settings.handedness = match value {
+                    "RightHanded" => Handedness::RightHanded,
+                    "LeftHanded" => Handedness::LeftHanded,
+                    _ => return Err(invalid()),
+                },
+                "light_position" => settings.light_position = parse_vector3(value).ok_or_else(invalid)?,
+                "light_color" => settings.light_color = parse_vector3(value).ok_or_else(invalid)?,
+                "light_intensity" => settings.light_intensity = match value.parse::<f32>() {
+                    Ok(intensity) if intensity >= 0.0 => intensity,
+                    _ => return Err(invalid()),
+                },
+                "light_falloff" => settings.light_falloff = value.parse().map_err(|_| invalid())?,
+                "light_attenuation" => settings.light_attenuation = parse_attenuation(value).ok_or_else(invalid)?,
+                "ambient_floor" => settings.ambient_floor = value.parse().map_err(|_| invalid())?,
+                "fog_density" => settings.fog_density = match value.parse::<f32>() {
+                    Ok(density) if density >= 0.0 => density,
+                    _ => return Err(invalid()),
+                },
+                "fog_color" => settings.fog_color = parse_vector3(value).ok_or_else(invalid)?,
+                "auto_exposure" => settings.auto_exposure = value.parse().map_err(|_| invalid())?,
+                "target_luminance" => settings.target_luminance = value.parse().map_err(|_| invalid())?,
+                "adaptation_speed" => settings.adaptation_speed = value.parse().map_err(|_| invalid())?,
+                "show_orbits" => settings.show_orbits = value.parse().map_err(|_| invalid())?,
+                "show_minimap" => settings.show_minimap = value.parse().map_err(|_| invalid())?,
+                "rear_view" => settings.rear_view = value.parse().map_err(|_| invalid())?,
+                "cloud_shell" => settings.cloud_shell = value.parse().map_err(|_| invalid())?,
+                "ring_shadow" => settings.ring_shadow = value.parse().map_err(|_| invalid())?,
+                "dithered_transparency" => settings.dithered_transparency = value.parse().map_err(|_| invalid())?,
+                "supersampling" => settings.supersampling = match value.parse() {
+                    Ok(samples @ (1 | 4 | 9 | 16)) => samples,
+                    _ => return Err(invalid()),
+                },
+                "deterministic_draw" => settings.deterministic_draw = value.parse().map_err(|_| invalid())?,
+                "level_of_detail" => settings.level_of_detail = value.parse().map_err(|_| invalid())?,
+                "layer_mask" => {
+                    let digits = value.strip_prefix("0b").unwrap_or(value);
+                    settings.layer_mask = u32::from_str_radix(digits, 2).map_err(|_| invalid())?;
+                }
+                "nave_wireframe" => settings.nave_wireframe = match value {
+                    "None" => None,
+                    "DepthTested" => Some(WireframeStyle::DepthTested),
+                    "HiddenLine" => Some(WireframeStyle::HiddenLine),
+                    "AllVisible" => Some(WireframeStyle::AllVisible),
+                    _ => return Err(invalid()),
+                },
+                "body_fill" => settings.body_fill = match value {
+                    "Solid" => FillMode::Solid,
+                    "Wireframe" => FillMode::Wireframe,
+                    "WireframeOverSolid" => FillMode::WireframeOverSolid,
+                    _ => return Err(invalid()),
+                },
+                "show_overdraw" => settings.show_overdraw = value.parse().map_err(|_| invalid())?,
+                "raster_precision" => settings.raster_precision = match value {
+                    "Float" => RasterPrecision::Float,
+                    "Fixed" => RasterPrecision::Fixed,
+                    _ => return Err(invalid()),
+                },
+                "cull_mode" => settings.cull_mode = match value {
+                    "None" => CullMode::None,
+                    "Back" => CullMode::Back,
+                    "Front" => CullMode::Front,
+                    _ => return Err(invalid()),
+                },
+                "front_face" => settings.front_face = match value {
+                    "CounterClockwise" => Winding::CounterClockwise,
+                    "Clockwise" => Winding::Clockwise,
+                    _ => return Err(invalid()),
+                },
+                "specular_aa" => settings.specular_aa = value.parse().map_err(|_| invalid())?,
+                "color_clamp" => settings.color_clamp = match value {
+                    "HardClamp" => ColorClampMode::HardClamp,
+                    "PreserveHue" => ColorClampMode::PreserveHue,
+                    "ToneMap" => ColorClampMode::ToneMap,
+                    _ => return Err(invalid()),
+                },
+                "gamma_correct" => settings.gamma_correct = value.parse().map_err(|_| invalid())?,
+                "debug_barycentric" => settings.debug_barycentric = value.parse().map_err(|_| invalid())?,
+                "debug_facing" => settings.debug_facing = value.parse().map_err(|_| invalid())?,
+                "debug_vertex_colors" => settings.debug_vertex_colors = value.parse().map_err(|_| invalid())?,
+                "bloom_enabled" => settings.post.bloom_enabled = value.parse().map_err(|_| invalid())?,
+                "bloom_threshold" => settings.post.bloom_threshold = value.parse().map_err(|_| invalid())?,
+                "bloom_intensity" => settings.post.bloom_intensity = value.parse().map_err(|_| invalid())?,
+                "god_rays_enabled" => settings.post.god_rays_enabled = value.parse().map_err(|_| invalid())?,
+                "god_rays_density" => settings.post.god_rays_density = value.parse().map_err(|_| invalid())?,
+                "god_rays_decay" => settings.post.god_rays_decay = value.parse().map_err(|_| invalid())?,
+                "god_rays_weight" => settings.post.god_rays_weight = value.parse().map_err(|_| invalid())?,
+                "dof_enabled" => settings.post.dof_enabled = value.parse().map_err(|_| invalid())?,
+                "dof_focus_distance" => settings.post.dof_focus_distance = value.parse().map_err(|_| invalid())?,
+                "dof_aperture" => settings.post.dof_aperture = value.parse().map_err(|_| invalid())?,
+                _ => {} // Claves desconocidas: de versiones más nuevas, se ignoran
+            }
+        }
+
+        // Con los planos iguales o invertidos la proyección degenera
+        if settings.far_plane <= settings.near_plane {
+            return Err(format!(
+                "far_plane ({}) must be greater than near_plane ({})",
+                settings.far_plane, settings.near_plane,
+            ));
+        }
+
+        Ok(settings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_through_text() {
+        let default = RenderSettings::default();
+        assert_eq!(default.to_string().parse(), Ok(default.clone()));
+
+        let tuned = RenderSettings {
+            fov_y: 0.7,
+            far_plane: 500.0,
+            light_position: Vector3::new(1.5, -2.0, 3.25),
+            nave_wireframe: Some(WireframeStyle::HiddenLine),
+            body_fill: FillMode::WireframeOverSolid,
+            gamma_correct: !default.gamma_correct,
+            ..RenderSettings::default()
+        };
+        assert_eq!(tuned.to_string().parse(), Ok(tuned));
+    }
+
+    #[test]
+    fn ignores_unknown_keys() {
+        let text = format!("{}from_a_newer_version = 42\n", RenderSettings::default());
+        assert_eq!(text.parse(), Ok(RenderSettings::default()));
+    }
+
+    #[test]
+    fn rejects_far_plane_not_past_near_plane() {
+        let near = RenderSettings::default().near_plane;
+        assert!(format!("far_plane = {}", near).parse::<RenderSettings>().is_err());
+        assert!(format!("far_plane = {}", near * 0.5).parse::<RenderSettings>().is_err());
+        assert!("far_plane = lejos".parse::<RenderSettings>().is_err());
+    }
+}
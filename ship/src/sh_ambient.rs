@@ -0,0 +1,119 @@
+// sh_ambient.rs
+use raylib::prelude::*;
+use std::f32::consts::PI;
+
+/// Diffuse ambient stored as second-order spherical harmonics: 9 RGB
+/// coefficients that reproduce the low-frequency part of an environment.
+/// Projection is expensive and happens once; evaluation is a handful of
+/// multiply-adds per fragment and yields the light a white Lambertian
+/// surface facing `normal` would reflect.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ShAmbient {
+    pub coefficients: [Vector3; 9],
+}
+
+// Resolución de la rejilla lat-long usada para integrar el entorno
+const PROJECTION_ROWS: usize = 32;
+const PROJECTION_COLUMNS: usize = 64;
+
+// Convolución con el coseno (Ramamoorthi & Hanrahan), ya dividida entre PI
+const BAND_WEIGHTS: [f32; 3] = [1.0, 2.0 / 3.0, 1.0 / 4.0];
+
+fn basis(d: Vector3) -> [f32; 9] {
+    [
+        0.282095,
+        0.488603 * d.y,
+        0.488603 * d.z,
+        0.488603 * d.x,
+        1.092548 * d.x * d.y,
+        1.092548 * d.y * d.z,
+        0.315392 * (3.0 * d.z * d.z - 1.0),
+        1.092548 * d.x * d.z,
+        0.546274 * (d.x * d.x - d.y * d.y),
+    ]
+}
+
+fn band(index: usize) -> usize {
+    match index {
+        0 => 0,
+        1..=3 => 1,
+        _ => 2,
+    }
+}
+
+/// Projects the radiance returned by `sample` (indexed by direction) onto
+/// the first 9 SH basis functions
+pub fn project<F: Fn(Vector3) -> Vector3>(sample: F) -> ShAmbient {
+    let mut coefficients = [Vector3::zero(); 9];
+    let d_theta = PI / PROJECTION_ROWS as f32;
+    let d_phi = 2.0 * PI / PROJECTION_COLUMNS as f32;
+
+    for row in 0..PROJECTION_ROWS {
+        let theta = (row as f32 + 0.5) * d_theta;
+        // Ángulo sólido de las celdas de esta fila
+        let solid_angle = theta.sin() * d_theta * d_phi;
+
+        for column in 0..PROJECTION_COLUMNS {
+            let phi = (column as f32 + 0.5) * d_phi;
+            let direction = Vector3::new(theta.sin() * phi.cos(), theta.cos(), theta.sin() * phi.sin());
+            let radiance = sample(direction) * solid_angle;
+
+            for (coefficient, y) in coefficients.iter_mut().zip(basis(direction)) {
+                *coefficient += radiance * y;
+            }
+        }
+    }
+
+    ShAmbient { coefficients }
+}
+
+impl ShAmbient {
+    /// Cosine-weighted irradiance around `normal`, divided by PI
+    pub fn evaluate(&self, normal: Vector3) -> Vector3 {
+        let mut result = Vector3::zero();
+        for (index, (coefficient, y)) in self.coefficients.iter().zip(basis(normal.normalized())).enumerate() {
+            result += *coefficient * (BAND_WEIGHTS[band(index)] * y);
+        }
+        Vector3::new(result.x.max(0.0), result.y.max(0.0), result.z.max(0.0))
+    }
+
+    /// Average radiance over the whole sphere (the DC term:
+    /// L00 = avg * Y00 * 4PI and Y00^2 * 4PI = 1)
+    pub fn average(&self) -> Vector3 {
+        self.coefficients[0] * 0.282095
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normals() -> Vec<Vector3> {
+        (0..40).map(|i| {
+            let (theta, phi) = (i as f32 * 0.41, i as f32 * 1.73);
+            Vector3::new(theta.sin() * phi.cos(), theta.cos(), theta.sin() * phi.sin())
+        }).collect()
+    }
+
+    #[test]
+    fn uniform_environment_gives_the_same_ambient_everywhere() {
+        let white = project(|_| Vector3::one());
+        assert!((white.average() - Vector3::one()).length() < 1e-2, "{:?}", white.average());
+        for normal in normals() {
+            // Un lambertiano blanco bajo un cielo blanco refleja todo lo que le llega
+            let ambient = white.evaluate(normal);
+            assert!((ambient - Vector3::one()).length() < 1e-2, "{:?} -> {:?}", normal, ambient);
+        }
+    }
+
+    #[test]
+    fn ambient_follows_the_bright_side() {
+        // Luz solo desde arriba y en rojo
+        let sky = project(|direction| if direction.y > 0.0 { Vector3::new(1.0, 0.0, 0.0) } else { Vector3::zero() });
+        let up = sky.evaluate(Vector3::new(0.0, 1.0, 0.0));
+        let side = sky.evaluate(Vector3::new(1.0, 0.0, 0.0));
+        let down = sky.evaluate(Vector3::new(0.0, -1.0, 0.0));
+        assert!(up.x > side.x && side.x > down.x, "{:?} {:?} {:?}", up, side, down);
+        assert!(up.y.abs() < 1e-6 && up.z.abs() < 1e-6);
+    }
+}
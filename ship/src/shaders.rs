@@ -2,58 +2,31 @@
 use raylib::prelude::*;
 use crate::vertex::Vertex;
 use crate::Uniforms;
-use crate::matrix::multiply_matrix_vector4;
+use crate::matrix::{multiply_matrix_vector4, new_matrix4, ModelTransform};
 use crate::fragment::Fragment;
-use crate::framebuffer::Framebuffer;
+use crate::framebuffer::{BlendMode, DepthBias, Framebuffer};
 use crate::triangle;
-use crate::light::Light;
+use crate::light::{Light, LightKind, MAX_LIGHTS};
+use crate::obj::Material;
+use crate::palette::{Palette, sample_stops};
+use crate::render_queue::view_depth;
+use crate::environment::Environment;
+use crate::texture::Texture;
 
 // ============================================================================
 // VERTEX SHADER
 // ============================================================================
+// Solo la transformación estándar: dónde está cada malla (la órbita de la
+// luna, el tamaño de los anillos) lo dicen su matriz de modelo y su geometría
 pub fn vertex_shader(vertex: &Vertex, uniforms: &Uniforms) -> Vertex {
-    let mut position_vec4 = Vector4::new(
+    let position_vec4 = Vector4::new(
         vertex.position.x,
         vertex.position.y,
         vertex.position.z,
         1.0
     );
 
-    // Modificar geometría según el tipo de render
-    match uniforms.render_type {
-        1 => { // Anillos procedurales
-            // Generar geometría de anillos usando las coordenadas del vértice
-            let angle = vertex.position.x.atan2(vertex.position.z);
-            let inner_radius = 1.8;
-            let outer_radius = 2.8;
-            let ring_width = outer_radius - inner_radius;
-            
-            // Usar Y del vértice para interpolar entre radio interno y externo
-            let radius = inner_radius + (vertex.position.y + 1.0) * 0.5 * ring_width;
-            
-            position_vec4.x = radius * angle.cos();
-            position_vec4.z = radius * angle.sin();
-            position_vec4.y = vertex.position.z * 0.05; // Anillo delgado con ligera ondulación
-        }
-        2 => { // Luna orbital
-            let moon_orbit_time = uniforms.time * 0.3;
-            let moon_distance = 4.5;
-            let moon_inclination = 0.2; // Inclinación orbital
-            
-            let moon_x = moon_distance * moon_orbit_time.cos();
-            let moon_z = moon_distance * moon_orbit_time.sin();
-            let moon_y = (moon_orbit_time * 2.0).sin() * moon_inclination;
-            
-            let moon_scale = 0.25; // Tamaño de la luna
-            position_vec4.x = moon_x + vertex.position.x * moon_scale;
-            position_vec4.y = moon_y + vertex.position.y * moon_scale;
-            position_vec4.z = moon_z + vertex.position.z * moon_scale;
-        }
-        _ => {} // Planeta normal - sin deformación
-    }
-
-    // Pipeline de transformación estándar
-    let world_position = multiply_matrix_vector4(&uniforms.model_matrix, &position_vec4);
+    let world_position = multiply_matrix_vector4(&uniforms.model.matrix, &position_vec4);
     let view_position = multiply_matrix_vector4(&uniforms.view_matrix, &world_position);
     let clip_position = multiply_matrix_vector4(&uniforms.projection_matrix, &view_position);
 
@@ -77,67 +50,117 @@ pub fn vertex_shader(vertex: &Vertex, uniforms: &Uniforms) -> Vertex {
         normal: vertex.normal,
         tex_coords: vertex.tex_coords,
         color: vertex.color,
+        tangent: vertex.tangent,
         transformed_position: Vector3::new(screen_position.x, screen_position.y, screen_position.z),
-        transformed_normal: transform_normal(&vertex.normal, &uniforms.model_matrix),
+        transformed_normal: transform_normal(&vertex.normal, &uniforms.model.normal_matrix),
+        material_id: vertex.material_id,
+        clip_position,
     }
 }
 
-fn transform_normal(normal: &Vector3, model_matrix: &Matrix) -> Vector3 {
+// `normal_matrix` es la inversa transpuesta del modelo (`ModelTransform`): con
+// escala no uniforme la matriz del modelo misma inclinaría la normal
+fn transform_normal(normal: &Vector3, normal_matrix: &Matrix) -> Vector3 {
     let normal_vec4 = Vector4::new(normal.x, normal.y, normal.z, 0.0);
-    let transformed = multiply_matrix_vector4(model_matrix, &normal_vec4);
+    let transformed = multiply_matrix_vector4(normal_matrix, &normal_vec4);
     let mut result = Vector3::new(transformed.x, transformed.y, transformed.z);
     result.normalize();
     result
 }
 
+/// `normal` tilted by the tangent-space normal that `normal_map` stores at
+/// `tex_coords`: each 8-bit channel is one axis, 128 for 0 (so 0 and 255
+/// are about -1 and 1), with +Z along `normal`,
+/// +X along `tangent` and +Y along the bitangent (growing `v`, the OpenGL
+/// convention). `tangent.w` is the bitangent's sign (see
+/// `Obj::with_computed_tangents`); a zero tangent, from a mesh without UVs,
+/// leaves `normal` as it is. Everything in object space, like the shaders
+pub fn normal_mapped(normal: Vector3, tangent: Vector4, tex_coords: Vector2, normal_map: &Texture) -> Vector3 {
+    // Gram-Schmidt: interpolada sobre la cara, la tangente deja de ser perpendicular a la normal
+    let direction = Vector3::new(tangent.x, tangent.y, tangent.z);
+    let direction = direction - normal * normal.dot(direction);
+    if tangent.w == 0.0 || direction.length() < 1e-6 {
+        return normal;
+    }
+    let tangent_direction = direction.normalized();
+    let bitangent = normal.cross(tangent_direction) * tangent.w.signum();
+
+    // 128 es el cero: así un mapa plano de 8 bits (128, 128, 255) deja la normal exacta
+    let sample = (normal_map.sample(tex_coords.x, tex_coords.y) * 255.0 - Vector3::new(128.0, 128.0, 128.0)) / 127.0;
+    (tangent_direction * sample.x + bitangent * sample.y + normal * sample.z).normalized()
+}
+
 // ============================================================================
 // FUNCIONES DE RUIDO PROCEDURAL
 // ============================================================================
 
-// Hash function para generar valores pseudo-aleatorios
-fn hash(n: f32) -> f32 {
-    ((n * 12.9898).sin() * 43758.5453).fract()
+// Hash entero de una celda de la retícula: sus coordenadas mezcladas con
+// primos grandes y luego la permutación de salida de PCG. A diferencia del
+// hash con seno no se repite ni deja bandas a lo largo de los ejes
+fn hash(x: i32, y: i32, z: i32) -> u32 {
+    let seed = (x as u32).wrapping_mul(0x8da6_b343)
+        ^ (y as u32).wrapping_mul(0xd816_3841)
+        ^ (z as u32).wrapping_mul(0xcb1a_b31f);
+    let state = seed.wrapping_mul(747_796_405).wrapping_add(2_891_336_453);
+    let word = ((state >> ((state >> 28) + 4)) ^ state).wrapping_mul(277_803_737);
+    (word >> 22) ^ word
 }
 
-// Noise 3D mejorado
-fn noise3d(p: &Vector3) -> f32 {
-    let i = Vector3::new(p.x.floor(), p.y.floor(), p.z.floor());
-    let f = Vector3::new(p.x.fract(), p.y.fract(), p.z.fract());
-    
-    // Suavizado cúbico
-    let u = Vector3::new(f.x * f.x * (3.0 - 2.0 * f.x), 
-                         f.y * f.y * (3.0 - 2.0 * f.y),
-                         f.z * f.z * (3.0 - 2.0 * f.z));
-    
+// El Perlin 3D rara vez pasa de ±0.7: escalado así tiene la dispersión del
+// ruido de valor anterior, con la que se ajustaron los umbrales de los shaders
+const NOISE_GAIN: f32 = 1.4;
+
+// Gradientes del ruido de Perlin mejorado: los centros de las 12 aristas de un cubo
+const NOISE_GRADIENTS: [Vector3; 12] = [
+    Vector3::new(1.0, 1.0, 0.0), Vector3::new(-1.0, 1.0, 0.0), Vector3::new(1.0, -1.0, 0.0), Vector3::new(-1.0, -1.0, 0.0),
+    Vector3::new(1.0, 0.0, 1.0), Vector3::new(-1.0, 0.0, 1.0), Vector3::new(1.0, 0.0, -1.0), Vector3::new(-1.0, 0.0, -1.0),
+    Vector3::new(0.0, 1.0, 1.0), Vector3::new(0.0, -1.0, 1.0), Vector3::new(0.0, 1.0, -1.0), Vector3::new(0.0, -1.0, -1.0),
+];
+
+// Ruido de gradiente 3D (Perlin mejorado) en [-1, 1]: cada esquina de la
+// celda aporta su gradiente por la distancia al punto, mezclado con la curva
+// quíntica. Continuo también a través de las celdas con coordenadas negativas.
+// `seed` desplaza la retícula un número entero de celdas (0 no la mueve): la
+// misma posición cae en gradientes distintos sin perder precisión
+fn noise3d(p: &Vector3, seed: u32) -> f32 {
+    let cell = Vector3::new(p.x.floor(), p.y.floor(), p.z.floor());
+    let f = *p - cell; // Posición dentro de la celda, siempre en [0, 1)
+    let (ix, iy, iz) = (
+        (cell.x as i32).wrapping_add(seed.wrapping_mul(0x9e37_79b9) as i32),
+        (cell.y as i32).wrapping_add(seed.wrapping_mul(0x85eb_ca6b) as i32),
+        (cell.z as i32).wrapping_add(seed.wrapping_mul(0xc2b2_ae35) as i32),
+    );
+
+    // Suavizado quíntico: derivada segunda continua, sin costuras entre celdas
+    let fade = |t: f32| t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+    let u = Vector3::new(fade(f.x), fade(f.y), fade(f.z));
+
+    let corner = |dx: i32, dy: i32, dz: i32| {
+        let gradient = NOISE_GRADIENTS[(hash(ix.wrapping_add(dx), iy.wrapping_add(dy), iz.wrapping_add(dz)) % 12) as usize];
+        gradient.dot(f - Vector3::new(dx as f32, dy as f32, dz as f32))
+    };
+    let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+
     // Interpolación de las 8 esquinas del cubo
-    let n000 = hash(i.x + i.y * 57.0 + i.z * 113.0);
-    let n100 = hash(i.x + 1.0 + i.y * 57.0 + i.z * 113.0);
-    let n010 = hash(i.x + (i.y + 1.0) * 57.0 + i.z * 113.0);
-    let n110 = hash(i.x + 1.0 + (i.y + 1.0) * 57.0 + i.z * 113.0);
-    let n001 = hash(i.x + i.y * 57.0 + (i.z + 1.0) * 113.0);
-    let n101 = hash(i.x + 1.0 + i.y * 57.0 + (i.z + 1.0) * 113.0);
-    let n011 = hash(i.x + (i.y + 1.0) * 57.0 + (i.z + 1.0) * 113.0);
-    let n111 = hash(i.x + 1.0 + (i.y + 1.0) * 57.0 + (i.z + 1.0) * 113.0);
-    
-    let nx00 = n000 * (1.0 - u.x) + n100 * u.x;
-    let nx10 = n010 * (1.0 - u.x) + n110 * u.x;
-    let nx01 = n001 * (1.0 - u.x) + n101 * u.x;
-    let nx11 = n011 * (1.0 - u.x) + n111 * u.x;
-    
-    let nxy0 = nx00 * (1.0 - u.y) + nx10 * u.y;
-    let nxy1 = nx01 * (1.0 - u.y) + nx11 * u.y;
-    
-    nxy0 * (1.0 - u.z) + nxy1 * u.z
+    let nx00 = lerp(corner(0, 0, 0), corner(1, 0, 0), u.x);
+    let nx10 = lerp(corner(0, 1, 0), corner(1, 1, 0), u.x);
+    let nx01 = lerp(corner(0, 0, 1), corner(1, 0, 1), u.x);
+    let nx11 = lerp(corner(0, 1, 1), corner(1, 1, 1), u.x);
+
+    let nxy0 = lerp(nx00, nx10, u.y);
+    let nxy1 = lerp(nx01, nx11, u.y);
+
+    (lerp(nxy0, nxy1, u.z) * NOISE_GAIN).clamp(-1.0, 1.0)
 }
 
 // Fractal Brownian Motion - múltiples octavas de ruido
-fn fbm(p: &Vector3, octaves: i32) -> f32 {
+fn fbm(p: &Vector3, octaves: i32, seed: u32) -> f32 {
     let mut value = 0.0;
     let mut amplitude = 0.5;
     let mut frequency = 1.0;
     
     for _ in 0..octaves {
-        value += noise3d(&Vector3::new(p.x * frequency, p.y * frequency, p.z * frequency)) * amplitude;
+        value += noise3d(&Vector3::new(p.x * frequency, p.y * frequency, p.z * frequency), seed) * amplitude;
         amplitude *= 0.5;
         frequency *= 2.0;
     }
@@ -146,13 +169,13 @@ fn fbm(p: &Vector3, octaves: i32) -> f32 {
 }
 
 // Turbulencia - valor absoluto del FBM
-fn turbulence(p: &Vector3, octaves: i32) -> f32 {
+fn turbulence(p: &Vector3, octaves: i32, seed: u32) -> f32 {
     let mut value = 0.0;
     let mut amplitude = 0.5;
     let mut frequency = 1.0;
     
     for _ in 0..octaves {
-        value += noise3d(&Vector3::new(p.x * frequency, p.y * frequency, p.z * frequency)).abs() * amplitude;
+        value += noise3d(&Vector3::new(p.x * frequency, p.y * frequency, p.z * frequency), seed).abs() * amplitude;
         amplitude *= 0.5;
         frequency *= 2.0;
     }
@@ -173,11 +196,47 @@ fn lerp_color(a: &Vector3, b: &Vector3, t: f32) -> Vector3 {
     )
 }
 
+// Capas de los shaders procedurales (bit N-1 = "CAPA N"). Una capa apagada
+// devuelve un valor neutro para poder ver cómo se construye la superficie.
+pub const LAYER_ALL: u32 = 0b1111;
+
+fn layer(layers: u32, index: u32, value: f32, neutral: f32) -> f32 {
+    if layers & (1 << (index - 1)) != 0 { value } else { neutral }
+}
+
 // ============================================================================
 // SISTEMA DE ILUMINACIÓN
 // ============================================================================
 
-fn calculate_lighting(normal: &Vector3, light_dir: &Vector3, view_dir: &Vector3) -> (f32, f32) {
+/// One light as a fragment receives it: the direction towards it, in the
+/// same object space as the shader normals, and its color already scaled by
+/// intensity and distance falloff
+#[derive(Clone, Copy)]
+struct IncidentLight {
+    direction: Vector3,
+    radiance: Vector3,
+}
+
+/// Diffuse and specular summed over `lights`, each tinted by its radiance.
+/// Both sums are capped at 1 per channel, so piling lights up saturates
+/// the surface instead of overflowing before the final clamp. With no
+/// lights both are zero and only the ambient term remains. `shininess` is
+/// the Blinn-Phong exponent
+fn calculate_lighting(normal: &Vector3, lights: &[IncidentLight], view_dir: &Vector3,
+                      shininess: f32) -> (Vector3, Vector3) {
+    let mut diffuse = Vector3::zero();
+    let mut specular = Vector3::zero();
+    for light in lights {
+        let (light_diffuse, light_specular) = blinn_phong(normal, &light.direction, view_dir, shininess);
+        diffuse += light.radiance * light_diffuse;
+        specular += light.radiance * light_specular;
+    }
+    let saturate = |v: Vector3| Vector3::new(v.x.min(1.0), v.y.min(1.0), v.z.min(1.0));
+    (saturate(diffuse), saturate(specular))
+}
+
+// Términos de una sola luz en la dirección dada
+fn blinn_phong(normal: &Vector3, light_dir: &Vector3, view_dir: &Vector3, shininess: f32) -> (f32, f32) {
     // Normalizar vectores
     let mut n = *normal;
     n.normalize();
@@ -197,11 +256,299 @@ fn calculate_lighting(normal: &Vector3, light_dir: &Vector3, view_dir: &Vector3)
     );
     let mut h_norm = h;
     h_norm.normalize();
-    let specular = (n.x * h_norm.x + n.y * h_norm.y + n.z * h_norm.z).max(0.0).powf(32.0);
+    let specular = (n.x * h_norm.x + n.y * h_norm.y + n.z * h_norm.z).max(0.0).powf(shininess);
     
     (diffuse, specular)
 }
 
+/// Fresnel-style rim, a cheap stand-in for an atmosphere: the material's
+/// `rim_color` scaled by `(1 - n·v)^rim_power`, zero facing the camera and
+/// full at the silhouette. The higher the power, the thinner the band
+fn rim_light(normal: &Vector3, view_dir: &Vector3, material: &SurfaceMaterial) -> Vector3 {
+    if material.rim_power <= 0.0 {
+        return Vector3::zero();
+    }
+    let facing = normal.normalized().dot(view_dir.normalized()).clamp(0.0, 1.0);
+    material.rim_color * (1.0 - facing).powf(material.rim_power)
+}
+
+/// Per-fragment light terms shared by the planet shaders, with the body's
+/// `SurfaceMaterial` that scales them. The ambient fill
+/// is kept apart from the direct light: distance falloff only dims the
+/// direct part, and the ambient never drops below `ambient_floor`, so the
+/// night side of a distant planet keeps its detail instead of going black.
+struct SurfaceLight<'a> {
+    ambient_light: Vector3, // Relleno ambiental direccional (SH del entorno)
+    ambient_floor: f32,     // Coeficiente ambiental mínimo
+    incident: [IncidentLight; MAX_LIGHTS], // Luces del rig vistas desde el fragmento, ya atenuadas
+    light_count: usize,
+    material: SurfaceMaterial, // Cómo responde la superficie a esa luz
+    view_dir: Vector3,         // De la superficie hacia la cámara real, en espacio de objeto
+    world_view_dir: Vector3,   // La misma dirección en el mundo, donde está el entorno
+    normal_matrix: Matrix,     // Para llevar las normales de los shaders al mundo
+    environment: &'a Environment,
+}
+
+impl<'a> SurfaceLight<'a> {
+    /// Light reaching the point `local` (object space) of the body, which is
+    /// `world` in the world with normal `world_normal` there
+    fn new(local: &Vector3, world: Vector3, world_normal: Vector3, uniforms: &Uniforms<'a>) -> Self {
+        // Las normales de los shaders están en espacio de objeto: las luces y la cámara también
+        let to_object = uniforms.model.world_to_object;
+        let eye = uniforms.camera_position;
+        let camera = multiply_matrix_vector4(&to_object, &Vector4::new(eye.x, eye.y, eye.z, 1.0));
+
+        let mut lighting = SurfaceLight {
+            ambient_light: uniforms.environment.ambient_fill(world_normal),
+            ambient_floor: uniforms.ambient_floor,
+            incident: [IncidentLight { direction: Vector3::zero(), radiance: Vector3::zero() }; MAX_LIGHTS],
+            light_count: 0,
+            material: uniforms.material,
+            view_dir: (Vector3::new(camera.x, camera.y, camera.z) - *local).normalized(),
+            world_view_dir: (eye - world).normalized(),
+            normal_matrix: uniforms.model.normal_matrix,
+            environment: uniforms.environment,
+        };
+
+        for light in uniforms.lights.lights() {
+            let (direction, attenuation) = match light.kind {
+                LightKind::Point { position } => {
+                    let position = multiply_matrix_vector4(&to_object, &Vector4::new(position.x, position.y, position.z, 1.0));
+                    let direction = (Vector3::new(position.x, position.y, position.z) - *local).normalized();
+                    let falloff = light_attenuation(light.distance_from(world), uniforms.light_falloff);
+                    (direction, falloff * light.attenuation_at(world))
+                }
+                // Sin posición no hay distancia a la que atenuar
+                LightKind::Directional { direction } => {
+                    let toward = multiply_matrix_vector4(&to_object, &Vector4::new(-direction.x, -direction.y, -direction.z, 0.0));
+                    (Vector3::new(toward.x, toward.y, toward.z).normalized(), 1.0)
+                }
+            };
+            lighting.incident[lighting.light_count] = IncidentLight {
+                direction,
+                radiance: light.color * (light.intensity * attenuation),
+            };
+            lighting.light_count += 1;
+        }
+        lighting
+    }
+
+    fn incident(&self) -> &[IncidentLight] {
+        &self.incident[..self.light_count]
+    }
+
+    fn ambient(&self, color: Vector3, ambient: f32) -> Vector3 {
+        color * self.ambient_light * ambient.max(self.ambient_floor)
+    }
+
+    /// The environment mirrored by a surface with object-space `normal`,
+    /// weighted by Schlick's Fresnel so it is strongest at grazing angles
+    fn environment_reflection(&self, normal: &Vector3) -> Vector3 {
+        let normal = transform_normal(normal, &self.normal_matrix);
+        let n_dot_v = normal.dot(self.world_view_dir).max(0.0);
+        let reflected = normal * (2.0 * n_dot_v) - self.world_view_dir;
+        let fresnel = 0.1 + 0.9 * (1.0 - n_dot_v).powf(5.0);
+        self.environment.sample(reflected) * fresnel
+    }
+}
+
+/// Per-body tweaks for the planet shaders, so two bodies of the same type
+/// can look different (a red Mars and a gray Moon, both rocky). Left at
+/// their defaults, every shader keeps its built-in look.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ShaderParams {
+    /// Replaces the shader's palette entries in the order it declares them
+    pub palette: [Option<Vector3>; PALETTE_SLOTS],
+    pub noise_scale: f32,                // Multiplica la frecuencia del ruido de superficie
+    pub surface_rotation: Option<f32>,   // Velocidad de giro de la superficie (cada shader tiene la suya)
+    pub parallax_scale: f32,             // Profundidad del relieve por parallax (solo el rocoso; 0 lo apaga)
+    pub seed: u32,                       // Semilla del ruido: otra superficie con el mismo shader (0: la de siempre)
+    /// Replaces the shader's main gradient (elevation, lava, bands, hue)
+    /// and with it the `palette` entries that build it
+    pub gradient: Option<Palette>,
+    /// Replace the fields of the planet type's `SurfaceMaterial`
+    pub diffuse: Option<Vector3>,
+    pub specular_strength: Option<f32>,
+    pub shininess: Option<f32>,
+    pub ambient: Option<f32>,
+    pub rim_color: Option<Vector3>,
+    pub rim_power: Option<f32>,
+}
+
+/// How a planet's surface takes the light, applied on top of the color its
+/// shader computes: `diffuse` tints the diffuse term, `specular_strength`
+/// scales the Blinn-Phong highlight and `shininess` is its exponent, and
+/// `ambient` is the ambient fill. Glossy and matte planets differ only in
+/// these numbers. `rim_color` and `rim_power` are the atmosphere's rim
+/// (`rim_light`), only drawn by the ocean and gas giant shaders. Meshes
+/// with a `.mtl` use `obj::Material` instead.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SurfaceMaterial {
+    pub diffuse: Vector3,
+    pub specular_strength: f32,
+    pub shininess: f32,
+    pub ambient: f32,
+    pub rim_color: Vector3,
+    pub rim_power: f32, // 0: sin borde
+}
+
+impl SurfaceMaterial {
+    // La luna de `render_moon`: gris mate
+    const MOON: SurfaceMaterial = SurfaceMaterial::gray(0.9, 0.0, 32.0, 0.1);
+
+    const fn gray(diffuse: f32, specular_strength: f32, shininess: f32, ambient: f32) -> Self {
+        SurfaceMaterial {
+            diffuse: Vector3::new(diffuse, diffuse, diffuse),
+            specular_strength,
+            shininess,
+            ambient,
+            rim_color: Vector3::new(0.0, 0.0, 0.0),
+            rim_power: 0.0,
+        }
+    }
+
+    const fn with_rim(self, rim_color: Vector3, rim_power: f32) -> Self {
+        SurfaceMaterial { rim_color, rim_power, ..self }
+    }
+
+    /// The built-in material of each planet shader
+    pub fn for_planet_type(planet_type: i32) -> Self {
+        match planet_type {
+            0 => SurfaceMaterial::gray(0.8, 0.1, 32.0, 0.15), // Rocoso: casi mate
+            // Gaseoso: la atmósfera difunde la luz y deja un borde crema
+            1 => SurfaceMaterial::gray(0.7, 0.0, 32.0, 0.3).with_rim(Vector3::new(0.45, 0.4, 0.3), 4.0),
+            // Océano: el brillo es el del agua, con un borde azul de atmósfera
+            2 => SurfaceMaterial::gray(0.75, 0.4, 32.0, 0.2).with_rim(Vector3::new(0.25, 0.5, 1.0), 3.0),
+            3 => SurfaceMaterial::gray(0.4, 0.0, 32.0, 0.1),  // Volcánico: lo ilumina su lava
+            4 => SurfaceMaterial::gray(0.5, 0.8, 32.0, 0.3),  // Cristal: el más brillante
+            _ => SurfaceMaterial::gray(1.0, 0.0, 32.0, 0.2),
+        }
+    }
+}
+
+// Colores de paleta sustituibles (el shader del océano es el que más usa: 7)
+pub const PALETTE_SLOTS: usize = 8;
+
+impl Default for ShaderParams {
+    fn default() -> Self {
+        ShaderParams {
+            palette: [None; PALETTE_SLOTS],
+            noise_scale: 1.0,
+            surface_rotation: None,
+            parallax_scale: 0.0,
+            seed: 0,
+            gradient: None,
+            diffuse: None,
+            specular_strength: None,
+            shininess: None,
+            ambient: None,
+            rim_color: None,
+            rim_power: None,
+        }
+    }
+}
+
+impl ShaderParams {
+    /// The planet type's material with this body's overrides applied
+    pub fn material(&self, planet_type: i32) -> SurfaceMaterial {
+        let material = SurfaceMaterial::for_planet_type(planet_type);
+        SurfaceMaterial {
+            diffuse: self.diffuse.unwrap_or(material.diffuse),
+            specular_strength: self.specular_strength.unwrap_or(material.specular_strength),
+            shininess: self.shininess.unwrap_or(material.shininess),
+            ambient: self.ambient.unwrap_or(material.ambient),
+            rim_color: self.rim_color.unwrap_or(material.rim_color),
+            rim_power: self.rim_power.unwrap_or(material.rim_power),
+        }
+    }
+
+    fn color(&self, slot: usize, default: Vector3) -> Vector3 {
+        self.palette[slot].unwrap_or(default)
+    }
+
+    fn rotation(&self, default: f32) -> f32 {
+        self.surface_rotation.unwrap_or(default)
+    }
+
+    // Gradiente cargado del cuerpo, o las paradas por defecto del shader
+    fn gradient(&self, stops: &[(f32, Vector3)], t: f32) -> Vector3 {
+        match &self.gradient {
+            Some(palette) => palette.sample(t),
+            None => sample_stops(stops, t),
+        }
+    }
+}
+
+// Distancia hasta la que la luz directa llega sin atenuar (la órbita interior)
+const LIGHT_FALLOFF_REFERENCE: f32 = 5.0;
+
+/// Direct-light falloff: 1 up to `LIGHT_FALLOFF_REFERENCE`, then
+/// (reference / distance)^exponent. 0 disables it, 2 is inverse square
+pub fn light_attenuation(distance: f32, exponent: f32) -> f32 {
+    (LIGHT_FALLOFF_REFERENCE / distance.max(LIGHT_FALLOFF_REFERENCE)).powf(exponent)
+}
+
+/// Toksvig specular antialiasing: `average_normal_length` is the length of
+/// the (unnormalized) average of the normals inside a pixel's footprint.
+/// 1.0 means they all agree; the shorter it gets, the more they spread, and
+/// the Blinn-Phong exponent is lowered so the lobe covers that spread
+/// instead of flickering between the individual normals.
+pub fn toksvig_shininess(shininess: f32, average_normal_length: f32) -> f32 {
+    let length = average_normal_length.clamp(1e-4, 1.0);
+    let factor = length / (length + shininess * (1.0 - length));
+    shininess * factor
+}
+
+// Pasos del parallax: pocos mirando de frente, más cuanto más rasante
+const PARALLAX_MIN_STEPS: f32 = 4.0;
+const PARALLAX_MAX_STEPS: f32 = 16.0;
+// Coseno mínimo de la vista: limita el desplazamiento cerca del horizonte
+const PARALLAX_MIN_COS: f32 = 0.2;
+
+/// Parallax occlusion mapping: marches the view ray below the surface in
+/// tangent space until it passes under `height` (in [0, 1], 1 = surface
+/// level, 0 = `scale` deep) and returns where to sample instead of `pos`.
+/// `view_dir` points from the surface toward the eye; everything in object
+/// space. The offset goes away from the viewer, so low areas look recessed
+/// at grazing angles. A `scale` of 0 returns `pos` unchanged.
+pub fn parallax_offset<F>(pos: Vector3, normal: Vector3, view_dir: Vector3, scale: f32, height: F) -> Vector3
+where
+    F: Fn(Vector3) -> f32,
+{
+    if scale <= 0.0 {
+        return pos;
+    }
+
+    let (tangent, bitangent) = tangent_basis(&normal);
+    let view = Vector3::new(view_dir.dot(tangent), view_dir.dot(bitangent), view_dir.dot(normal));
+    if view.z <= 0.0 {
+        return pos; // Vista desde atrás de la superficie
+    }
+
+    // Desplazamiento en el plano tangente al bajar `scale` de profundidad
+    let shift = (tangent * view.x + bitangent * view.y) * (scale / view.z.max(PARALLAX_MIN_COS));
+    let steps = (PARALLAX_MAX_STEPS + (PARALLAX_MIN_STEPS - PARALLAX_MAX_STEPS) * view.z).round() as i32;
+
+    // (profundidad del rayo, profundidad del terreno) del paso anterior
+    let mut previous = (0.0, 1.0 - height(pos));
+    if previous.1 <= 0.0 {
+        return pos;
+    }
+    for step in 1..=steps {
+        let ray_depth = step as f32 / steps as f32;
+        let terrain_depth = 1.0 - height(pos - shift * ray_depth);
+        if ray_depth >= terrain_depth {
+            // Cruce entre el paso anterior y este, interpolando linealmente
+            let above = previous.1 - previous.0;
+            let below = ray_depth - terrain_depth;
+            let t = above / (above + below).max(1e-6);
+            return pos - shift * (previous.0 + (ray_depth - previous.0) * t);
+        }
+        previous = (ray_depth, terrain_depth);
+    }
+    pos - shift
+}
+
 // ============================================================================
 // ROTACIÓN DEL PLANETA
 // ============================================================================
@@ -223,52 +570,73 @@ fn rotate_position(pos: &Vector3, time: f32, speed: f32) -> Vector3 {
 // ============================================================================
 // Capas: Base terrain, cráteres, elevación, iluminación
 
-fn rocky_planet_shader(pos: &Vector3, time: f32, normal: &Vector3) -> Vector3 {
-    let rotated_pos = rotate_position(pos, time, 0.2);
-    
+// Ruido del terreno rocoso (capas 1 a 3): base, cráteres y montañas
+fn rocky_terrain(rotated_pos: &Vector3, layers: u32, seed: u32) -> (f32, f32, f32) {
     // CAPA 1: Terreno base con ruido fractal
-    let base_noise = fbm(&rotated_pos, 5);
+    let base_noise = layer(layers, 1, fbm(rotated_pos, 5, seed), 0.45);
     
     // CAPA 2: Cráteres usando turbulencia
     let crater_scale = 8.0;
-    let crater_noise = turbulence(&Vector3::new(
+    let crater_noise = layer(layers, 2, turbulence(&Vector3::new(
         rotated_pos.x * crater_scale,
         rotated_pos.y * crater_scale,
         rotated_pos.z * crater_scale
-    ), 3);
+    ), 3, seed), 0.0);
     
     // CAPA 3: Elevación para montañas
     let mountain_scale = 3.0;
-    let mountain_noise = fbm(&Vector3::new(
+    let mountain_noise = layer(layers, 3, fbm(&Vector3::new(
         rotated_pos.x * mountain_scale,
         rotated_pos.y * mountain_scale,
         rotated_pos.z * mountain_scale
-    ), 4);
+    ), 4, seed), 0.45);
+
+    (base_noise, crater_noise, mountain_noise)
+}
+
+// Relieve del rocoso para el parallax, en [0, 1]: la elevación del terreno
+// con los cráteres hundidos
+fn rocky_height(pos: &Vector3, time: f32, layers: u32, params: &ShaderParams) -> f32 {
+    let rotated_pos = rotate_position(pos, time, params.rotation(0.2)) * params.noise_scale;
+    let (base_noise, crater_noise, mountain_noise) = rocky_terrain(&rotated_pos, layers, params.seed);
+    let elevation = (base_noise + mountain_noise) * 0.5;
+    let crater_factor = (crater_noise - 0.6).max(0.0) * 2.0;
+    (elevation - crater_factor * 0.5).clamp(0.0, 1.0)
+}
+
+fn rocky_planet_shader(pos: &Vector3, time: f32, normal: &Vector3, layers: u32, lighting: &SurfaceLight,
+                       params: &ShaderParams) -> (Vector3, Vector3) {
+    // Relieve simulado: el ruido se muestrea donde el rayo de vista corta el terreno
+    let pos = &parallax_offset(*pos, *normal, lighting.view_dir, params.parallax_scale,
+                               |p| rocky_height(&p, time, layers, params));
+
+    // En el rocoso la posición rotada solo alimenta el ruido: se escala directamente
+    let rotated_pos = rotate_position(pos, time, params.rotation(0.2)) * params.noise_scale;
+    let (base_noise, crater_noise, mountain_noise) = rocky_terrain(&rotated_pos, layers, params.seed);
     
     // CAPA 4: Detalle fino
-    let detail_noise = noise3d(&Vector3::new(
+    let detail_noise = layer(layers, 4, noise3d(&Vector3::new(
         rotated_pos.x * 12.0,
         rotated_pos.y * 12.0,
         rotated_pos.z * 12.0
-    ));
+    ), params.seed), 0.5);
     
     // Paleta de colores rocosos
-    let deep_color = Vector3::new(0.3, 0.15, 0.1);  // Marrón oscuro
-    let mid_color = Vector3::new(0.5, 0.3, 0.2);    // Marrón rojizo
-    let high_color = Vector3::new(0.6, 0.45, 0.3);  // Arena
-    let peak_color = Vector3::new(0.7, 0.6, 0.5);   // Gris claro
+    let deep_color = params.color(0, Vector3::new(0.3, 0.15, 0.1));  // Marrón oscuro
+    let mid_color = params.color(1, Vector3::new(0.5, 0.3, 0.2));    // Marrón rojizo
+    let high_color = params.color(2, Vector3::new(0.6, 0.45, 0.3));  // Arena
+    let peak_color = params.color(3, Vector3::new(0.7, 0.6, 0.5));   // Gris claro
     
     // Combinar capas
     let elevation = (base_noise + mountain_noise) * 0.5;
     let crater_factor = (crater_noise - 0.6).max(0.0) * 2.0;
     
-    let mut color = if elevation > 0.7 {
-        lerp_color(&high_color, &peak_color, (elevation - 0.7) * 3.33)
-    } else if elevation > 0.4 {
-        lerp_color(&mid_color, &high_color, (elevation - 0.4) * 3.33)
-    } else {
-        lerp_color(&deep_color, &mid_color, elevation * 2.5)
-    };
+    let mut color = params.gradient(&[
+        (0.0, deep_color),
+        (0.4, mid_color),
+        (0.7, high_color),
+        (1.0, peak_color),
+    ], elevation);
     
     // Aplicar cráteres (oscurecer)
     color = lerp_color(&color, &Vector3::new(0.2, 0.1, 0.05), crater_factor * 0.5);
@@ -277,12 +645,11 @@ fn rocky_planet_shader(pos: &Vector3, time: f32, normal: &Vector3) -> Vector3 {
     color = color * (0.9 + detail_noise * 0.2);
     
     // Iluminación
-    let light_dir = Vector3::new(1.0, 0.5, 1.0);
-    let view_dir = Vector3::new(0.0, 0.0, 1.0);
-    let (diffuse, specular) = calculate_lighting(normal, &light_dir, &view_dir);
+    let material = &lighting.material;
+    let (diffuse, specular) = calculate_lighting(normal, lighting.incident(), &lighting.view_dir, material.shininess);
     
-    let ambient = 0.15;
-    color * (ambient + diffuse * 0.8) + Vector3::new(specular * 0.1, specular * 0.1, specular * 0.1)
+    let lit = lighting.ambient(color, material.ambient) + color * material.diffuse * diffuse + specular * material.specular_strength;
+    (lit, Vector3::zero())
 }
 
 // ============================================================================
@@ -290,8 +657,9 @@ fn rocky_planet_shader(pos: &Vector3, time: f32, normal: &Vector3) -> Vector3 {
 // ============================================================================
 // Capas: Bandas horizontales, turbulencia, tormentas, nubes
 
-fn gas_giant_shader(pos: &Vector3, time: f32, normal: &Vector3) -> Vector3 {
-    let rotated_pos = rotate_position(pos, time, 0.8);
+fn gas_giant_shader(pos: &Vector3, time: f32, normal: &Vector3, layers: u32, lighting: &SurfaceLight,
+                    params: &ShaderParams) -> (Vector3, Vector3) {
+    let rotated_pos = rotate_position(pos, time, params.rotation(0.8));
     
     // Coordenadas esféricas para bandas
     let lat = rotated_pos.y;
@@ -299,47 +667,46 @@ fn gas_giant_shader(pos: &Vector3, time: f32, normal: &Vector3) -> Vector3 {
     
     // CAPA 1: Bandas horizontales principales
     let band_freq = 8.0;
-    let band_pattern = (lat * band_freq + time * 0.3).sin();
+    let band_pattern = layer(layers, 1, (lat * band_freq + time * 0.3).sin(), 0.0);
     
     // CAPA 2: Turbulencia atmosférica
-    let turb_scale = 4.0;
-    let turbulence_val = fbm(&Vector3::new(
+    let turb_scale = 4.0 * params.noise_scale;
+    let turbulence_val = layer(layers, 2, fbm(&Vector3::new(
         lon * turb_scale,
         lat * turb_scale * 0.5,
         time * 0.1
-    ), 4);
+    ), 4, params.seed), 0.0);
     
     // CAPA 3: Gran Mancha Roja (tormenta)
     let storm_center = Vector3::new(0.3, -0.2, 0.0);
     let dist_to_storm = ((rotated_pos.x - storm_center.x).powi(2) + 
                          (rotated_pos.y - storm_center.y).powi(2) + 
                          (rotated_pos.z - storm_center.z).powi(2)).sqrt();
-    let storm_factor = (1.0 - (dist_to_storm / 0.4).min(1.0)).max(0.0);
+    let storm_factor = layer(layers, 3, (1.0 - (dist_to_storm / 0.4).min(1.0)).max(0.0), 0.0);
     let storm_swirl = (lon * 6.0 + turbulence_val * 3.0 + time).sin() * storm_factor;
     
     // CAPA 4: Nubes de alta altitud
-    let cloud_noise = noise3d(&Vector3::new(
-        lon * 16.0,
-        lat * 12.0,
+    let cloud_noise = layer(layers, 4, noise3d(&Vector3::new(
+        lon * 16.0 * params.noise_scale,
+        lat * 12.0 * params.noise_scale,
         time * 0.05
-    ));
+    ), params.seed), -1.0);
     
     // Paleta de colores
-    let base_cream = Vector3::new(0.9, 0.85, 0.7);
-    let dark_band = Vector3::new(0.6, 0.45, 0.3);
-    let orange_band = Vector3::new(0.9, 0.6, 0.3);
-    let storm_red = Vector3::new(0.8, 0.3, 0.2);
-    let white_cloud = Vector3::new(0.95, 0.95, 0.95);
+    let base_cream = params.color(0, Vector3::new(0.9, 0.85, 0.7));
+    let dark_band = params.color(1, Vector3::new(0.6, 0.45, 0.3));
+    let orange_band = params.color(2, Vector3::new(0.9, 0.6, 0.3));
+    let storm_red = params.color(3, Vector3::new(0.8, 0.3, 0.2));
+    let white_cloud = params.color(4, Vector3::new(0.95, 0.95, 0.95));
     
     // Mezclar bandas
     let band_mix = (band_pattern + turbulence_val * 0.5 + 1.0) * 0.5;
-    let mut color = if band_mix > 0.65 {
-        lerp_color(&base_cream, &orange_band, (band_mix - 0.65) * 2.86)
-    } else if band_mix > 0.35 {
-        base_cream
-    } else {
-        lerp_color(&dark_band, &base_cream, band_mix * 2.86)
-    };
+    let mut color = params.gradient(&[
+        (0.0, dark_band),
+        (0.35, base_cream),
+        (0.65, base_cream),
+        (1.0, orange_band),
+    ], band_mix);
     
     // Aplicar tormenta
     color = lerp_color(&color, &storm_red, storm_factor * (0.6 + storm_swirl * 0.2));
@@ -349,12 +716,12 @@ fn gas_giant_shader(pos: &Vector3, time: f32, normal: &Vector3) -> Vector3 {
     color = lerp_color(&color, &white_cloud, cloud_factor);
     
     // Iluminación suave (atmósfera difunde la luz)
-    let light_dir = Vector3::new(1.0, 0.3, 1.0);
-    let view_dir = Vector3::new(0.0, 0.0, 1.0);
-    let (diffuse, _) = calculate_lighting(normal, &light_dir, &view_dir);
+    let material = &lighting.material;
+    let (diffuse, specular) = calculate_lighting(normal, lighting.incident(), &lighting.view_dir, material.shininess);
     
-    let ambient = 0.3;
-    color * (ambient + diffuse * 0.7)
+    let lit = lighting.ambient(color, material.ambient) + color * material.diffuse * diffuse
+        + specular * material.specular_strength + rim_light(normal, &lighting.view_dir, material);
+    (lit, Vector3::zero())
 }
 
 // ============================================================================
@@ -362,45 +729,49 @@ fn gas_giant_shader(pos: &Vector3, time: f32, normal: &Vector3) -> Vector3 {
 // ============================================================================
 // Capas: Océanos profundos, continentes, nubes, casquetes polares
 
-fn ocean_planet_shader(pos: &Vector3, time: f32, normal: &Vector3) -> Vector3 {
-    let rotated_pos = rotate_position(pos, time, 0.4);
+fn ocean_planet_shader(pos: &Vector3, time: f32, normal: &Vector3, layers: u32, lighting: &SurfaceLight,
+                       params: &ShaderParams) -> (Vector3, Vector3) {
+    let rotated_pos = rotate_position(pos, time, params.rotation(0.4));
     
     let lat = rotated_pos.y;
     let lon = rotated_pos.x.atan2(rotated_pos.z);
     
+    // La latitud (casquetes) usa la posición sin escalar; el ruido, la escalada
+    let noise_pos = rotated_pos * params.noise_scale;
+
     // CAPA 1: Terreno base (tierra vs agua)
-    let terrain_noise = fbm(&rotated_pos, 4);
+    let terrain_noise = layer(layers, 1, fbm(&noise_pos, 4, params.seed), 0.0);
     let is_land = terrain_noise > 0.35;
     
     // CAPA 2: Variación oceánica
-    let ocean_depth = fbm(&Vector3::new(
-        rotated_pos.x * 4.0,
-        rotated_pos.y * 4.0,
-        rotated_pos.z * 4.0 + time * 0.1
-    ), 3);
+    let ocean_depth = layer(layers, 2, fbm(&Vector3::new(
+        noise_pos.x * 4.0,
+        noise_pos.y * 4.0,
+        noise_pos.z * 4.0 + time * 0.1
+    ), 3, params.seed), 0.0);
     
     // CAPA 3: Vegetación en tierra
-    let vegetation = fbm(&Vector3::new(
-        rotated_pos.x * 6.0,
-        rotated_pos.y * 6.0,
-        rotated_pos.z * 6.0
-    ), 3);
+    let vegetation = layer(layers, 3, fbm(&Vector3::new(
+        noise_pos.x * 6.0,
+        noise_pos.y * 6.0,
+        noise_pos.z * 6.0
+    ), 3, params.seed), 0.0);
     
     // CAPA 4: Nubes dinámicas
-    let cloud_coverage = fbm(&Vector3::new(
-        lon * 8.0,
-        lat * 6.0 + time * 0.05,
+    let cloud_coverage = layer(layers, 4, fbm(&Vector3::new(
+        lon * 8.0 * params.noise_scale,
+        lat * 6.0 * params.noise_scale + time * 0.05,
         time * 0.02
-    ), 4);
+    ), 4, params.seed), 0.0);
     
     // Colores
-    let deep_ocean = Vector3::new(0.05, 0.15, 0.4);
-    let shallow_ocean = Vector3::new(0.1, 0.4, 0.7);
-    let beach = Vector3::new(0.8, 0.75, 0.6);
-    let grass = Vector3::new(0.2, 0.6, 0.2);
-    let forest = Vector3::new(0.1, 0.4, 0.15);
-    let ice = Vector3::new(0.9, 0.95, 1.0);
-    let cloud_white = Vector3::new(1.0, 1.0, 1.0);
+    let deep_ocean = params.color(0, Vector3::new(0.05, 0.15, 0.4));
+    let shallow_ocean = params.color(1, Vector3::new(0.1, 0.4, 0.7));
+    let beach = params.color(2, Vector3::new(0.8, 0.75, 0.6));
+    let grass = params.color(3, Vector3::new(0.2, 0.6, 0.2));
+    let forest = params.color(4, Vector3::new(0.1, 0.4, 0.15));
+    let ice = params.color(5, Vector3::new(0.9, 0.95, 1.0));
+    let cloud_white = params.color(6, Vector3::new(1.0, 1.0, 1.0));
     
     let mut color = if is_land {
         if terrain_noise > 0.5 {
@@ -435,15 +806,15 @@ fn ocean_planet_shader(pos: &Vector3, time: f32, normal: &Vector3) -> Vector3 {
     color = lerp_color(&color, &cloud_white, cloud_alpha);
     
     // Iluminación
-    let light_dir = Vector3::new(1.0, 0.5, 0.8);
-    let view_dir = Vector3::new(0.0, 0.0, 1.0);
-    let (diffuse, specular) = calculate_lighting(normal, &light_dir, &view_dir);
+    let material = &lighting.material;
+    let (diffuse, specular) = calculate_lighting(normal, lighting.incident(), &lighting.view_dir, material.shininess);
     
-    // Especular más fuerte en océanos
-    let spec_strength = if !is_land { 0.4 } else { 0.05 };
+    // Especular más fuerte en océanos: el agua brilla lo que dice el material y la tierra mucho menos
+    let spec_strength = if !is_land { material.specular_strength } else { material.specular_strength * 0.125 };
     
-    let ambient = 0.2;
-    color * (ambient + diffuse * 0.75) + Vector3::new(specular * spec_strength, specular * spec_strength, specular * spec_strength)
+    let lit = lighting.ambient(color, material.ambient) + color * material.diffuse * diffuse + specular * spec_strength
+        + rim_light(normal, &lighting.view_dir, material);
+    (lit, Vector3::zero())
 }
 
 // ============================================================================
@@ -451,54 +822,51 @@ fn ocean_planet_shader(pos: &Vector3, time: f32, normal: &Vector3) -> Vector3 {
 // ============================================================================
 // Capas: Lava activa, corteza enfriada, emisión de luz, erupciones
 
-fn volcanic_planet_shader(pos: &Vector3, time: f32, normal: &Vector3) -> Vector3 {
-    let rotated_pos = rotate_position(pos, time, 0.15);
+fn volcanic_planet_shader(pos: &Vector3, time: f32, normal: &Vector3, layers: u32, lighting: &SurfaceLight,
+                          params: &ShaderParams) -> (Vector3, Vector3) {
+    let rotated_pos = rotate_position(pos, time, params.rotation(0.15)) * params.noise_scale;
     
-    // CAPA 1: Red de lava activa
-    let lava_veins = turbulence(&Vector3::new(
+    // CAPA 1: Red de lava activa. La turbulencia de 4 octavas rara vez pasa
+    // de 0.6: se estira a [0, 1] para recorrer todo el degradado
+    let lava_veins = layer(layers, 1, (turbulence(&Vector3::new(
         rotated_pos.x * 6.0,
         rotated_pos.y * 6.0,
         rotated_pos.z * 6.0 + time * 0.5
-    ), 4);
+    ), 4, params.seed) / 0.6).min(1.0), 0.0);
     
     // CAPA 2: Pulso de actividad volcánica
-    let pulse = (time * 2.0).sin() * 0.5 + 0.5;
+    let pulse = layer(layers, 2, (time * 2.0).sin() * 0.5 + 0.5, 1.0);
     let activity = lava_veins * pulse;
     
     // CAPA 3: Erupciones localizadas
     let eruption_scale = 3.0;
-    let eruption_noise = noise3d(&Vector3::new(
+    let eruption_noise = layer(layers, 3, noise3d(&Vector3::new(
         rotated_pos.x * eruption_scale,
         rotated_pos.y * eruption_scale + time * 3.0,
         rotated_pos.z * eruption_scale
-    ));
+    ), params.seed), 0.0);
     
-    // CAPA 4: Corteza agrietada
-    let cracks = turbulence(&Vector3::new(
+    // CAPA 4: Corteza agrietada (la de 2 octavas rara vez pasa de 0.55)
+    let cracks = layer(layers, 4, (turbulence(&Vector3::new(
         rotated_pos.x * 10.0,
         rotated_pos.y * 10.0,
         rotated_pos.z * 10.0
-    ), 2);
+    ), 2, params.seed) / 0.55).min(1.0), 0.0);
     
     // Colores
-    let black_rock = Vector3::new(0.1, 0.05, 0.05);
-    let cooling_lava = Vector3::new(0.4, 0.1, 0.05);
-    let hot_lava = Vector3::new(0.9, 0.3, 0.1);
-    let white_hot = Vector3::new(1.0, 0.9, 0.6);
-    
-    let mut color = if activity > 0.75 {
-        // Lava muy activa
-        lerp_color(&hot_lava, &white_hot, (activity - 0.75) * 4.0)
-    } else if activity > 0.5 {
-        // Lava caliente
-        lerp_color(&cooling_lava, &hot_lava, (activity - 0.5) * 4.0)
-    } else if activity > 0.3 {
-        // Lava enfriándose
-        lerp_color(&black_rock, &cooling_lava, (activity - 0.3) * 5.0)
-    } else {
-        // Roca negra
-        black_rock
-    };
+    let black_rock = params.color(0, Vector3::new(0.1, 0.05, 0.05));
+    let cooling_lava = params.color(1, Vector3::new(0.4, 0.1, 0.05));
+    let hot_lava = params.color(2, Vector3::new(0.9, 0.3, 0.1));
+    let white_hot = params.color(3, Vector3::new(1.0, 0.9, 0.6));
+    
+    // Roca negra, lava enfriándose, caliente y muy activa
+    let mut color = params.gradient(&[
+        (0.0, black_rock),
+        (0.3, black_rock),
+        (0.5, cooling_lava),
+        (0.75, hot_lava),
+        (1.0, white_hot),
+    ], activity);
     
     // Añadir grietas iluminadas
     if cracks > 0.7 {
@@ -513,14 +881,15 @@ fn volcanic_planet_shader(pos: &Vector3, time: f32, normal: &Vector3) -> Vector3
     }
     
     // Iluminación + auto-iluminación
-    let light_dir = Vector3::new(1.0, 0.5, 1.0);
-    let view_dir = Vector3::new(0.0, 0.0, 1.0);
-    let (diffuse, _) = calculate_lighting(normal, &light_dir, &view_dir);
+    let material = &lighting.material;
+    let (diffuse, specular) = calculate_lighting(normal, lighting.incident(), &lighting.view_dir, material.shininess);
     
     let self_illum = activity * 0.5; // La lava emite luz
-    let ambient = 0.1;
     
-    color * (ambient + diffuse * 0.4 + self_illum)
+    // La emisión de la lava no depende de las luces: va aparte
+    let lit = lighting.ambient(color, material.ambient) + color * material.diffuse * diffuse
+        + specular * material.specular_strength;
+    (lit, color * self_illum)
 }
 
 // ============================================================================
@@ -528,44 +897,72 @@ fn volcanic_planet_shader(pos: &Vector3, time: f32, normal: &Vector3) -> Vector3
 // ============================================================================
 // Capas: Estructura cristalina, reflexiones, colores prismáticos, brillo
 
-fn crystal_planet_shader(pos: &Vector3, time: f32, normal: &Vector3) -> Vector3 {
-    let rotated_pos = rotate_position(pos, time, 0.6);
+// Normal de las caras: la geométrica inclinada por el gradiente de la
+// estructura cristalina (diferencias finitas sobre la posición sin rotar)
+fn crystal_facet_normal(pos: &Vector3, normal: &Vector3, time: f32, params: &ShaderParams) -> Vector3 {
+    let facet_height = |p: Vector3| {
+        let rotated = rotate_position(&p, time, params.rotation(0.6));
+        fbm(&(rotated * (6.0 * params.noise_scale)), 3, params.seed)
+    };
+    let h = 0.005;
+    let base = facet_height(*pos);
+    let gradient = Vector3::new(
+        facet_height(*pos + Vector3::new(h, 0.0, 0.0)) - base,
+        facet_height(*pos + Vector3::new(0.0, h, 0.0)) - base,
+        facet_height(*pos + Vector3::new(0.0, 0.0, h)) - base,
+    ) / h;
+
+    // Solo la componente tangente inclina la normal
+    let tangential = gradient - *normal * gradient.dot(*normal);
+    (*normal - tangential * 0.04).normalized()
+}
+
+fn tangent_basis(normal: &Vector3) -> (Vector3, Vector3) {
+    let reference = if normal.y.abs() < 0.99 { Vector3::new(0.0, 1.0, 0.0) } else { Vector3::new(1.0, 0.0, 0.0) };
+    let tangent = reference.cross(*normal).normalized();
+    let bitangent = normal.cross(tangent);
+    (tangent, bitangent)
+}
+
+// `footprint`: tamaño del píxel en espacio de objeto; 0 desactiva el AA especular
+fn crystal_planet_shader(pos: &Vector3, time: f32, normal: &Vector3, layers: u32, lighting: &SurfaceLight,
+                         params: &ShaderParams, footprint: f32) -> (Vector3, Vector3) {
+    let rotated_pos = rotate_position(pos, time, params.rotation(0.6)) * params.noise_scale;
     
     // CAPA 1: Estructura de cristales
     let crystal_scale = 6.0;
-    let crystal_pattern = fbm(&Vector3::new(
+    let crystal_pattern = layer(layers, 1, fbm(&Vector3::new(
         rotated_pos.x * crystal_scale,
         rotated_pos.y * crystal_scale,
         rotated_pos.z * crystal_scale
-    ), 3);
+    ), 3, params.seed), 0.0);
     
     // CAPA 2: Colores prismáticos (iridiscencia)
-    let hue_shift = (crystal_pattern * 10.0 + time * 0.5).sin() * 0.5 + 0.5;
+    let hue_shift = layer(layers, 2, (crystal_pattern * 10.0 + time * 0.5).sin() * 0.5 + 0.5, 0.5);
     
     // CAPA 3: Vetas internas
-    let internal_structure = fbm(&Vector3::new(
+    let internal_structure = layer(layers, 3, fbm(&Vector3::new(
         rotated_pos.x * 4.0,
         rotated_pos.y * 4.0,
         rotated_pos.z * 4.0
-    ), 3);
+    ), 3, params.seed), 0.0);
     
     // CAPA 4: Pulso de energía
-    let energy_pulse = ((time * 1.5).sin() * 0.5 + 0.5) * 0.3;
+    let energy_pulse = layer(layers, 4, ((time * 1.5).sin() * 0.5 + 0.5) * 0.3, 0.0);
     
     // Colores base del cristal
-    let crystal_blue = Vector3::new(0.3, 0.6, 1.0);
-    let crystal_purple = Vector3::new(0.7, 0.3, 1.0);
-    let crystal_cyan = Vector3::new(0.2, 0.9, 0.9);
-    let crystal_white = Vector3::new(0.95, 0.95, 1.0);
+    let crystal_blue = params.color(0, Vector3::new(0.3, 0.6, 1.0));
+    let crystal_purple = params.color(1, Vector3::new(0.7, 0.3, 1.0));
+    let crystal_cyan = params.color(2, Vector3::new(0.2, 0.9, 0.9));
+    let crystal_white = params.color(3, Vector3::new(0.95, 0.95, 1.0));
     
     // Color base según estructura
-    let mut color = if hue_shift > 0.66 {
-        lerp_color(&crystal_blue, &crystal_cyan, (hue_shift - 0.66) * 3.0)
-    } else if hue_shift > 0.33 {
-        lerp_color(&crystal_purple, &crystal_blue, (hue_shift - 0.33) * 3.0)
-    } else {
-        lerp_color(&crystal_cyan, &crystal_purple, hue_shift * 3.0)
-    };
+    let mut color = params.gradient(&[
+        (0.0, crystal_cyan),
+        (1.0 / 3.0, crystal_purple),
+        (2.0 / 3.0, crystal_blue),
+        (1.0, crystal_cyan),
+    ], hue_shift);
     
     // Añadir vetas brillantes
     if internal_structure > 0.6 {
@@ -573,24 +970,58 @@ fn crystal_planet_shader(pos: &Vector3, time: f32, normal: &Vector3) -> Vector3
     }
     
     // Iluminación especular fuerte (cristales reflejan mucho)
-    let light_dir = Vector3::new(1.0, 0.5, 1.0);
-    let view_dir = Vector3::new(0.0, 0.0, 1.0);
-    let (diffuse, specular) = calculate_lighting(normal, &light_dir, &view_dir);
+    let material = &lighting.material;
+    let shininess = material.shininess;
+
+    // Caras del cristal: la CAPA 1 también inclina la normal
+    let (shading_normal, shininess) = if layers & 1 == 0 {
+        (*normal, shininess)
+    } else if footprint > 0.0 {
+        // Promedio de las normales dentro del píxel (antialiasing especular)
+        let (tangent, bitangent) = tangent_basis(normal);
+        let half = footprint * 0.5;
+        let average = (crystal_facet_normal(&(*pos + tangent * half), normal, time, params)
+            + crystal_facet_normal(&(*pos - tangent * half), normal, time, params)
+            + crystal_facet_normal(&(*pos + bitangent * half), normal, time, params)
+            + crystal_facet_normal(&(*pos - bitangent * half), normal, time, params)) * 0.25;
+        (average.normalized(), toksvig_shininess(shininess, average.length()))
+    } else {
+        (crystal_facet_normal(pos, normal, time, params), shininess)
+    };
+    let (diffuse, specular) = calculate_lighting(&shading_normal, lighting.incident(), &lighting.view_dir, shininess);
     
-    let ambient = 0.3;
-    color * (ambient + diffuse * 0.5) + 
-    Vector3::new(specular * 0.8, specular * 0.8, specular * 0.8) +
-    color * energy_pulse
+    // El pulso de energía brilla por sí mismo; cada cara refleja el entorno de fondo
+    let lit = lighting.ambient(color, material.ambient) + color * material.diffuse * diffuse
+        + specular * material.specular_strength + lighting.environment_reflection(&shading_normal);
+    (lit, color * energy_pulse)
+}
+
+// ============================================================================
+// SHADER DE MATERIAL (mallas con .mtl, como la nave)
+// ============================================================================
+
+/// Blinn-Phong with the mesh's own material: `Kd` times `albedo` (the
+/// texture's color, white without one) tints the ambient and diffuse terms,
+/// `Ks` the highlight, and `Ns` is its exponent
+fn material_shader(normal: &Vector3, lighting: &SurfaceLight, material: &Material, albedo: Vector3) -> Vector3 {
+    let (diffuse, specular) = calculate_lighting(normal, lighting.incident(), &lighting.view_dir, material.shininess);
+
+    let color = material.diffuse * albedo;
+    let ambient = 0.2;
+    lighting.ambient(color, ambient) + color * diffuse + material.specular * specular
 }
 
 // ============================================================================
 // FUNCIONES DE RENDERIZADO ESPECIALES
 // ============================================================================
 
+// Anillos: `vertex_array` es su propia malla (`Obj::annulus`), plana sobre el
+// plano XZ del planeta, entre RING_INNER_RADIUS y RING_OUTER_RADIUS
 pub fn render_rings(framebuffer: &mut Framebuffer, uniforms: &Uniforms, vertex_array: &[Vertex], light: &Light) {
     let mut transformed_vertices = Vec::with_capacity(vertex_array.len());
     let mut ring_uniforms = uniforms.clone();
     ring_uniforms.render_type = 1;
+    ring_uniforms.depth_bias = RING_DEPTH_BIAS;
 
     for vertex in vertex_array {
         let transformed = vertex_shader(vertex, &ring_uniforms);
@@ -610,12 +1041,21 @@ pub fn render_rings(framebuffer: &mut Framebuffer, uniforms: &Uniforms, vertex_a
 
     let mut fragments = Vec::new();
     for tri in &triangles {
-        fragments.extend(triangle::triangle(&tri[0], &tri[1], &tri[2], light));
+        for tri in triangle::clip_to_frustum(&tri[0], &tri[1], &tri[2], &uniforms.viewport_matrix) {
+            fragments.extend(triangle::triangle(&tri[0], &tri[1], &tri[2], light, uniforms.raster_precision));
+        }
     }
 
+    // Niebla de los anillos: toda a la profundidad del centro del planeta
+    let center = multiply_matrix_vector4(&uniforms.model.matrix, &Vector4::new(0.0, 0.0, 0.0, 1.0));
+    let ring_center = Vector3::new(center.x, center.y, center.z);
+
     // Fragment shader para anillos
     for fragment in fragments {
-        let radius = (fragment.world_position.x.powi(2) + fragment.world_position.z.powi(2)).sqrt();
+        // Distancia al eje en el plano de la malla (world_position está en
+        // espacio de objeto), la misma que mide ring_shadow
+        let local = fragment.world_position;
+        let radius = (local.x * local.x + local.z * local.z).sqrt();
         
         // Bandas de colores en los anillos
         let band_pattern = (radius * 15.0).sin() * 0.5 + 0.5;
@@ -623,32 +1063,37 @@ pub fn render_rings(framebuffer: &mut Framebuffer, uniforms: &Uniforms, vertex_a
         let ring_color2 = Vector3::new(0.6, 0.5, 0.4);
         let gap_color = Vector3::new(0.3, 0.2, 0.15);
         
-        // Crear gaps (huecos) en los anillos
-        let gap = ((radius * 20.0).sin() * 0.5 + 0.5) < 0.2;
-        
+        let gap = ring_gap(radius);
         let color = if gap {
-            gap_color * 0.5
+            gap_color
         } else {
             lerp_color(&ring_color1, &ring_color2, band_pattern)
         };
         
-        // Iluminación simple
+        // Iluminación simple con dirección fija: el Sol queda casi en el plano de
+        // los anillos, y con su dirección real solo les quedaría el ambiente
         let ring_normal = Vector3::new(0.0, 1.0, 0.0);
         let light_dir = Vector3::new(1.0, 1.0, 1.0);
         let view_dir = Vector3::new(0.0, 0.0, 1.0);
-        let (diffuse, _) = calculate_lighting(&ring_normal, &light_dir, &view_dir);
+        let (diffuse, _) = blinn_phong(&ring_normal, &light_dir, &view_dir, 32.0);
         
-        let final_color = color * (0.3 + diffuse * 0.7);
-        
-        framebuffer.point(
-            fragment.position.x as i32,
-            fragment.position.y as i32,
-            final_color,
-            fragment.depth,
-        );
+        let lit = ring_uniforms.fog.apply(color * (0.3 + diffuse * 0.7), ring_center, &ring_uniforms);
+        let final_color = resolve_color(lit, &ring_uniforms);
+        let (x, y) = (fragment.position.x as i32, fragment.position.y as i32);
+        let depth = ring_uniforms.depth_bias.apply(fragment.depth, fragment.depth_slope);
+
+        // Las bandas son opacas; los huecos dejan ver lo que hay detrás
+        if gap && uniforms.dithered_transparency {
+            framebuffer.write(x, y, final_color, RING_GAP_ALPHA, depth, BlendMode::Dithered);
+        } else if gap {
+            framebuffer.blend_point(x, y, final_color, RING_GAP_ALPHA, depth);
+        } else {
+            framebuffer.point(x, y, final_color, depth);
+        }
     }
 }
 
+// Luna con cráteres: la matriz de modelo de `uniforms` ya la pone en su órbita
 pub fn render_moon(framebuffer: &mut Framebuffer, uniforms: &Uniforms, vertex_array: &[Vertex], light: &Light) {
     let mut transformed_vertices = Vec::with_capacity(vertex_array.len());
     let mut moon_uniforms = uniforms.clone();
@@ -672,7 +1117,9 @@ pub fn render_moon(framebuffer: &mut Framebuffer, uniforms: &Uniforms, vertex_ar
 
     let mut fragments = Vec::new();
     for tri in &triangles {
-        fragments.extend(triangle::triangle(&tri[0], &tri[1], &tri[2], light));
+        for tri in triangle::clip_to_frustum(&tri[0], &tri[1], &tri[2], &uniforms.viewport_matrix) {
+            fragments.extend(triangle::triangle(&tri[0], &tri[1], &tri[2], light, uniforms.raster_precision));
+        }
     }
 
     // Fragment shader para luna
@@ -682,7 +1129,7 @@ pub fn render_moon(framebuffer: &mut Framebuffer, uniforms: &Uniforms, vertex_ar
             fragment.world_position.x * 8.0,
             fragment.world_position.y * 8.0,
             fragment.world_position.z * 8.0
-        ), 3);
+        ), 3, uniforms.shader_params.seed);
         
         let base_color = Vector3::new(0.6, 0.6, 0.6);
         let crater_color = Vector3::new(0.4, 0.4, 0.4);
@@ -693,13 +1140,17 @@ pub fn render_moon(framebuffer: &mut Framebuffer, uniforms: &Uniforms, vertex_ar
             base_color
         };
         
-        // Iluminación
-        let moon_normal = fragment.world_position;
-        let light_dir = Vector3::new(1.0, 1.0, 1.0);
-        let view_dir = Vector3::new(0.0, 0.0, 1.0);
-        let (diffuse, _) = calculate_lighting(&moon_normal, &light_dir, &view_dir);
+        // Iluminación, con la normal de la malla en el punto de la luna
+        let local = fragment.world_position;
+        let moon_normal = fragment.normal.normalized();
+        let world = multiply_matrix_vector4(&moon_uniforms.model.matrix, &Vector4::new(local.x, local.y, local.z, 1.0));
+        let lighting = SurfaceLight::new(&local, Vector3::new(world.x, world.y, world.z),
+                                         transform_normal(&moon_normal, &moon_uniforms.model.normal_matrix), &moon_uniforms);
+        let material = SurfaceMaterial::MOON;
+        let (diffuse, _) = calculate_lighting(&moon_normal, lighting.incident(), &lighting.view_dir, material.shininess);
         
-        let final_color = color * (0.1 + diffuse * 0.9);
+        let lit = color * material.ambient + color * material.diffuse * diffuse;
+        let final_color = resolve_color(moon_uniforms.fog.apply(lit, Vector3::new(world.x, world.y, world.z), &moon_uniforms), &moon_uniforms);
         
         framebuffer.point(
             fragment.position.x as i32,
@@ -710,40 +1161,664 @@ pub fn render_moon(framebuffer: &mut Framebuffer, uniforms: &Uniforms, vertex_ar
     }
 }
 
+// Atmósfera: cascarón esférico un poco más grande que el planeta, sumado de
+// forma aditiva. Más denso y brillante en el limbo, teñido por la luz.
+// Anillos: radios en espacio de objeto del planeta y cuánta luz bloquean
+pub const RING_INNER_RADIUS: f32 = 1.8;
+pub const RING_OUTER_RADIUS: f32 = 2.8;
+const RING_SHADOW_OPACITY: f32 = 0.65;
+// Opacidad del polvo en los huecos: casi se ve a través
+const RING_GAP_ALPHA: f32 = 0.25;
+// Los anillos se ven casi de canto: el sesgo crece con la pendiente para que
+// la superficie del planeta gane siempre donde se cruzan
+const RING_DEPTH_BIAS: DepthBias = DepthBias { constant: 1e-5, slope_scale: 1.0 };
+
+// Huecos (gaps) entre las bandas de los anillos
+fn ring_gap(radius: f32) -> bool {
+    ((radius * 20.0).sin() * 0.5 + 0.5) < 0.2
+}
+
+/// Fraction of the direct light that gets past the rings to `world`: the
+/// point is projected onto the ring plane (the model's local XZ plane)
+/// along the direction to the light, and is shadowed when the hit lands on
+/// a band between the ring radii.
+pub fn ring_shadow(world: Vector3, model_matrix: &Matrix, light: &Light) -> f32 {
+    let to_world = |v: Vector4| {
+        let result = multiply_matrix_vector4(model_matrix, &v);
+        Vector3::new(result.x, result.y, result.z)
+    };
+    let center = to_world(Vector4::new(0.0, 0.0, 0.0, 1.0));
+    let axis = to_world(Vector4::new(0.0, 1.0, 0.0, 0.0));
+    let scale = axis.length();
+    let plane_normal = axis / scale;
+
+    let light_distance = light.distance_from(world);
+    let light_dir = light.direction_from(world);
+    let facing = light_dir.dot(plane_normal);
+    if facing.abs() < 1e-6 {
+        return 1.0; // Luz rasante: el plano no se cruza
+    }
+
+    // El plano tiene que quedar entre el punto y la luz
+    let t = (center - world).dot(plane_normal) / facing;
+    if t <= 0.0 || t >= light_distance {
+        return 1.0;
+    }
+
+    let radius = (world + light_dir * t - center).length() / scale;
+    if (RING_INNER_RADIUS..=RING_OUTER_RADIUS).contains(&radius) && !ring_gap(radius) {
+        1.0 - RING_SHADOW_OPACITY
+    } else {
+        1.0
+    }
+}
+
+// scale: radio del cascarón relativo al planeta (1.0 + grosor)
+pub fn render_atmosphere(framebuffer: &mut Framebuffer, uniforms: &Uniforms, vertex_array: &[Vertex],
+                         scale: f32, color: Vector3, light: &Light) {
+    let shell_scale = new_matrix4(
+        scale, 0.0,   0.0,   0.0,
+        0.0,   scale, 0.0,   0.0,
+        0.0,   0.0,   scale, 0.0,
+        0.0,   0.0,   0.0,   1.0
+    );
+    let mut shell_uniforms = uniforms.clone();
+    shell_uniforms.model = ModelTransform::new(shell_scale * uniforms.model.matrix);
+
+    let mut transformed_vertices = Vec::with_capacity(vertex_array.len());
+    for vertex in vertex_array {
+        transformed_vertices.push(vertex_shader(vertex, &shell_uniforms));
+    }
+
+    let mut fragments = Vec::new();
+    for tri in transformed_vertices.chunks_exact(3) {
+        for tri in triangle::clip_to_frustum(&tri[0], &tri[1], &tri[2], &uniforms.viewport_matrix) {
+            fragments.extend(triangle::triangle(&tri[0], &tri[1], &tri[2], light, uniforms.raster_precision));
+        }
+    }
+
+    let camera_pos = uniforms.camera_position;
+    let center = multiply_matrix_vector4(&shell_uniforms.model.matrix, &Vector4::new(0.0, 0.0, 0.0, 1.0));
+    let center = Vector3::new(center.x, center.y, center.z);
+
+    for fragment in fragments {
+        let local = fragment.world_position;
+        let world = multiply_matrix_vector4(
+            &shell_uniforms.model.matrix,
+            &Vector4::new(local.x, local.y, local.z, 1.0),
+        );
+        let world = Vector3::new(world.x, world.y, world.z);
+
+        let mut normal = world - center;
+        normal.normalize();
+        let mut view_dir = camera_pos - world;
+        view_dir.normalize();
+        let light_dir = light.direction_from(world);
+
+        // Solo la cara visible del cascarón (evita sumar dos veces fuera del disco)
+        let n_dot_v = normal.dot(view_dir);
+        if n_dot_v <= 0.0 {
+            continue;
+        }
+
+        // Densidad dependiente de la vista: el camino óptico crece en el limbo
+        let density = (1.0 - n_dot_v).powf(3.0);
+        // Iluminación "envuelta" para que el halo se extienda un poco al lado nocturno
+        let lit = ((normal.dot(light_dir) + 0.3) / 1.3).clamp(0.0, 1.0);
+
+        let glow = color * (density * lit * 1.5 * uniforms.exposure);
+
+        framebuffer.add_point(
+            fragment.position.x as i32,
+            fragment.position.y as i32,
+            glow,
+            fragment.depth,
+        );
+    }
+}
+
+/// Translucent cloud shell around a planet, alpha-blended over the surface.
+/// Coverage comes from FBM sampled on the shell rotated at its own `speed`
+/// (rad/s, like the surface shaders' rotation) so clouds drift over the
+/// terrain and show parallax against it. `coverage` in [0, 1] is roughly
+/// the fraction of the sky covered.
+pub fn render_cloud_layer(framebuffer: &mut Framebuffer, uniforms: &Uniforms, vertex_array: &[Vertex],
+                          scale: f32, speed: f32, coverage: f32, light: &Light) {
+    let shell_scale = new_matrix4(
+        scale, 0.0,   0.0,   0.0,
+        0.0,   scale, 0.0,   0.0,
+        0.0,   0.0,   scale, 0.0,
+        0.0,   0.0,   0.0,   1.0
+    );
+    let mut shell_uniforms = uniforms.clone();
+    shell_uniforms.model = ModelTransform::new(shell_scale * uniforms.model.matrix);
+
+    let mut transformed_vertices = Vec::with_capacity(vertex_array.len());
+    for vertex in vertex_array {
+        transformed_vertices.push(vertex_shader(vertex, &shell_uniforms));
+    }
+
+    let mut fragments = Vec::new();
+    for tri in transformed_vertices.chunks_exact(3) {
+        for tri in triangle::clip_to_frustum(&tri[0], &tri[1], &tri[2], &uniforms.viewport_matrix) {
+            fragments.extend(triangle::triangle(&tri[0], &tri[1], &tri[2], light, uniforms.raster_precision));
+        }
+    }
+
+    let camera_pos = uniforms.camera_position;
+    let center = multiply_matrix_vector4(&shell_uniforms.model.matrix, &Vector4::new(0.0, 0.0, 0.0, 1.0));
+    let center = Vector3::new(center.x, center.y, center.z);
+    let threshold = 1.0 - coverage.clamp(0.0, 1.0);
+    let blend_mode = if uniforms.dithered_transparency { BlendMode::Dithered } else { BlendMode::AlphaOver };
+
+    for fragment in fragments {
+        let local = fragment.world_position;
+        let world = multiply_matrix_vector4(
+            &shell_uniforms.model.matrix,
+            &Vector4::new(local.x, local.y, local.z, 1.0),
+        );
+        let world = Vector3::new(world.x, world.y, world.z);
+
+        let mut normal = world - center;
+        normal.normalize();
+        let mut view_dir = camera_pos - world;
+        view_dir.normalize();
+        let light_dir = light.direction_from(world);
+
+        // Solo la cara visible del cascarón: la trasera queda tapada por el planeta
+        if normal.dot(view_dir) <= 0.0 {
+            continue;
+        }
+
+        // Cobertura: FBM en el cascarón girado a su propia velocidad,
+        // con un borde suave alrededor del umbral
+        let cloud_pos = rotate_position(&local, uniforms.time, speed) * 2.5;
+        let density = fbm(&cloud_pos, 5, uniforms.shader_params.seed) / 0.97; // FBM de 5 octavas: máximo 0.97
+        let alpha = ((density - threshold) / 0.15 + 0.5).clamp(0.0, 1.0) * 0.9;
+        if alpha <= 0.0 {
+            continue;
+        }
+
+        let lit = normal.dot(light_dir).max(0.0);
+        let color = Vector3::new(1.0, 1.0, 1.0) * ((0.15 + 0.85 * lit) * uniforms.exposure);
+
+        framebuffer.write(
+            fragment.position.x as i32,
+            fragment.position.y as i32,
+            uniforms.color_clamp.apply(color),
+            alpha,
+            fragment.depth,
+            blend_mode,
+        );
+    }
+}
+
 // ============================================================================
 // FRAGMENT SHADER PRINCIPAL
 // ============================================================================
 
+/// Exponential fog: a surface at view-space depth `d` keeps
+/// `exp(-density * d)` of its own color and takes the rest from `color`.
+/// A density of zero turns it off
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Fog {
+    pub color: Vector3,
+    pub density: f32,
+}
+
+impl Fog {
+    /// `color` lit at `world`, faded by its depth from the camera
+    pub fn apply(&self, color: Vector3, world: Vector3, uniforms: &Uniforms) -> Vector3 {
+        if self.density <= 0.0 {
+            return color;
+        }
+        let depth = view_depth(world, &uniforms.view_matrix, uniforms.handedness).max(0.0);
+        let amount = 1.0 - (-self.density * depth).exp();
+        color.lerp(self.color, amount)
+    }
+}
+
+/// How the final color is brought back into [0, 1]
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum ColorClampMode {
+    /// Each channel clamped on its own: a saturated channel stops while the
+    /// others keep growing, so bright colors drift toward yellow and white
+    #[default]
+    HardClamp,
+    /// The whole color is scaled down when its largest channel passes 1.0,
+    /// keeping the ratio between channels (and so the hue)
+    PreserveHue,
+    /// ACES filmic curve: highlights roll off smoothly instead of clipping
+    ToneMap,
+}
+
+impl ColorClampMode {
+    pub fn next(self) -> ColorClampMode {
+        match self {
+            ColorClampMode::HardClamp => ColorClampMode::PreserveHue,
+            ColorClampMode::PreserveHue => ColorClampMode::ToneMap,
+            ColorClampMode::ToneMap => ColorClampMode::HardClamp,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ColorClampMode::HardClamp => "Clamp",
+            ColorClampMode::PreserveHue => "Preservar tono",
+            ColorClampMode::ToneMap => "Tone map",
+        }
+    }
+
+    pub fn apply(self, color: Vector3) -> Vector3 {
+        let color = Vector3::new(color.x.max(0.0), color.y.max(0.0), color.z.max(0.0));
+        let color = match self {
+            ColorClampMode::HardClamp => color,
+            ColorClampMode::PreserveHue => {
+                let max_channel = color.x.max(color.y).max(color.z);
+                if max_channel > 1.0 { color / max_channel } else { color }
+            }
+            ColorClampMode::ToneMap => {
+                // Aproximación de Narkowicz a la curva ACES
+                let aces = |x: f32| (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14);
+                Vector3::new(aces(color.x), aces(color.y), aces(color.z))
+            }
+        };
+        Vector3::new(color.x.min(1.0), color.y.min(1.0), color.z.min(1.0))
+    }
+}
+
+// Tamaño aproximado de un píxel sobre la superficie, en espacio de objeto:
+// altura del frustum a esa distancia / píxeles de alto, sin la escala del modelo
+fn pixel_footprint(local: &Vector3, uniforms: &Uniforms) -> f32 {
+    let world = multiply_matrix_vector4(&uniforms.model.matrix, &Vector4::new(local.x, local.y, local.z, 1.0));
+    let world = Vector3::new(world.x, world.y, world.z);
+    let distance = (uniforms.camera_position - world).length();
+
+    let viewport_height = 2.0 * uniforms.viewport_matrix.m5.abs();
+    let world_size = 2.0 * distance / (uniforms.projection_matrix.m5 * viewport_height);
+    let model_scale = Vector3::new(uniforms.model.matrix.m0, uniforms.model.matrix.m1, uniforms.model.matrix.m2).length();
+
+    world_size / model_scale.max(1e-6)
+}
+
 pub fn fragment_shader(fragment: &Fragment, uniforms: &Uniforms) -> Vector3 {
+    resolve_color(shade_fragment(fragment, uniforms), uniforms)
+}
+
+/// The fragment shader up to, but not including, exposure and the color
+/// clamp: linear color that doesn't depend on the frame's exposure, so it
+/// can be cached while the surface stays the same
+pub fn shade_fragment(fragment: &Fragment, uniforms: &Uniforms) -> Vector3 {
+    // Debug: pesos baricéntricos crudos como RGB (un color puro por vértice)
+    if uniforms.render_type == 3 {
+        return fragment.barycentric;
+    }
+    // Debug: sentido de giro en pantalla, verde = cara frontal, rojo = trasera
+    if uniforms.render_type == 4 {
+        let front = fragment.signed_area * uniforms.front_face_sign() > 0.0;
+        return if front { Vector3::new(0.0, 1.0, 0.0) } else { Vector3::new(1.0, 0.0, 0.0) };
+    }
+    // Debug: color por vértice de la malla interpolado, sin iluminación
+    if uniforms.render_type == 5 {
+        return fragment.vertex_color;
+    }
+
     let pos = fragment.world_position;
     let time = uniforms.time;
     let planet_type = uniforms.planet_type;
     
-    // Calcular normal desde la posición del mundo (para esferas, el normal apunta desde el centro)
-    let mut normal = Vector3::new(
-        fragment.world_position.x,
-        fragment.world_position.y,
-        fragment.world_position.z
-    );
+    // Calcular normal desde la posición del mundo (para esferas, el normal apunta desde el centro).
+    // La nave no es una esfera: usa la normal interpolada de su malla
+    let mut normal = if planet_type == 10 {
+        fragment.normal
+    } else {
+        Vector3::new(
+            fragment.world_position.x,
+            fragment.world_position.y,
+            fragment.world_position.z
+        )
+    };
     normal.normalize();
 
-    let color = match planet_type {
-        0 => rocky_planet_shader(&pos, time, &normal),
-        1 => gas_giant_shader(&pos, time, &normal),
-        2 => ocean_planet_shader(&pos, time, &normal),
-        3 => volcanic_planet_shader(&pos, time, &normal),
-        4 => crystal_planet_shader(&pos, time, &normal),
-        _ => Vector3::new(0.5, 0.5, 0.5),
+    let world = multiply_matrix_vector4(&uniforms.model.matrix, &Vector4::new(pos.x, pos.y, pos.z, 1.0));
+    let world = Vector3::new(world.x, world.y, world.z);
+    let mut world_normal = transform_normal(&normal, &uniforms.model.normal_matrix);
+
+    // Doble cara: si la normal se aleja del observador se ve la cara trasera,
+    // que se sombrea como la delantera invirtiendo la normal
+    if uniforms.double_sided && world_normal.dot(uniforms.camera_position - world) < 0.0 {
+        normal = -normal;
+        world_normal = -world_normal;
+    }
+
+    // Detalle del mapa de normales del cuerpo, si tiene uno (y la malla, UV)
+    if let Some(normal_map) = uniforms.normal_map {
+        normal = normal_mapped(normal, fragment.tangent, fragment.tex_coords, normal_map);
+        world_normal = transform_normal(&normal, &uniforms.model.normal_matrix);
+    }
+
+    // Relleno ambiental direccional (SH del entorno) según la normal en el mundo,
+    // y cada luz del rig atenuada según su distancia al fragmento
+    let lighting = SurfaceLight::new(&pos, world, world_normal, uniforms);
+
+    // Color iluminado y emisión propia (lava, pulso del cristal), que se suma al final
+    let (color, emission) = match planet_type {
+        0 => rocky_planet_shader(&pos, time, &normal, uniforms.layer_mask, &lighting, &uniforms.shader_params),
+        1 => gas_giant_shader(&pos, time, &normal, uniforms.layer_mask, &lighting, &uniforms.shader_params),
+        2 => ocean_planet_shader(&pos, time, &normal, uniforms.layer_mask, &lighting, &uniforms.shader_params),
+        3 => volcanic_planet_shader(&pos, time, &normal, uniforms.layer_mask, &lighting, &uniforms.shader_params),
+        4 => {
+            let footprint = if uniforms.specular_aa { pixel_footprint(&pos, uniforms) } else { 0.0 };
+            crystal_planet_shader(&pos, time, &normal, uniforms.layer_mask, &lighting, &uniforms.shader_params, footprint)
+        }
+        10 => {
+            let default = Material::default();
+            let material = uniforms.materials.get(fragment.material_id).unwrap_or(&default);
+            let albedo = uniforms
+                .texture
+                .map_or(Vector3::one(), |texture| texture.sample(fragment.tex_coords.x, fragment.tex_coords.y));
+            (material_shader(&normal, &lighting, material, albedo), Vector3::zero())
+        }
+        _ => (Vector3::new(0.5, 0.5, 0.5), Vector3::zero()),
     };
 
-    // Clamp valores entre 0 y 1
-    Vector3::new(
-        color.x.max(0.0).min(1.0),
-        color.y.max(0.0).min(1.0),
-        color.z.max(0.0).min(1.0),
-    )
+    // El planeta con anillos (tipo 3) recibe su sombra
+    let color = match uniforms.lights.primary() {
+        Some(sun) if uniforms.ring_shadow && planet_type == 3 => {
+            color * ring_shadow(world, &uniforms.model.matrix, sun)
+        }
+        _ => color,
+    };
+
+    // Niebla sobre el color ya iluminado. La emisión se suma después: ni la
+    // sombra de los anillos ni la niebla apagan la lava. La exposición y el
+    // clamp vienen después
+    uniforms.fog.apply(color, world, uniforms) + emission
+}
+
+/// Last step of the fragment shader: exposure, then back to [0, 1], then
+/// gamma if it's on. The debug views are shown as they are
+pub fn resolve_color(color: Vector3, uniforms: &Uniforms) -> Vector3 {
+    if (3..=5).contains(&uniforms.render_type) {
+        return color;
+    }
+
+    // Exposición (auto-exposure la ajusta frame a frame)
+    let color = color * uniforms.exposure;
+
+    // De vuelta a [0, 1] según el modo elegido (por defecto, clamp por canal)
+    let color = uniforms.color_clamp.apply(color);
+
+    if uniforms.gamma_correct { gamma_encode(color) } else { color }
+}
+
+// Gamma de la pantalla: el color lineal se codifica con 1 / DISPLAY_GAMMA
+const DISPLAY_GAMMA: f32 = 2.2;
+
+/// Linear color in [0, 1] encoded for the display (`c^(1/2.2)`), so the
+/// mid-tones the lighting computes aren't shown darker than they are
+pub fn gamma_encode(color: Vector3) -> Vector3 {
+    let encode = |c: f32| c.max(0.0).powf(1.0 / DISPLAY_GAMMA);
+    Vector3::new(encode(color.x), encode(color.y), encode(color.z))
 }
 
 pub fn set_planet_type(_planet_type: i32) {
     // Función legacy - el tipo se pasa en uniforms
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Puntos de una retícula que no coincide con la del ruido
+    fn grid_points() -> impl Iterator<Item = Vector3> {
+        (0..24).flat_map(|i| (0..24).flat_map(move |j| (0..8).map(move |k| {
+            Vector3::new(i as f32 * 0.37 - 4.1, j as f32 * 0.29 + 1.3, k as f32 * 0.53 - 2.2)
+        })))
+    }
+
+    #[test]
+    fn noise_stays_in_range_and_varies() {
+        let values: Vec<f32> = grid_points().map(|p| noise3d(&p, 0)).collect();
+        assert!(values.iter().all(|v| (-1.0..=1.0).contains(v)));
+        let mean = values.iter().sum::<f32>() / values.len() as f32;
+        let spread = values.iter().map(|v| (v - mean).abs()).sum::<f32>() / values.len() as f32;
+        assert!(mean.abs() < 0.05, "media {}", mean);
+        assert!(spread > 0.15, "dispersión {}", spread);
+
+        // Ruido de gradiente: cero en los vértices de la retícula
+        assert_eq!(noise3d(&Vector3::new(3.0, -7.0, 12.0), 0), 0.0);
+        assert!(grid_points().all(|p| (0.0..=1.0).contains(&turbulence(&p, 4, 0))));
+        assert!(grid_points().all(|p| fbm(&p, 4, 0).abs() <= 1.0));
+    }
+
+    #[test]
+    fn noise_has_no_axis_aligned_period() {
+        let axes = [Vector3::new(1.0, 0.0, 0.0), Vector3::new(0.0, 1.0, 0.0), Vector3::new(0.0, 0.0, 1.0)];
+        for axis in axes {
+            for period in 1..=64 {
+                let shift = axis * period as f32;
+                let (total, count) = grid_points().fold((0.0, 0), |(total, count), p| {
+                    (total + (noise3d(&p, 0) - noise3d(&(p + shift), 0)).abs(), count + 1)
+                });
+                // Repetido, la diferencia media sería cero
+                assert!(total / count as f32 > 0.15, "periodo {} en {:?}", period, axis);
+            }
+        }
+    }
+
+    #[test]
+    fn seeds_change_the_noise_but_not_its_continuity() {
+        let p = Vector3::new(0.4, 2.7, -1.9);
+        assert_ne!(noise3d(&p, 0), noise3d(&p, 1));
+        for seed in [0, 1, 7] {
+            let step = Vector3::new(1e-3, 1e-3, 1e-3);
+            assert!(grid_points().all(|p| (noise3d(&p, seed) - noise3d(&(p + step), seed)).abs() < 0.02));
+        }
+    }
+
+    #[test]
+    fn flat_normal_map_leaves_the_normal_unperturbed() {
+        let flat = Texture::from_colors(1, 1, &[Color::new(128, 128, 255, 255)]);
+        let normals = [Vector3::new(0.0, 0.0, 1.0), Vector3::new(1.0, 2.0, -0.5).normalized()];
+        // Una tangente que no es perpendicular a la normal, como tras interpolar
+        let tangents = [Vector4::new(1.0, 0.0, 0.0, 1.0), Vector4::new(0.3, -1.0, 0.8, -1.0)];
+        for normal in normals {
+            for tangent in tangents {
+                let mapped = normal_mapped(normal, tangent, Vector2::new(0.3, 0.7), &flat);
+                assert!((mapped - normal).length() < 1e-5, "{:?} -> {:?}", normal, mapped);
+            }
+        }
+    }
+
+    #[test]
+    fn normal_map_tilts_along_the_tangent_only_with_uvs() {
+        // Todo hacia +X de la tangente
+        let tilted = Texture::from_colors(1, 1, &[Color::new(255, 128, 128, 255)]);
+        let normal = Vector3::new(0.0, 1.0, 0.0);
+        let mapped = normal_mapped(normal, Vector4::new(0.0, 0.0, 1.0, 1.0), Vector2::zero(), &tilted);
+        assert!((mapped - Vector3::new(0.0, 0.0, 1.0)).length() < 1e-5);
+        // Sin UV la tangente es cero: queda la normal geométrica
+        let mapped = normal_mapped(normal, Vector4::new(0.0, 0.0, 0.0, 0.0), Vector2::zero(), &tilted);
+        assert_eq!(mapped, normal);
+    }
+
+    #[test]
+    fn toksvig_widens_the_lobe_as_the_normals_spread() {
+        for shininess in [8.0, 32.0, 256.0] {
+            assert_eq!(toksvig_shininess(shininess, 1.0), shininess);
+            let mut previous = shininess;
+            for step in 1..=20 {
+                let lowered = toksvig_shininess(shininess, 1.0 - step as f32 * 0.045);
+                assert!(lowered < previous && lowered > 0.0, "{} con {}: {}", shininess, step, lowered);
+                previous = lowered;
+            }
+        }
+
+        // Exponente más bajo: lóbulo más ancho, con más brillo fuera del pico
+        let off_peak = 0.95_f32;
+        let sharp = toksvig_shininess(64.0, 1.0);
+        let spread = toksvig_shininess(64.0, 0.9);
+        assert!(off_peak.powf(spread) > off_peak.powf(sharp));
+    }
+
+    // Colores de `uniforms` a lo largo de un arco del ecuador de la esfera unitaria, frente a la cámara
+    fn shade_along_the_equator(uniforms: &Uniforms, samples: usize) -> Vec<Vector3> {
+        (0..samples)
+            .map(|i| {
+                let angle = (i as f32 / samples as f32 - 0.5) * 1.2;
+                let position = Vector3::new(angle.sin(), 0.0, angle.cos());
+                shade_fragment(&Fragment::new(0.0, 0.0, Vector3::zero(), 0.5, position), uniforms)
+            })
+            .collect()
+    }
+
+    // Energía de alta frecuencia: media de la segunda diferencia entre muestras vecinas
+    fn roughness(colors: &[Vector3]) -> f32 {
+        colors.windows(3).map(|c| (c[0] - c[1] * 2.0 + c[2]).length()).sum::<f32>() / (colors.len() - 2) as f32
+    }
+
+    #[test]
+    fn detail_layer_carries_the_high_frequencies() {
+        let environment = Environment::starfield();
+        let all = crate::tests::test_uniforms(&environment, 64, 64);
+        let without_detail = Uniforms { layer_mask: LAYER_ALL & !0b1000, ..all.clone() };
+        let with = shade_along_the_equator(&all, 2000);
+        let without = shade_along_the_equator(&without_detail, 2000);
+        assert_ne!(with, without);
+        assert!(roughness(&without) < roughness(&with) * 0.7, "{} vs {}", roughness(&without), roughness(&with));
+    }
+
+    #[test]
+    fn preserve_hue_keeps_the_channel_ratios_of_bright_colors() {
+        for color in [Vector3::new(3.0, 1.5, 0.3), Vector3::new(1.2, 0.1, 0.9), Vector3::new(0.2, 5.0, 2.5)] {
+            let kept = ColorClampMode::PreserveHue.apply(color);
+            let scale = color.x.max(color.y).max(color.z);
+            assert!((kept - color / scale).length() < 1e-5, "{:?} -> {:?}", color, kept);
+            assert!((kept.x.max(kept.y).max(kept.z) - 1.0).abs() < 1e-5);
+
+            // El clamp por canal sí cambia el tono: el rojo (3, 1.5, 0.3) acaba amarillo
+            let clamped = ColorClampMode::HardClamp.apply(color);
+            assert!((clamped - kept).length() > 0.1);
+        }
+        // Un color que ya cabe no se toca
+        let dim = Vector3::new(0.4, 0.7, 0.1);
+        assert_eq!(ColorClampMode::PreserveHue.apply(dim), dim);
+    }
+
+    #[test]
+    fn shadowed_side_keeps_the_ambient_floor() {
+        let environment = Environment::starfield();
+        // Una luz puntual lejana detrás del planeta, con caída fuerte: el lado
+        // que ve la cámara no recibe nada de luz directa
+        let lights = [Light::point(Vector3::new(0.0, 0.0, -100.0))];
+        let night = |ambient_floor: f32| Uniforms {
+            lights: crate::light::LightRig::new(&lights),
+            light_falloff: 2.0,
+            ambient_floor,
+            ..crate::tests::test_uniforms(&environment, 64, 64)
+        };
+        let dim = shade_along_the_equator(&night(0.3), 16);
+        let bright = shade_along_the_equator(&night(0.6), 16);
+        for (dim, bright) in dim.iter().zip(&bright) {
+            assert!(dim.x + dim.y + dim.z > 0.01, "{:?}", dim);
+            // Solo queda el término ambiental, proporcional al mínimo
+            assert!((*bright - *dim * 2.0).length() < 1e-4, "{:?} vs {:?}", dim, bright);
+        }
+    }
+
+    #[test]
+    fn shader_param_overrides_change_the_same_planet_type() {
+        let environment = Environment::starfield();
+        let base = crate::tests::test_uniforms(&environment, 64, 64);
+        let mut palette = [None; PALETTE_SLOTS];
+        palette[0] = Some(Vector3::new(0.8, 0.2, 0.1));
+        // Los dos son rocosos: un Marte rojo frente a la luna gris de siempre
+        let overrides = [
+            ShaderParams { palette, ..ShaderParams::default() },
+            ShaderParams { noise_scale: 2.5, ..ShaderParams::default() },
+            ShaderParams { seed: 7, ..ShaderParams::default() },
+        ];
+        let reference = shade_along_the_equator(&base, 200);
+        for shader_params in overrides {
+            let uniforms = Uniforms { shader_params, ..base.clone() };
+            assert_ne!(shade_along_the_equator(&uniforms, 200), reference, "{:?}", shader_params);
+        }
+    }
+
+    #[test]
+    fn cloud_shell_turns_at_its_own_speed() {
+        let environment = Environment::starfield();
+        let sphere = crate::obj::Obj::icosphere(Vector3::zero(), 1.0, 3);
+        let sphere = sphere.get_vertex_array();
+        let light = Light::point(Vector3::new(5.0, 0.0, 5.0));
+        let clouds = |time: f32, speed: f32, surface_rotation: Option<f32>| {
+            let uniforms = Uniforms {
+                time,
+                shader_params: ShaderParams { surface_rotation, ..ShaderParams::default() },
+                ..crate::tests::test_uniforms(&environment, 64, 64)
+            };
+            let mut framebuffer = Framebuffer::new(64, 64);
+            render_cloud_layer(&mut framebuffer, &uniforms, sphere, 1.05, speed, 0.5, &light);
+            framebuffer.to_rgba_bytes()
+        };
+
+        // Quietas, las nubes no cambian con el tiempo aunque la superficie gire
+        assert_eq!(clouds(0.0, 0.0, None), clouds(4.0, 0.0, Some(0.8)));
+        // Con su velocidad sí se mueven, y la de la superficie no las arrastra
+        let moving = clouds(4.0, 0.3, None);
+        assert_ne!(moving, clouds(0.0, 0.3, None));
+        assert_eq!(moving, clouds(4.0, 0.3, Some(0.8)));
+        assert_ne!(moving, clouds(4.0, 0.8, None));
+    }
+
+    #[test]
+    fn rings_shade_the_fragment_behind_them_from_the_light() {
+        // Un punto del hemisferio sur y una luz que, vista desde él, pasa por
+        // el plano de los anillos a radio 2, en una banda y no en un hueco
+        let point = Vector3::new(0.9, -0.436, 0.0);
+        let toward_light = Vector3::new(1.1 / 0.436, 1.0, 0.0).normalized();
+        let sun = Light::directional(-toward_light);
+        let identity = Matrix::identity();
+        assert!((ring_shadow(point, &identity, &sun) - (1.0 - RING_SHADOW_OPACITY)).abs() < 1e-6);
+        // Con la luz en otro sitio la banda se mueve y el punto queda libre
+        assert_eq!(ring_shadow(point, &identity, &Light::directional(Vector3::new(0.0, -1.0, 0.0))), 1.0);
+        // Y sobre el plano no hay nada entre el punto y la luz
+        assert_eq!(ring_shadow(Vector3::new(0.9, 0.436, 0.0), &identity, &sun), 1.0);
+
+        // En el shader del planeta con anillos
+        let environment = Environment::starfield();
+        let lights = [sun];
+        let uniforms = Uniforms {
+            planet_type: 3,
+            material: SurfaceMaterial::for_planet_type(3),
+            lights: crate::light::LightRig::new(&lights),
+            ..crate::tests::test_uniforms(&environment, 64, 64)
+        };
+        let fragment = Fragment::new(0.0, 0.0, Vector3::zero(), 0.5, point);
+        let lit = shade_fragment(&fragment, &uniforms);
+        let shadowed = shade_fragment(&fragment, &Uniforms { ring_shadow: true, ..uniforms.clone() });
+        assert!(shadowed.x + shadowed.y + shadowed.z < (lit.x + lit.y + lit.z) * 0.9, "{:?} vs {:?}", shadowed, lit);
+    }
+
+    #[test]
+    fn parallax_shifts_away_from_a_grazing_viewer() {
+        let pos = Vector3::new(0.0, 0.0, 1.0);
+        let normal = Vector3::new(0.0, 0.0, 1.0);
+        // Terreno plano a media profundidad
+        let height = |_: Vector3| 0.5;
+        let grazing = Vector3::new(0.95, 0.0, 0.31).normalized();
+        let head_on = Vector3::new(0.2, 0.0, 0.98).normalized();
+
+        let shifted = parallax_offset(pos, normal, grazing, 0.1, height) - pos;
+        // En el plano tangente y alejándose de quien mira
+        assert!(shifted.z.abs() < 1e-6 && shifted.y.abs() < 1e-6, "{:?}", shifted);
+        assert!(shifted.x < -0.05, "{:?}", shifted);
+        // De frente el desplazamiento es mucho menor
+        let straight = parallax_offset(pos, normal, head_on, 0.1, height) - pos;
+        assert!(straight.x < 0.0 && straight.x > shifted.x * 0.5, "{:?} vs {:?}", straight, shifted);
+
+        assert_eq!(parallax_offset(pos, normal, grazing, 0.0, height), pos);
+    }
+}
+
@@ -0,0 +1,108 @@
+// stats.rs
+use crate::settings::RenderSettings;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+// Filas entre vaciados del archivo: ante un cierre abrupto se pierde a lo sumo un segundo
+const STATS_FLUSH_INTERVAL: u32 = 60;
+
+/// What one frame cost the renderer. The framebuffer counts it while the
+/// frame is drawn and `Framebuffer::clear` resets it.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RenderStats {
+    pub triangles_submitted: u32, // Triángulos de los cuerpos que llegaron al rasterizador
+    pub triangles_culled: u32,    // De esos, los que no dejaron fragmentos en pantalla (fuera o degenerados)
+    pub fragments: u32,           // Fragmentos que pasaron por el test de profundidad
+}
+
+/// Per-frame `RenderStats` appended to a CSV file (`--log stats.csv`), one
+/// row per frame under a header, so a session can be plotted afterwards.
+/// The writer is flushed every `STATS_FLUSH_INTERVAL` rows.
+pub struct StatsLog<W: Write> {
+    writer: W,
+    frame: u32,
+}
+
+impl StatsLog<BufWriter<File>> {
+    pub fn create(path: &str) -> io::Result<Self> {
+        StatsLog::new(BufWriter::new(File::create(path)?))
+    }
+}
+
+impl<W: Write> StatsLog<W> {
+    /// Writes the header right away
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writeln!(writer, "frame,frame_time_ms,triangles_submitted,triangles_culled,fragments,effects")?;
+        Ok(StatsLog { writer, frame: 0 })
+    }
+
+    pub fn log(&mut self, frame_time: f32, stats: &RenderStats, settings: &RenderSettings) -> io::Result<()> {
+        writeln!(
+            self.writer,
+            "{},{:.3},{},{},{},{}",
+            self.frame,
+            frame_time * 1000.0,
+            stats.triangles_submitted,
+            stats.triangles_culled,
+            stats.fragments,
+            active_effects(settings),
+        )?;
+        self.frame += 1;
+        if self.frame.is_multiple_of(STATS_FLUSH_INTERVAL) {
+            self.writer.flush()?;
+        }
+        Ok(())
+    }
+}
+
+// Efectos activos separados por `+` (sin comas, para no romper la columna)
+fn active_effects(settings: &RenderSettings) -> String {
+    let effects = [
+        (settings.post.bloom_enabled, "bloom"),
+        (settings.post.god_rays_enabled, "god_rays"),
+        (settings.post.dof_enabled, "dof"),
+        (settings.auto_exposure, "auto_exposure"),
+        (settings.cloud_shell, "clouds"),
+        (settings.fog_density > 0.0, "fog"),
+        (settings.gamma_correct, "gamma"),
+        (settings.ring_shadow, "ring_shadow"),
+        (settings.dithered_transparency, "dithered"),
+        (settings.target_aspect.is_some(), "letterbox"),
+        (settings.supersampling > 1, "supersampling"),
+        (settings.nave_wireframe.is_some(), "wireframe"),
+        (settings.body_fill.draws_edges(), "body_wireframe"),
+        (settings.show_orbits, "orbits"),
+        (settings.rear_view, "rear_view"),
+        (settings.level_of_detail, "lod"),
+    ];
+    effects
+        .iter()
+        .filter(|(enabled, _)| *enabled)
+        .map(|(_, name)| *name)
+        .collect::<Vec<_>>()
+        .join("+")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_a_header_and_one_row_per_frame() {
+        let mut output = Vec::new();
+        let settings = RenderSettings { show_orbits: false, gamma_correct: true, ..RenderSettings::default() };
+        let stats = RenderStats { triangles_submitted: 960, triangles_culled: 410, fragments: 12345 };
+        {
+            let mut log = StatsLog::new(&mut output).unwrap();
+            log.log(0.0165, &stats, &settings).unwrap();
+            log.log(0.02, &RenderStats::default(), &settings).unwrap();
+        }
+
+        let text = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "frame,frame_time_ms,triangles_submitted,triangles_culled,fragments,effects");
+        assert_eq!(lines[1], "0,16.500,960,410,12345,auto_exposure+gamma+lod");
+        assert_eq!(lines[2], "1,20.000,0,0,0,auto_exposure+gamma+lod");
+        assert_eq!(lines.len(), 3);
+    }
+}
@@ -0,0 +1,172 @@
+// surface_cache.rs
+use crate::Uniforms;
+use crate::framebuffer::DepthBias;
+use crate::light::LightRig;
+use crate::matrix::Handedness;
+use crate::shaders::{Fog, ShaderParams, SurfaceMaterial};
+use crate::obj::Winding;
+use crate::triangle::{CullMode, RasterPrecision};
+use crate::vertex::Vertex;
+use raylib::prelude::*;
+
+/// One shaded fragment as `render_body` writes it: pixel, depth and the
+/// linear color before exposure
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ShadedFragment {
+    pub x: i32,
+    pub y: i32,
+    pub depth: f32,
+    pub color: Vector3,
+}
+
+/// Everything a body's shaded fragments depend on. Exposure and the color
+/// clamp aren't part of it: they're applied when the fragments are written,
+/// so auto-exposure settling doesn't throw the cache away.
+#[derive(Clone, Debug, PartialEq)]
+struct SurfaceKey {
+    mesh: (usize, usize), // Dirección y largo del arreglo de vértices
+    materials: (usize, usize), // Lo mismo para los materiales de la malla
+    texture: usize,            // Dirección de la textura (0: sin textura)
+    environment: usize,
+    model_matrix: Matrix,
+    view_matrix: Matrix,
+    projection_matrix: Matrix,
+    viewport_matrix: Matrix,
+    time: f32,
+    planet_type: i32,
+    render_type: i32,
+    handedness: Handedness,
+    layer_mask: u32,
+    raster_precision: RasterPrecision,
+    specular_aa: bool,
+    double_sided: bool,
+    ring_shadow: bool,
+    depth_bias: DepthBias,
+    deterministic_draw: bool,
+    lights: LightRig,
+    light_falloff: f32,
+    ambient_floor: f32,
+    shader_params: ShaderParams,
+    material: SurfaceMaterial,
+    fog: Fog,
+    cull_mode: CullMode,
+    front_face: Winding,
+}
+
+impl SurfaceKey {
+    fn new(uniforms: &Uniforms, vertex_array: &[Vertex]) -> Self {
+        SurfaceKey {
+            mesh: (vertex_array.as_ptr() as usize, vertex_array.len()),
+            materials: (uniforms.materials.as_ptr() as usize, uniforms.materials.len()),
+            texture: uniforms.texture.map_or(0, |texture| texture as *const _ as usize),
+            environment: uniforms.environment as *const _ as usize,
+            model_matrix: uniforms.model.matrix,
+            view_matrix: uniforms.view_matrix,
+            projection_matrix: uniforms.projection_matrix,
+            viewport_matrix: uniforms.viewport_matrix,
+            time: uniforms.time,
+            planet_type: uniforms.planet_type,
+            render_type: uniforms.render_type,
+            handedness: uniforms.handedness,
+            layer_mask: uniforms.layer_mask,
+            raster_precision: uniforms.raster_precision,
+            specular_aa: uniforms.specular_aa,
+            double_sided: uniforms.double_sided,
+            ring_shadow: uniforms.ring_shadow,
+            depth_bias: uniforms.depth_bias,
+            deterministic_draw: uniforms.deterministic_draw,
+            lights: uniforms.lights,
+            light_falloff: uniforms.light_falloff,
+            ambient_floor: uniforms.ambient_floor,
+            shader_params: uniforms.shader_params,
+            material: uniforms.material,
+            fog: uniforms.fog,
+            cull_mode: uniforms.cull_mode,
+            front_face: uniforms.front_face,
+        }
+    }
+}
+
+/// The shaded fragments of one body from the last frame it was drawn. While
+/// nothing it depends on changes (the simulation is paused and the camera
+/// holds still) they are written again as they are, skipping the vertex
+/// shader, the rasterizer and the procedural noise. Any change to the key
+/// (time, camera, a shader parameter, a render setting) reshades the body.
+#[derive(Default)]
+pub struct SurfaceCache {
+    key: Option<SurfaceKey>,
+    fragments: Vec<ShadedFragment>,
+}
+
+impl SurfaceCache {
+    pub fn new() -> Self {
+        SurfaceCache::default()
+    }
+
+    /// Fragments for `uniforms` and `vertex_array`: the cached ones when the
+    /// key matches, otherwise the result of `shade`, kept for next time
+    pub fn fragments<F>(&mut self, uniforms: &Uniforms, vertex_array: &[Vertex], shade: F) -> &[ShadedFragment]
+    where
+        F: FnOnce() -> Vec<ShadedFragment>,
+    {
+        let key = SurfaceKey::new(uniforms, vertex_array);
+        if self.key.as_ref() != Some(&key) {
+            self.fragments = shade();
+            self.key = Some(key);
+        }
+        &self.fragments
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::environment::Environment;
+    use crate::framebuffer::Framebuffer;
+    use crate::light::Light;
+    use crate::obj::Obj;
+    use std::cell::RefCell;
+
+    #[test]
+    fn reshades_only_when_the_key_changes() {
+        let environment = Environment::starfield();
+        let uniforms = crate::tests::test_uniforms(&environment, 64, 64);
+        let mesh = Obj::icosphere(Vector3::zero(), 1.0, 1);
+        let mesh = mesh.get_vertex_array();
+        let fragment = ShadedFragment { x: 1, y: 2, depth: 0.5, color: Vector3::one() };
+
+        let mut cache = SurfaceCache::new();
+        let mut shaded = 0;
+        for time in [0.0, 0.0, 0.0, 1.0] {
+            let uniforms = Uniforms { time, ..uniforms.clone() };
+            cache.fragments(&uniforms, mesh, || {
+                shaded += 1;
+                vec![fragment]
+            });
+        }
+        // El primer frame y el del tiempo nuevo
+        assert_eq!(shaded, 2);
+    }
+
+    #[test]
+    fn cached_surface_matches_the_live_one() {
+        let environment = Environment::starfield();
+        let uniforms = crate::tests::test_uniforms(&environment, 64, 64);
+        let mesh = Obj::icosphere(Vector3::zero(), 1.0, 2);
+        let mesh = mesh.get_vertex_array();
+        let light = Light::point(Vector3::zero());
+
+        let mut live = Framebuffer::new(64, 64);
+        crate::render_body(&mut live, &uniforms, mesh, &light);
+
+        // Dos frames iguales: el segundo sale entero de la caché
+        let mut body = crate::CelestialBody::new(0, 0.0, 0.0, 0.0, 1.0, "Roca".to_string());
+        body.surface_cache = Some(RefCell::new(SurfaceCache::new()));
+        for _ in 0..2 {
+            let mut cached = Framebuffer::new(64, 64);
+            crate::render_celestial_body(&mut cached, &uniforms, mesh, &light, &body);
+            assert_eq!(cached.to_rgba_bytes(), live.to_rgba_bytes());
+        }
+    }
+}
+
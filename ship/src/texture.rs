@@ -0,0 +1,119 @@
+// texture.rs
+use raylib::prelude::*;
+use std::io;
+
+/// What `Texture::sample` does with coordinates outside [0, 1]
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum TextureWrap {
+    #[default]
+    Repeat, // El patrón se repite (coordenada módulo 1)
+    Clamp,  // Se estira el texel del borde
+}
+
+/// An image sampled by UV coordinates with bilinear filtering, as linear
+/// RGB in [0, 1]. (0, 0) is the bottom-left corner and (1, 1) the top-right,
+/// the OBJ convention, so `v` grows upwards while image rows go down.
+pub struct Texture {
+    width: usize,
+    height: usize,
+    texels: Vec<Vector3>,
+    pub wrap: TextureWrap,
+}
+
+impl Texture {
+    /// Pixels row by row, top row first, as an image stores them
+    pub fn from_colors(width: usize, height: usize, colors: &[Color]) -> Self {
+        assert_eq!(colors.len(), width * height, "color count doesn't match the size");
+        assert!(width > 0 && height > 0, "texture must have at least one texel");
+        let texels = colors
+            .iter()
+            .map(|color| Vector3::new(color.r as f32, color.g as f32, color.b as f32) / 255.0)
+            .collect();
+        Texture { width, height, texels, wrap: TextureWrap::default() }
+    }
+
+    /// Any image format raylib can load (PNG, JPG, BMP, TGA...)
+    pub fn load(path: &str) -> io::Result<Self> {
+        let image = Image::load_image(path).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
+        let (width, height) = (image.width().max(0) as usize, image.height().max(0) as usize);
+        if width == 0 || height == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "empty image"));
+        }
+        Ok(Texture::from_colors(width, height, &image.get_image_data()))
+    }
+
+    pub fn with_wrap(mut self, wrap: TextureWrap) -> Self {
+        self.wrap = wrap;
+        self
+    }
+
+    /// Filtered color at (u, v): the four nearest texel centers blended
+    pub fn sample(&self, u: f32, v: f32) -> Vector3 {
+        // Centros de texel en (i + 0.5) / tamaño; las filas van de arriba hacia abajo
+        let x = u * self.width as f32 - 0.5;
+        let y = (1.0 - v) * self.height as f32 - 0.5;
+        let x0 = x.floor();
+        let y0 = y.floor();
+        let fx = x - x0;
+        let fy = y - y0;
+        let (x0, y0) = (x0 as i64, y0 as i64);
+
+        let top = self.texel(x0, y0) * (1.0 - fx) + self.texel(x0 + 1, y0) * fx;
+        let bottom = self.texel(x0, y0 + 1) * (1.0 - fx) + self.texel(x0 + 1, y0 + 1) * fx;
+        top * (1.0 - fy) + bottom * fy
+    }
+
+    // Texel (x, y) con los índices fuera de la imagen resueltos según `wrap`
+    fn texel(&self, x: i64, y: i64) -> Vector3 {
+        let (width, height) = (self.width as i64, self.height as i64);
+        let (x, y) = match self.wrap {
+            TextureWrap::Repeat => (x.rem_euclid(width), y.rem_euclid(height)),
+            TextureWrap::Clamp => (x.clamp(0, width - 1), y.clamp(0, height - 1)),
+        };
+        self.texels[y as usize * self.width + x as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: Vector3, b: Vector3) {
+        assert!((a - b).length() < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    // Tablero de 4x4, blanco en la esquina de arriba a la izquierda
+    fn checker() -> Texture {
+        let colors: Vec<Color> = (0..16)
+            .map(|i| if (i % 4 + i / 4) % 2 == 0 { Color::WHITE } else { Color::BLACK })
+            .collect();
+        Texture::from_colors(4, 4, &colors)
+    }
+
+    #[test]
+    fn samples_texel_centers_and_blends_between_them() {
+        let texture = checker();
+        // v crece hacia arriba: la fila de arriba es v cerca de 1
+        assert_close(texture.sample(0.125, 0.875), Vector3::one());
+        assert_close(texture.sample(0.375, 0.875), Vector3::zero());
+        assert_close(texture.sample(0.125, 0.625), Vector3::zero());
+        assert_close(texture.sample(0.25, 0.875), Vector3::one() * 0.5);
+
+        let ramp = Texture::from_colors(2, 1, &[Color::BLACK, Color::WHITE]);
+        for t in [0.0, 0.3, 0.5, 1.0] {
+            let u = 0.25 + 0.5 * t;
+            assert_close(ramp.sample(u, 0.5), Vector3::one() * t);
+        }
+    }
+
+    #[test]
+    fn wraps_or_clamps_outside_the_unit_square() {
+        let ramp = Texture::from_colors(2, 1, &[Color::BLACK, Color::WHITE]);
+        // Repeat: a la izquierda del primer centro se mezcla con el último texel
+        assert_close(ramp.sample(0.0, 0.5), Vector3::one() * 0.5);
+        assert_close(ramp.sample(1.25, 0.5), Vector3::zero());
+        let clamped = Texture::from_colors(2, 1, &[Color::BLACK, Color::WHITE]).with_wrap(TextureWrap::Clamp);
+        assert_close(clamped.sample(0.0, 0.5), Vector3::zero());
+        assert_close(clamped.sample(1.5, 0.5), Vector3::one());
+    }
+}
@@ -1,7 +1,8 @@
 use crate::fragment::Fragment;
 use crate::vertex::Vertex;
 use crate::light::Light;
-use raylib::prelude::Vector3;
+use crate::matrix::multiply_matrix_vector4;
+use raylib::prelude::{Matrix, Vector3, Vector4};
 
 fn barycentric_coordinates(p_x: f32, p_y: f32, a: &Vertex, b: &Vertex, c: &Vertex)  -> (f32, f32, f32) {
     let a_x = a.transformed_position.x;   
@@ -24,16 +25,176 @@ fn barycentric_coordinates(p_x: f32, p_y: f32, a: &Vertex, b: &Vertex, c: &Verte
     (w1, w2, w3)
 }
 
-pub fn triangle(v1: &Vertex, v2: &Vertex, v3: &Vertex, light: &Light) -> Vec<Fragment> {
-    let mut fragments = Vec::new();
+/// How `triangle()` decides pixel coverage.
+///
+/// `Fixed`, the default, snaps the vertices to a 1/256 pixel grid and
+/// evaluates the edge functions exactly in integers with a top-left fill
+/// rule, so every pixel on an edge shared by two triangles belongs to
+/// exactly one of them: a mesh tiles the screen with no cracks and no
+/// pixel shaded twice. `Float` evaluates the edge functions in f32 at pixel
+/// centers and keeps every pixel with all three >= 0, so pixel centers
+/// lying exactly on a shared edge are drawn by both triangles, and rounding
+/// can leave a rare one uncovered.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum RasterPrecision {
+    Float,
+    #[default]
+    Fixed,
+}
 
-    let base_color = Vector3::new(0.5, 0.5, 0.5);
+impl RasterPrecision {
+    pub fn name(self) -> &'static str {
+        match self {
+            RasterPrecision::Float => "Float",
+            RasterPrecision::Fixed => "Fixed",
+        }
+    }
+}
+
+/// Which faces are dropped before rasterizing, by whether their winding
+/// on screen makes them front or back faces
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum CullMode {
+    /// Both sides are drawn
+    None,
+    #[default]
+    Back,
+    Front,
+}
+
+impl CullMode {
+    pub fn next(self) -> CullMode {
+        match self {
+            CullMode::None => CullMode::Back,
+            CullMode::Back => CullMode::Front,
+            CullMode::Front => CullMode::None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CullMode::None => "None",
+            CullMode::Back => "Back",
+            CullMode::Front => "Front",
+        }
+    }
+
+    /// Whether a triangle with screen-space `signed_area` is dropped, when
+    /// front faces are the ones whose area has the sign of `front_face_sign`.
+    /// Degenerate triangles count as back faces
+    pub fn culls(self, signed_area: f32, front_face_sign: f32) -> bool {
+        let front = signed_area * front_face_sign > 0.0;
+        match self {
+            CullMode::None => false,
+            CullMode::Back => !front,
+            CullMode::Front => front,
+        }
+    }
+}
+
+/// Twice the area of the triangle on screen (y down), signed by its winding
+pub fn signed_area(v1: &Vertex, v2: &Vertex, v3: &Vertex) -> f32 {
+    let (a, b, c) = (v1.transformed_position, v2.transformed_position, v3.transformed_position);
+    (b.x - a.x) * (c.y - a.y) - (b.y - a.y) * (c.x - a.x)
+}
+
+// Bits de subpíxel del camino en punto fijo (1/256 de píxel)
+const SUBPIXEL_BITS: u32 = 8;
+const SUBPIXEL_ONE: i64 = 1 << SUBPIXEL_BITS;
+
+pub fn triangle(v1: &Vertex, v2: &Vertex, v3: &Vertex, light: &Light,
+                precision: RasterPrecision) -> Vec<Fragment> {
+    match precision {
+        RasterPrecision::Float => triangle_float(v1, v2, v3, light),
+        RasterPrecision::Fixed => triangle_fixed(v1, v2, v3, light),
+    }
+}
+
+/// Sutherland-Hodgman clip of a vertex-shaded triangle against the near
+/// plane (clip z >= -w, for either handedness) and the four side planes
+/// (-w <= x, y <= w): the part inside, as up to 5 triangles fanned with the
+/// original winding. Vertices created on a plane interpolate every
+/// attribute at the crossing and are projected with `viewport_matrix`.
+///
+/// The near plane is what keeps vertices behind the camera from dividing
+/// by a negative or tiny w and landing anywhere on the screen. The sides
+/// keep the new vertices on the near plane, which project far outside the
+/// viewport, from growing the rasterizer's bounding box without limit. The
+/// far plane isn't clipped. A triangle fully inside comes back unchanged,
+/// one fully outside any plane is dropped
+pub fn clip_to_frustum(v1: &Vertex, v2: &Vertex, v3: &Vertex, viewport_matrix: &Matrix) -> Vec<[Vertex; 3]> {
+    // Distancia con signo a cada plano (>= 0 dentro)
+    let planes: [fn(&Vector4) -> f32; 5] = [
+        |c| c.z + c.w,
+        |c| c.w + c.x,
+        |c| c.w - c.x,
+        |c| c.w + c.y,
+        |c| c.w - c.y,
+    ];
+    let inside = |v: &Vertex| planes.iter().all(|plane| plane(&v.clip_position) >= 0.0);
+    if inside(v1) && inside(v2) && inside(v3) {
+        return vec![[v1.clone(), v2.clone(), v3.clone()]];
+    }
+
+    // Un plano a la vez; el polígono conserva el orden de los vértices
+    let mut polygon = vec![v1.clone(), v2.clone(), v3.clone()];
+    for plane in planes {
+        let distances: Vec<f32> = polygon.iter().map(|v| plane(&v.clip_position)).collect();
+        if distances.iter().all(|&d| d >= 0.0) {
+            continue;
+        }
+        let mut clipped = Vec::with_capacity(polygon.len() + 1);
+        for i in 0..polygon.len() {
+            let next = (i + 1) % polygon.len();
+            let (da, db) = (distances[i], distances[next]);
+            if da >= 0.0 {
+                clipped.push(polygon[i].clone());
+            }
+            if (da >= 0.0) != (db >= 0.0) {
+                clipped.push(clip_vertex(&polygon[i], &polygon[next], da / (da - db), viewport_matrix));
+            }
+        }
+        polygon = clipped;
+        if polygon.len() < 3 {
+            return Vec::new();
+        }
+    }
+
+    (1..polygon.len() - 1)
+        .map(|i| [polygon[0].clone(), polygon[i].clone(), polygon[i + 1].clone()])
+        .collect()
+}
+
+// Vértice en la fracción t de a -> b; todo se interpola en espacio de clip
+fn clip_vertex(a: &Vertex, b: &Vertex, t: f32, viewport_matrix: &Matrix) -> Vertex {
+    let clip = a.clip_position.lerp(b.clip_position, t);
+    let ndc = Vector4::new(clip.x / clip.w, clip.y / clip.w, clip.z / clip.w, 1.0);
+    let screen = multiply_matrix_vector4(viewport_matrix, &ndc);
+    Vertex {
+        position: a.position.lerp(b.position, t),
+        normal: a.normal.lerp(b.normal, t),
+        tex_coords: a.tex_coords.lerp(b.tex_coords, t),
+        color: a.color.lerp(b.color, t),
+        tangent: a.tangent.lerp(b.tangent, t),
+        transformed_position: Vector3::new(screen.x, screen.y, screen.z),
+        transformed_normal: a.transformed_normal.lerp(b.transformed_normal, t).normalized(),
+        material_id: a.material_id,
+        clip_position: clip,
+    }
+}
+
+fn triangle_float(v1: &Vertex, v2: &Vertex, v3: &Vertex, light: &Light) -> Vec<Fragment> {
+    let mut fragments = Vec::new();
 
     let min_x = v1.transformed_position.x.min(v2.transformed_position.x).min(v3.transformed_position.x).floor() as i32;
     let max_x = v1.transformed_position.x.max(v2.transformed_position.x).max(v3.transformed_position.x).ceil() as i32;
     let min_y = v1.transformed_position.y.min(v2.transformed_position.y).min(v3.transformed_position.y).floor() as i32;
     let max_y = v1.transformed_position.y.max(v2.transformed_position.y).max(v3.transformed_position.y).ceil() as i32;
 
+    let (a, b, c) = (v1.transformed_position, v2.transformed_position, v3.transformed_position);
+    let signed_area = (b.x - a.x) * (c.y - a.y) - (b.y - a.y) * (c.x - a.x);
+    let slope = depth_slope(a, b, c);
+
     for y in min_y..=max_y {
         for x in min_x..=max_x {
             let p_x = x as f32 + 0.5; //sample at pixel center
@@ -43,62 +204,326 @@ pub fn triangle(v1: &Vertex, v2: &Vertex, v3: &Vertex, light: &Light) -> Vec<Fra
             let (w1, w2, w3) = barycentric_coordinates(p_x, p_y, v1, v2, v3);
 
             if w1 >= 0.0 && w2 >= 0.0 && w3 >= 0.0 {
+                let mut fragment = shade_fragment(p_x, p_y, (w1, w2, w3), v1, v2, v3, light);
+                fragment.signed_area = signed_area;
+                fragment.depth_slope = slope;
+                fragments.push(fragment);
+            }
+        }
+    }
 
-                // Interpolate normals using barycentric coordinates
-                let interpolated_normal = Vector3::new(
-                    w1 * v1.transformed_normal.x + w2 * v2.transformed_normal.x + w3 * v3.transformed_normal.x,
-                    w1 * v1.transformed_normal.y + w2 * v2.transformed_normal.y + w3 * v3.transformed_normal.y,
-                    w1 * v1.transformed_normal.z + w2 * v2.transformed_normal.z + w3 * v3.transformed_normal.z,
-                );
-
-                // Normalize the interpolated normal
-                let normal_length = (interpolated_normal.x * interpolated_normal.x + interpolated_normal.y * interpolated_normal.y + interpolated_normal.z * interpolated_normal.z).sqrt();
-
-                let mut normalized_normal = interpolated_normal;
-                if normal_length > 0.0 {
-                    normalized_normal.x /= normal_length;
-                    normalized_normal.y /= normal_length;
-                    normalized_normal.z /= normal_length;
-                }
-                
-                // Calculate position in world space for this fragment
-                let world_pos = Vector3::new(
-                    w1 * v1.position.x + w2 * v2.position.x + w3 * v3.position.x,
-                    w1 * v1.position.y + w2 * v2.position.y + w3 * v3.position.y,
-                    w1 * v1.position.z + w2 * v2.position.z + w3 * v3.position.z,
-                );
-
-                // Light direction (from surface to light) for this fragment
-                let mut light_dir = Vector3::new(
-                    light.position.x - world_pos.x,
-                    light.position.y - world_pos.y,
-                    light.position.z - world_pos.z,
-                );
-
-                // Normalize light direction
-                let light_length = (light_dir.x * light_dir.x + light_dir.y * light_dir.y + light_dir.z * light_dir.z).sqrt();
-                if light_length > 0.0 {
-                    light_dir.x /= light_length;
-                    light_dir.y /= light_length;
-                    light_dir.z /= light_length;
-                }
+    fragments
+}
+
+/// Steepest change of depth per pixel across the screen-space triangle
+/// `a, b, c` (x, y in pixels, z = depth): the larger of |dz/dx| and |dz/dy|
+/// of its plane. Grows without bound as the triangle turns edge-on;
+/// degenerate triangles get 0 (they produce no fragments anyway)
+pub fn depth_slope(a: Vector3, b: Vector3, c: Vector3) -> f32 {
+    let area = (b.x - a.x) * (c.y - a.y) - (b.y - a.y) * (c.x - a.x);
+    if area == 0.0 {
+        return 0.0;
+    }
+    let dz_dx = ((b.z - a.z) * (c.y - a.y) - (c.z - a.z) * (b.y - a.y)) / area;
+    let dz_dy = ((c.z - a.z) * (b.x - a.x) - (b.z - a.z) * (c.x - a.x)) / area;
+    dz_dx.abs().max(dz_dy.abs())
+}
+
+fn snap(value: f32) -> i64 {
+    (value * SUBPIXEL_ONE as f32).round() as i64
+}
 
-                // Calculate per-fragment lighting intensity using interpolated normal and light direction
-                let intensity = (normalized_normal.x * light_dir.x + normalized_normal.y * light_dir.y + normalized_normal.z * light_dir.z).max(0.0);
+// Función de arista: > 0 si p está del lado interior de a -> b
+fn edge_function(a: (i64, i64), b: (i64, i64), p: (i64, i64)) -> i64 {
+    (b.0 - a.0) * (p.1 - a.1) - (b.1 - a.1) * (p.0 - a.0)
+}
 
-                let shaded_color = Vector3::new(
-                    base_color.x * intensity,
-                    base_color.y * intensity,
-                    base_color.z * intensity,
-                );
+// Regla top-left (pantalla con y hacia abajo, triángulo de área positiva):
+// una arista compartida se recorre en sentidos opuestos por los dos vecinos,
+// así que exactamente uno de ellos se queda con los píxeles sobre ella
+fn is_top_left(a: (i64, i64), b: (i64, i64)) -> bool {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    dy < 0 || (dy == 0 && dx > 0)
+}
+
+fn triangle_fixed(v1: &Vertex, v2: &Vertex, v3: &Vertex, light: &Light) -> Vec<Fragment> {
+    let mut fragments = Vec::new();
 
-                // Interpolate depth using barycentric coordinates
-                let depth = w1 * v1.transformed_position.z + w2 * v2.transformed_position.z + w3 * v3.transformed_position.z;
+    let p1 = (snap(v1.transformed_position.x), snap(v1.transformed_position.y));
+    let mut p2 = (snap(v2.transformed_position.x), snap(v2.transformed_position.y));
+    let mut p3 = (snap(v3.transformed_position.x), snap(v3.transformed_position.y));
 
-                fragments.push(Fragment::new(p_x, p_y, shaded_color, depth, world_pos));
+    let mut area = edge_function(p1, p2, p3);
+    if area == 0 {
+        return fragments;
+    }
+    let signed_area = area as f32 / (SUBPIXEL_ONE * SUBPIXEL_ONE) as f32;
+    let slope = depth_slope(v1.transformed_position, v2.transformed_position, v3.transformed_position);
+    // Ambos sentidos de giro se rasterizan: se normaliza a área positiva
+    let flipped = area < 0;
+    if flipped {
+        std::mem::swap(&mut p2, &mut p3);
+        area = -area;
+    }
+
+    // Bias de -1 excluye los píxeles exactamente sobre aristas que no son top-left
+    let bias = |a, b| if is_top_left(a, b) { 0 } else { -1 };
+    let (bias1, bias2, bias3) = (bias(p2, p3), bias(p3, p1), bias(p1, p2));
+
+    let min_x = p1.0.min(p2.0).min(p3.0).div_euclid(SUBPIXEL_ONE);
+    let max_x = p1.0.max(p2.0).max(p3.0).div_euclid(SUBPIXEL_ONE);
+    let min_y = p1.1.min(p2.1).min(p3.1).div_euclid(SUBPIXEL_ONE);
+    let max_y = p1.1.max(p2.1).max(p3.1).div_euclid(SUBPIXEL_ONE);
+
+    for y in min_y..=max_y {
+        for x in min_x..=max_x {
+            // Centro del píxel en coordenadas de subpíxel
+            let p = (x * SUBPIXEL_ONE + SUBPIXEL_ONE / 2, y * SUBPIXEL_ONE + SUBPIXEL_ONE / 2);
+
+            let e1 = edge_function(p2, p3, p);
+            let e2 = edge_function(p3, p1, p);
+            let e3 = edge_function(p1, p2, p);
+
+            if e1 + bias1 >= 0 && e2 + bias2 >= 0 && e3 + bias3 >= 0 {
+                let w1 = e1 as f32 / area as f32;
+                let (mut w2, mut w3) = (e2 as f32 / area as f32, e3 as f32 / area as f32);
+                if flipped {
+                    std::mem::swap(&mut w2, &mut w3);
+                }
+
+                let (p_x, p_y) = (x as f32 + 0.5, y as f32 + 0.5);
+                let mut fragment = shade_fragment(p_x, p_y, (w1, w2, w3), v1, v2, v3, light);
+                fragment.signed_area = signed_area;
+                fragment.depth_slope = slope;
+                fragments.push(fragment);
             }
         }
     }
 
     fragments
-}
\ No newline at end of file
+}
+
+/// Screen-space barycentric weights made perspective-correct: each one is
+/// divided by its vertex's clip-space w and the three renormalized, so what
+/// they interpolate varies linearly across the triangle in 3D instead of on
+/// the screen. Triangles with a vertex on or behind the camera plane
+/// (w <= 0) keep the screen-space weights
+fn perspective_weights((w1, w2, w3): (f32, f32, f32), v1: &Vertex, v2: &Vertex, v3: &Vertex) -> (f32, f32, f32) {
+    let (cw1, cw2, cw3) = (v1.clip_position.w, v2.clip_position.w, v3.clip_position.w);
+    if cw1 <= 0.0 || cw2 <= 0.0 || cw3 <= 0.0 {
+        return (w1, w2, w3);
+    }
+    let (p1, p2, p3) = (w1 / cw1, w2 / cw2, w3 / cw3);
+    let inverse_w = p1 + p2 + p3; // 1/w interpolado en pantalla
+    (p1 / inverse_w, p2 / inverse_w, p3 / inverse_w)
+}
+
+// Interpola los atributos del triángulo en un punto cubierto y lo sombrea.
+// La profundidad (z de NDC, ya lineal en pantalla) usa los pesos de pantalla;
+// el resto de atributos, los corregidos por perspectiva
+fn shade_fragment(p_x: f32, p_y: f32, screen_weights: (f32, f32, f32),
+                  v1: &Vertex, v2: &Vertex, v3: &Vertex, light: &Light) -> Fragment {
+    let base_color = Vector3::new(0.5, 0.5, 0.5);
+    let (w1, w2, w3) = perspective_weights(screen_weights, v1, v2, v3);
+
+    // Interpolate normals using barycentric coordinates
+    let interpolated_normal = Vector3::new(
+        w1 * v1.transformed_normal.x + w2 * v2.transformed_normal.x + w3 * v3.transformed_normal.x,
+        w1 * v1.transformed_normal.y + w2 * v2.transformed_normal.y + w3 * v3.transformed_normal.y,
+        w1 * v1.transformed_normal.z + w2 * v2.transformed_normal.z + w3 * v3.transformed_normal.z,
+    );
+
+    // Normalize the interpolated normal
+    let normal_length = (interpolated_normal.x * interpolated_normal.x + interpolated_normal.y * interpolated_normal.y + interpolated_normal.z * interpolated_normal.z).sqrt();
+
+    let mut normalized_normal = interpolated_normal;
+    if normal_length > 0.0 {
+        normalized_normal.x /= normal_length;
+        normalized_normal.y /= normal_length;
+        normalized_normal.z /= normal_length;
+    }
+    
+    // Calculate position in world space for this fragment
+    let world_pos = Vector3::new(
+        w1 * v1.position.x + w2 * v2.position.x + w3 * v3.position.x,
+        w1 * v1.position.y + w2 * v2.position.y + w3 * v3.position.y,
+        w1 * v1.position.z + w2 * v2.position.z + w3 * v3.position.z,
+    );
+
+    // Light direction (from surface to light) for this fragment
+    let light_dir = light.direction_from(world_pos);
+
+    // Calculate per-fragment lighting intensity using interpolated normal and light direction
+    let intensity = (normalized_normal.x * light_dir.x + normalized_normal.y * light_dir.y + normalized_normal.z * light_dir.z).max(0.0);
+
+    let shaded_color = Vector3::new(
+        base_color.x * intensity,
+        base_color.y * intensity,
+        base_color.z * intensity,
+    );
+
+    // Interpolate depth using barycentric coordinates
+    let (s1, s2, s3) = screen_weights;
+    let depth = s1 * v1.transformed_position.z + s2 * v2.transformed_position.z + s3 * v3.transformed_position.z;
+
+    let mut fragment = Fragment::new(p_x, p_y, shaded_color, depth, world_pos);
+    fragment.barycentric = Vector3::new(s1, s2, s3);
+    fragment.normal = v1.normal * w1 + v2.normal * w2 + v3.normal * w3;
+    fragment.material_id = v1.material_id;
+    fragment.tex_coords = v1.tex_coords * w1 + v2.tex_coords * w2 + v3.tex_coords * w3;
+    fragment.vertex_color = v1.color * w1 + v2.color * w2 + v3.color * w3;
+    // Por componente: Vector4 no tiene suma ni escala (su `*` es el producto de cuaterniones)
+    fragment.tangent = Vector4::new(
+        w1 * v1.tangent.x + w2 * v2.tangent.x + w3 * v3.tangent.x,
+        w1 * v1.tangent.y + w2 * v2.tangent.y + w3 * v3.tangent.y,
+        w1 * v1.tangent.z + w2 * v2.tangent.z + w3 * v3.tangent.z,
+        w1 * v1.tangent.w + w2 * v2.tangent.w + w3 * v3.tangent.w,
+    );
+    fragment
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use raylib::prelude::Vector2;
+    use std::collections::HashMap;
+
+    fn screen_vertex(x: f32, y: f32) -> Vertex {
+        Vertex::new(Vector3::new(x, y, 0.5), Vector3::new(0.0, 0.0, 1.0), Vector2::zero())
+    }
+
+    // Cuántas veces se escribe cada píxel al rasterizar todos los triángulos
+    fn coverage(triangles: &[[(f32, f32); 3]], precision: RasterPrecision) -> HashMap<(i32, i32), u32> {
+        let light = Light::point(Vector3::zero());
+        let mut counts = HashMap::new();
+        for [a, b, c] in triangles {
+            let (v1, v2, v3) = (screen_vertex(a.0, a.1), screen_vertex(b.0, b.1), screen_vertex(c.0, c.1));
+            for fragment in triangle(&v1, &v2, &v3, &light, precision) {
+                *counts.entry((fragment.position.x as i32, fragment.position.y as i32)).or_insert(0) += 1;
+            }
+        }
+        counts
+    }
+
+    #[test]
+    fn fixed_point_covers_a_split_quad_exactly_once() {
+        // Todas las aristas, la diagonal incluida, pasan por centros de píxel:
+        // el peor caso para la propiedad de las aristas compartidas
+        let quad = [
+            [(0.5, 0.5), (8.5, 0.5), (8.5, 8.5)],
+            [(0.5, 0.5), (8.5, 8.5), (0.5, 8.5)],
+        ];
+        let counts = coverage(&quad, RasterPrecision::Fixed);
+        assert!(counts.values().all(|&count| count == 1));
+        let expected: Vec<(i32, i32)> = (0..8).flat_map(|y| (0..8).map(move |x| (x, y))).collect();
+        let mut covered: Vec<(i32, i32)> = counts.into_keys().collect();
+        covered.sort_by_key(|&(x, y)| (y, x));
+        assert_eq!(covered, expected);
+
+        // En f32 los centros sobre la diagonal los dibujan los dos triángulos
+        let float_counts = coverage(&quad, RasterPrecision::Float);
+        assert!(float_counts.values().any(|&count| count > 1));
+    }
+
+    #[test]
+    fn top_left_rule_tiles_a_fan_without_gaps_or_overlap() {
+        // Cuatro triángulos alrededor del centro, en ambos sentidos de giro:
+        // aristas compartidas diagonales, horizontales y verticales
+        let (c, l, r, t, b) = ((8.5, 8.5), 0.5, 16.5, 0.5, 16.5);
+        let fan = [
+            [c, (l, t), (r, t)],
+            [c, (r, b), (r, t)],
+            [c, (r, b), (l, b)],
+            [c, (l, t), (l, b)],
+            // Y un corte horizontal y otro vertical que pasan por centros de píxel
+            [(20.5, 0.5), (28.5, 0.5), (28.5, 4.5)],
+            [(20.5, 0.5), (28.5, 4.5), (20.5, 4.5)],
+            [(20.5, 4.5), (28.5, 4.5), (24.5, 8.5)],
+            [(20.5, 4.5), (24.5, 8.5), (20.5, 8.5)],
+            [(24.5, 8.5), (28.5, 4.5), (28.5, 8.5)],
+        ];
+        let counts = coverage(&fan, RasterPrecision::Fixed);
+        assert!(counts.values().all(|&count| count == 1));
+        // El cuadrado de 16x16 y el rectángulo de 8x8, enteros
+        for y in 0..16 {
+            for x in 0..16 {
+                assert!(counts.contains_key(&(x, y)), "hueco en ({}, {})", x, y);
+            }
+        }
+        for y in 0..8 {
+            for x in 20..28 {
+                assert!(counts.contains_key(&(x, y)), "hueco en ({}, {})", x, y);
+            }
+        }
+        assert_eq!(counts.len(), 16 * 16 + 8 * 8);
+    }
+
+    #[test]
+    fn interpolation_is_perspective_correct_on_a_receding_floor() {
+        use crate::matrix::{create_projection_matrix, create_viewport_matrix};
+        let projection = create_projection_matrix(std::f32::consts::FRAC_PI_3, 160.0 / 120.0, 0.1, 100.0);
+        let viewport = create_viewport_matrix(0.0, 0.0, 160.0, 120.0);
+        let to_screen = |world: Vector3| {
+            let clip = multiply_matrix_vector4(&projection, &Vector4::new(world.x, world.y, world.z, 1.0));
+            let screen = multiply_matrix_vector4(&viewport, &Vector4::new(clip.x / clip.w, clip.y / clip.w, clip.z / clip.w, 1.0));
+            (clip, Vector3::new(screen.x, screen.y, screen.z))
+        };
+        // Suelo de z = -1 a z = -40 visto desde el origen, casi rasante; v crece con la distancia
+        let floor_vertex = |x: f32, z: f32| {
+            let world = Vector3::new(x, -1.0, z);
+            let mut vertex = Vertex::new(world, Vector3::new(0.0, 1.0, 0.0), Vector2::new(0.0, (-z - 1.0) / 39.0));
+            (vertex.clip_position, vertex.transformed_position) = to_screen(world);
+            vertex
+        };
+        let corners = [floor_vertex(-1.0, -1.0), floor_vertex(1.0, -1.0), floor_vertex(1.0, -40.0), floor_vertex(-1.0, -40.0)];
+
+        let light = Light::point(Vector3::zero());
+        let (mut fragments, mut worst_screen_space) = (0, 0.0_f32);
+        for [a, b, c] in [[0, 1, 2], [0, 2, 3]] {
+            let (v1, v2, v3) = (&corners[a], &corners[b], &corners[c]);
+            for fragment in triangle(v1, v2, v3, &light, RasterPrecision::Fixed) {
+                fragments += 1;
+                // La posición interpolada vuelve a caer en el centro de su píxel
+                let (_, reprojected) = to_screen(fragment.world_position);
+                assert!((reprojected.x - fragment.position.x).abs() < 0.05 && (reprojected.y - fragment.position.y).abs() < 0.05,
+                        "{:?} -> {:?}", fragment.position, reprojected);
+                // Y la UV sigue a la distancia real: los cuadros del tablero se acortan con ella
+                assert!((fragment.tex_coords.y - (-fragment.world_position.z - 1.0) / 39.0).abs() < 1e-3);
+
+                let weights = fragment.barycentric;
+                let screen_space = v1.position * weights.x + v2.position * weights.y + v3.position * weights.z;
+                worst_screen_space = worst_screen_space.max((screen_space - fragment.world_position).length());
+            }
+        }
+        assert!(fragments > 100);
+        // Con los pesos de pantalla la posición se desviaría varias unidades
+        assert!(worst_screen_space > 5.0, "{}", worst_screen_space);
+    }
+
+    #[test]
+    fn barycentric_debug_is_one_pure_channel_at_a_vertex() {
+        let environment = crate::environment::Environment::starfield();
+        let uniforms = crate::Uniforms { render_type: 3, ..crate::tests::test_uniforms(&environment, 64, 64) };
+        let light = Light::point(Vector3::zero());
+        // El mismo triángulo empezando por cada vértice: la esquina en el centro
+        // del píxel (4, 4) es siempre la de arriba a la izquierda, que se cubre
+        let corners = [(4.5, 4.5), (28.5, 4.5), (4.5, 28.5)];
+        for precision in [RasterPrecision::Float, RasterPrecision::Fixed] {
+            for first in 0..3 {
+                let [a, b, c] = [0, 1, 2].map(|i| corners[(first + i) % 3]);
+                let fragments = triangle(&screen_vertex(a.0, a.1), &screen_vertex(b.0, b.1), &screen_vertex(c.0, c.1),
+                                         &light, precision);
+                let at_vertex = fragments.iter().find(|f| (f.position.x as i32, f.position.y as i32) == (4, 4)).unwrap();
+                let color = crate::shaders::shade_fragment(at_vertex, &uniforms);
+                // El vértice que está en (4, 4) es el `(3 - first) % 3` de la llamada
+                let mut expected = [0.0; 3];
+                expected[(3 - first) % 3] = 1.0;
+                assert!((color - Vector3::new(expected[0], expected[1], expected[2])).length() < 1e-5,
+                        "{:?} empezando en {}: {:?}", precision, first, color);
+                // En el resto, pesos que suman 1
+                assert!(fragments.iter().all(|f| (f.barycentric.x + f.barycentric.y + f.barycentric.z - 1.0).abs() < 1e-4));
+            }
+        }
+    }
+}
+
@@ -1,7 +1,7 @@
 //vertex.rs
 #![allow(dead_code)]
 
-use raylib::math::{Vector2, Vector3};
+use raylib::math::{Vector2, Vector3, Vector4};
 
 #[derive(Clone, Debug)]
 pub struct Vertex {
@@ -9,8 +9,11 @@ pub struct Vertex {
   pub normal: Vector3,
   pub tex_coords: Vector2,
   pub color: Vector3,
+  pub tangent: Vector4, // Dirección de u creciente (xyz) y signo de la bitangente (w); cero sin UV
   pub transformed_position: Vector3,
   pub transformed_normal: Vector3,
+  pub material_id: usize, // Material de su cara en la malla (ver Obj::materials)
+  pub clip_position: Vector4, // Posición en espacio de clip tras el vertex shader (recorte y perspectiva)
 }
 
 impl Vertex {
@@ -20,8 +23,11 @@ impl Vertex {
       normal,
       tex_coords,
       color: Vector3::new(0.0, 0.0, 0.0), // Black
+      tangent: Vector4::new(0.0, 0.0, 0.0, 0.0),
       transformed_position: position,
       transformed_normal: normal,
+      material_id: 0,
+      clip_position: Vector4::new(0.0, 0.0, 0.0, 1.0),
     }
   }
 
@@ -31,8 +37,11 @@ impl Vertex {
       normal: Vector3::new(0.0, 0.0, 0.0),
       tex_coords: Vector2::new(0.0, 0.0),
       color,
+      tangent: Vector4::new(0.0, 0.0, 0.0, 0.0),
       transformed_position: Vector3::new(0.0, 0.0, 0.0),
       transformed_normal: Vector3::new(0.0, 0.0, 0.0),
+      material_id: 0,
+      clip_position: Vector4::new(0.0, 0.0, 0.0, 1.0),
     }
   }
 
@@ -49,8 +58,11 @@ impl Default for Vertex {
       normal: Vector3::new(0.0, 1.0, 0.0),
       tex_coords: Vector2::new(0.0, 0.0),
       color: Vector3::new(0.0, 0.0, 0.0), // Black
+      tangent: Vector4::new(0.0, 0.0, 0.0, 0.0),
       transformed_position: Vector3::new(0.0, 0.0, 0.0),
       transformed_normal: Vector3::new(0.0, 1.0, 0.0),
+      material_id: 0,
+      clip_position: Vector4::new(0.0, 0.0, 0.0, 1.0),
     }
   }
 }
\ No newline at end of file
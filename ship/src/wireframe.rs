@@ -0,0 +1,168 @@
+// wireframe.rs
+use raylib::prelude::*;
+use crate::framebuffer::Framebuffer;
+use crate::line::{clip_line, line};
+use crate::triangle::{clip_to_frustum, triangle};
+use crate::vertex::Vertex;
+use crate::shaders::vertex_shader;
+use crate::light::Light;
+use crate::Uniforms;
+
+/// How occluded edges are treated when drawing a mesh as lines
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WireframeStyle {
+    /// Edges are depth-tested against whatever is already in the depth buffer
+    DepthTested,
+    /// The mesh is first rendered to depth only, so only its visible edges
+    /// survive and no surface fill is drawn (technical-drawing look)
+    HiddenLine,
+    /// Every edge is drawn, ignoring depth
+    AllVisible,
+}
+
+impl WireframeStyle {
+    pub fn next(self) -> Option<WireframeStyle> {
+        match self {
+            WireframeStyle::DepthTested => Some(WireframeStyle::HiddenLine),
+            WireframeStyle::HiddenLine => Some(WireframeStyle::AllVisible),
+            WireframeStyle::AllVisible => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            WireframeStyle::DepthTested => "Depth tested",
+            WireframeStyle::HiddenLine => "Hidden line",
+            WireframeStyle::AllVisible => "All visible",
+        }
+    }
+}
+
+/// Whether the bodies are filled, drawn as their triangle edges or both
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum FillMode {
+    #[default]
+    Solid,
+    /// Only the edges of the front faces inside the frustum
+    Wireframe,
+    /// The filled surface with its edges drawn on top
+    WireframeOverSolid,
+}
+
+impl FillMode {
+    pub fn next(self) -> FillMode {
+        match self {
+            FillMode::Solid => FillMode::Wireframe,
+            FillMode::Wireframe => FillMode::WireframeOverSolid,
+            FillMode::WireframeOverSolid => FillMode::Solid,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            FillMode::Solid => "Solid",
+            FillMode::Wireframe => "Wireframe",
+            FillMode::WireframeOverSolid => "Wireframe over solid",
+        }
+    }
+
+    pub fn fills(self) -> bool {
+        self != FillMode::Wireframe
+    }
+
+    pub fn draws_edges(self) -> bool {
+        self != FillMode::Solid
+    }
+}
+
+// Offset de la pasada de profundidad, proporcional a (1 - z) para que sea
+// aproximadamente constante en espacio de vista
+const HIDDEN_LINE_OFFSET: f32 = 0.002;
+
+pub fn render_wireframe(framebuffer: &mut Framebuffer, uniforms: &Uniforms,
+                        vertex_array: &[Vertex], style: WireframeStyle, color: Vector3) {
+    let transformed_vertices: Vec<Vertex> = vertex_array
+        .iter()
+        .map(|vertex| vertex_shader(vertex, uniforms))
+        .collect();
+
+    // Pasada 1 (solo HiddenLine): superficie a profundidad, ligeramente atrás
+    if style == WireframeStyle::HiddenLine {
+        let light = Light::point(Vector3::zero());
+        for tri in transformed_vertices.chunks_exact(3) {
+            for clipped in clip_to_frustum(&tri[0], &tri[1], &tri[2], &uniforms.viewport_matrix) {
+                for fragment in triangle(&clipped[0], &clipped[1], &clipped[2], &light, uniforms.raster_precision) {
+                    let offset = HIDDEN_LINE_OFFSET * (1.0 - fragment.depth).abs().max(1e-6);
+                    framebuffer.depth_point(
+                        fragment.position.x as i32,
+                        fragment.position.y as i32,
+                        fragment.depth + offset,
+                    );
+                }
+            }
+        }
+    }
+
+    // Pasada 2: aristas
+    let line_color = Color::new(
+        (color.x.clamp(0.0, 1.0) * 255.0) as u8,
+        (color.y.clamp(0.0, 1.0) * 255.0) as u8,
+        (color.z.clamp(0.0, 1.0) * 255.0) as u8,
+        255,
+    );
+    framebuffer.set_current_color(line_color);
+
+    // Cada arista se recorta en espacio de clip, como en render_body_edges
+    for tri in transformed_vertices.chunks_exact(3) {
+        for (a, b) in [(&tri[0], &tri[1]), (&tri[1], &tri[2]), (&tri[2], &tri[0])] {
+            let Some((start, end)) = clip_line(a, b, &uniforms.viewport_matrix) else {
+                continue;
+            };
+            for fragment in line(&start, &end) {
+                let x = fragment.position.x as i32;
+                let y = fragment.position.y as i32;
+                match style {
+                    WireframeStyle::AllVisible => framebuffer.set_pixel(x, y),
+                    _ => framebuffer.point(x, y, color, fragment.depth),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::environment::Environment;
+    use crate::obj::Obj;
+    use std::path::Path;
+
+    // Cubo de lado 2 en el origen, caras en sentido antihorario vistas desde afuera
+    const CUBE: &str = "v -1 -1 -1\nv 1 -1 -1\nv 1 1 -1\nv -1 1 -1\nv -1 -1 1\nv 1 -1 1\nv 1 1 1\nv -1 1 1\n\
+        f 1 4 3 2\nf 5 6 7 8\nf 1 2 6 5\nf 4 8 7 3\nf 1 5 8 4\nf 2 3 7 6\n";
+
+    #[test]
+    fn hidden_line_drops_the_back_edges() {
+        let cube = Obj::parse(CUBE, Path::new("")).unwrap();
+        let environment = Environment::starfield();
+        let uniforms = crate::tests::test_uniforms(&environment, 64, 64);
+        let white = Vector3::one();
+        let drawn = |style: WireframeStyle, point: Vector3| {
+            let mut framebuffer = Framebuffer::new(64, 64);
+            render_wireframe(&mut framebuffer, &uniforms, cube.get_vertex_array(), style, white);
+            let screen = crate::project_to_screen(point, &uniforms.view_matrix, &uniforms.projection_matrix,
+                                                  &uniforms.viewport_matrix).unwrap();
+            framebuffer.get_pixel_color(screen.x as i32, screen.y as i32) == Some(Color::WHITE)
+        };
+
+        // La cámara mira la cara z = 1 de frente: la cara de atrás se ve por dentro de ella
+        let front_edge = Vector3::new(0.0, 1.0, 1.0);
+        let back_edge = Vector3::new(0.0, 1.0, -1.0);
+        for style in [WireframeStyle::DepthTested, WireframeStyle::HiddenLine, WireframeStyle::AllVisible] {
+            assert!(drawn(style, front_edge), "{:?}", style);
+        }
+        assert!(drawn(WireframeStyle::AllVisible, back_edge));
+        assert!(drawn(WireframeStyle::DepthTested, back_edge));
+        assert!(!drawn(WireframeStyle::HiddenLine, back_edge));
+    }
+}